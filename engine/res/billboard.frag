@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 1, binding = 0) uniform texture2D t_billboard;
+layout(set = 1, binding = 1) uniform sampler s_billboard;
+
+void main() {
+  vec4 color = texture(sampler2D(t_billboard, s_billboard), v_tex_coords);
+  // Cut out rather than blended, so billboards needn't be sorted
+  if (color.a < 0.5) {
+    discard;
+  }
+  f_color = vec4(color.rgb, 1.0);
+}
@@ -0,0 +1,24 @@
+#version 450
+
+layout(location = 0) in vec3 a_center;
+layout(location = 1) in vec2 a_corner;
+layout(location = 2) in vec2 a_tex_coords;
+
+layout(location = 0) out vec2 v_tex_coords;
+
+layout(set = 0, binding = 0)
+uniform Uniforms {
+  vec3 u_view_position;
+  mat4 u_view_proj;
+  mat4 u_view;
+};
+
+void main() {
+  // Facing the camera of the view drawn
+  vec3 right = vec3(u_view[0][0], u_view[1][0], u_view[2][0]);
+  vec3 up = vec3(u_view[0][1], u_view[1][1], u_view[2][1]);
+  vec3 position = a_center + right * a_corner.x + up * a_corner.y;
+
+  gl_Position = u_view_proj * vec4(position, 1.0);
+  v_tex_coords = a_tex_coords;
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_input;
+layout(set = 0, binding = 1) uniform sampler s_input;
+
+layout(set = 1, binding = 0)
+uniform EffectUniforms {
+  vec2 u_texel_size;
+  float u_time;
+  float u_strength;
+};
+
+// Shifts red outwards and blue inwards from the center, u_strength is the
+// shift at the corners in pixels
+
+void main() {
+  vec2 offset = (v_tex_coords - 0.5) * 2.0 * u_strength * u_texel_size;
+  vec4 color = texture(sampler2D(t_input, s_input), v_tex_coords);
+  float r = texture(sampler2D(t_input, s_input), v_tex_coords + offset).r;
+  float b = texture(sampler2D(t_input, s_input), v_tex_coords - offset).b;
+  f_color = vec4(r, color.g, b, color.a);
+}
@@ -0,0 +1,42 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_input;
+layout(set = 0, binding = 1) uniform sampler s_input;
+
+layout(set = 1, binding = 0)
+uniform EffectUniforms {
+  vec2 u_texel_size;
+  float u_time;
+  // Blend between the input and the graded color
+  float u_strength;
+};
+
+// Strip of size slices, blue picks the slice, red and green the pixel in it
+layout(set = 2, binding = 0) uniform texture2D t_lut;
+layout(set = 2, binding = 1) uniform sampler s_lut;
+
+vec3 lut_at(vec3 color, float size, float slice) {
+  // Sample pixel centers so entries don't blend with the next slice
+  vec2 uv = vec2((slice * size + 0.5 + color.r * (size - 1.0)) / (size * size),
+                 (0.5 + color.g * (size - 1.0)) / size);
+  return textureLod(sampler2D(t_lut, s_lut), uv, 0.0).rgb;
+}
+
+void main() {
+  vec4 color = texture(sampler2D(t_input, s_input), v_tex_coords);
+  float size = float(textureSize(sampler2D(t_lut, s_lut), 0).y);
+
+  // LUTs are authored on sRGB encoded colors
+  vec3 encoded = pow(clamp(color.rgb, 0.0, 1.0), vec3(1.0 / 2.2));
+  float blue = encoded.b * (size - 1.0);
+  float slice = floor(blue);
+  vec3 graded = mix(lut_at(encoded, size, slice),
+                    lut_at(encoded, size, min(slice + 1.0, size - 1.0)),
+                    blue - slice);
+
+  f_color = vec4(mix(color.rgb, pow(graded, vec3(2.2)), u_strength), color.a);
+}
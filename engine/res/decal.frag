@@ -0,0 +1,36 @@
+#version 450
+
+layout(location = 0) flat in mat4 v_inverse_model;
+layout(location = 4) flat in float v_opacity;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0)
+uniform DecalUniforms {
+  mat4 u_view_proj;
+  mat4 u_inverse_view_proj;
+};
+
+// Depth of the scene the decal is projected onto
+layout(set = 1, binding = 0) uniform texture2D t_depth;
+layout(set = 1, binding = 1) uniform sampler s_depth;
+
+layout(set = 2, binding = 0) uniform texture2D t_decal;
+layout(set = 2, binding = 1) uniform sampler s_decal;
+
+void main() {
+  ivec2 pixel = ivec2(gl_FragCoord.xy);
+  float depth = texelFetch(sampler2D(t_depth, s_depth), pixel, 0).r;
+  vec2 uv = gl_FragCoord.xy / vec2(textureSize(sampler2D(t_depth, s_depth), 0));
+  vec4 world = u_inverse_view_proj * vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+
+  vec3 local = (v_inverse_model * vec4(world.xyz / world.w, 1.0)).xyz;
+  // Sampled before discarding, keeps derivatives for mipmapping defined
+  vec4 color = texture(sampler2D(t_decal, s_decal), local.xz + 0.5);
+  // Surfaces outside the box are left alone
+  if (any(greaterThan(abs(local), vec3(0.5)))) {
+    discard;
+  }
+
+  f_color = vec4(color.rgb, color.a * v_opacity);
+}
@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec3 a_position;
+layout(location = 1) in mat4 a_model;
+layout(location = 5) in mat4 a_inverse_model;
+layout(location = 9) in float a_opacity;
+
+layout(location = 0) flat out mat4 v_inverse_model;
+layout(location = 4) flat out float v_opacity;
+
+layout(set = 0, binding = 0)
+uniform DecalUniforms {
+  mat4 u_view_proj;
+  mat4 u_inverse_view_proj;
+};
+
+void main() {
+  gl_Position = u_view_proj * a_model * vec4(a_position, 1.0);
+  v_inverse_model = a_inverse_model;
+  v_opacity = a_opacity;
+}
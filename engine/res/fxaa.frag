@@ -0,0 +1,54 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_input;
+layout(set = 0, binding = 1) uniform sampler s_input;
+
+layout(set = 1, binding = 0)
+uniform EffectUniforms {
+  vec2 u_texel_size;
+  float u_time;
+  float u_strength;
+};
+
+// FXAA after Timothy Lottes' console version, blurring along the edges
+// found from luma differences. u_strength scales the search span.
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+
+float luma(vec3 color) {
+  return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+vec3 sample_at(vec2 uv) {
+  return texture(sampler2D(t_input, s_input), uv).rgb;
+}
+
+void main() {
+  vec2 uv = v_tex_coords;
+  float nw = luma(sample_at(uv + vec2(-1.0, -1.0) * u_texel_size));
+  float ne = luma(sample_at(uv + vec2(1.0, -1.0) * u_texel_size));
+  float sw = luma(sample_at(uv + vec2(-1.0, 1.0) * u_texel_size));
+  float se = luma(sample_at(uv + vec2(1.0, 1.0) * u_texel_size));
+  vec4 center = texture(sampler2D(t_input, s_input), uv);
+  float m = luma(center.rgb);
+
+  float luma_min = min(m, min(min(nw, ne), min(sw, se)));
+  float luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+  vec2 dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+  float reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+  float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+  dir = clamp(dir * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * u_texel_size * u_strength;
+
+  vec3 a = 0.5 * (sample_at(uv + dir * (1.0 / 3.0 - 0.5)) + sample_at(uv + dir * (2.0 / 3.0 - 0.5)));
+  vec3 b = a * 0.5 + 0.25 * (sample_at(uv - dir * 0.5) + sample_at(uv + dir * 0.5));
+  float luma_b = luma(b);
+
+  f_color = vec4((luma_b < luma_min || luma_b > luma_max) ? a : b, center.a);
+}
@@ -28,7 +28,8 @@ float invGridAlpha(in vec2 p, in vec2 ddx, in vec2 ddy) {
 }
 
 void main() {
-    float grid = (1 - invGridAlpha(v_tex_coords, dFdx(v_tex_coords), dFdy(v_tex_coords)));
+    vec2 p = v_tex_coords / grid_scale;
+    float grid = (1 - invGridAlpha(p, dFdx(p), dFdy(p)));
     float dist = length(v_view_position);
 	float fade_factor = (FADE_FAR - dist) / (FADE_FAR - FADE_NEAR);
 	fade_factor = clamp(fade_factor, 0.0, 1.0);
@@ -0,0 +1,80 @@
+#version 450
+
+layout(location=0) in vec3 a_position;
+layout(location=1) in vec2 a_tex_coords;
+layout(location=2) in vec3 a_normal;
+layout(location=3) in vec3 a_tangent;
+layout(location=4) in vec3 a_bitangent;
+layout(location=9) in vec4 a_color;
+layout(location=10) in vec2 a_tex_coords2;
+
+layout(location=0) out vec2 v_tex_coords;
+layout(location=1) out vec3 v_position;
+layout(location=2) out vec4 v_light_position;
+layout(location=3) out vec3 v_view_position;
+layout(location=4) out vec4 v_color;
+layout(location=5) out vec2 v_tex_coords2;
+
+layout(set=1, binding=0) 
+uniform Uniforms {
+  vec3 u_view_position; 
+  mat4 u_view_proj;
+};
+
+layout(location=5) in mat4 model_matrix;
+
+layout(set=2, binding=0) uniform Light {
+  vec4 light_position;
+  vec3 light_color;
+};
+
+struct MorphDelta {
+  vec4 position;
+  vec4 normal;
+};
+
+layout(set=3, binding=0) readonly buffer MorphTargets {
+  uvec4 u_morph_header;
+  MorphDelta u_deltas[];
+};
+
+layout(set=3, binding=1) uniform MorphWeights {
+  vec4 u_weights[2];
+};
+
+void main() {
+  v_tex_coords = a_tex_coords;
+  v_tex_coords2 = a_tex_coords2;
+  v_color = a_color;
+
+  uint vertex_count = u_morph_header.x;
+  uint target_count = u_morph_header.y;
+  vec3 position = a_position;
+  vec3 morphed_normal = a_normal;
+  for (uint i = 0; i < target_count; i++) {
+    float weight = u_weights[i / 4][i % 4];
+    MorphDelta delta = u_deltas[i * vertex_count + gl_VertexIndex];
+    position += weight * delta.position.xyz;
+    morphed_normal += weight * delta.normal.xyz;
+  }
+
+  mat3 normal_matrix = mat3(transpose(inverse(model_matrix)));
+  vec3 normal = normalize(normal_matrix * morphed_normal);
+  vec3 tangent = normalize(normal_matrix * a_tangent);
+  vec3 bitangent = normalize(normal_matrix * a_bitangent);
+
+  mat3 tangent_matrix = transpose(mat3(
+    tangent,
+    bitangent,
+    normal
+  ));
+
+  vec4 model_space = model_matrix * vec4(position, 1.0);
+  v_position = model_space.xyz;
+
+  v_position = tangent_matrix * model_space.xyz;
+  v_light_position = vec4(tangent_matrix * light_position.xyz, light_position.w);
+  v_view_position = tangent_matrix * u_view_position;
+
+  gl_Position = u_view_proj * model_space;
+}
\ No newline at end of file
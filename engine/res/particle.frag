@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+layout(location = 1) in vec4 v_color;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+  // Round and soft edged, added to what's behind
+  float falloff = 1.0 - smoothstep(0.0, 1.0, length(v_tex_coords));
+  f_color = vec4(v_color.rgb, v_color.a * falloff);
+}
@@ -0,0 +1,50 @@
+#version 450
+
+struct Particle {
+  vec3 position;
+  float age;
+  vec3 velocity;
+  float lifetime;
+  vec4 color;
+  float size;
+  float gravity;
+};
+
+layout(location = 0) out vec2 v_tex_coords;
+layout(location = 1) out vec4 v_color;
+
+layout(set = 0, binding = 0)
+uniform Uniforms {
+  vec3 u_view_position;
+  mat4 u_view_proj;
+  mat4 u_view;
+};
+
+layout(set = 1, binding = 0) readonly buffer Particles {
+  Particle particles[];
+};
+
+// Particles alive this frame, one instance each
+layout(set = 1, binding = 1) readonly buffer Alive {
+  uint alive[];
+};
+
+const vec2 CORNERS[6] = vec2[6](
+  vec2(-1, -1), vec2(1, -1), vec2(1, 1),
+  vec2(-1, -1), vec2(1, 1), vec2(-1, 1)
+);
+
+void main() {
+  Particle particle = particles[alive[gl_InstanceIndex]];
+  vec2 corner = CORNERS[gl_VertexIndex];
+
+  // Facing the camera
+  vec3 right = vec3(u_view[0][0], u_view[1][0], u_view[2][0]);
+  vec3 up = vec3(u_view[0][1], u_view[1][1], u_view[2][1]);
+  vec3 position = particle.position + (right * corner.x + up * corner.y) * particle.size * 0.5;
+
+  gl_Position = u_view_proj * vec4(position, 1.0);
+  v_tex_coords = corner;
+  // Fades out over its life
+  v_color = vec4(particle.color.rgb, particle.color.a * (1.0 - particle.age / particle.lifetime));
+}
@@ -4,6 +4,8 @@ layout(location=0) in vec2 v_tex_coords;
 layout(location=1) in vec3 v_position;
 layout(location=2) in vec4 v_light_position;
 layout(location=3) in vec3 v_view_position;
+layout(location=4) in vec4 v_color;
+layout(location=5) in vec2 v_tex_coords2;
 
 layout(location=0) out vec4 f_color;
 
@@ -11,6 +13,20 @@ layout(set = 0, binding = 0) uniform texture2D t_diffuse;
 layout(set = 0, binding = 1) uniform sampler s_diffuse;
 layout(set = 0, binding = 2) uniform texture2D t_normal;
 layout(set = 0, binding = 3) uniform sampler s_normal;
+layout(set = 0, binding = 4) uniform texture2D t_metallic_roughness;
+layout(set = 0, binding = 5) uniform sampler s_metallic_roughness;
+layout(set = 0, binding = 6)
+uniform Material {
+  vec4 u_base_color;
+  uint u_map_flags;
+  float u_metallic;
+  float u_roughness;
+};
+
+const uint DIFFUSE_MAP = 1;
+const uint NORMAL_MAP = 2;
+const uint REFLECTION_MAP = 4;
+const uint METALLIC_ROUGHNESS_MAP = 8;
 
 layout(set = 2, binding = 0)
 uniform Light {
@@ -19,8 +35,25 @@ uniform Light {
 };
 
 void main() {
-  vec4 object_color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
-  vec4 object_normal = texture(sampler2D(t_normal, s_normal), v_tex_coords);
+  vec4 object_color = u_base_color * v_color;
+  if ((u_map_flags & REFLECTION_MAP) != 0) {
+    // The reflection is drawn flipped horizontally, at the window's size
+    vec2 uv = gl_FragCoord.xy / vec2(textureSize(sampler2D(t_diffuse, s_diffuse), 0));
+    object_color *= texture(sampler2D(t_diffuse, s_diffuse), vec2(1.0 - uv.x, uv.y));
+  } else if ((u_map_flags & DIFFUSE_MAP) != 0) {
+    object_color *= texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+  }
+  vec4 object_normal = vec4(0.5, 0.5, 1.0, 1.0);
+  if ((u_map_flags & NORMAL_MAP) != 0) {
+    object_normal = texture(sampler2D(t_normal, s_normal), v_tex_coords);
+  }
+  float metallic = u_metallic;
+  float roughness = u_roughness;
+  if ((u_map_flags & METALLIC_ROUGHNESS_MAP) != 0) {
+    vec4 metallic_roughness = texture(sampler2D(t_metallic_roughness, s_metallic_roughness), v_tex_coords);
+    roughness *= metallic_roughness.g;
+    metallic *= metallic_roughness.b;
+  }
 
   float ambient_strength = 0.1;
   vec3 ambient_color = light_color * ambient_strength;
@@ -29,11 +62,16 @@ void main() {
   vec3 light_dir = normalize(v_light_position.xyz - (v_position * v_light_position.w));
   
   float diffuse_strength = max(dot(normal, light_dir), 0.0);
-  vec3 diffuse_color = light_color * diffuse_strength;
+  // Metals have no diffuse light, only highlights
+  vec3 diffuse_color = light_color * diffuse_strength * (1.0 - metallic);
 
   vec3 view_dir = normalize(v_view_position - (v_position * v_light_position.w));
   vec3 half_dir = normalize(view_dir + light_dir);
-  float specular_strength = pow(max(dot(normal, half_dir), 0.0), 32);
+  // Roughness 0.5 gives the exponent of 32 materials without one always had,
+  // fully rough surfaces have no highlight
+  float shininess = exp2(10.0 * (1.0 - roughness));
+  float specular_weight = min(2.0 * (1.0 - roughness), 1.0);
+  float specular_strength = pow(max(dot(normal, half_dir), 0.0), shininess) * specular_weight;
   vec3 specular_color = specular_strength * light_color;
 
   vec3 result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
@@ -5,11 +5,15 @@ layout(location=1) in vec2 a_tex_coords;
 layout(location=2) in vec3 a_normal;
 layout(location=3) in vec3 a_tangent;
 layout(location=4) in vec3 a_bitangent;
+layout(location=9) in vec4 a_color;
+layout(location=10) in vec2 a_tex_coords2;
 
 layout(location=0) out vec2 v_tex_coords;
 layout(location=1) out vec3 v_position;
 layout(location=2) out vec4 v_light_position;
 layout(location=3) out vec3 v_view_position;
+layout(location=4) out vec4 v_color;
+layout(location=5) out vec2 v_tex_coords2;
 
 layout(set=1, binding=0) 
 uniform Uniforms {
@@ -26,6 +30,8 @@ layout(set=2, binding=0) uniform Light {
 
 void main() {
   v_tex_coords = a_tex_coords;
+  v_tex_coords2 = a_tex_coords2;
+  v_color = a_color;
 
   mat3 normal_matrix = mat3(transpose(inverse(model_matrix)));
   vec3 normal = normalize(normal_matrix * a_normal);
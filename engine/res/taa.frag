@@ -0,0 +1,38 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_input;
+layout(set = 0, binding = 1) uniform sampler s_input;
+
+layout(set = 1, binding = 0)
+uniform EffectUniforms {
+  vec2 u_texel_size;
+  float u_time;
+  // Weight of the current frame
+  float u_strength;
+};
+
+layout(set = 2, binding = 0) uniform texture2D t_history;
+layout(set = 2, binding = 1) uniform sampler s_history;
+
+void main() {
+  vec4 current = texture(sampler2D(t_input, s_input), v_tex_coords);
+
+  // History outside the colors around the pixel belongs to something that
+  // moved away
+  vec3 low = current.rgb;
+  vec3 high = current.rgb;
+  for (int x = -1; x <= 1; x++) {
+    for (int y = -1; y <= 1; y++) {
+      vec3 neighbour = texture(sampler2D(t_input, s_input), v_tex_coords + vec2(x, y) * u_texel_size).rgb;
+      low = min(low, neighbour);
+      high = max(high, neighbour);
+    }
+  }
+  vec3 history = clamp(texture(sampler2D(t_history, s_history), v_tex_coords).rgb, low, high);
+
+  f_color = vec4(mix(history, current.rgb, u_strength), current.a);
+}
@@ -0,0 +1,47 @@
+#version 450
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=1) in vec3 v_position;
+layout(location=2) in vec3 v_normal;
+layout(location=3) in vec2 v_tex_coords2;
+
+layout(location=0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_splat;
+layout(set = 0, binding = 1) uniform sampler s_splat;
+layout(set = 0, binding = 2) uniform texture2DArray t_layers;
+layout(set = 0, binding = 3) uniform sampler s_layers;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+  vec3 u_view_position;
+  mat4 u_view_proj;
+};
+
+layout(set = 2, binding = 0)
+uniform Light {
+  vec4 light_position;
+  vec3 light_color;
+};
+
+void main() {
+  vec4 weights = texture(sampler2D(t_splat, s_splat), v_tex_coords);
+  weights /= max(dot(weights, vec4(1.0)), 0.0001);
+
+  vec3 object_color = vec3(0.0);
+  for (int i = 0; i < 4; i++) {
+    object_color += weights[i] * texture(sampler2DArray(t_layers, s_layers), vec3(v_tex_coords2, i)).rgb;
+  }
+
+  float ambient_strength = 0.1;
+  vec3 ambient_color = light_color * ambient_strength;
+
+  vec3 normal = normalize(v_normal);
+  vec3 light_dir = normalize(light_position.xyz - (v_position * light_position.w));
+
+  float diffuse_strength = max(dot(normal, light_dir), 0.0);
+  vec3 diffuse_color = light_color * diffuse_strength;
+
+  vec3 result = (ambient_color + diffuse_color) * object_color;
+  f_color = vec4(result, 1.0);
+}
@@ -0,0 +1,27 @@
+#version 450
+
+layout(location=0) in vec3 a_position;
+layout(location=1) in vec2 a_tex_coords;
+layout(location=2) in vec3 a_normal;
+layout(location=10) in vec2 a_tex_coords2;
+
+layout(location=0) out vec2 v_tex_coords;
+layout(location=1) out vec3 v_position;
+layout(location=2) out vec3 v_normal;
+layout(location=3) out vec2 v_tex_coords2;
+
+layout(set=1, binding=0)
+uniform Uniforms {
+  vec3 u_view_position;
+  mat4 u_view_proj;
+};
+
+void main() {
+  v_tex_coords = a_tex_coords;
+  v_tex_coords2 = a_tex_coords2;
+  // Terrain vertices are already in world space
+  v_position = a_position;
+  v_normal = a_normal;
+
+  gl_Position = u_view_proj * vec4(a_position, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_input;
+layout(set = 0, binding = 1) uniform sampler s_input;
+
+layout(set = 1, binding = 0)
+uniform EffectUniforms {
+  vec2 u_texel_size;
+  float u_time;
+  float u_strength;
+};
+
+// ACES filmic curve fitted by Krzysztof Narkowicz, u_strength is the
+// exposure
+vec3 aces(vec3 x) {
+  return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+  vec4 color = texture(sampler2D(t_input, s_input), v_tex_coords);
+  f_color = vec4(aces(color.rgb * u_strength), color.a);
+}
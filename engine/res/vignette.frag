@@ -0,0 +1,24 @@
+#version 450
+
+layout(location = 0) in vec2 v_tex_coords;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform texture2D t_input;
+layout(set = 0, binding = 1) uniform sampler s_input;
+
+layout(set = 1, binding = 0)
+uniform EffectUniforms {
+  vec2 u_texel_size;
+  float u_time;
+  float u_strength;
+};
+
+// Darkens towards the corners, u_strength is how much the corners lose
+
+void main() {
+  vec4 color = texture(sampler2D(t_input, s_input), v_tex_coords);
+  float radius = length(v_tex_coords - 0.5) * 1.41421356;
+  float falloff = smoothstep(0.4, 1.0, radius);
+  f_color = vec4(color.rgb * (1.0 - falloff * u_strength), color.a);
+}
@@ -0,0 +1,91 @@
+use nalgebra::{UnitQuaternion, Vector3};
+
+use super::{JointPose, Pose};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+#[derive(Debug, Clone)]
+pub enum Keyframes {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<UnitQuaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub joint: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub keyframes: Keyframes,
+}
+
+impl Channel {
+    /// Returns the two keyframes surrounding `time` and the blend factor
+    /// between them.
+    fn keys(&self, time: f32) -> (usize, usize, f32) {
+        let last = self.times.len() - 1;
+        if time <= self.times[0] {
+            return (0, 0, 0.0);
+        }
+        if time >= self.times[last] {
+            return (last, last, 0.0);
+        }
+
+        let next = self.times.iter().position(|&t| t > time).unwrap_or(last);
+        let prev = next - 1;
+        let factor = match self.interpolation {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => {
+                (time - self.times[prev]) / (self.times[next] - self.times[prev])
+            }
+        };
+
+        (prev, next, factor)
+    }
+
+    fn apply(&self, time: f32, pose: &mut JointPose) {
+        if self.times.is_empty() {
+            return;
+        }
+
+        let (prev, next, t) = self.keys(time);
+        match &self.keyframes {
+            Keyframes::Translation(values) => {
+                pose.translation = values[prev].lerp(&values[next], t);
+            }
+            Keyframes::Rotation(values) => {
+                pose.rotation = values[prev]
+                    .try_slerp(&values[next], t, f32::EPSILON)
+                    .unwrap_or(values[prev]);
+            }
+            Keyframes::Scale(values) => {
+                pose.scale = values[prev].lerp(&values[next], t);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    /// Samples the clip at `time` on top of `rest`, joints without channels
+    /// keep their rest pose.
+    pub fn sample(&self, time: f32, rest: &Pose) -> Pose {
+        let mut pose = rest.clone();
+        for channel in &self.channels {
+            if let Some(joint) = pose.joints.get_mut(channel.joint) {
+                channel.apply(time, joint);
+            }
+        }
+        pose
+    }
+}
@@ -0,0 +1,214 @@
+pub mod clip;
+pub mod morph;
+
+pub use clip::{AnimationClip, Channel, Interpolation, Keyframes};
+pub use morph::MorphWeights;
+
+use nalgebra::{Isometry3, Matrix4, Translation3, UnitQuaternion, Vector3};
+
+use crate::render::{binding, state};
+
+/// Upper bound of joints per skeleton, sizes the joint matrix buffer.
+pub const MAX_JOINTS: usize = 128;
+
+#[derive(Debug, Clone, Copy)]
+pub struct JointPose {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl JointPose {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Isometry3::from_parts(Translation3::from(self.translation), self.rotation).to_matrix()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    pub fn blend(&self, other: &JointPose, t: f32) -> JointPose {
+        JointPose {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: self
+                .rotation
+                .try_slerp(&other.rotation, t, f32::EPSILON)
+                .unwrap_or(self.rotation),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pose {
+    pub joints: Vec<JointPose>,
+}
+
+impl Pose {
+    pub fn blend(&self, other: &Pose, t: f32) -> Pose {
+        Pose {
+            joints: self
+                .joints
+                .iter()
+                .zip(other.joints.iter())
+                .map(|(a, b)| a.blend(b, t))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub inverse_bind: Matrix4<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    pub rest: Pose,
+    /// Joint indices sorted parents first
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>, rest: Pose) -> Self {
+        let depth = |mut joint: usize| {
+            let mut depth = 0;
+            while let Some(parent) = joints[joint].parent {
+                joint = parent;
+                depth += 1;
+            }
+            depth
+        };
+        let mut order = (0..joints.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&joint| depth(joint));
+
+        Self {
+            joints,
+            rest,
+            order,
+        }
+    }
+
+    pub fn joint_matrices(&self, pose: &Pose) -> Vec<Matrix4<f32>> {
+        let mut globals = vec![Matrix4::identity(); self.joints.len()];
+        for &i in &self.order {
+            let local = pose.joints[i].matrix();
+            globals[i] = match self.joints[i].parent {
+                Some(parent) => globals[parent] * local,
+                None => local,
+            };
+        }
+
+        globals
+            .iter()
+            .zip(self.joints.iter())
+            .map(|(global, joint)| global * joint.inverse_bind)
+            .collect()
+    }
+}
+
+pub struct PlayingClip {
+    pub clip: usize,
+    pub time: f32,
+    pub speed: f32,
+    pub weight: f32,
+    pub looping: bool,
+}
+
+/// Plays and blends the clips of the entity's model, writing the resulting
+/// joint matrices into a storage buffer read by the skinned pipeline.
+pub struct AnimationPlayer {
+    pub clips: Vec<PlayingClip>,
+    /// Posed by `advance`, waiting for `upload`
+    matrices: Vec<[[f32; 4]; 4]>,
+    joint_buffer: binding::Buffer,
+    pub(crate) joint_group: binding::BufferGroup,
+}
+
+impl AnimationPlayer {
+    pub fn new(state: &state::WgpuState, layout: &binding::BindGroupLayout) -> Self {
+        let joint_buffer = binding::Buffer::new_init(
+            state,
+            "joints",
+            &[<[[f32; 4]; 4]>::from(Matrix4::<f32>::identity()); MAX_JOINTS],
+            binding::BufferUsage::Storage,
+        );
+        let joint_group =
+            binding::BufferGroup::from_buffer(state, "joints", layout, &[&joint_buffer]);
+
+        Self {
+            clips: Vec::new(),
+            matrices: Vec::new(),
+            joint_buffer,
+            joint_group,
+        }
+    }
+
+    pub fn play(&mut self, clip: usize, looping: bool) -> &mut Self {
+        self.clips.clear();
+        self.clips.push(PlayingClip {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            weight: 1.0,
+            looping,
+        });
+        self
+    }
+
+    /// Adds a clip on top of the current ones, blended by `weight`.
+    pub fn blend_in(&mut self, clip: usize, weight: f32, looping: bool) -> &mut Self {
+        self.clips.push(PlayingClip {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            weight,
+            looping,
+        });
+        self
+    }
+
+    /// Advances the clips by `dt` and poses the skeleton, without touching
+    /// the GPU so players can advance in parallel.
+    pub fn advance(&mut self, skeleton: &Skeleton, animations: &[AnimationClip], dt: f32) {
+        let mut pose = skeleton.rest.clone();
+        let mut total_weight = 0.0;
+
+        for playing in self.clips.iter_mut() {
+            let clip = match animations.get(playing.clip) {
+                Some(clip) => clip,
+                None => continue,
+            };
+
+            playing.time += dt * playing.speed;
+            if playing.looping && clip.duration > 0.0 {
+                playing.time %= clip.duration;
+            } else {
+                playing.time = playing.time.min(clip.duration);
+            }
+
+            if playing.weight <= 0.0 {
+                continue;
+            }
+
+            let sampled = clip.sample(playing.time, &skeleton.rest);
+            total_weight += playing.weight;
+            pose = pose.blend(&sampled, playing.weight / total_weight);
+        }
+
+        self.matrices = skeleton
+            .joint_matrices(&pose)
+            .into_iter()
+            .take(MAX_JOINTS)
+            .map(<[[f32; 4]; 4]>::from)
+            .collect();
+    }
+
+    /// Writes the pose of the last `advance`.
+    pub fn upload(&mut self, state: &state::WgpuState) {
+        if !self.matrices.is_empty() {
+            self.joint_buffer.write(state, &self.matrices);
+            self.matrices.clear();
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::render::{binding, model, state};
+
+/// Number of blend shapes a single mesh can mix, matches `morph.vert`.
+pub const MAX_MORPH_TARGETS: usize = 8;
+
+/// Per-entity blend shape weights. Holds one bind group per mesh of the
+/// entity's model, pairing the mesh's target deltas with these weights.
+pub struct MorphWeights {
+    pub weights: Vec<f32>,
+    pub dirty: bool,
+    buffer: binding::Buffer,
+    pub(crate) groups: Vec<binding::BufferGroup>,
+}
+
+impl MorphWeights {
+    pub fn new(
+        state: &state::WgpuState,
+        layout: &binding::BindGroupLayout,
+        model: &model::Model,
+    ) -> Self {
+        let weights = vec![0.0; model.geometry.morph_target_count()];
+        let buffer = binding::Buffer::new_init(
+            state,
+            "morph_weights",
+            &[0.0f32; MAX_MORPH_TARGETS],
+            binding::BufferUsage::Uniform,
+        );
+        let groups = model
+            .geometry
+            .meshes
+            .iter()
+            .map(|mesh| {
+                binding::BufferGroup::from_buffer(
+                    state,
+                    "morph",
+                    layout,
+                    &[mesh.morph_buffer.as_ref(), &buffer],
+                )
+            })
+            .collect();
+
+        Self {
+            weights,
+            dirty: true,
+            buffer,
+            groups,
+        }
+    }
+
+    pub fn set_weight(&mut self, target: usize, weight: f32) -> &mut Self {
+        if let Some(w) = self.weights.get_mut(target) {
+            *w = weight;
+            self.dirty = true;
+        }
+        self
+    }
+
+    pub fn flush(&mut self, state: &state::WgpuState) {
+        if self.dirty {
+            self.dirty = false;
+            let mut data = [0.0f32; MAX_MORPH_TARGETS];
+            for (slot, weight) in data.iter_mut().zip(self.weights.iter()) {
+                *slot = *weight;
+            }
+            self.buffer.write(state, &data);
+        }
+    }
+}
@@ -0,0 +1,3235 @@
+//! The engine behind [`App`]: the window, the renderer and the editor
+//! driving a [`world::World`].
+
+use crate::{
+    assets, autosave, bounds, camera, character, editor, error, events, hierarchy, input, inspect,
+    lifecycle, locale, logging, physics, plugin, prefab, render, resources, scene, schedule,
+    session, settings, stats, transform, world,
+};
+
+use futures::executor::block_on;
+
+use crate::render::{
+    binding, frame, model, renderpass, state, texture,
+    traits::{
+        DrawBillboards, DrawDebugLines, DrawDecals, DrawFramebuffer, DrawGrid, DrawLight,
+        DrawParticles, DrawSprites, DrawText,
+    },
+};
+use imgui::{im_str, ComboBox, Condition, ImString};
+use log::{error, info, warn};
+use nalgebra::Matrix4;
+use std::sync::Arc;
+use winit::{
+    event::*,
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
+    window::{Window, WindowBuilder},
+};
+
+use anyhow::*;
+
+/// Scene opened unless `--scene` names another, saved with F5 and loaded
+/// with F9, or on startup when present
+const SCENE_FILE: &str = "scene.ron";
+
+const CAMERA_FOVY: f32 = 75.0;
+const CAMERA_ZNEAR: f32 = 0.1;
+const CAMERA_ZFAR: f32 = 100.0;
+/// World units the orthographic view (F3) shows from bottom to top
+const ORTHO_HEIGHT: f32 = 20.0;
+/// Recall a camera bookmark, held with control to store one
+const BOOKMARK_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+/// Seconds between the keys of a path made from bookmarks
+const BOOKMARK_PATH_STEP: f32 = 2.0;
+/// Size of the camera preview window's image
+const PREVIEW_SIZE: [u32; 2] = [320, 180];
+/// How far across the window the mouse may move between pressing and
+/// releasing and still count as a click
+const CLICK_SLOP: f64 = 0.005;
+/// How far in front of the camera models dropped over empty space spawn
+const SPAWN_DISTANCE: f32 = 10.0;
+/// Frame size of headless renders unless `--size` is given
+const HEADLESS_SIZE: (u32, u32) = (1280, 720);
+/// How long headless renders wait for the scene's models to load
+const HEADLESS_LOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+/// Mean channel difference from 0 to 1 a headless render may have from
+/// its golden image
+const GOLDEN_TOLERANCE: f32 = 0.01;
+/// How long the window has to keep its size before the swapchain and
+/// targets are recreated, so dragging its edge doesn't recreate them for
+/// every step
+const RESIZE_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(100);
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Light {
+    position: nalgebra::Vector3<f32>,
+    ty: f32,
+    color: nalgebra::Vector3<f32>,
+}
+
+impl Light {
+    fn from_settings(settings: &editor::light::LightSettings) -> Self {
+        Self {
+            position: settings.position.into(),
+            ty: match settings.ty {
+                editor::light::LightType::Directional => 0.0,
+                editor::light::LightType::Point => 1.0,
+            },
+            color: nalgebra::Vector3::from(settings.color) * settings.intensity,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for Light {}
+unsafe impl bytemuck::Zeroable for Light {}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct Uniforms {
+    view_position: nalgebra::Vector4<f32>,
+    view_proj: nalgebra::Matrix4<f32>,
+    view: nalgebra::Matrix4<f32>,
+}
+
+unsafe impl bytemuck::Pod for Uniforms {}
+unsafe impl bytemuck::Zeroable for Uniforms {}
+
+impl Uniforms {
+    fn new() -> Self {
+        Self {
+            view_position: nalgebra::zero(),
+            view_proj: nalgebra::Matrix4::identity(),
+            view: nalgebra::Matrix4::identity(),
+        }
+    }
+
+    fn update_view_proj(&mut self, camera: &camera::Camera) {
+        self.view_position = camera.eye.to_homogeneous();
+        self.view_proj = camera.view_proj;
+        self.view = camera.view_transform().to_homogeneous();
+    }
+}
+
+/// Saved camera pose.
+#[derive(Debug, Clone, Copy)]
+struct CameraBookmark {
+    eye: nalgebra::Point3<f32>,
+    at: nalgebra::Point3<f32>,
+}
+
+/// How the window covers its monitor, cycled through with F11 or
+/// Alt+Enter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WindowMode {
+    Windowed,
+    /// A borderless window covering the monitor at its desktop resolution
+    Borderless,
+    /// Takes over the monitor in its largest video mode
+    Exclusive,
+}
+
+impl WindowMode {
+    fn next(self) -> Self {
+        match self {
+            WindowMode::Windowed => WindowMode::Borderless,
+            WindowMode::Borderless => WindowMode::Exclusive,
+            WindowMode::Exclusive => WindowMode::Windowed,
+        }
+    }
+}
+
+/// What drives the camera, cycled through with F2.
+enum CameraMode {
+    Fly,
+    Orbit(camera::orbit::OrbitController),
+    FirstPerson(camera::fps::FirstPersonController),
+    /// Plays back the camera track of an entity, started with F6
+    Path {
+        entity: legion::Entity,
+        time: f32,
+    },
+}
+
+struct Engine {
+    window: Window,
+    state: state::WgpuState,
+    pipelines: render::Pipelines,
+    camera: camera::Camera,
+    camera_controller: camera::flycam::FlyCamController,
+    camera_mode: CameraMode,
+    bookmarks: [Option<CameraBookmark>; 9],
+    /// Cursor hidden and locked, the mouse always looks around
+    cursor_grabbed: bool,
+    /// Where `select` was pressed, the click selects if released close by
+    click_start: Option<(f64, f64)>,
+    player: legion::Entity,
+    uniforms: Uniforms,
+    uniform_buffer: binding::Buffer,
+    uniform_group: binding::BufferGroup,
+    depth_texture: texture::Texture,
+    obj_model: model::Model,
+    light_buffer: binding::Buffer,
+    light_group: binding::BufferGroup,
+    light: Light,
+    /// The light as the light panel edits it
+    light_settings: editor::light::LightSettings,
+    imgui: imgui::Context,
+    imgui_renderer: imgui_wgpu::Renderer,
+    last_cursor: Option<imgui::MouseCursor>,
+    platform: imgui_winit_support::WinitPlatform,
+    light_depth_map: texture::Texture,
+    /// What the preview camera sees, shown in its own window
+    preview: render::viewport::Viewport,
+    /// The preview camera's view detached into a window of its own
+    game_window: Option<render::window::SceneWindow>,
+    /// Opens `game_window` once the event loop can create windows
+    game_window_requested: bool,
+    /// Previews of the loaded assets for the asset browser
+    thumbnails: render::thumbnail::Thumbnails,
+    framebuffer: frame::Framebuffer,
+    layouts: render::Layouts,
+    world: world::World,
+    grid: render::grid::Grid,
+    debug_lines: render::debug::DebugLines,
+    /// Entity labels, and their names while debug drawing is on
+    text: render::text::TextRenderer,
+    /// Sprite entities, drawn over the scene
+    sprites: render::sprite::SpriteRenderer,
+    /// Distant entities, drawn in their place by every view
+    billboards: render::billboard::BillboardRenderer,
+    /// Decal entities, projected onto the scene before the grid and sprites
+    decals: render::decal::DecalRenderer,
+    /// Particles of every emitter, simulated and drawn on the GPU
+    particles: render::particles::ParticleSystem,
+    /// Tests indirectly drawn entities against the frustum on the GPU
+    culling: render::culling::Culling,
+    /// Effects run on the scene before the UI is drawn over it
+    post: render::post::PostProcess,
+    /// What the first mirror in the scene reflects
+    reflection: render::reflection::Reflection,
+    editor: editor::Editor,
+    /// Editor operations to undo with Ctrl+Z and redo with Ctrl+Y
+    history: editor::history::History,
+    /// Components the inspector window shows
+    inspectors: inspect::InspectorRegistry,
+    /// The scene as it was when play started, restored when it stops
+    play_snapshot: Option<scene::Scene>,
+    /// Scene file saved with F5 and loaded with F9
+    scene_path: std::path::PathBuf,
+    autosave: autosave::Autosave,
+    /// A crashed session left an autosave, nothing is autosaved until the
+    /// user recovered or discarded it
+    recovery_pending: bool,
+    /// Latest log records, shown in the console
+    log: logging::LogBuffer,
+    log_handle: logging::LogHandle,
+    /// Log level last applied from the settings, the command line and
+    /// environment win until it is changed there
+    log_level: log::LevelFilter,
+    /// Draws camera frustums and the picking ray, toggled with F10
+    show_debug: bool,
+    /// Shows frame times, draw counts and memory, toggled with F1
+    show_stats: bool,
+    settings: settings::Settings,
+    /// Shows the settings window, toggled with F12
+    show_settings: bool,
+    /// Whether the editor plugin was added, the scene is drawn alone
+    /// without it
+    show_editor: bool,
+    /// String table of the UI language picked in the settings
+    locale: locale::Locale,
+    /// Languages, themes and fonts the settings window offers
+    ui_choices: editor::settings::UiChoices,
+    /// Font, size and DPI the UI fonts were last built for
+    built_fonts: (Option<String>, f32, f64),
+    lifecycle: lifecycle::Lifecycle,
+    /// Size the window was last resized to and when, applied after
+    /// `RESIZE_DEBOUNCE`
+    pending_resize: Option<(winit::dpi::PhysicalSize<u32>, std::time::Instant)>,
+    window_mode: WindowMode,
+    /// Clamps and smooths frame times and limits the frame rate
+    pacer: schedule::FramePacer,
+}
+
+impl Engine {
+    async fn new(
+        window: Window,
+        log: logging::LogBuffer,
+        log_handle: logging::LogHandle,
+        settings: settings::Settings,
+        options: &Options,
+        mut extensions: Extensions,
+    ) -> Result<Self> {
+        let resources = Arc::new(resources::Resources::with_res_dir(
+            options.res_dir.as_deref(),
+        ));
+        let state = state::WgpuState::new(
+            options.backend,
+            &window,
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            resources.clone(),
+        )
+        .await
+        .unwrap();
+        info!("Wgpu initialized");
+
+        let mut layouts = render::Layouts::new(&state);
+        extensions.build_layouts(&state, &mut layouts);
+
+        let mut camera = camera::Camera::new(
+            [0.0, 5.0, 10.0].into(),
+            [0.0, 0.0, 0.0].into(),
+            camera::projection::Projection::new(
+                state.width(),
+                state.height(),
+                CAMERA_FOVY,
+                CAMERA_ZNEAR,
+                CAMERA_ZFAR,
+            ),
+        );
+        // Entity cameras leave out the editor's helpers
+        camera.layers |= render::layer::RenderLayer::EDITOR;
+        let camera_controller = camera::flycam::FlyCamController::new(4.0, 100.0);
+        info!("Camera and controller initialized");
+
+        let mut uniforms = Uniforms::new();
+        uniforms.update_view_proj(&camera);
+
+        let uniform_buffer = binding::Buffer::new_init(
+            &state,
+            "uniforms",
+            &[uniforms],
+            binding::BufferUsage::Uniform,
+        );
+
+        let uniform_group = binding::BufferGroup::from_buffer(
+            &state,
+            "uniforms",
+            &layouts.uniforms,
+            &[&uniform_buffer],
+        );
+
+        let light_settings = editor::light::LightSettings {
+            position: [-0.25, 0.25, -0.25],
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            ty: editor::light::LightType::Directional,
+        };
+        let light = Light::from_settings(&light_settings);
+
+        let light_buffer =
+            binding::Buffer::new_init(&state, "light", &[light], binding::BufferUsage::Uniform);
+
+        let light_group =
+            binding::BufferGroup::from_buffer(&state, "light", &layouts.light, &[&light_buffer]);
+
+        let depth_texture = texture::Texture::create_depth_texture(&state, "depth_texture");
+
+        let mut pipelines = render::Pipelines::new(&state, &layouts)?;
+        extensions.build_pipelines(&state, &layouts, &mut pipelines)?;
+
+        let mut world = world::World::new(resources.clone());
+
+        let obj_model = model::Model::load(
+            &state,
+            &layouts.material,
+            &mut world.assets.textures,
+            resources.path("cube.obj")?,
+        )?;
+
+        let mut imgui = imgui::Context::create();
+        let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui);
+        platform.attach_window(
+            imgui.io_mut(),
+            &window,
+            imgui_winit_support::HiDpiMode::Default,
+        );
+        imgui.set_ini_filename(Some(std::path::PathBuf::from(editor::layout::LAYOUT_FILE)));
+
+        let built_fonts = (
+            settings.ui.font.clone(),
+            settings.ui.font_size,
+            window.scale_factor(),
+        );
+        build_fonts(&mut imgui, &resources, &built_fonts);
+
+        let mut imgui_renderer = imgui_wgpu::Renderer::new(
+            &mut imgui,
+            &state.device(),
+            &state.queue(),
+            imgui_wgpu::RendererConfig {
+                texture_format: state.format(),
+                ..Default::default()
+            },
+        );
+
+        let light_depth_map = texture::Texture::create_depth_texture(&state, "light_depth_map");
+
+        let preview = render::viewport::Viewport::new(
+            &state,
+            &mut imgui_renderer,
+            &layouts.uniforms,
+            "preview",
+            PREVIEW_SIZE[0],
+            PREVIEW_SIZE[1],
+            &Uniforms::new(),
+        );
+
+        let thumbnails = render::thumbnail::Thumbnails::new(
+            &state,
+            &layouts,
+            &mut world.assets.textures,
+            camera::projection::Projection::new(
+                render::thumbnail::THUMBNAIL_SIZE,
+                render::thumbnail::THUMBNAIL_SIZE,
+                CAMERA_FOVY,
+                CAMERA_ZNEAR,
+                CAMERA_ZFAR,
+            ),
+        )?;
+
+        let framebuffer = frame::Framebuffer::new(
+            &state,
+            "depth_framebuffer",
+            &layouts.frame,
+            &[&depth_texture],
+        );
+
+        load_default_models(&mut world, &state, &layouts)?;
+        extensions.setup(&mut world);
+
+        let autosave = autosave::Autosave::new(autosave::claim_path());
+        let recovery_pending = autosave.recoverable();
+        if recovery_pending {
+            warn!("Found the autosave of a session that crashed");
+        }
+
+        if options.scene.exists() {
+            world.load(&state, &layouts, &options.scene)?;
+        } else {
+            world.push_entity((
+                world::ModelIdent("block".into()),
+                transform::Transform::new(&state, "block_transform"),
+                world::Name("block".into()),
+            ))?;
+
+            let mut transform = transform::Transform::new(&state, "block_transform");
+            transform.set_position(nalgebra::Translation3::new(-2.5, 0.0, 0.0));
+            world.push_entity((
+                world::ModelIdent("block".into()),
+                transform,
+                world::Name("block 2".into()),
+            ))?;
+        }
+
+        let mut transform = transform::Transform::new(&state, "player_transform");
+        transform.set_position(nalgebra::Translation3::new(0.0, 2.0, 5.0));
+        let player = world.add_character(
+            "player",
+            transform,
+            character::CharacterController::default(),
+        )?;
+
+        let mut transform = transform::Transform::new(&state, "preview_camera_transform");
+        transform.set_isometry(nalgebra::Isometry3::face_towards(
+            &nalgebra::Point3::new(6.0, 4.0, 6.0),
+            &nalgebra::Point3::origin(),
+            &nalgebra::Vector3::y(),
+        ));
+        let preview_camera = world.add_camera(
+            "preview camera",
+            transform,
+            camera::projection::Projection::new(
+                PREVIEW_SIZE[0],
+                PREVIEW_SIZE[1],
+                CAMERA_FOVY,
+                CAMERA_ZNEAR,
+                CAMERA_ZFAR,
+            ),
+        )?;
+        world.set_preview_camera(Some(preview_camera));
+
+        world.update_collision_world();
+
+        let grid = render::grid::Grid::new(&state, "grid", &layouts.grid);
+        let text = render::text::TextRenderer::new(&state, &layouts.texture);
+        let sprites = render::sprite::SpriteRenderer::new(&state, &layouts.uniforms);
+        let billboards = render::billboard::BillboardRenderer::new(&state);
+        let decals = render::decal::DecalRenderer::new(
+            &state,
+            &layouts.uniforms,
+            &layouts.texture,
+            &depth_texture,
+        );
+        let particles = render::particles::ParticleSystem::new(&state, &layouts.uniforms)?;
+        let culling = render::culling::Culling::new(&state)?;
+        let reflection = render::reflection::Reflection::new(
+            &state,
+            &layouts.uniforms,
+            &mut world.assets.textures,
+            &Uniforms::new(),
+        );
+        let mut post = render::post::PostProcess::new(&state, &layouts.texture, &layouts.uniforms)?;
+        for (name, strength, textured) in render::post::BUILTIN_EFFECTS.iter() {
+            post.push(
+                &state,
+                &layouts.uniforms,
+                name,
+                &format!("{}.frag.spv", name),
+                *strength,
+                *textured,
+            )?;
+        }
+
+        let mut lifecycle = lifecycle::Lifecycle::new();
+        for hook in extensions.hooks {
+            lifecycle.register(hook);
+        }
+        lifecycle.dispatch(&mut world, lifecycle::LifecycleEvent::SceneLoaded);
+
+        let mut engine = Self {
+            window,
+            state,
+            pipelines,
+            camera,
+            camera_controller,
+            camera_mode: CameraMode::Fly,
+            bookmarks: [None; 9],
+            cursor_grabbed: false,
+            click_start: None,
+            player,
+            uniforms,
+            uniform_buffer,
+            uniform_group,
+            depth_texture,
+            obj_model,
+            light_buffer,
+            light_group,
+            light,
+            light_settings,
+            imgui,
+            imgui_renderer,
+            last_cursor: None,
+            platform,
+            light_depth_map,
+            preview,
+            game_window: None,
+            game_window_requested: false,
+            thumbnails,
+            framebuffer,
+            layouts,
+            world,
+            grid,
+            debug_lines: render::debug::DebugLines::new(),
+            text,
+            sprites,
+            billboards,
+            decals,
+            particles,
+            culling,
+            post,
+            reflection,
+            editor: editor::Editor::new(),
+            history: editor::history::History::new(),
+            inspectors: inspect::InspectorRegistry::with_defaults(),
+            play_snapshot: None,
+            scene_path: options.scene.clone(),
+            autosave,
+            recovery_pending,
+            log,
+            log_handle,
+            log_level: settings.logging.level,
+            show_debug: false,
+            show_stats: false,
+            settings,
+            show_settings: false,
+            show_editor: extensions.editor,
+            locale: locale::Locale::default(),
+            ui_choices: editor::settings::UiChoices::default(),
+            built_fonts,
+            lifecycle,
+            pending_resize: None,
+            window_mode: WindowMode::Windowed,
+            pacer: schedule::FramePacer::new(0),
+        };
+        engine.apply_settings();
+        Ok(engine)
+    }
+
+    fn apply_settings(&mut self) {
+        self.state.set_vsync(self.settings.graphics.vsync);
+        self.pacer.set_max_fps(self.settings.graphics.max_fps);
+        self.world
+            .set_indirect_draw(self.settings.graphics.indirect_draw);
+        let antialiasing = self.settings.graphics.antialiasing;
+        let mut effects = self
+            .settings
+            .graphics
+            .post_effects
+            .iter()
+            .filter(|effect| {
+                *effect != render::post::FXAA && *effect != render::post::COLOR_GRADING
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if antialiasing == settings::Antialiasing::Fxaa {
+            effects.push(render::post::FXAA.to_string());
+        }
+        // Skipped while it has no LUT
+        effects.push(render::post::COLOR_GRADING.to_string());
+        self.post.set_enabled(&effects);
+        self.post
+            .set_taa(antialiasing == settings::Antialiasing::Taa);
+        let lut = self
+            .settings
+            .graphics
+            .color_lut
+            .as_ref()
+            .map(|lut| format!("{}/{}", render::post::LUT_DIR, lut));
+        if self.post.texture(render::post::COLOR_GRADING) != lut.as_deref() {
+            if let Err(e) = self.post.set_texture(
+                &self.state,
+                &mut self.world.assets.textures,
+                &self.layouts.texture,
+                render::post::COLOR_GRADING,
+                lut.as_deref(),
+            ) {
+                error!("Could not load color grading LUT {:?}: {:?}", lut, e);
+            }
+        }
+        if self.settings.logging.level != self.log_level {
+            self.log_level = self.settings.logging.level;
+            self.log_handle.set_level(self.log_level);
+        }
+        self.camera_controller
+            .set_speed(self.settings.editor.camera_speed);
+        self.grid
+            .set_cell_size(&self.state, self.settings.editor.grid_size);
+        if let Some(mut input) = self.world.resources().get_mut::<input::Input>() {
+            input.map = self.settings.input_map();
+        }
+        let resources = self.world.assets.resources().clone();
+        if self.locale.language() != self.settings.ui.language {
+            match locale::Locale::load(&resources, &self.settings.ui.language) {
+                Ok(locale) => self.locale = locale,
+                Err(e) => error!(
+                    "Could not load language {:?}: {:?}",
+                    self.settings.ui.language, e
+                ),
+            }
+        }
+        // Labels use the UI font, or the first one there is with imgui's own
+        let label_font = self.settings.ui.font.clone().or_else(|| {
+            resources
+                .list(editor::theme::FONT_DIR, "ttf")
+                .into_iter()
+                .next()
+        });
+        match label_font {
+            Some(font) if self.text.font() != Some(font.as_str()) => {
+                if let Err(e) = self.text.load_font(&resources, &font) {
+                    error!("Could not load label font {:?}: {:?}", font, e);
+                }
+            }
+            Some(_) => {}
+            None => warn!(
+                "No font in {:?}, labels are not drawn",
+                editor::theme::FONT_DIR
+            ),
+        }
+        if let Err(e) =
+            editor::theme::apply_theme(&mut self.imgui, &resources, &self.settings.ui.theme)
+        {
+            error!(
+                "Could not apply theme {:?}: {:?}",
+                self.settings.ui.theme, e
+            );
+        }
+    }
+
+    /// Rebuilds the UI fonts when the font settings or the window's DPI
+    /// changed since they were built.
+    fn update_fonts(&mut self) {
+        let wanted = (
+            self.settings.ui.font.clone(),
+            self.settings.ui.font_size,
+            self.window.scale_factor(),
+        );
+        if wanted == self.built_fonts {
+            return;
+        }
+        let resources = self.world.assets.resources().clone();
+        build_fonts(&mut self.imgui, &resources, &wanted);
+        self.imgui_renderer.reload_font_texture(
+            &mut self.imgui,
+            self.state.device(),
+            self.state.queue(),
+        );
+        self.built_fonts = wanted;
+    }
+
+    fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        winit::dpi::PhysicalSize::<u32> {
+            width: self.state.width(),
+            height: self.state.height(),
+        }
+    }
+
+    /// Resizes to `new_size` once the window stops changing size.
+    fn request_resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.pending_resize = Some((new_size, std::time::Instant::now()));
+    }
+
+    fn resize_pending(&self) -> bool {
+        self.pending_resize.is_some()
+    }
+
+    /// Applies a requested resize that has settled, or any requested one
+    /// with `force`.
+    fn apply_resize(&mut self, force: bool) {
+        match self.pending_resize {
+            Some((size, requested)) if force || requested.elapsed() >= RESIZE_DEBOUNCE => {
+                self.pending_resize = None;
+                // Minimized windows have no size to draw at
+                if size.width > 0 && size.height > 0 {
+                    self.resize(size);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        info!(
+            "Resize from {:?} to {:?}",
+            (self.state.width() as u32, self.state.height() as u32),
+            (new_size.width as u32, new_size.height as u32)
+        );
+        self.camera.resize(new_size.width, new_size.height);
+        self.world.resize_cameras(new_size.width, new_size.height);
+        self.state
+            .recreate_swapchain(new_size.width, new_size.height);
+        let depth_texture = texture::Texture::create_depth_texture(&self.state, "depth_texture");
+        let old = std::mem::replace(&mut self.depth_texture, depth_texture);
+        self.state.texture_pool().release(old);
+        self.framebuffer
+            .update_textures(&self.state, &self.layouts.frame, &[&self.depth_texture]);
+        self.decals
+            .set_depth(&self.state, &self.layouts.texture, &self.depth_texture);
+        self.post.resize(&self.state, &self.layouts.texture);
+        self.reflection
+            .resize(&self.state, &mut self.world.assets.textures);
+        self.world.rebind_materials(
+            &self.state,
+            &self.layouts,
+            render::reflection::REFLECTION_TEXTURE,
+        );
+    }
+
+    fn save_scene(&self) {
+        if let Err(e) = self.world.save(&self.scene_path) {
+            error!("Could not save scene: {:?}", e);
+        }
+    }
+
+    fn toggle_projection(&mut self) {
+        let (width, height) = (self.state.width(), self.state.height());
+        let projection = if self.camera.projection().is_orthographic() {
+            info!("Perspective projection");
+            camera::projection::Projection::new(
+                width,
+                height,
+                CAMERA_FOVY,
+                CAMERA_ZNEAR,
+                CAMERA_ZFAR,
+            )
+        } else {
+            info!("Orthographic projection");
+            camera::projection::Projection::orthographic(
+                width,
+                height,
+                ORTHO_HEIGHT,
+                CAMERA_ZNEAR,
+                CAMERA_ZFAR,
+            )
+        };
+        self.camera.set_projection(projection);
+    }
+
+    fn cycle_window_mode(&mut self) {
+        use winit::window::Fullscreen;
+
+        let mut mode = self.window_mode.next();
+        let fullscreen = match mode {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(self.window.current_monitor())),
+            WindowMode::Exclusive => {
+                let video_mode = self.window.current_monitor().and_then(|monitor| {
+                    monitor.video_modes().max_by_key(|video_mode| {
+                        let size = video_mode.size();
+                        (size.width * size.height, video_mode.refresh_rate())
+                    })
+                });
+                match video_mode {
+                    Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                    None => {
+                        warn!("No video mode for exclusive fullscreen, back to windowed");
+                        mode = WindowMode::Windowed;
+                        None
+                    }
+                }
+            }
+        };
+        self.window.set_fullscreen(fullscreen);
+        self.window_mode = mode;
+        info!("Window mode {:?}", mode);
+        // The window reports its new size too, this covers platforms that
+        // don't before the next frame
+        self.request_resize(self.window.inner_size());
+    }
+
+    fn alt_held(&self) -> bool {
+        self.world
+            .resources()
+            .get::<input::Input>()
+            .map_or(false, |input| input.alt_held())
+    }
+
+    fn toggle_scroll_mode(&mut self) {
+        use camera::flycam::ScrollMode;
+
+        let controller = &mut self.camera_controller;
+        controller.scroll_mode = match controller.scroll_mode {
+            ScrollMode::Dolly => ScrollMode::Fov,
+            ScrollMode::Fov => ScrollMode::Dolly,
+        };
+        info!("Scrolling adjusts {:?}", controller.scroll_mode);
+    }
+
+    fn toggle_raw_camera(&mut self) {
+        let controller = &mut self.camera_controller;
+        controller.raw = !controller.raw;
+        if controller.raw {
+            info!("Raw camera movement");
+        } else {
+            info!("Smoothed camera movement");
+        }
+    }
+
+    /// Stores the camera pose in `slot`, or moves the camera back to it.
+    fn camera_bookmark(&mut self, slot: usize, store: bool) {
+        if store {
+            self.bookmarks[slot] = Some(CameraBookmark {
+                eye: self.camera.eye,
+                at: self.camera.at(),
+            });
+            info!("Stored camera bookmark {}", slot + 1);
+            return;
+        }
+        let bookmark = match self.bookmarks[slot] {
+            Some(bookmark) => bookmark,
+            None => return,
+        };
+        self.camera.look_at(bookmark.eye, bookmark.at);
+        if let CameraMode::Orbit(controller) = &self.camera_mode {
+            self.camera_mode = CameraMode::Orbit(camera::orbit::OrbitController::from_camera(
+                &self.camera,
+                controller.distance,
+                100.0,
+            ));
+        }
+        info!("Recalled camera bookmark {}", slot + 1);
+    }
+
+    /// Frames the bounds of the selected entity.
+    fn focus_selected(&mut self) {
+        let bounds = self
+            .world
+            .selection()
+            .and_then(|entity| self.world.bounds(entity));
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let center = bounds.aabb.center();
+        let radius = bounds.extents().norm() / 2.0;
+        self.camera.frame(center, radius);
+        if let CameraMode::Orbit(controller) = &mut self.camera_mode {
+            controller.focus = center;
+            controller.distance = (self.camera.eye - center).norm();
+        }
+    }
+
+    /// Selects the entity under the window position `(x, y)`, from 0 to 1,
+    /// keeping the selection when nothing is there.
+    fn select_at(&mut self, x: f32, y: f32) {
+        let hit = self
+            .view_camera()
+            .ray_at(x, y)
+            .and_then(|ray| self.world.raycast(&ray, 1024.0));
+        if let Some(hit) = hit {
+            self.world.select(Some(hit.entity));
+        }
+    }
+
+    /// Spawns `model` on whatever is under the cursor, or in front of the
+    /// camera over empty space, and selects it.
+    fn spawn_at_cursor(&mut self, model: &str) {
+        let (x, y) = match self.world.resources().get::<input::Input>() {
+            Some(input) => input.mouse_position,
+            None => return,
+        };
+        let ray = match self.view_camera().ray_at(x as f32, y as f32) {
+            Some(ray) => ray,
+            None => return,
+        };
+        let position = match self.world.raycast(&ray, 1024.0) {
+            Some(hit) => hit.point,
+            None => ray.point_at(SPAWN_DISTANCE),
+        };
+        self.spawn_and_select(model, position);
+    }
+
+    /// Spawns `model` in front of the camera and selects it.
+    fn add_entity(&mut self, model: &str) {
+        let position = self.view_camera().ray().point_at(SPAWN_DISTANCE);
+        self.spawn_and_select(model, position);
+    }
+
+    fn spawn_and_select(&mut self, model: &str, position: nalgebra::Point3<f32>) {
+        match self.world.spawn_model(&self.state, model, position) {
+            Ok(entity) => {
+                self.world.propagate_transforms();
+                self.world.select(Some(entity));
+                self.record_spawn(entity);
+                self.editor.use_asset(model);
+            }
+            Err(e) => error!("Could not spawn {:?}: {:?}", model, e),
+        }
+    }
+
+    fn delete_selected(&mut self) {
+        if let Some(entity) = self.world.selection() {
+            match editor::history::Snapshot::take(&self.world, entity) {
+                Ok(snapshot) => self
+                    .history
+                    .push(editor::history::Command::Remove(snapshot)),
+                Err(e) => warn!("Deleting {:?} cannot be undone: {:?}", entity, e),
+            }
+            self.world.remove_entity(entity);
+            self.world.select(None);
+        }
+    }
+
+    /// Copies the selected entity and selects the copy.
+    fn duplicate_selected(&mut self) {
+        if let Some(entity) = self.world.selection() {
+            match self.world.duplicate(&self.state, entity) {
+                Ok(copy) => {
+                    self.world.select(Some(copy));
+                    self.record_spawn(copy);
+                }
+                Err(e) => error!("Could not duplicate {:?}: {:?}", entity, e),
+            }
+        }
+    }
+
+    fn record_spawn(&mut self, entity: legion::Entity) {
+        match editor::history::Snapshot::take(&self.world, entity) {
+            Ok(snapshot) => self.history.push(editor::history::Command::Spawn(snapshot)),
+            Err(e) => warn!("Spawning {:?} cannot be undone: {:?}", entity, e),
+        }
+    }
+
+    /// Runs `edit` on `entity`, recording what it changed for undo.
+    fn record_edit(
+        &mut self,
+        entity: legion::Entity,
+        edit: impl FnOnce(&mut world::World) -> Result<()>,
+    ) -> Result<()> {
+        let before = self.world.entity_data(entity).ok();
+        edit(&mut self.world)?;
+        if let (Some(before), Ok(after)) = (before, self.world.entity_data(entity)) {
+            if before != after {
+                self.history.edit(entity, before, after, false);
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts playing, remembering the scene to restore when play stops,
+    /// or resumes a paused simulation.
+    fn play(&mut self) {
+        match self.world.play_state() {
+            schedule::PlayState::Edit => {
+                match self.world.scene() {
+                    Ok(scene) => self.play_snapshot = Some(scene),
+                    Err(e) => {
+                        error!("Could not snapshot the scene to play: {:?}", e);
+                        return;
+                    }
+                }
+                self.world.set_play_state(schedule::PlayState::Play);
+                self.lifecycle
+                    .dispatch(&mut self.world, lifecycle::LifecycleEvent::PlayStart);
+            }
+            schedule::PlayState::Paused => self.world.set_play_state(schedule::PlayState::Play),
+            schedule::PlayState::Play => {}
+        }
+    }
+
+    fn pause(&mut self) {
+        if self.world.play_state() == schedule::PlayState::Play {
+            self.world.set_play_state(schedule::PlayState::Paused);
+        }
+    }
+
+    /// Goes back to editing, restoring the scene from before play started.
+    fn stop(&mut self) {
+        if self.world.play_state() == schedule::PlayState::Edit {
+            return;
+        }
+        self.world.set_play_state(schedule::PlayState::Edit);
+        self.lifecycle
+            .dispatch(&mut self.world, lifecycle::LifecycleEvent::PlayStop);
+
+        if let Some(scene) = self.play_snapshot.take() {
+            if let Err(e) = self.world.load_scene(&self.state, &self.layouts, scene) {
+                error!("Could not restore the scene from before play: {:?}", e);
+            }
+        }
+        // The restored entities have new ids
+        self.world.select(None);
+        self.history = editor::history::History::new();
+    }
+
+    fn undo(&mut self) {
+        match self.history.undo(&mut self.world, &self.state) {
+            Ok(true) => info!("Undo"),
+            Ok(false) => info!("Nothing to undo"),
+            Err(e) => error!("Could not undo: {:?}", e),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.history.redo(&mut self.world, &self.state) {
+            Ok(true) => info!("Redo"),
+            Ok(false) => info!("Nothing to redo"),
+            Err(e) => error!("Could not redo: {:?}", e),
+        }
+    }
+
+    /// The entity camera being looked through, or the editor camera.
+    fn view_camera(&self) -> camera::Camera {
+        self.world
+            .active_camera()
+            .map(|(_, camera)| camera)
+            .unwrap_or_else(|| self.camera.clone())
+    }
+
+    /// Clears the selection, returning whether anything was selected.
+    fn deselect(&mut self) -> bool {
+        let selected = self.world.selection().is_some();
+        self.world.select(None);
+        selected
+    }
+
+    fn toggle_debug_draw(&mut self) {
+        self.show_debug = !self.show_debug;
+    }
+
+    fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+    }
+
+    fn toggle_settings(&mut self) {
+        self.show_settings = !self.show_settings;
+        if self.show_settings {
+            let resources = self.world.assets.resources();
+            let mut themes = editor::theme::BUILTIN_THEMES
+                .iter()
+                .map(|theme| theme.to_string())
+                .collect::<Vec<_>>();
+            themes.extend(resources.list(editor::theme::THEME_DIR, "ron"));
+            self.ui_choices = editor::settings::UiChoices {
+                effects: self
+                    .post
+                    .effects()
+                    .iter()
+                    .map(|effect| effect.name.clone())
+                    .collect(),
+                luts: resources.list(render::post::LUT_DIR, "png"),
+                languages: locale::languages(resources),
+                themes,
+                fonts: resources.list(editor::theme::FONT_DIR, "ttf"),
+            };
+        }
+    }
+
+    /// Queues the frustums of the cameras not looked through and the
+    /// picking ray of `view_camera`.
+    fn draw_camera_debug(&mut self, view_camera: &camera::Camera, hit: Option<f32>) {
+        let active = self.world.active_camera().map(|(entity, _)| entity);
+        for (entity, camera) in self.world.camera_views() {
+            if Some(entity) == active {
+                continue;
+            }
+            if let Some(corners) = camera.frustum_corners() {
+                self.debug_lines
+                    .corners(&corners, nalgebra::Vector3::new(1.0, 1.0, 0.0));
+            }
+        }
+        if active.is_some() {
+            if let Some(corners) = self.camera.frustum_corners() {
+                self.debug_lines
+                    .corners(&corners, nalgebra::Vector3::new(1.0, 1.0, 1.0));
+            }
+        }
+
+        let ray = view_camera.ray();
+        match hit {
+            Some(distance) => {
+                self.debug_lines
+                    .ray(&ray, distance, nalgebra::Vector3::new(1.0, 0.0, 0.0));
+                self.debug_lines.cross(
+                    ray.point_at(distance),
+                    0.25,
+                    nalgebra::Vector3::new(0.0, 1.0, 0.0),
+                );
+            }
+            None => self
+                .debug_lines
+                .ray(&ray, CAMERA_ZFAR, nalgebra::Vector3::new(1.0, 0.0, 0.0)),
+        }
+    }
+
+    /// Plays the first camera track, made from the camera bookmarks when
+    /// there is none, or stops playing.
+    fn toggle_camera_path(&mut self) {
+        if let CameraMode::Path { .. } = self.camera_mode {
+            info!("Stopped camera path");
+            self.camera_mode = CameraMode::Fly;
+            return;
+        }
+
+        let entity = match self.world.camera_paths().first() {
+            Some(entity) => *entity,
+            None => {
+                let mut path = camera::path::CameraPath::new(false);
+                for (i, bookmark) in self.bookmarks.iter().flatten().enumerate() {
+                    path.push(camera::path::CameraKey {
+                        time: i as f32 * BOOKMARK_PATH_STEP,
+                        position: bookmark.eye,
+                        rotation: nalgebra::Isometry3::face_towards(
+                            &bookmark.eye,
+                            &bookmark.at,
+                            &nalgebra::Vector3::y(),
+                        )
+                        .rotation,
+                        easing: camera::path::Easing::EaseInOut,
+                    });
+                }
+                if path.duration() <= 0.0 {
+                    info!("Store two camera bookmarks to play them as a path");
+                    return;
+                }
+                self.world.add_camera_path("bookmark path", path)
+            }
+        };
+        info!("Playing camera path {:?}", entity);
+        self.camera_mode = CameraMode::Path { entity, time: 0.0 };
+    }
+
+    /// Looks through the next camera entity, back to the editor camera
+    /// after the last.
+    fn cycle_active_camera(&mut self) {
+        let cameras = self.world.cameras();
+        let next = match self.world.active_camera() {
+            Some((current, _)) => cameras
+                .iter()
+                .position(|camera| *camera == current)
+                .and_then(|index| cameras.get(index + 1))
+                .copied(),
+            None => cameras.first().copied(),
+        };
+        match next {
+            Some(camera) => info!("Looking through camera {:?}", camera),
+            None => info!("Looking through the editor camera"),
+        }
+        self.world.set_active_camera(next);
+    }
+
+    fn toggle_camera_mode(&mut self) {
+        self.camera_mode = match self.camera_mode {
+            CameraMode::Fly => {
+                info!("Orbit camera");
+                CameraMode::Orbit(camera::orbit::OrbitController::from_camera(
+                    &self.camera,
+                    10.0,
+                    100.0,
+                ))
+            }
+            CameraMode::Orbit(_) => {
+                info!("First person camera");
+                CameraMode::FirstPerson(camera::fps::FirstPersonController::new(
+                    self.player,
+                    0.7,
+                    100.0,
+                ))
+            }
+            CameraMode::Path { .. } => {
+                info!("Fly camera");
+                CameraMode::Fly
+            }
+            CameraMode::FirstPerson(_) => {
+                info!("Fly camera");
+                // Stop walking once nothing drives the character
+                if let Err(e) =
+                    self.world
+                        .set_character_input(self.player, nalgebra::Vector3::zeros(), false)
+                {
+                    error!("Could not stop character: {:?}", e);
+                }
+                CameraMode::Fly
+            }
+        };
+    }
+
+    fn set_cursor_grab(&mut self, grab: bool) {
+        if grab == self.cursor_grabbed {
+            return;
+        }
+        if let Err(e) = self.window.set_cursor_grab(grab) {
+            error!("Could not grab cursor: {}", e);
+            return;
+        }
+        self.window.set_cursor_visible(!grab);
+        self.cursor_grabbed = grab;
+        // Let imgui set its cursor again once released
+        self.last_cursor = None;
+        if let Some(mut input) = self.world.resources().get_mut::<input::Input>() {
+            input.relative_mouse = grab;
+        }
+    }
+
+    fn mouse_motion(&mut self, delta: (f64, f64)) {
+        if self.world.is_replaying() {
+            return;
+        }
+        let (width, height) = (self.state.width() as f64, self.state.height() as f64);
+        if let Some(mut input) = self.world.resources().get_mut::<input::Input>() {
+            input.process_motion((delta.0 / width, delta.1 / height));
+        }
+    }
+
+    fn load_scene(&mut self) {
+        match self
+            .world
+            .load(&self.state, &self.layouts, &self.scene_path)
+        {
+            Ok(()) => self
+                .lifecycle
+                .dispatch(&mut self.world, lifecycle::LifecycleEvent::SceneLoaded),
+            Err(e) => error!("Could not load scene: {}", error::describe(&e)),
+        }
+    }
+
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        if let Some(event) = events::WindowEvent::from_winit(event) {
+            self.world.send_event(event);
+        }
+        if let WindowEvent::Focused(false) = event {
+            self.set_cursor_grab(false);
+        }
+        // The replay drives the input meanwhile
+        if self.world.is_replaying() {
+            return false;
+        }
+
+        let mut input = match self.world.resources().get_mut::<input::Input>() {
+            Some(input) => input,
+            None => return false,
+        };
+
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => input.process_key(*key, *state),
+            WindowEvent::MouseWheel { delta, .. } => {
+                input.process_scroll(delta);
+                true
+            }
+            WindowEvent::MouseInput { button, state, .. } => {
+                input.process_button(*button, *state);
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                input.process_cursor((
+                    position.to_logical::<f64>(self.state.width() as f64).x,
+                    position.to_logical::<f64>(self.state.height() as f64).y,
+                ));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update(&mut self, dt: std::time::Duration) {
+        /*let old_position: cgmath::Vector3<_> = self.light.position.into();
+        self.light.position = cgmath::Quaternion::from_axis_angle(
+            (0.0, 1.0, 0.0).into(),
+            cgmath::Deg(60.0 * dt.as_secs_f32()),
+        ) * old_position;
+        self.light_buffer.write(&self.state, &[self.light]);*/
+        self.imgui.io_mut().update_delta_time(dt);
+        let dt = self.world.begin_frame(dt);
+
+        // Typing into an editor field doesn't trigger the editing shortcuts
+        let shortcuts = !self.imgui.io().want_capture_keyboard;
+        let (undo, redo, toggle_play) = match self.world.resources().get::<input::Input>() {
+            Some(input) if shortcuts && input.ctrl_held() => (
+                input.key_pressed(VirtualKeyCode::Z),
+                input.key_pressed(VirtualKeyCode::Y),
+                input.key_pressed(VirtualKeyCode::P),
+            ),
+            _ => (false, false, false),
+        };
+        let (toggle_grab, focus, bookmark, delete, duplicate) =
+            match self.world.resources().get::<input::Input>() {
+                Some(input) => (
+                    input.action_pressed("grab_cursor"),
+                    input.action_pressed("focus"),
+                    BOOKMARK_KEYS
+                        .iter()
+                        .position(|key| input.key_pressed(*key))
+                        .map(|slot| (slot, input.ctrl_held())),
+                    shortcuts && input.action_pressed("delete"),
+                    shortcuts && input.ctrl_held() && input.key_pressed(VirtualKeyCode::D),
+                ),
+                None => (false, false, None, false, false),
+            };
+        if toggle_grab {
+            self.set_cursor_grab(!self.cursor_grabbed);
+        }
+        if focus {
+            self.focus_selected();
+        }
+        if let Some((slot, store)) = bookmark {
+            self.camera_bookmark(slot, store);
+        }
+        if delete {
+            self.delete_selected();
+        }
+        if undo {
+            self.undo();
+        }
+        if redo {
+            self.redo();
+        }
+        if duplicate {
+            self.duplicate_selected();
+        }
+        if toggle_play {
+            if self.world.play_state() == schedule::PlayState::Edit {
+                self.play();
+            } else {
+                self.stop();
+            }
+        }
+
+        let want_capture_mouse = self.imgui.io().want_capture_mouse && !self.cursor_grabbed;
+        let click = match self.world.resources().get::<input::Input>() {
+            Some(input) => {
+                if input.action_pressed("select") && !want_capture_mouse && !self.cursor_grabbed {
+                    self.click_start = Some(input.mouse_position);
+                }
+                match self.click_start {
+                    Some((start_x, start_y)) if !input.action("select") => {
+                        self.click_start = None;
+                        let (x, y) = input.mouse_position;
+                        let moved = (x - start_x).abs().max((y - start_y).abs());
+                        if moved <= CLICK_SLOP {
+                            Some((x, y))
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            None => None,
+        };
+        if let Some((x, y)) = click {
+            self.select_at(x as f32, y as f32);
+        }
+        let walk = match self.world.resources().get::<input::Input>() {
+            Some(input) => {
+                let look = if (input.action("look") || self.cursor_grabbed) && !want_capture_mouse {
+                    Some((
+                        input.axis("look_horizontal") as f64,
+                        input.axis("look_vertical") as f64,
+                    ))
+                } else {
+                    None
+                };
+                let scroll = if want_capture_mouse {
+                    0.0
+                } else {
+                    input.axis("zoom")
+                };
+                match &mut self.camera_mode {
+                    CameraMode::Fly => {
+                        if let Some((dx, dy)) = look {
+                            self.camera_controller.process_mouse(dx, dy);
+                        }
+                        if scroll != 0.0 {
+                            self.camera_controller.process_scroll(&self.camera, scroll);
+                        }
+                        self.camera_controller
+                            .update_camera(&mut self.camera, &input, dt);
+                        None
+                    }
+                    CameraMode::FirstPerson(controller) => {
+                        if let Some((dx, dy)) = look {
+                            controller.process_mouse(dx, dy);
+                        }
+                        Some(controller.update_camera(&mut self.camera, &input, dt))
+                    }
+                    CameraMode::Orbit(controller) => {
+                        if let Some((dx, dy)) = look {
+                            controller.process_mouse(dx, dy);
+                        }
+                        controller.process_scroll(scroll);
+                        controller.update_camera(&mut self.camera, &input, dt);
+                        None
+                    }
+                    CameraMode::Path { .. } => None,
+                }
+            }
+            None => None,
+        };
+        if let CameraMode::FirstPerson(controller) = &self.camera_mode {
+            if let Some((movement, jump)) = walk {
+                if let Err(e) = self
+                    .world
+                    .set_character_input(controller.target, movement, jump)
+                {
+                    error!("Could not move character: {:?}", e);
+                }
+            }
+            if let Some(position) = self.world.position(controller.target) {
+                controller.follow(&mut self.camera, position);
+            }
+        }
+        if let CameraMode::Path { entity, time } = &mut self.camera_mode {
+            *time += dt.as_secs_f32();
+            match self.world.camera_path_pose(*entity, *time) {
+                Some(pose) => {
+                    let eye = nalgebra::Point3::from(pose.translation.vector);
+                    self.camera
+                        .look_at(eye, eye + pose.rotation * nalgebra::Vector3::z());
+                }
+                None => {
+                    info!("Camera path finished");
+                    self.camera_mode = CameraMode::Fly;
+                }
+            }
+        }
+        let active = self.world.active_camera().map(|(_, camera)| camera);
+        self.world
+            .update_camera(active.as_ref().unwrap_or(&self.camera));
+
+        self.lifecycle
+            .dispatch(&mut self.world, lifecycle::LifecycleEvent::Frame(dt));
+        self.world.update(&self.state, &self.layouts);
+
+        self.autosave.heartbeat();
+        // Only the scene being edited is worth recovering
+        if !self.recovery_pending && self.world.play_state() == schedule::PlayState::Edit {
+            self.autosave.update(&self.world);
+        }
+    }
+
+    /// Loads the autosave of the crashed session, or discards it.
+    fn resolve_recovery(&mut self, recover: bool) {
+        self.recovery_pending = false;
+        if !recover {
+            info!("Discarded the autosave");
+            self.autosave.discard();
+            return;
+        }
+        let path = self.autosave.path().to_path_buf();
+        match self.world.load(&self.state, &self.layouts, &path) {
+            Ok(()) => {
+                info!("Recovered the scene from {:?}", path);
+                self.world.select(None);
+                self.history = editor::history::History::new();
+                self.lifecycle
+                    .dispatch(&mut self.world, lifecycle::LifecycleEvent::SceneLoaded);
+            }
+            Err(e) => error!("Could not recover the scene: {}", error::describe(&e)),
+        }
+    }
+
+    /// Removes the autosave on a clean exit, unless it still waits to be
+    /// recovered, and frees its slot.
+    fn exit(&self) {
+        if !self.recovery_pending {
+            self.autosave.discard();
+        }
+        self.autosave.release();
+    }
+
+    /// The editor state to restore on the next run.
+    fn session(&self) -> session::Session {
+        session::Session {
+            camera: Some(session::CameraPose {
+                eye: self.camera.eye.coords.into(),
+                at: self.camera.at().coords.into(),
+            }),
+            scene: Some(self.scene_path.clone()),
+            selected: self
+                .world
+                .selection()
+                .and_then(|entity| self.world.name(entity)),
+            panels: session::OpenPanels {
+                stats: self.show_stats,
+                settings: self.show_settings,
+                debug: self.show_debug,
+                game_window: self.game_window.is_some(),
+            },
+            recent_assets: self.editor.recent_assets().to_vec(),
+        }
+    }
+
+    fn save_session(&self) {
+        if let Err(e) = self.session().save(session::SESSION_FILE) {
+            error!("Could not save the session: {:?}", e);
+        }
+    }
+
+    /// Puts the camera, the selection and the windows back where `session`
+    /// left them. The selection only carries over to the same scene, and
+    /// only for named entities.
+    fn restore_session(&mut self, session: session::Session) {
+        if let Some(pose) = session.camera {
+            self.camera.look_at(pose.eye.into(), pose.at.into());
+        }
+        if session.scene.as_ref() == Some(&self.scene_path) {
+            if let Some(name) = session.selected {
+                let entity = self.world.find_by_name(&name);
+                self.world.select(entity);
+            }
+        }
+        self.show_stats = session.panels.stats;
+        self.show_debug = session.panels.debug;
+        if session.panels.settings != self.show_settings {
+            self.toggle_settings();
+        }
+        self.game_window_requested = session.panels.game_window;
+        self.editor.set_recent_assets(session.recent_assets);
+    }
+
+    fn render(&mut self, dt: std::time::Duration) -> Result<(), wgpu::SwapChainError> {
+        struct UIData<'a> {
+            entry: Option<legion::world::Entry<'a>>,
+            models: Vec<String>,
+            prefabs: Vec<String>,
+            layers: Vec<String>,
+            name: Option<String>,
+        }
+
+        let mut encoder = self.state.encoder();
+
+        let sc = self.state.frame()?.output;
+
+        // Entity cameras are seen through instead of the editor camera
+        let view_camera = self.view_camera();
+        let preview = self.world.preview_camera();
+        let preview_texture = self.preview.texture_id;
+        let preview_detached = self.game_window.is_some();
+        let preview_size = [self.preview.width as f32, self.preview.height as f32];
+
+        let hit = self.world.raycast(&view_camera.ray(), 1024.0);
+        if self.show_debug {
+            self.draw_camera_debug(&view_camera, hit.as_ref().map(|hit| hit.distance));
+        }
+        self.debug_lines.upload(&self.state);
+        for (position, label) in self.world.labels(self.show_debug, view_camera.layers) {
+            self.text
+                .text(position, label.text, label.height, label.color.into());
+        }
+        self.text
+            .upload(&self.state, view_camera.right(), view_camera.up());
+        let mut sprites = self.world.sprites(view_camera.layers);
+        self.sprites.upload(
+            &self.state,
+            &mut sprites,
+            &self.world.assets.textures,
+            &self.layouts.texture,
+        );
+        let inspected = self.world.selection();
+        let inspected_before = inspected.and_then(|entity| self.world.entity_data(entity).ok());
+        let hierarchy = self.world.hierarchy();
+
+        self.thumbnails.update(
+            &self.state,
+            &mut self.imgui_renderer,
+            &mut encoder,
+            &self.pipelines,
+            &self.layouts,
+            &mut self.world.assets,
+            &self.light_group,
+            |camera| {
+                let mut uniforms = Uniforms::new();
+                uniforms.update_view_proj(camera);
+                uniforms
+            },
+        );
+        // Chosen by the view camera for every view, so they agree on which
+        // entities are billboards
+        let mut billboards = self.world.billboards(&view_camera);
+        self.billboards.upload(
+            &self.state,
+            &mut encoder,
+            &self.pipelines,
+            &self.layouts,
+            &self.world.assets,
+            &self.light_group,
+            |camera| {
+                let mut uniforms = Uniforms::new();
+                uniforms.update_view_proj(camera);
+                uniforms
+            },
+            &mut billboards,
+        );
+
+        let asset_list = editor::assets::AssetList {
+            models: self.world.assets.models.names().cloned().collect(),
+            materials: self.world.assets.materials.names().cloned().collect(),
+            textures: self.world.assets.textures.names().cloned().collect(),
+            recent: self
+                .editor
+                .recent_assets()
+                .iter()
+                .filter(|name| self.world.assets.models.contains(name.as_str()))
+                .cloned()
+                .collect(),
+        };
+        let models = asset_list.models.clone();
+        let prefabs = self.world.prefab_names().cloned().collect::<Vec<_>>();
+        let layers = self.world.collision_layer_names().to_vec();
+        let loading = self.world.assets.loader.status();
+        let materials = inspected
+            .map(|entity| self.world.entity_materials(entity))
+            .unwrap_or_default();
+        let (input_map, pressed) = match self.world.resources().get::<input::Input>() {
+            Some(input) if self.show_settings => (input.map.clone(), input.pressed_binding()),
+            _ => (input::InputMap::new(), None),
+        };
+        let stats = if self.show_stats {
+            Some((self.world.stats().clone(), self.world.entity_count()))
+        } else {
+            None
+        };
+
+        let entry = if let Some(entity) = inspected {
+            if let Some(entry) = self.world.entry(entity) {
+                Some(entry)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let name = entry.as_ref().and_then(|entry| {
+            entry
+                .get_component::<world::Name>()
+                .ok()
+                .map(|name| name.0.clone())
+        });
+        let ui_data = UIData {
+            entry,
+            models,
+            prefabs,
+            layers,
+            name,
+        };
+
+        let mut updated_transform = false;
+        let mut make_prefab = false;
+        let mut collider_shape: Option<physics::ColliderShape> = None;
+        let mut collision_layers: Option<physics::CollisionLayers> = None;
+        let mut instantiate: Option<String> = None;
+        let mut detach_preview = false;
+        let mut recovery = None;
+        let recovery_pending = self.recovery_pending;
+        let mut hierarchy_actions = Vec::new();
+        let mut asset_actions = Vec::new();
+        let mut material_actions = Vec::new();
+        let mut light_changed = false;
+        let mut play_action = None;
+        let play_state = self.world.play_state();
+        let editor_state = &mut self.editor;
+        let light_settings = &mut self.light_settings;
+        let log = &self.log;
+        let settings = &mut self.settings;
+        let show_settings = &mut self.show_settings;
+        let ui_choices = &self.ui_choices;
+        let locale = &self.locale;
+        let mut settings_changed = false;
+        let thumbnails = &self.thumbnails;
+        let inspectors = &self.inspectors;
+
+        self.update_fonts();
+        let show_editor = self.show_editor;
+        let ui = self.imgui.frame();
+        if show_editor {
+            let window = imgui::Window::new(im_str!("Hello Imgui from WGPU!"));
+            window
+                .size(
+                    [self.state.width() as f32, self.state.height() as f32],
+                    Condition::Always,
+                )
+                .title_bar(false)
+                .position([0.0, 0.0], Condition::Always)
+                .draw_background(false)
+                .menu_bar(false)
+                .bring_to_front_on_focus(false)
+                .mouse_inputs(false)
+                .build(&ui, || {
+                    ui.text(im_str!(
+                        "{}: {}",
+                        locale.get("overlay.fps"),
+                        (1.0 / dt.as_secs_f32()).round()
+                    ));
+                    ui.separator();
+                    let mouse_pos = ui.io().mouse_pos;
+                    ui.text(im_str!(
+                        "{}: ({:.1}, {:.1})",
+                        locale.get("overlay.mouse"),
+                        mouse_pos[0],
+                        mouse_pos[1],
+                    ));
+
+                    hierarchy_actions = editor::hierarchy::panel(
+                        &ui,
+                        locale,
+                        editor_state,
+                        &hierarchy,
+                        inspected,
+                        &asset_list.models,
+                    );
+                    asset_actions =
+                        editor::assets::panel(&ui, locale, editor_state, &asset_list, thumbnails);
+                    light_changed = editor::light::panel(&ui, locale, light_settings);
+                    play_action = editor::play::toolbar(&ui, locale, play_state);
+                    editor::console::panel(&ui, locale, editor_state, log);
+                    if *show_settings {
+                        settings_changed = editor::settings::panel(
+                            &ui,
+                            locale,
+                            editor_state,
+                            settings,
+                            &input_map,
+                            ui_choices,
+                            pressed,
+                            show_settings,
+                        );
+                    }
+                    if let Some((stats, entity_count)) = &stats {
+                        editor::stats::overlay(&ui, locale, stats, *entity_count);
+                    }
+                    if !materials.is_empty() {
+                        material_actions = editor::material::panel(
+                            &ui,
+                            locale,
+                            &materials,
+                            &asset_list.textures,
+                            thumbnails,
+                        );
+                    }
+
+                    if !loading.is_empty() {
+                        let loading_window = imgui::Window::new(&locale.label("windows.loading"));
+
+                        loading_window.always_auto_resize(true).build(&ui, || {
+                            for (name, status) in loading.iter() {
+                                let overlay = match status {
+                                    assets::LoadStatus::Failed(e) => im_str!("{}: {}", name, e),
+                                    status => im_str!("{}: {:?}", name, status),
+                                };
+                                imgui::ProgressBar::new(status.progress())
+                                    .overlay_text(&overlay)
+                                    .size([300.0, 0.0])
+                                    .build(&ui);
+                            }
+                        });
+                    }
+
+                    if preview.is_some() && !preview_detached {
+                        let preview_window =
+                            imgui::Window::new(&locale.label("windows.camera_preview"));
+
+                        preview_window.always_auto_resize(true).build(&ui, || {
+                            imgui::Image::new(preview_texture, preview_size).build(&ui);
+                            detach_preview = ui.button(&locale.label("windows.detach"), [0.0, 0.0]);
+                        });
+                    }
+
+                    if recovery_pending {
+                        let recover_window = imgui::Window::new(&locale.label("windows.recover"));
+
+                        recover_window.always_auto_resize(true).build(&ui, || {
+                            ui.text(locale.get("windows.recover_prompt"));
+                            if ui.button(&locale.label("windows.recover_load"), [0.0, 0.0]) {
+                                recovery = Some(true);
+                            }
+                            ui.same_line(0.0);
+                            if ui.button(&locale.label("windows.recover_discard"), [0.0, 0.0]) {
+                                recovery = Some(false);
+                            }
+                        });
+                    }
+
+                    if !ui_data.prefabs.is_empty() {
+                        let prefab_window = imgui::Window::new(&locale.label("windows.prefabs"));
+
+                        prefab_window.always_auto_resize(true).build(&ui, || {
+                            for prefab in ui_data.prefabs.iter() {
+                                if ui.button(&im_str!("{}", prefab), [0.0, 0.0]) {
+                                    instantiate = Some(prefab.clone());
+                                }
+                            }
+                        });
+                    }
+
+                    if ui_data.entry.is_some() {
+                        // The ### suffix keeps the window id stable across entities
+                        let title = match &ui_data.name {
+                            Some(name) => im_str!("{}###Inspect", name),
+                            None => im_str!(
+                                "{} {:?}###Inspect",
+                                locale.get("inspector.entity"),
+                                inspected.unwrap()
+                            ),
+                        };
+                        let inspect_window =
+                            editor::layout::window(&ui, editor::layout::Panel::Inspector, &title);
+
+                        inspect_window.always_auto_resize(true).build(&ui, || {
+                            if let Some(mut entry) = ui_data.entry {
+                                if inspectors.render(&mut entry, &ui) {
+                                    updated_transform = true;
+                                }
+                                if let Ok(bounds) = entry.get_component::<bounds::Bounds>() {
+                                    let center = bounds.center();
+                                    let extents = bounds.extents();
+                                    ui.text(im_str!(
+                                        "{}: ({:.2}, {:.2}, {:.2}) {} ({:.2}, {:.2}, {:.2})",
+                                        locale.get("inspector.bounds"),
+                                        center.x,
+                                        center.y,
+                                        center.z,
+                                        locale.get("inspector.size"),
+                                        extents.x,
+                                        extents.y,
+                                        extents.z,
+                                    ));
+                                }
+                                {
+                                    ui.text(locale.get("inspector.model"));
+                                    let model = entry.get_component_mut::<world::ModelIdent>().ok();
+                                    if let Some(mut model) = model {
+                                        let mut index = ui_data
+                                            .models
+                                            .iter()
+                                            .enumerate()
+                                            .find(|(_, m)| *m == &model.0)
+                                            .map(|(i, _)| i)
+                                            .expect("Must have model");
+                                        let init = index;
+                                        let imstrs = ui_data
+                                            .models
+                                            .iter()
+                                            .map(|m| im_str!("{}", m))
+                                            .collect::<Vec<_>>();
+                                        ComboBox::new(&locale.label("inspector.model"))
+                                            .build_simple(
+                                                &ui,
+                                                &mut index,
+                                                imstrs.as_slice(),
+                                                &|s: &ImString| s.into(),
+                                            );
+
+                                        if init != index {
+                                            model.0 = ui_data.models[index].clone();
+                                            updated_transform = true;
+                                        }
+                                    }
+                                }
+                                {
+                                    let shape = entry
+                                        .get_component::<physics::ColliderShape>()
+                                        .ok()
+                                        .copied()
+                                        .unwrap_or_default();
+                                    let mut index = physics::ColliderShape::ALL
+                                        .iter()
+                                        .position(|s| *s == shape)
+                                        .unwrap_or(0);
+                                    let names = physics::ColliderShape::ALL
+                                        .iter()
+                                        .map(|s| im_str!("{:?}", s))
+                                        .collect::<Vec<_>>();
+                                    if ComboBox::new(&locale.label("inspector.collider"))
+                                        .build_simple(
+                                            &ui,
+                                            &mut index,
+                                            names.as_slice(),
+                                            &|s: &ImString| s.into(),
+                                        )
+                                    {
+                                        collider_shape = Some(physics::ColliderShape::ALL[index]);
+                                    }
+                                }
+                                {
+                                    let layers = entry
+                                        .get_component::<physics::CollisionLayers>()
+                                        .ok()
+                                        .cloned()
+                                        .unwrap_or_default();
+                                    let mut edited = layers.clone();
+                                    ui.text(locale.get("inspector.collision_layers"));
+                                    for layer in ui_data.layers.iter() {
+                                        let mut member = edited.is_member(layer);
+                                        if ui.checkbox(&im_str!("{}##member", layer), &mut member) {
+                                            edited.set_member(layer, member);
+                                        }
+                                        ui.same_line(0.0);
+                                        let mut collides = edited.collides_with(layer);
+                                        if ui.checkbox(
+                                            &im_str!(
+                                                "{}##{}",
+                                                locale.get("inspector.collides"),
+                                                layer
+                                            ),
+                                            &mut collides,
+                                        ) {
+                                            edited.set_collides_with(
+                                                layer,
+                                                collides,
+                                                &ui_data.layers,
+                                            );
+                                        }
+                                    }
+                                    if edited != layers {
+                                        collision_layers = Some(edited);
+                                    }
+                                }
+                                if entry.get_component::<prefab::PrefabInstance>().is_err()
+                                    && ui.button(&locale.label("inspector.make_prefab"), [0.0, 0.0])
+                                {
+                                    make_prefab = true;
+                                }
+                            }
+                        });
+                    }
+                });
+        }
+
+        // Sliders held down keep merging into the same undo step
+        let dragging = ui.is_any_item_active();
+
+        if updated_transform {
+            self.world.propagate_transforms();
+            self.world
+                .update_entity_world_transform(inspected.unwrap())
+                .expect("Internal err");
+        }
+
+        if let Some(shape) = collider_shape {
+            if let Err(e) = self.world.set_collider_shape(inspected.unwrap(), shape) {
+                error!("Could not change collider: {}", error::describe(&e));
+            }
+        }
+
+        if let Some(layers) = collision_layers {
+            if let Err(e) = self.world.set_collision_layers(inspected.unwrap(), layers) {
+                error!("Could not change collision layers: {}", error::describe(&e));
+            }
+        }
+
+        if make_prefab {
+            let name = format!("prefab {}", self.world.prefab_names().count());
+            if let Err(e) = self.world.create_prefab(name, inspected.unwrap()) {
+                error!("Could not create prefab: {}", error::describe(&e));
+            }
+        }
+
+        // Everything the inspector changed this frame is one undo step
+        if let (Some(entity), Some(before)) = (inspected, inspected_before) {
+            if let Ok(after) = self.world.entity_data(entity) {
+                if after != before {
+                    self.history.edit(entity, before, after, dragging);
+                }
+            }
+        }
+
+        for action in hierarchy_actions {
+            use editor::hierarchy::HierarchyAction;
+
+            let result = match action {
+                HierarchyAction::Select(entity) => {
+                    self.world.select(Some(entity));
+                    Ok(())
+                }
+                HierarchyAction::Rename(entity, name) => {
+                    self.record_edit(entity, |world| world.set_name(entity, &name))
+                }
+                HierarchyAction::SetVisible(entity, visible) => {
+                    self.world.set_visible(entity, visible)
+                }
+                HierarchyAction::Reparent(entity, parent) => {
+                    let before = self.world.parent(entity);
+                    self.world.set_parent(entity, parent).map(|()| {
+                        self.world.propagate_transforms();
+                        self.history.push(editor::history::Command::Reparent {
+                            entity,
+                            before,
+                            after: parent,
+                        });
+                    })
+                }
+                HierarchyAction::Add(model) => {
+                    self.add_entity(&model);
+                    Ok(())
+                }
+                HierarchyAction::Delete => {
+                    self.delete_selected();
+                    Ok(())
+                }
+                HierarchyAction::Duplicate => {
+                    self.duplicate_selected();
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                error!("Could not edit hierarchy: {}", error::describe(&e));
+            }
+        }
+
+        for action in asset_actions {
+            match action {
+                editor::assets::AssetAction::Spawn(model) => self.spawn_at_cursor(&model),
+            }
+        }
+
+        match play_action {
+            Some(editor::play::PlayAction::Play) => self.play(),
+            Some(editor::play::PlayAction::Pause) => self.pause(),
+            Some(editor::play::PlayAction::Step) => self.world.step(),
+            Some(editor::play::PlayAction::Stop) => self.stop(),
+            None => {}
+        }
+
+        for action in material_actions {
+            let result = match &action {
+                editor::material::MaterialAction::SetBaseColor(source, color) => {
+                    self.world.set_material_color(&self.state, source, *color)
+                }
+                editor::material::MaterialAction::SetTexture(source, slot, texture) => self
+                    .world
+                    .set_material_texture(&self.state, &self.layouts, source, *slot, texture),
+            };
+            if let Err(e) = result {
+                error!("Could not edit material: {}", error::describe(&e));
+            }
+        }
+
+        if settings_changed {
+            self.apply_settings();
+            if let Err(e) = self.settings.save(settings::SETTINGS_FILE) {
+                error!("Could not save settings: {:?}", e);
+            }
+        }
+
+        if light_changed {
+            self.light = Light::from_settings(&self.light_settings);
+            self.light_buffer.write(&self.state, &[self.light]);
+        }
+
+        if detach_preview {
+            self.game_window_requested = true;
+        }
+
+        if let Some(recover) = recovery {
+            self.resolve_recovery(recover);
+        }
+
+        if let Some(prefab) = instantiate {
+            match self.world.instantiate(&self.state, &prefab, None) {
+                Ok(entity) => self.record_spawn(entity),
+                Err(e) => error!(
+                    "Could not instantiate prefab {:?}: {}",
+                    prefab,
+                    error::describe(&e)
+                ),
+            }
+        }
+
+        self.world.run_stage(schedule::Stage::Extract);
+        self.uniforms.update_view_proj(&view_camera);
+        if let Some(jitter) = self.post.jitter(&self.state) {
+            self.uniforms.view_proj =
+                Matrix4::new_translation(&nalgebra::Vector3::new(jitter.x, jitter.y, 0.0))
+                    * self.uniforms.view_proj;
+        }
+        self.uniform_buffer.write(&self.state, &[self.uniforms]);
+        let mut decals = self.world.decals(view_camera.layers);
+        self.decals.upload(
+            &self.state,
+            self.uniforms.view_proj,
+            &mut decals,
+            &self.world.assets.textures,
+            &self.layouts.texture,
+        );
+        let (delta, elapsed) = self
+            .world
+            .resources()
+            .get::<schedule::Time>()
+            .map(|time| (time.delta_seconds(), time.elapsed.as_secs_f32()))
+            .unwrap_or_default();
+        self.particles.simulate(
+            &self.state,
+            &mut encoder,
+            delta,
+            elapsed,
+            &self.world.particle_emitters(view_camera.layers),
+        );
+
+        let culling = if self.settings.graphics.gpu_culling {
+            Some(&self.culling)
+        } else {
+            None
+        };
+        {
+            // Seen from behind, a mirror shows nothing
+            let mirror = self
+                .world
+                .mirror_plane()
+                .filter(|(plane, _)| plane.distance(&view_camera.eye) > 0.0);
+            let mirror_camera =
+                mirror.map(|(plane, _)| render::reflection::mirror_camera(&view_camera, &plane));
+            if let (Some((plane, clip)), Some(camera)) = (&mirror, &mirror_camera) {
+                let mut uniforms = Uniforms::new();
+                uniforms.update_view_proj(camera);
+                uniforms.view_proj = render::reflection::view_proj(&view_camera, plane, clip);
+                self.reflection.write_uniforms(&self.state, &uniforms);
+                if let Err(e) =
+                    self.world
+                        .prepare_indirect(&self.state, &mut encoder, culling, camera, true)
+                {
+                    error!("Error preparing reflection draws: {}", e);
+                }
+            }
+
+            // Cleared without a mirror, so materials sampling it don't show
+            // a stale reflection
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] = &[&(
+                self.reflection.view(),
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.3,
+                    a: 1.0,
+                }),
+            )];
+            let depth_attachment: &dyn renderpass::IntoDepthAttachment = &(
+                &self.reflection.depth_texture.view,
+                wgpu::LoadOp::Clear(1.0),
+            );
+            let mut render_pass =
+                renderpass::render_pass(&mut encoder, color_attachments, depth_attachment);
+
+            if let Some(camera) = &mirror_camera {
+                self.world
+                    .render_reflection(
+                        &self.state,
+                        &mut render_pass,
+                        &self.pipelines,
+                        camera,
+                        &self.reflection.uniform_group,
+                        &self.light_group,
+                    )
+                    .expect("Error rendering reflection");
+                render_pass.set_pipeline(&self.pipelines.billboard);
+                render_pass.draw_billboards(&self.billboards, &self.reflection.uniform_group);
+            }
+        }
+
+        if let Err(e) =
+            self.world
+                .prepare_indirect(&self.state, &mut encoder, culling, &view_camera, false)
+        {
+            error!("Error preparing draws: {}", e);
+        }
+
+        // Effects read the scene from their own target
+        let scene_view = if self.post.is_active() {
+            self.post.target()
+        } else {
+            &sc.view
+        };
+        {
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] = &[&(
+                scene_view,
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.3,
+                    a: 1.0,
+                }),
+            )];
+
+            let depth_attachment: &dyn renderpass::IntoDepthAttachment =
+                &(&self.depth_texture.view, wgpu::LoadOp::Clear(1.0));
+
+            let mut render_pass =
+                renderpass::render_pass(&mut encoder, color_attachments, depth_attachment);
+
+            self.world
+                .render(
+                    &self.state,
+                    &mut render_pass,
+                    &self.pipelines,
+                    &view_camera,
+                    &self.uniform_group,
+                    &self.light_group,
+                )
+                .expect("Error rendering");
+
+            render_pass.set_pipeline(&self.pipelines.billboard);
+            render_pass.draw_billboards(&self.billboards, &self.uniform_group);
+
+            render_pass.set_pipeline(
+                self.pipelines
+                    .light
+                    .get(self.obj_model.geometry.index_format()),
+            );
+            render_pass.draw_light_model(&self.obj_model, &self.uniform_group, &self.light_group);
+        }
+
+        {
+            // Decals read the depth of the scene drawn so far, so it can't
+            // be attached
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] =
+                &[&(scene_view, wgpu::LoadOp::Load)];
+            let mut render_pass = renderpass::render_pass(&mut encoder, color_attachments, None);
+            render_pass.set_pipeline(&self.pipelines.decal);
+            render_pass.draw_decals(&self.decals);
+        }
+
+        {
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] =
+                &[&(scene_view, wgpu::LoadOp::Load)];
+            let depth_attachment: &dyn renderpass::IntoDepthAttachment =
+                &(&self.depth_texture.view, wgpu::LoadOp::Load);
+            let mut render_pass =
+                renderpass::render_pass(&mut encoder, color_attachments, depth_attachment);
+
+            render_pass.draw_particles(&self.particles, &self.uniform_group);
+
+            render_pass.set_pipeline(&self.pipelines.grid);
+            render_pass.draw_grid(&self.grid, &self.uniform_group);
+
+            render_pass.set_pipeline(&self.pipelines.debug);
+            render_pass.draw_debug_lines(&self.debug_lines, &self.uniform_group);
+
+            render_pass.set_pipeline(&self.pipelines.text);
+            render_pass.draw_text(&self.text, &self.uniform_group);
+
+            // Drawn last, over everything in the scene
+            render_pass.set_pipeline(&self.pipelines.sprite);
+            render_pass.draw_sprites(&self.sprites);
+        }
+
+        if self.post.is_active() {
+            let time = self
+                .world
+                .resources()
+                .get::<schedule::Time>()
+                .map(|time| time.elapsed.as_secs_f32())
+                .unwrap_or_default();
+            self.post.apply(&self.state, &mut encoder, &sc.view, time);
+        }
+
+        // Held until the frame is submitted, presented when dropped
+        let game_frame = match (&self.game_window, &preview) {
+            (Some(window), Some(_)) => match self.state.window_frame(window.id()) {
+                Some(Ok(frame)) => Some(frame.output),
+                Some(Err(e)) => {
+                    warn!("Could not get game view frame: {:?}", e);
+                    None
+                }
+                None => None,
+            },
+            _ => None,
+        };
+        if let Some((_, mut camera)) = preview {
+            let target = match (&self.game_window, &game_frame) {
+                (Some(window), Some(frame)) => {
+                    let (width, height) = window.size();
+                    camera.resize(width, height);
+                    let mut uniforms = Uniforms::new();
+                    uniforms.update_view_proj(&camera);
+                    window.write_uniforms(&self.state, &uniforms);
+                    Some((
+                        &frame.view,
+                        &window.depth_texture.view,
+                        &window.uniform_group,
+                    ))
+                }
+                _ => {
+                    camera.resize(self.preview.width, self.preview.height);
+                    let mut uniforms = Uniforms::new();
+                    uniforms.update_view_proj(&camera);
+                    self.preview.write_uniforms(&self.state, &uniforms);
+                    self.preview.view(&self.imgui_renderer).map(|view| {
+                        (
+                            view,
+                            &self.preview.depth_texture.view,
+                            &self.preview.uniform_group,
+                        )
+                    })
+                }
+            };
+
+            if let Some((view, depth_view, uniform_group)) = target {
+                let color_attachments: &[&dyn renderpass::IntoColorAttachment] = &[&(
+                    view,
+                    wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.1,
+                        g: 0.2,
+                        b: 0.3,
+                        a: 1.0,
+                    }),
+                )];
+                let depth_attachment: &dyn renderpass::IntoDepthAttachment =
+                    &(depth_view, wgpu::LoadOp::Clear(1.0));
+
+                let mut render_pass =
+                    renderpass::render_pass(&mut encoder, color_attachments, depth_attachment);
+
+                self.world
+                    .render(
+                        &self.state,
+                        &mut render_pass,
+                        &self.pipelines,
+                        &camera,
+                        uniform_group,
+                        &self.light_group,
+                    )
+                    .expect("Error rendering preview");
+
+                render_pass.set_pipeline(&self.pipelines.billboard);
+                render_pass.draw_billboards(&self.billboards, uniform_group);
+
+                render_pass.set_pipeline(&self.pipelines.debug);
+                render_pass.draw_debug_lines(&self.debug_lines, uniform_group);
+            }
+        }
+
+        {
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] =
+                &[&(&sc.view, wgpu::LoadOp::Load)];
+
+            let mut render_pass = renderpass::render_pass(&mut encoder, color_attachments, None);
+            render_pass.set_viewport(0.0, 0.0, 200.0, 200.0, 0.0, 1.0);
+
+            render_pass.set_pipeline(&self.pipelines.depth);
+
+            render_pass.draw_framebuffer(&self.framebuffer, &self.uniform_group);
+        }
+
+        {
+            // A grabbed cursor stays hidden whatever imgui wants
+            if !self.cursor_grabbed && self.last_cursor != ui.mouse_cursor() {
+                self.last_cursor = ui.mouse_cursor();
+                self.platform.prepare_render(&ui, &self.window);
+            }
+
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] =
+                &[&(&sc.view, wgpu::LoadOp::Load)];
+
+            let mut render_pass = renderpass::render_pass(&mut encoder, color_attachments, None);
+
+            self.imgui_renderer
+                .render(
+                    ui.render(),
+                    &self.state.queue(),
+                    &self.state.device(),
+                    &mut render_pass,
+                )
+                .expect("Failed to render UI!");
+        }
+
+        self.state.queue().submit(std::iter::once(encoder.finish()));
+        drop(game_frame);
+        Ok(())
+    }
+
+    /// Opens the windows asked for since the last frame.
+    pub fn open_requested_windows(&mut self, target: &EventLoopWindowTarget<()>) {
+        if !std::mem::take(&mut self.game_window_requested) || self.game_window.is_some() {
+            return;
+        }
+        let window = WindowBuilder::new()
+            .with_title(self.locale.get("windows.game_view"))
+            .with_inner_size(winit::dpi::PhysicalSize::new(
+                PREVIEW_SIZE[0] * 2,
+                PREVIEW_SIZE[1] * 2,
+            ))
+            .build(target);
+        match window {
+            Ok(window) => {
+                info!("Open game view window");
+                self.game_window = Some(render::window::SceneWindow::new(
+                    &mut self.state,
+                    window,
+                    &self.layouts.uniforms,
+                    &Uniforms::new(),
+                ));
+            }
+            Err(e) => error!("Could not open game view window: {:?}", e),
+        }
+    }
+
+    /// Handles events of windows besides the main one.
+    pub fn secondary_window_event(&mut self, id: winit::window::WindowId, event: &WindowEvent) {
+        let window = match &mut self.game_window {
+            Some(window) if window.id() == id => window,
+            _ => return,
+        };
+        match event {
+            WindowEvent::Resized(size) => window.resize(&mut self.state, *size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                window.resize(&mut self.state, **new_inner_size)
+            }
+            WindowEvent::CloseRequested => {
+                if let Some(window) = self.game_window.take() {
+                    info!("Close game view window");
+                    window.close(&mut self.state);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_title(&self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    pub fn request_redraw(&self) {
+        self.window.request_redraw();
+    }
+
+    pub fn window_id(&self) -> winit::window::WindowId {
+        self.window.id()
+    }
+
+    pub fn inmgui_event<T>(&mut self, event: &Event<T>) {
+        self.platform
+            .handle_event(self.imgui.io_mut(), &self.window, event)
+    }
+}
+
+/// Models every scene can use without listing them in its assets.
+fn load_default_models(
+    world: &mut world::World,
+    state: &state::WgpuState,
+    layouts: &render::Layouts,
+) -> Result<()> {
+    world.load_model_with_lods(
+        state,
+        layouts,
+        "block",
+        "cube.obj",
+        &model::lod::DEFAULT_SCREEN_SIZES,
+    )?;
+    world.load_model_async("pizza_box", "14037_Pizza_Box_v2_L1.obj");
+    for (name, primitive) in [
+        (
+            "sphere",
+            model::Primitive::Sphere {
+                radius: 1.0,
+                segments: 32,
+                rings: 16,
+            },
+        ),
+        (
+            "capsule",
+            model::Primitive::Capsule {
+                radius: 0.5,
+                height: 1.0,
+                segments: 32,
+                rings: 16,
+            },
+        ),
+        (
+            "cylinder",
+            model::Primitive::Cylinder {
+                radius: 0.5,
+                height: 2.0,
+                segments: 32,
+            },
+        ),
+        (
+            "plane",
+            model::Primitive::Plane {
+                width: 10.0,
+                depth: 10.0,
+                subdivisions: 0,
+            },
+        ),
+    ]
+    .iter()
+    {
+        world.load_primitive(state, layouts, *name, *primitive)?;
+    }
+    for name in [
+        "block",
+        "pizza_box",
+        "sphere",
+        "capsule",
+        "cylinder",
+        "plane",
+    ]
+    .iter()
+    {
+        world.assets.keep_loaded(*name);
+    }
+    Ok(())
+}
+
+/// Builds the UI fonts for `(font, size, hidpi_factor)`, falling back to
+/// the default font when `font` can't be loaded.
+fn build_fonts(
+    imgui: &mut imgui::Context,
+    resources: &resources::Resources,
+    (font, size, hidpi_factor): &(Option<String>, f32, f64),
+) {
+    if let Err(e) =
+        editor::theme::build_fonts(imgui, resources, font.as_deref(), *size, *hidpi_factor)
+    {
+        error!("Could not load font {:?}: {:?}", font, e);
+        editor::theme::build_fonts(imgui, resources, None, *size, *hidpi_factor)
+            .expect("Default font is built in");
+    }
+}
+
+/// How the engine starts, from the command line or set by the binary.
+pub struct Options {
+    /// Opened at startup, saved with F5 and loaded with F9
+    pub scene: std::path::PathBuf,
+    /// Inner size of the window or of headless frames
+    pub size: Option<(u32, u32)>,
+    /// Renders `scene` to this image without a window and exits
+    pub headless: Option<std::path::PathBuf>,
+    /// Image a headless render has to match
+    pub golden: Option<std::path::PathBuf>,
+    pub backend: wgpu::BackendBit,
+    /// Overrides the level of the settings and the environment
+    pub log_level: Option<log::LevelFilter>,
+    /// Searched for resources before the default paths
+    pub res_dir: Option<std::path::PathBuf>,
+    /// Input is recorded to this file
+    pub record: Option<std::path::PathBuf>,
+    /// Input is replayed from this file, exiting once it ends
+    pub replay: Option<std::path::PathBuf>,
+    /// Worker threads of parallel systems, one per core when unset
+    pub threads: Option<usize>,
+    /// Address the world is replicated on to other instances
+    pub serve: Option<String>,
+    /// Address of the server whose world is mirrored
+    pub connect: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            scene: SCENE_FILE.into(),
+            size: None,
+            headless: None,
+            golden: None,
+            backend: wgpu::BackendBit::PRIMARY,
+            log_level: None,
+            res_dir: None,
+            record: None,
+            replay: None,
+            threads: None,
+            serve: None,
+            connect: None,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the process arguments, printing the usage and exiting when
+    /// they don't make sense.
+    pub fn parse() -> Self {
+        use clap::{App as Command, Arg};
+
+        let matches = Command::new("Nodas engine")
+            .version(env!("CARGO_PKG_VERSION"))
+            .arg(
+                Arg::with_name("scene")
+                    .long("scene")
+                    .value_name("FILE")
+                    .default_value(SCENE_FILE)
+                    .help("Scene to open, saved with F5 and loaded with F9"),
+            )
+            .arg(
+                Arg::with_name("size")
+                    .long("size")
+                    .value_name("WIDTHxHEIGHT")
+                    .validator(|size| parse_size(&size).map(|_| ()))
+                    .help("Size of the window or of headless renders"),
+            )
+            .arg(
+                Arg::with_name("headless")
+                    .long("headless")
+                    .value_name("IMAGE")
+                    .help("Renders the scene to an image without a window and exits"),
+            )
+            .arg(
+                Arg::with_name("golden")
+                    .long("golden")
+                    .value_name("IMAGE")
+                    .requires("headless")
+                    .help("Fails the headless render unless it matches this image"),
+            )
+            .arg(
+                Arg::with_name("backend")
+                    .long("backend")
+                    .possible_values(&["primary", "vulkan", "metal", "dx12", "dx11", "gl"])
+                    .default_value("primary")
+                    .help("Graphics API to render with"),
+            )
+            .arg(
+                Arg::with_name("log-level")
+                    .long("log-level")
+                    .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+                    .help("Level of modules without one in the settings or NODAS_LOG"),
+            )
+            .arg(
+                Arg::with_name("res")
+                    .long("res")
+                    .value_name("DIR")
+                    .help("Resource directory searched before the default ones"),
+            )
+            .arg(
+                Arg::with_name("record")
+                    .long("record")
+                    .value_name("FILE")
+                    .conflicts_with("replay")
+                    .help("Records the input to replay it later"),
+            )
+            .arg(
+                Arg::with_name("replay")
+                    .long("replay")
+                    .value_name("FILE")
+                    .help("Replays recorded input and exits once it ends"),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .long("threads")
+                    .value_name("COUNT")
+                    .validator(|threads| match threads.parse::<usize>() {
+                        Ok(threads) if threads > 0 => Ok(()),
+                        _ => Err(format!("Invalid thread count {:?}", threads)),
+                    })
+                    .help("Worker threads of parallel systems, to compare their scaling"),
+            )
+            .arg(
+                Arg::with_name("serve")
+                    .long("serve")
+                    .value_name("ADDR")
+                    .conflicts_with("connect")
+                    .help("Replicates the world to the instances connecting to this address"),
+            )
+            .arg(
+                Arg::with_name("connect")
+                    .long("connect")
+                    .value_name("ADDR")
+                    .help("Mirrors the world of the instance serving on this address"),
+            )
+            .get_matches();
+
+        let path = |name: &str| matches.value_of(name).map(std::path::PathBuf::from);
+        Self {
+            scene: path("scene").unwrap_or_else(|| SCENE_FILE.into()),
+            size: matches
+                .value_of("size")
+                .and_then(|size| parse_size(size).ok()),
+            headless: path("headless"),
+            golden: path("golden"),
+            backend: match matches.value_of("backend") {
+                Some("vulkan") => wgpu::BackendBit::VULKAN,
+                Some("metal") => wgpu::BackendBit::METAL,
+                Some("dx12") => wgpu::BackendBit::DX12,
+                Some("dx11") => wgpu::BackendBit::DX11,
+                Some("gl") => wgpu::BackendBit::GL,
+                _ => wgpu::BackendBit::PRIMARY,
+            },
+            log_level: matches
+                .value_of("log-level")
+                .and_then(|level| level.parse().ok()),
+            res_dir: path("res"),
+            record: path("record"),
+            replay: path("replay"),
+            threads: matches
+                .value_of("threads")
+                .and_then(|threads| threads.parse().ok()),
+            serve: matches.value_of("serve").map(str::to_string),
+            connect: matches.value_of("connect").map(str::to_string),
+        }
+    }
+}
+
+/// `<width>x<height>`, both above zero.
+fn parse_size(size: &str) -> std::result::Result<(u32, u32), String> {
+    let mut parts = size.split('x').map(str::parse::<u32>);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(width)), Some(Ok(height)), None) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!(
+            "Invalid size {:?}, expected <width>x<height>",
+            size
+        )),
+    }
+}
+
+/// Renders a scene without a window from its active camera, or from where
+/// the editor camera starts, and saves the frame to `output`. Fails when it
+/// doesn't match the golden image.
+fn render_headless(
+    options: &Options,
+    mut extensions: Extensions,
+    output: &std::path::Path,
+) -> Result<()> {
+    let (width, height) = options.size.unwrap_or(HEADLESS_SIZE);
+    let resources = Arc::new(resources::Resources::with_res_dir(
+        options.res_dir.as_deref(),
+    ));
+    let state = block_on(state::WgpuState::new_headless(
+        options.backend,
+        width,
+        height,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        resources.clone(),
+    ))?;
+    let mut layouts = render::Layouts::new(&state);
+    extensions.build_layouts(&state, &mut layouts);
+    let mut pipelines = render::Pipelines::new(&state, &layouts)?;
+    extensions.build_pipelines(&state, &layouts, &mut pipelines)?;
+
+    let mut world = world::World::new(resources);
+    load_default_models(&mut world, &state, &layouts)?;
+    extensions.setup(&mut world);
+    world.load(&state, &layouts, &options.scene)?;
+    let start = std::time::Instant::now();
+    while !world.assets_ready() {
+        if start.elapsed() > HEADLESS_LOAD_TIMEOUT {
+            bail!("Timed out loading the models of {:?}", options.scene);
+        }
+        world.update(&state, &layouts);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let mut camera = world
+        .active_camera()
+        .map(|(_, camera)| camera)
+        .unwrap_or_else(|| {
+            camera::Camera::new(
+                [0.0, 5.0, 10.0].into(),
+                [0.0, 0.0, 0.0].into(),
+                camera::projection::Projection::new(
+                    width,
+                    height,
+                    CAMERA_FOVY,
+                    CAMERA_ZNEAR,
+                    CAMERA_ZFAR,
+                ),
+            )
+        });
+    camera.resize(width, height);
+    // Transforms, bounds and detail levels of the loaded entities
+    world.update_camera(&camera);
+    world.update(&state, &layouts);
+    let mut uniforms = Uniforms::new();
+    uniforms.update_view_proj(&camera);
+    let uniform_buffer = binding::Buffer::new_init(
+        &state,
+        "uniforms",
+        &[uniforms],
+        binding::BufferUsage::Uniform,
+    );
+    let uniform_group = binding::BufferGroup::from_buffer(
+        &state,
+        "uniforms",
+        &layouts.uniforms,
+        &[&uniform_buffer],
+    );
+    let light = Light::from_settings(&editor::light::LightSettings {
+        position: [-0.25, 0.25, -0.25],
+        color: [1.0, 1.0, 1.0],
+        intensity: 1.0,
+        ty: editor::light::LightType::Directional,
+    });
+    let light_buffer =
+        binding::Buffer::new_init(&state, "light", &[light], binding::BufferUsage::Uniform);
+    let light_group =
+        binding::BufferGroup::from_buffer(&state, "light", &layouts.light, &[&light_buffer]);
+
+    let offscreen = render::headless::Offscreen::new(&state);
+    let mut encoder = state.encoder();
+    world.prepare_indirect(&state, &mut encoder, None, &camera, false)?;
+    {
+        let color_attachments: &[&dyn renderpass::IntoColorAttachment] = &[&(
+            &offscreen.view,
+            wgpu::LoadOp::Clear(wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            }),
+        )];
+        let depth_attachment: &dyn renderpass::IntoDepthAttachment =
+            &(&offscreen.depth_texture.view, wgpu::LoadOp::Clear(1.0));
+        let mut render_pass =
+            renderpass::render_pass(&mut encoder, color_attachments, depth_attachment);
+        world.render(
+            &state,
+            &mut render_pass,
+            &pipelines,
+            &camera,
+            &uniform_group,
+            &light_group,
+        )?;
+    }
+    state.queue().submit(std::iter::once(encoder.finish()));
+
+    let image = offscreen.read(&state)?;
+    image
+        .save(output)
+        .with_context(|| format!("Could not save {:?}", output))?;
+    info!("Rendered {:?} to {:?}", options.scene, output);
+
+    if let Some(golden) = &options.golden {
+        let expected = image::open(golden)
+            .with_context(|| format!("Could not load golden image {:?}", golden))?
+            .to_rgba8();
+        match render::headless::difference(&image, &expected) {
+            Some(difference) if difference <= GOLDEN_TOLERANCE => {
+                info!("Matches {:?} ({:.4} apart)", golden, difference)
+            }
+            Some(difference) => bail!("Differs from {:?} by {:.4}", golden, difference),
+            None => bail!("Size differs from {:?}", golden),
+        }
+    }
+    Ok(())
+}
+
+/// Run on the world once it is created, before the scene is loaded
+type Setup = Box<dyn FnOnce(&mut world::World)>;
+type LayoutBuilder = Box<dyn Fn(&state::WgpuState) -> binding::BindGroupLayout>;
+type PipelineBuilder =
+    Box<dyn Fn(&state::WgpuState, &render::Layouts) -> Result<wgpu::RenderPipeline>>;
+
+/// What was added to the app, applied once the world exists.
+#[derive(Default)]
+struct Extensions {
+    /// Names of the plugins built, in order
+    plugins: Vec<&'static str>,
+    setup: Vec<Setup>,
+    hooks: Vec<lifecycle::Hook>,
+    layouts: Vec<(String, LayoutBuilder)>,
+    pipelines: Vec<(String, PipelineBuilder)>,
+    /// Whether the editor windows are drawn over the scene
+    editor: bool,
+}
+
+impl Extensions {
+    fn setup(&mut self, world: &mut world::World) {
+        for setup in self.setup.drain(..) {
+            setup(world);
+        }
+    }
+
+    fn build_layouts(&self, state: &state::WgpuState, layouts: &mut render::Layouts) {
+        for (name, build) in self.layouts.iter() {
+            layouts.custom.insert(name.clone(), build(state));
+        }
+    }
+
+    fn build_pipelines(
+        &self,
+        state: &state::WgpuState,
+        layouts: &render::Layouts,
+        pipelines: &mut render::Pipelines,
+    ) -> Result<()> {
+        for (name, build) in self.pipelines.iter() {
+            let pipeline = build(state, layouts).context(format!("Could not build {:?}", name))?;
+            pipelines.custom.insert(name.clone(), pipeline);
+        }
+        Ok(())
+    }
+}
+
+/// Configures the engine and runs it. Plugins, systems and lifecycle hooks
+/// are added before [`App::run`] opens the window, or renders headless when
+/// the options ask for it. Physics, audio and the editor are switched on by
+/// plugins, an app without [`plugin::DefaultPlugins`] only gets what it
+/// adds.
+pub struct App {
+    options: Options,
+    extensions: Extensions,
+}
+
+impl App {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            extensions: Extensions::default(),
+        }
+    }
+
+    /// An app configured by the process arguments, see [`Options::parse`].
+    pub fn from_args() -> Self {
+        Self::new(Options::parse())
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+
+    /// Lets `plugin` add what it needs to the app. Adding the same plugin
+    /// again does nothing.
+    pub fn add_plugin<P: plugin::Plugin>(&mut self, plugin: P) -> &mut Self {
+        let name = plugin.name();
+        if !self.has_plugin(name) {
+            self.extensions.plugins.push(name);
+            plugin.build(self);
+        }
+        self
+    }
+
+    pub fn has_plugin(&self, name: &str) -> bool {
+        self.extensions.plugins.iter().any(|plugin| *plugin == name)
+    }
+
+    /// Adds `system` to the schedule of the world at `stage`.
+    pub fn add_system<S: legion::systems::ParallelRunnable + 'static>(
+        &mut self,
+        stage: schedule::Stage,
+        system: S,
+    ) -> &mut Self {
+        self.add_setup(move |world| world.add_system(stage, system))
+    }
+
+    /// Runs `setup` on the world before the scene is loaded, to register
+    /// events, prefabs or resources.
+    pub fn add_setup<F: FnOnce(&mut world::World) + 'static>(&mut self, setup: F) -> &mut Self {
+        self.extensions.setup.push(Box::new(setup));
+        self
+    }
+
+    pub fn add_lifecycle_hook<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: FnMut(&mut world::World, lifecycle::LifecycleEvent) + 'static,
+    {
+        self.extensions.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Adds a bind group layout, found under `name` in
+    /// [`render::Layouts::custom`].
+    pub fn add_layout<N, F>(&mut self, name: N, build: F) -> &mut Self
+    where
+        N: Into<String>,
+        F: Fn(&state::WgpuState) -> binding::BindGroupLayout + 'static,
+    {
+        self.extensions.layouts.push((name.into(), Box::new(build)));
+        self
+    }
+
+    /// Adds a render pipeline, found under `name` in
+    /// [`render::Pipelines::custom`]. It is built after the layouts, custom
+    /// ones included.
+    pub fn add_pipeline<N, F>(&mut self, name: N, build: F) -> &mut Self
+    where
+        N: Into<String>,
+        F: Fn(&state::WgpuState, &render::Layouts) -> Result<wgpu::RenderPipeline> + 'static,
+    {
+        self.extensions
+            .pipelines
+            .push((name.into(), Box::new(build)));
+        self
+    }
+
+    /// Loads model files ending in `extension` with `format`, in place of
+    /// the built in formats.
+    pub fn add_model_format<E: Into<String>>(
+        &mut self,
+        extension: E,
+        format: assets::ModelFormat,
+    ) -> &mut Self {
+        let extension = extension.into();
+        self.add_setup(move |world| world.assets.formats.register(extension, format))
+    }
+
+    pub(crate) fn enable_editor(&mut self) -> &mut Self {
+        self.extensions.editor = true;
+        self
+    }
+
+    /// Runs the engine until its window is closed, or renders the scene
+    /// and returns when running headless.
+    pub fn run(self) {
+        let App {
+            options,
+            extensions,
+        } = self;
+        // Logged once the logger is up
+        let (settings, settings_error) = match settings::Settings::load(settings::SETTINGS_FILE) {
+            Ok(settings) => (settings, None),
+            Err(e) => (settings::Settings::default(), Some(e)),
+        };
+        let log_buffer = logging::LogBuffer::new();
+        let log_handle =
+            logging::init(&settings.logging, options.log_level, log_buffer.clone()).unwrap();
+        if let Some(e) = settings_error {
+            warn!("Could not load settings, using the defaults: {:?}", e);
+        }
+        info!("Plugins: {}", extensions.plugins.join(", "));
+        if let Some(threads) = options.threads {
+            if let Err(e) = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()
+            {
+                warn!("Could not set the worker thread count: {}", e);
+            }
+        }
+        info!("{} worker threads", rayon::current_num_threads());
+
+        if let Some(output) = &options.headless {
+            if let Err(e) = render_headless(&options, extensions, output) {
+                error!("Headless render failed: {:?}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        let event_loop = EventLoop::new();
+        let mut builder = WindowBuilder::new().with_title("Nodas engine");
+        if let Some((width, height)) = options.size {
+            builder = builder.with_inner_size(winit::dpi::PhysicalSize::new(width, height));
+        }
+        let window = builder.build(&event_loop).unwrap();
+        info!("Window intialized");
+
+        let mut engine = block_on(Engine::new(
+            window, log_buffer, log_handle, settings, &options, extensions,
+        ))
+        .unwrap();
+        if let Some(path) = &options.record {
+            engine.world.start_recording(path);
+        }
+        if let Some(path) = &options.replay {
+            if let Err(e) = engine.world.start_replay(path) {
+                error!("Could not start replay: {:?}", e);
+                std::process::exit(1);
+            }
+        } else {
+            // Replays start from the same state they were recorded in
+            match session::Session::load(session::SESSION_FILE) {
+                Ok(session) => engine.restore_session(session),
+                Err(e) => warn!("Could not restore the session: {:?}", e),
+            }
+        }
+        event_loop.run(move |event, target, control_flow| {
+            match event {
+                Event::RedrawRequested(_) => {
+                    let dt = engine.pacer.frame();
+
+                    engine.apply_resize(false);
+                    engine.update(dt);
+                    // Replays of smoke tests end with the recording
+                    if engine.world.replay_finished() {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    match engine.render(dt) {
+                        Ok(_) => {}
+                        // Recreated once the window keeps its size
+                        Err(wgpu::SwapChainError::Outdated) if engine.resize_pending() => {}
+                        Err(wgpu::SwapChainError::Lost) if engine.resize_pending() => {
+                            engine.apply_resize(true)
+                        }
+                        Err(wgpu::SwapChainError::Lost) => engine.resize(engine.size()),
+                        Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+                        Err(e) => eprintln!("{:?}", e),
+                    }
+                }
+                Event::DeviceEvent {
+                    event: DeviceEvent::MouseMotion { delta },
+                    ..
+                } => engine.mouse_motion(delta),
+                Event::LoopDestroyed => {
+                    engine.exit();
+                    if options.replay.is_none() {
+                        engine.save_session();
+                    }
+                    if let Err(e) = engine.world.save_recording() {
+                        error!("Could not save recording: {:?}", e);
+                    }
+                }
+                Event::MainEventsCleared => {
+                    engine.open_requested_windows(target);
+                    // Exiting can't be taken back
+                    if *control_flow != ControlFlow::Exit {
+                        *control_flow = match engine.pacer.wait_until() {
+                            Some(due) => ControlFlow::WaitUntil(due),
+                            None => {
+                                engine.request_redraw();
+                                ControlFlow::Poll
+                            }
+                        };
+                    }
+                }
+                Event::WindowEvent {
+                    ref event,
+                    window_id,
+                } if window_id == engine.window_id() => {
+                    if !engine.input(event) {
+                        match event {
+                            WindowEvent::Resized(physical_size) => {
+                                engine.request_resize(*physical_size);
+                            }
+                            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                                engine.request_resize(**new_inner_size);
+                            }
+                            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                            WindowEvent::KeyboardInput { input, .. } => match input {
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Escape),
+                                    ..
+                                } => {
+                                    // Escape clears the selection first, then quits
+                                    if !engine.deselect() {
+                                        *control_flow = ControlFlow::Exit;
+                                    }
+                                }
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F5),
+                                    ..
+                                } => engine.save_scene(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F6),
+                                    ..
+                                } => engine.toggle_camera_path(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F9),
+                                    ..
+                                } => engine.load_scene(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F2),
+                                    ..
+                                } => engine.toggle_camera_mode(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F3),
+                                    ..
+                                } => engine.toggle_projection(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F4),
+                                    ..
+                                } => engine.toggle_scroll_mode(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F7),
+                                    ..
+                                } => engine.toggle_raw_camera(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F8),
+                                    ..
+                                } => engine.cycle_active_camera(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F10),
+                                    ..
+                                } => engine.toggle_debug_draw(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F1),
+                                    ..
+                                } => engine.toggle_stats(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F12),
+                                    ..
+                                } => engine.toggle_settings(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::F11),
+                                    ..
+                                } => engine.cycle_window_mode(),
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Return),
+                                    ..
+                                } if engine.alt_held() => engine.cycle_window_mode(),
+                                _ => {}
+                            },
+                            _ => {}
+                        }
+                    }
+                }
+                Event::WindowEvent {
+                    ref event,
+                    window_id,
+                } => engine.secondary_window_event(window_id, event),
+                _ => {}
+            }
+            engine.inmgui_event(&event);
+        });
+    }
+}
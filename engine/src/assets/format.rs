@@ -0,0 +1,46 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::*;
+use log::info;
+
+use crate::render::model;
+
+/// Decodes a model file, on the loader threads too.
+pub type ModelFormat = fn(&Path) -> Result<model::ModelData>;
+
+/// Model formats added on top of the built in ones, by lowercase
+/// extension. Clones share the formats, so the loader threads see the ones
+/// registered after they started.
+#[derive(Clone, Default)]
+pub struct ModelFormats(Arc<RwLock<HashMap<String, ModelFormat>>>);
+
+impl ModelFormats {
+    pub fn register<E: Into<String>>(&self, extension: E, format: ModelFormat) {
+        let extension = extension.into().to_lowercase();
+        info!("Model format {:?} registered", extension);
+        if let Ok(mut formats) = self.0.write() {
+            formats.insert(extension, format);
+        }
+    }
+
+    fn get(&self, path: &Path) -> Option<ModelFormat> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        self.0.read().ok()?.get(&extension).copied()
+    }
+
+    /// Loads `path` with the format registered for its extension, or with
+    /// [`model::ModelData::load`].
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<model::ModelData> {
+        match self.get(path.as_ref()) {
+            Some(format) => {
+                info!("Load model {:?}", path.as_ref());
+                format(path.as_ref())
+            }
+            None => model::ModelData::load(path),
+        }
+    }
+}
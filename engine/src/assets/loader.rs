@@ -0,0 +1,174 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use anyhow::*;
+use log::{error, info};
+
+use crate::{assets::ModelFormats, render::model};
+
+pub const LOADER_THREADS: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadStatus {
+    Queued,
+    Loading,
+    Uploading,
+    Loaded,
+    Failed(String),
+}
+
+impl LoadStatus {
+    pub fn progress(&self) -> f32 {
+        match self {
+            Self::Queued => 0.0,
+            Self::Loading => 0.33,
+            Self::Uploading => 0.66,
+            Self::Loaded | Self::Failed(_) => 1.0,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        match self {
+            Self::Queued | Self::Loading | Self::Uploading => true,
+            Self::Loaded | Self::Failed(_) => false,
+        }
+    }
+}
+
+struct Job {
+    name: String,
+    path: PathBuf,
+}
+
+type Status = Arc<Mutex<BTreeMap<String, LoadStatus>>>;
+
+/// Parses and decodes models on worker threads. The GPU upload still has to
+/// happen on the thread owning the `WgpuState`, see [`AssetLoader::finished`].
+pub struct AssetLoader {
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<(String, Result<model::ModelData>)>,
+    status: Status,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl AssetLoader {
+    pub fn new(threads: usize, formats: ModelFormats) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let status: Status = Arc::new(Mutex::new(BTreeMap::new()));
+
+        let workers = (0..threads)
+            .map(|i| {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                let status = status.clone();
+                let formats = formats.clone();
+                thread::Builder::new()
+                    .name(format!("asset-loader-{}", i))
+                    .spawn(move || loop {
+                        let job = match jobs.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        };
+
+                        status
+                            .lock()
+                            .unwrap()
+                            .insert(job.name.clone(), LoadStatus::Loading);
+                        let result = formats.load(&job.path);
+                        if results.send((job.name, result)).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("Could not spawn asset loader thread")
+            })
+            .collect();
+
+        Self {
+            jobs: Some(job_sender),
+            results,
+            status,
+            workers,
+        }
+    }
+
+    pub fn load_model<N: Into<String>, P: Into<PathBuf>>(&mut self, name: N, path: P) {
+        let name = name.into();
+        let path = path.into();
+        info!("Queue model {:?} from {:?}", name, path);
+        self.status
+            .lock()
+            .unwrap()
+            .insert(name.clone(), LoadStatus::Queued);
+        if let Some(jobs) = &self.jobs {
+            jobs.send(Job { name, path })
+                .expect("Asset loader threads exited");
+        }
+    }
+
+    /// Drains the models decoded since the last call, marking them as
+    /// uploading until [`AssetLoader::set_status`] reports the outcome.
+    pub fn finished(&mut self) -> Vec<(String, model::ModelData)> {
+        let mut finished = Vec::new();
+        while let Ok((name, result)) = self.results.try_recv() {
+            match result {
+                Ok(data) => {
+                    self.set_status(&name, LoadStatus::Uploading);
+                    finished.push((name, data));
+                }
+                Err(e) => {
+                    error!("Could not load {:?}: {:?}", name, e);
+                    self.set_status(&name, LoadStatus::Failed(e.to_string()));
+                }
+            }
+        }
+        finished
+    }
+
+    pub fn set_status(&self, name: &str, status: LoadStatus) {
+        self.status
+            .lock()
+            .unwrap()
+            .insert(String::from(name), status);
+    }
+
+    pub fn status(&self) -> Vec<(String, LoadStatus)> {
+        self.status
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, status)| (name.clone(), status.clone()))
+            .collect()
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.status
+            .lock()
+            .unwrap()
+            .values()
+            .any(LoadStatus::is_pending)
+    }
+
+    /// Forgets loaded and failed assets once nothing is pending anymore.
+    pub fn clear_finished(&self) {
+        let mut status = self.status.lock().unwrap();
+        if !status.values().any(LoadStatus::is_pending) {
+            status.clear();
+        }
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::render::model;
+
+/// Where a loaded model came from, enough to load it again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ModelSource {
+    File {
+        /// Resource name the model was requested with
+        path: PathBuf,
+        /// Screen sizes of the generated detail levels
+        #[serde(default)]
+        lods: Vec<f32>,
+        /// Authored detail levels and their screen sizes
+        #[serde(default)]
+        lod_files: Vec<(PathBuf, f32)>,
+    },
+    Primitive(model::Primitive),
+}
+
+impl ModelSource {
+    pub fn file<P: Into<PathBuf>>(path: P) -> Self {
+        Self::File {
+            path: path.into(),
+            lods: Vec::new(),
+            lod_files: Vec::new(),
+        }
+    }
+}
+
+/// Models the asset manager has loaded by name, saved with scenes so they
+/// can be loaded before their entities.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AssetManifest {
+    pub models: BTreeMap<String, ModelSource>,
+    /// Audio clips by name and resource path
+    #[serde(default)]
+    pub sounds: BTreeMap<String, PathBuf>,
+    /// Resource paths of the textures loaded for sprites, decals and
+    /// billboards
+    #[serde(default)]
+    pub textures: BTreeSet<PathBuf>,
+}
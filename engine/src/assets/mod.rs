@@ -0,0 +1,528 @@
+pub mod format;
+pub mod handle;
+pub mod loader;
+pub mod manifest;
+pub mod watcher;
+
+pub use format::{ModelFormat, ModelFormats};
+pub use handle::{Handle, HandleId};
+pub use loader::{AssetLoader, LoadStatus};
+pub use manifest::{AssetManifest, ModelSource};
+pub use watcher::AssetWatcher;
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::*;
+use log::{error, info};
+
+use crate::{
+    audio,
+    render::{binding, model, state, texture},
+    resources::Resources,
+};
+
+struct Entry<T> {
+    name: String,
+    asset: T,
+    /// Shared with every handle, the entry itself holds one reference
+    refs: Arc<()>,
+}
+
+/// Name-keyed storage for one kind of asset. Loading the same name twice
+/// returns the existing asset.
+pub struct Assets<T> {
+    next_id: HandleId,
+    entries: HashMap<HandleId, Entry<T>>,
+    names: HashMap<String, HandleId>,
+}
+
+impl<T> Assets<T> {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            entries: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Stores `asset` under `name`, replacing the previous asset of that
+    /// name while keeping existing handles valid.
+    pub fn insert<N: Into<String>>(&mut self, name: N, asset: T) -> Handle<T> {
+        let name = name.into();
+        if let Some(id) = self.names.get(&name) {
+            let entry = self.entries.get_mut(id).expect("Asset name without entry");
+            entry.asset = asset;
+            return Handle::new(*id, entry.refs.clone());
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let refs = Arc::new(());
+        self.names.insert(name.clone(), id);
+        self.entries.insert(
+            id,
+            Entry {
+                name,
+                asset,
+                refs: refs.clone(),
+            },
+        );
+
+        Handle::new(id, refs)
+    }
+
+    /// Returns the asset called `name`, running `load` only if it isn't
+    /// loaded yet.
+    pub fn get_or_load<N, F>(&mut self, name: N, load: F) -> Result<Handle<T>>
+    where
+        N: Into<String>,
+        F: FnOnce() -> Result<T>,
+    {
+        let name = name.into();
+        match self.handle(&name) {
+            Some(handle) => Ok(handle),
+            None => Ok(self.insert(name, load()?)),
+        }
+    }
+
+    pub fn handle(&self, name: &str) -> Option<Handle<T>> {
+        let id = self.names.get(name)?;
+        let entry = self.entries.get(id)?;
+        Some(Handle::new(*id, entry.refs.clone()))
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        self.entries.get(&handle.id).map(|entry| &entry.asset)
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.entries
+            .get_mut(&handle.id)
+            .map(|entry| &mut entry.asset)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&T> {
+        let id = self.names.get(name)?;
+        self.entries.get(id).map(|entry| &entry.asset)
+    }
+
+    pub fn name(&self, handle: &Handle<T>) -> Option<&str> {
+        self.entries
+            .get(&handle.id)
+            .map(|entry| entry.name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.entries.values().map(|entry| &entry.name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &T)> {
+        self.entries
+            .values()
+            .map(|entry| (&entry.name, &entry.asset))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut T)> {
+        self.entries
+            .values_mut()
+            .map(|entry| (&entry.name, &mut entry.asset))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Number of live handles, not counting the store itself.
+    pub fn ref_count(&self, name: &str) -> usize {
+        self.names
+            .get(name)
+            .and_then(|id| self.entries.get(id))
+            .map(|entry| Arc::strong_count(&entry.refs) - 1)
+            .unwrap_or(0)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<T> {
+        let id = self.names.remove(name)?;
+        self.entries.remove(&id).map(|entry| entry.asset)
+    }
+
+    /// Drops every asset no handle refers to anymore, returning their names.
+    pub fn unload_unused(&mut self) -> Vec<String> {
+        let unused = self
+            .entries
+            .values()
+            .filter(|entry| Arc::strong_count(&entry.refs) == 1)
+            .map(|entry| entry.name.clone())
+            .collect::<Vec<_>>();
+
+        for name in unused.iter() {
+            info!("Unload asset {:?}", name);
+            self.remove(name);
+        }
+
+        unused
+    }
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct AssetManager {
+    pub models: Assets<model::Model>,
+    pub materials: Assets<model::Material>,
+    pub material_arrays: Assets<model::MaterialArray>,
+    pub textures: Assets<texture::Texture>,
+    pub sounds: Assets<audio::AudioClip>,
+    pub loader: AssetLoader,
+    pub watcher: AssetWatcher,
+    pub formats: ModelFormats,
+    manifest: AssetManifest,
+    /// Models kept loaded with no entity using them
+    kept: HashSet<String>,
+    /// Models the last loaded manifest lists, kept until the next one
+    listed: Vec<String>,
+    /// Texture paths that could not be loaded, not tried again
+    failed_textures: HashSet<String>,
+    resources: Arc<Resources>,
+}
+
+impl AssetManager {
+    pub fn new(resources: Arc<Resources>) -> Self {
+        let formats = ModelFormats::default();
+        Self {
+            models: Assets::new(),
+            materials: Assets::new(),
+            material_arrays: Assets::new(),
+            textures: Assets::new(),
+            sounds: Assets::new(),
+            loader: AssetLoader::new(loader::LOADER_THREADS, formats.clone()),
+            watcher: AssetWatcher::new(),
+            formats,
+            manifest: AssetManifest::default(),
+            kept: HashSet::new(),
+            listed: Vec::new(),
+            failed_textures: HashSet::new(),
+            resources,
+        }
+    }
+
+    pub fn load_model<N: Into<String>, P: AsRef<Path>>(
+        &mut self,
+        state: &state::WgpuState,
+        material_layout: &binding::BindGroupLayout,
+        name: N,
+        path: P,
+    ) -> Result<Handle<model::Model>> {
+        self.load_model_with_lods(state, material_layout, name, path, &[])
+    }
+
+    /// Loads the model along with a decimated level for each of
+    /// `screen_sizes`.
+    pub fn load_model_with_lods<N: Into<String>, P: AsRef<Path>>(
+        &mut self,
+        state: &state::WgpuState,
+        material_layout: &binding::BindGroupLayout,
+        name: N,
+        path: P,
+        screen_sizes: &[f32],
+    ) -> Result<Handle<model::Model>> {
+        let name = name.into();
+        if let Some(handle) = self.models.handle(&name) {
+            return Ok(handle);
+        }
+
+        let data = self
+            .formats
+            .load(self.resources.path(path.as_ref())?)?
+            .with_generated_lods(screen_sizes);
+        self.watch(&name, &data);
+        let model = model::Model::from_data(state, material_layout, &mut self.textures, data)?;
+        self.manifest.models.insert(
+            name.clone(),
+            ModelSource::File {
+                path: path.as_ref().to_path_buf(),
+                lods: screen_sizes.to_vec(),
+                lod_files: Vec::new(),
+            },
+        );
+        Ok(self.models.insert(name, model))
+    }
+
+    /// Adds an authored detail level to the loaded model `name`, its meshes
+    /// have to use the same materials as the model.
+    pub fn load_lod<P: AsRef<Path>>(
+        &mut self,
+        state: &state::WgpuState,
+        name: &str,
+        path: P,
+        screen_size: f32,
+    ) -> Result<()> {
+        let handle = self
+            .models
+            .handle(name)
+            .context(format!("Model {:?} is not loaded", name))?;
+        let data = self.formats.load(self.resources.path(path.as_ref())?)?;
+        self.models
+            .get_mut(&handle)
+            .context("Cannot find model")?
+            .add_lod(
+                state,
+                model::LodData {
+                    meshes: data.meshes,
+                    screen_size,
+                },
+            )?;
+
+        if let Some(ModelSource::File { lod_files, .. }) = self.manifest.models.get_mut(name) {
+            lod_files.push((path.as_ref().to_path_buf(), screen_size));
+        }
+        Ok(())
+    }
+
+    fn watch(&mut self, name: &str, data: &model::ModelData) {
+        if let Some(path) = data.sources.first() {
+            self.watcher
+                .watch_model(name, path.clone(), data.sources.clone());
+        }
+    }
+
+    /// Queues models whose files changed on disk for reloading, the new
+    /// version replaces the old one in [`AssetManager::poll_loading`].
+    pub fn poll_changes(&mut self) {
+        for (name, path, changed) in self.watcher.poll() {
+            // Cached textures are keyed by their path, drop the stale ones
+            // so the reload uploads them again
+            let stale = self
+                .textures
+                .names()
+                .filter(|key| {
+                    changed
+                        .iter()
+                        .any(|source| key.starts_with(source.to_string_lossy().as_ref()))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            for key in stale {
+                info!("Drop changed texture {:?}", key);
+                self.textures.remove(&key);
+            }
+
+            self.loader.load_model(name, path);
+        }
+    }
+
+    pub fn load_primitive<N: Into<String>>(
+        &mut self,
+        state: &state::WgpuState,
+        material_layout: &binding::BindGroupLayout,
+        name: N,
+        primitive: model::Primitive,
+    ) -> Result<Handle<model::Model>> {
+        let name = name.into();
+        let textures = &mut self.textures;
+        let handle = self.models.get_or_load(name.as_str(), || {
+            model::Model::from_data(state, material_layout, textures, primitive.model_data())
+        })?;
+        self.manifest
+            .models
+            .insert(name, ModelSource::Primitive(primitive));
+        Ok(handle)
+    }
+
+    /// Queues `path` on the loader threads, the model shows up in
+    /// `models` once [`AssetManager::poll_loading`] has uploaded it.
+    pub fn load_model_async<N: Into<String>, P: AsRef<Path>>(&mut self, name: N, path: P) {
+        let name = name.into();
+        if self.models.contains(&name) {
+            return;
+        }
+
+        match self.resources.path(path.as_ref()) {
+            Ok(resolved) => {
+                self.manifest
+                    .models
+                    .insert(name.clone(), ModelSource::file(path.as_ref()));
+                self.loader.load_model(name, resolved)
+            }
+            Err(e) => {
+                error!("Could not load model {:?}: {:?}", name, e);
+                self.loader
+                    .set_status(&name, LoadStatus::Failed(e.to_string()));
+            }
+        }
+    }
+
+    /// Loads the audio clip at `path` under `name`.
+    pub fn load_sound<N: Into<String>, P: AsRef<Path>>(
+        &mut self,
+        name: N,
+        path: P,
+    ) -> Result<Handle<audio::AudioClip>> {
+        let name = name.into();
+        let resources = self.resources.clone();
+        let handle = self.sounds.get_or_load(name.as_str(), || {
+            info!("Load sound {:?}", path.as_ref());
+            audio::AudioClip::new(resources.read(path.as_ref())?.into_owned())
+        })?;
+        self.manifest
+            .sounds
+            .insert(name, path.as_ref().to_path_buf());
+        Ok(handle)
+    }
+
+    /// Loads the texture at the resource `path`, named by that path.
+    pub fn load_texture(
+        &mut self,
+        state: &state::WgpuState,
+        path: &str,
+    ) -> Result<Handle<texture::Texture>> {
+        let handle = self
+            .textures
+            .get_or_load(path, || texture::Texture::load(state, path, false))?;
+        self.manifest.textures.insert(PathBuf::from(path));
+        Ok(handle)
+    }
+
+    /// Points `handle` at the texture at `path`, loading it if `handle`
+    /// refers to another texture. Paths that failed to load are logged once
+    /// and left without a texture.
+    pub fn resolve_texture(
+        &mut self,
+        state: &state::WgpuState,
+        path: &str,
+        handle: &mut Option<Handle<texture::Texture>>,
+    ) {
+        let current = handle
+            .as_ref()
+            .and_then(|handle| self.textures.name(handle));
+        if current == Some(path) {
+            return;
+        }
+
+        *handle = None;
+        if path.is_empty() || self.failed_textures.contains(path) {
+            return;
+        }
+        match self.load_texture(state, path) {
+            Ok(loaded) => *handle = Some(loaded),
+            Err(e) => {
+                error!("Could not load texture {:?}: {:?}", path, e);
+                self.failed_textures.insert(String::from(path));
+            }
+        }
+    }
+
+    pub fn manifest(&self) -> &AssetManifest {
+        &self.manifest
+    }
+
+    /// Loads every model and texture of `manifest` that isn't loaded yet.
+    pub fn load_manifest(
+        &mut self,
+        state: &state::WgpuState,
+        material_layout: &binding::BindGroupLayout,
+        manifest: &AssetManifest,
+    ) -> Result<()> {
+        self.listed = manifest.models.keys().cloned().collect();
+        for (name, source) in manifest.models.iter() {
+            if self.models.contains(name) {
+                continue;
+            }
+
+            match source {
+                ModelSource::File {
+                    path,
+                    lods,
+                    lod_files,
+                } => {
+                    self.load_model_with_lods(state, material_layout, name.as_str(), path, lods)?;
+                    for (path, screen_size) in lod_files {
+                        self.load_lod(state, name, path, *screen_size)?;
+                    }
+                }
+                ModelSource::Primitive(primitive) => {
+                    self.load_primitive(state, material_layout, name.as_str(), *primitive)?;
+                }
+            }
+        }
+        for (name, path) in manifest.sounds.iter() {
+            self.load_sound(name.as_str(), path)?;
+        }
+        for path in manifest.textures.iter() {
+            self.load_texture(state, &path.to_string_lossy())?;
+        }
+        Ok(())
+    }
+
+    pub fn resources(&self) -> &Arc<Resources> {
+        &self.resources
+    }
+
+    /// Uploads the models decoded since the last call, returning their
+    /// names.
+    pub fn poll_loading(
+        &mut self,
+        state: &state::WgpuState,
+        material_layout: &binding::BindGroupLayout,
+    ) -> Vec<String> {
+        let mut loaded = Vec::new();
+        for (name, data) in self.loader.finished() {
+            self.watch(&name, &data);
+            match model::Model::from_data(state, material_layout, &mut self.textures, data) {
+                Ok(model) => {
+                    self.models.insert(name.as_str(), model);
+                    self.loader.set_status(&name, LoadStatus::Loaded);
+                    loaded.push(name);
+                }
+                Err(e) => {
+                    error!("Could not upload {:?}: {:?}", name, e);
+                    self.loader
+                        .set_status(&name, LoadStatus::Failed(e.to_string()));
+                }
+            }
+        }
+        self.loader.clear_finished();
+        loaded
+    }
+
+    /// Keeps the model `name` loaded while no entity uses it, for models
+    /// every scene can spawn.
+    pub fn keep_loaded<N: Into<String>>(&mut self, name: N) {
+        self.kept.insert(name.into());
+    }
+
+    /// Drops the assets no handle refers to, apart from the models kept
+    /// with [`AssetManager::keep_loaded`] and the ones the last loaded
+    /// manifest lists.
+    pub fn unload_unused(&mut self) {
+        // Held until the end so the kept models count as used
+        let _kept = self
+            .kept
+            .iter()
+            .chain(self.listed.iter())
+            .filter_map(|name| self.models.handle(name))
+            .collect::<Vec<_>>();
+        for name in self.models.unload_unused() {
+            self.watcher.unwatch_model(&name);
+            self.manifest.models.remove(&name);
+        }
+        self.materials.unload_unused();
+        self.material_arrays.unload_unused();
+        for name in self.textures.unload_unused() {
+            self.manifest.textures.remove(Path::new(&name));
+        }
+        // Sounds are played by name, they stay loaded
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::info;
+
+/// Files are polled rather than watched through OS notifications, which is
+/// plenty for a handful of assets and needs no platform code.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+struct WatchedAsset {
+    path: PathBuf,
+    sources: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Tracks the files every loaded model was built from.
+pub struct AssetWatcher {
+    models: HashMap<String, WatchedAsset>,
+    last_poll: Instant,
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl AssetWatcher {
+    pub fn new() -> Self {
+        Self {
+            models: HashMap::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Starts watching `sources` of model `name`, replacing what was watched
+    /// for it before. `path` is what gets loaded again on changes.
+    pub fn watch_model(&mut self, name: &str, path: PathBuf, sources: Vec<PathBuf>) {
+        let sources = sources
+            .into_iter()
+            .map(|source| {
+                let time = modified(&source);
+                (source, time)
+            })
+            .collect();
+        self.models
+            .insert(String::from(name), WatchedAsset { path, sources });
+    }
+
+    pub fn unwatch_model(&mut self, name: &str) {
+        self.models.remove(name);
+    }
+
+    /// Models with a changed source since the last poll, with their path
+    /// and the changed files. Returns nothing until the poll interval has
+    /// passed.
+    pub fn poll(&mut self) -> Vec<(String, PathBuf, Vec<PathBuf>)> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for (name, asset) in self.models.iter_mut() {
+            let mut changed_sources = Vec::new();
+            for (source, time) in asset.sources.iter_mut() {
+                let current = modified(source);
+                if current != *time {
+                    *time = current;
+                    changed_sources.push(source.clone());
+                }
+            }
+
+            if !changed_sources.is_empty() {
+                info!("Model {:?} changed on disk: {:?}", name, changed_sources);
+                changed.push((name.clone(), asset.path.clone(), changed_sources));
+            }
+        }
+        changed
+    }
+}
+
+impl Default for AssetWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
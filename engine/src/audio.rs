@@ -0,0 +1,260 @@
+//! Sound playback through rodio. Entities play clips with an
+//! [`AudioSource`], heard from the camera: spatial sources are panned
+//! between the listener's ears and fade with distance.
+
+use std::{collections::HashMap, io::Cursor, sync::Arc};
+
+use anyhow::*;
+use log::{error, info, warn};
+use nalgebra::{Point3, Vector3};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Source, SpatialSink};
+
+use crate::inspect::Inspect;
+
+/// Distance between the listener's ears
+const EAR_SPACING: f32 = 0.2;
+
+/// Encoded sound file, decoded every time it starts playing. OGG, WAV,
+/// FLAC and MP3 are supported.
+#[derive(Clone)]
+pub struct AudioClip {
+    data: Arc<[u8]>,
+}
+
+impl AudioClip {
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        let clip = Self { data: data.into() };
+        // Fail on load instead of every time it plays
+        clip.decoder()?;
+        Ok(clip)
+    }
+
+    fn decoder(&self) -> Result<Decoder<Cursor<Arc<[u8]>>>> {
+        Decoder::new(Cursor::new(self.data.clone())).context("Unsupported audio format")
+    }
+}
+
+/// Plays the clip called `clip` from the entity.
+#[derive(Debug, Clone, PartialEq, Inspect)]
+pub struct AudioSource {
+    pub clip: String,
+    pub looping: bool,
+    #[inspect_slider(min_value = 0.0, max_value = 1.0)]
+    pub volume: f32,
+    /// Cleared once a clip that doesn't loop finishes
+    pub playing: bool,
+    /// Panned and attenuated from the entity's position, otherwise heard
+    /// the same everywhere
+    pub spatial: bool,
+}
+
+impl AudioSource {
+    pub fn new<S: Into<String>>(clip: S) -> Self {
+        Self {
+            clip: clip.into(),
+            looping: false,
+            volume: 1.0,
+            playing: true,
+            spatial: true,
+        }
+    }
+}
+
+struct Listener {
+    position: Point3<f32>,
+    right: Vector3<f32>,
+}
+
+impl Listener {
+    fn left_ear(&self) -> [f32; 3] {
+        (self.position - self.right * EAR_SPACING / 2.0)
+            .coords
+            .into()
+    }
+
+    fn right_ear(&self) -> [f32; 3] {
+        (self.position + self.right * EAR_SPACING / 2.0)
+            .coords
+            .into()
+    }
+}
+
+struct Playing {
+    sink: SpatialSink,
+    clip: String,
+}
+
+pub struct Audio {
+    /// `None` without an output device, sounds are then skipped
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    listener: Listener,
+    playing: HashMap<legion::Entity, Playing>,
+    /// One shot sounds not tied to an entity
+    detached: Vec<SpatialSink>,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        let output = match OutputStream::try_default() {
+            Ok(output) => {
+                info!("Audio output initialized");
+                Some(output)
+            }
+            Err(e) => {
+                warn!("No audio output, sounds are disabled: {}", e);
+                None
+            }
+        };
+        Self::with_output(output)
+    }
+
+    /// Skips every sound, until replaced by one with an output.
+    pub fn disabled() -> Self {
+        Self::with_output(None)
+    }
+
+    fn with_output(output: Option<(OutputStream, OutputStreamHandle)>) -> Self {
+        Self {
+            output,
+            listener: Listener {
+                position: Point3::origin(),
+                right: Vector3::x(),
+            },
+            playing: HashMap::new(),
+            detached: Vec::new(),
+        }
+    }
+
+    /// Moves the ears, usually to the camera.
+    pub fn set_listener(&mut self, position: Point3<f32>, right: Vector3<f32>) {
+        self.listener = Listener { position, right };
+        let (left, right) = (self.listener.left_ear(), self.listener.right_ear());
+        for sink in self
+            .playing
+            .values()
+            .map(|playing| &playing.sink)
+            .chain(self.detached.iter())
+        {
+            sink.set_left_ear_position(left);
+            sink.set_right_ear_position(right);
+        }
+    }
+
+    /// Plays `clip` once, at `position` or everywhere with `None`.
+    pub fn play(&mut self, clip: &AudioClip, position: Option<Point3<f32>>, volume: f32) {
+        let emitter = position.unwrap_or(self.listener.position);
+        if let Some(sink) = self.start(clip, emitter, false, volume) {
+            self.detached.push(sink);
+        }
+        self.detached.retain(|sink| !sink.empty());
+    }
+
+    /// Starts, moves and stops the sinks of `sources`. Returns the
+    /// entities whose clip finished.
+    pub fn update<'a>(
+        &mut self,
+        sources: impl Iterator<Item = (legion::Entity, &'a AudioSource, Point3<f32>)>,
+        clip: impl Fn(&str) -> Option<&'a AudioClip>,
+    ) -> Vec<legion::Entity> {
+        let mut finished = Vec::new();
+        let mut seen = Vec::new();
+
+        for (entity, source, position) in sources {
+            if !source.playing {
+                continue;
+            }
+            seen.push(entity);
+
+            let emitter = if source.spatial {
+                position
+            } else {
+                self.listener.position
+            };
+            let emitter: [f32; 3] = emitter.coords.into();
+
+            match self.playing.get(&entity) {
+                Some(playing) if playing.clip == source.clip => {
+                    if playing.sink.empty() {
+                        self.playing.remove(&entity);
+                        finished.push(entity);
+                        continue;
+                    }
+                    playing.sink.set_emitter_position(emitter);
+                    playing.sink.set_volume(source.volume);
+                }
+                _ => {
+                    let started = match clip(&source.clip) {
+                        Some(clip) => {
+                            self.start(clip, Point3::from(emitter), source.looping, source.volume)
+                        }
+                        None => {
+                            error!("Audio clip {} not loaded", source.clip);
+                            finished.push(entity);
+                            None
+                        }
+                    };
+                    match started {
+                        Some(sink) => {
+                            self.playing.insert(
+                                entity,
+                                Playing {
+                                    sink,
+                                    clip: source.clip.clone(),
+                                },
+                            );
+                        }
+                        None => {
+                            self.playing.remove(&entity);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Stopped sources and removed entities
+        self.playing.retain(|entity, playing| {
+            let keep = seen.contains(entity);
+            if !keep {
+                playing.sink.stop();
+            }
+            keep
+        });
+        finished
+    }
+
+    fn start(
+        &self,
+        clip: &AudioClip,
+        emitter: Point3<f32>,
+        looping: bool,
+        volume: f32,
+    ) -> Option<SpatialSink> {
+        let (_, handle) = self.output.as_ref()?;
+        let sink = match SpatialSink::try_new(
+            handle,
+            emitter.coords.into(),
+            self.listener.left_ear(),
+            self.listener.right_ear(),
+        ) {
+            Ok(sink) => sink,
+            Err(e) => {
+                error!("Could not play sound: {}", e);
+                return None;
+            }
+        };
+        let decoder = match clip.decoder() {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                error!("Could not decode sound: {:?}", e);
+                return None;
+            }
+        };
+        if looping {
+            sink.append(decoder.repeat_infinite());
+        } else {
+            sink.append(decoder);
+        }
+        sink.set_volume(volume);
+        Some(sink)
+    }
+}
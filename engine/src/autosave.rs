@@ -0,0 +1,201 @@
+//! Periodic autosave of the scene being edited. Snapshots are serialized
+//! often and written to a file in the temp directory less often, a panic
+//! writes the latest one before unwinding. The file is removed on a clean
+//! exit, so finding it at startup means the last session crashed and its
+//! scene can be recovered.
+//!
+//! Every running editor autosaves to a slot of its own, held by a lock
+//! file it touches while it runs. A slot whose lock went stale belonged to
+//! an editor that is gone, its autosave is what a new one recovers.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::*;
+use log::{info, warn};
+
+use crate::world;
+
+/// How often the scene is serialized into the snapshot
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+/// How often the snapshot is written to the autosave file
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// How often a running editor touches the lock of its slot
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// Locks untouched for this long belong to an editor that is gone
+const STALE_LOCK: Duration = Duration::from_secs(20);
+/// Slots tried before falling back to one of the process' own
+const SLOTS: usize = 16;
+
+/// Where autosave `slot` is written, RON like the scene files.
+pub fn autosave_path(slot: usize) -> PathBuf {
+    std::env::temp_dir().join(format!("nodas_autosave_{}.ron", slot))
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    path.with_extension("lock")
+}
+
+/// Whether the lock at `path` is missing or no editor touched it lately.
+fn is_stale(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .map_or(false, |age| age >= STALE_LOCK)
+        })
+        .unwrap_or(true)
+}
+
+/// The autosave path of the first slot no running editor holds, locked
+/// for this one.
+pub fn claim_path() -> PathBuf {
+    for slot in 0..SLOTS {
+        let path = autosave_path(slot);
+        let lock = lock_path(&path);
+        if is_stale(&lock) && fs::write(&lock, std::process::id().to_string()).is_ok() {
+            return path;
+        }
+    }
+    warn!("Every autosave slot is taken, autosaving for this process only");
+    std::env::temp_dir().join(format!("nodas_autosave_pid{}.ron", std::process::id()))
+}
+
+/// Writes `source` next to `path` first, so a crash while writing leaves
+/// the previous autosave intact.
+fn write_atomic(path: &Path, source: &str) -> Result<()> {
+    let partial = path.with_extension("ron.partial");
+    fs::write(&partial, source).context(format!("Could not write autosave {:?}", partial))?;
+    fs::rename(&partial, path).context(format!("Could not replace autosave {:?}", path))
+}
+
+pub struct Autosave {
+    path: PathBuf,
+    /// The latest serialized scene, shared with the panic hook
+    snapshot: Arc<Mutex<Option<String>>>,
+    last_snapshot: Instant,
+    last_write: Instant,
+    last_heartbeat: Instant,
+    /// Whether the snapshot changed since it was written
+    unwritten: bool,
+}
+
+impl Autosave {
+    /// Starts autosaving to `path` and hooks panics to flush the snapshot.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let snapshot = Arc::new(Mutex::new(None::<String>));
+
+        let hook_path = path.clone();
+        let hook_snapshot = snapshot.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |panic| {
+            // Printed rather than logged, the logger may already be gone
+            // Whatever panicked may hold the lock
+            if let Ok(snapshot) = hook_snapshot.try_lock() {
+                if let Some(source) = snapshot.as_ref() {
+                    match write_atomic(&hook_path, source) {
+                        Ok(()) => eprintln!("Autosaved the scene to {:?}", hook_path),
+                        Err(e) => eprintln!("Could not autosave the scene: {:?}", e),
+                    }
+                }
+            }
+            previous(panic);
+        }));
+
+        let now = Instant::now();
+        Self {
+            path,
+            snapshot,
+            last_snapshot: now,
+            last_write: now,
+            last_heartbeat: now,
+            unwritten: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a crashed session left a scene to recover.
+    pub fn recoverable(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Snapshots `world` and writes the snapshot once their intervals
+    /// passed.
+    pub fn update(&mut self, world: &world::World) {
+        if self.last_snapshot.elapsed() >= SNAPSHOT_INTERVAL {
+            self.last_snapshot = Instant::now();
+            match self.snapshot(world) {
+                Ok(()) => self.unwritten = true,
+                Err(e) => warn!("Could not snapshot the scene: {:?}", e),
+            }
+        }
+        if self.unwritten && self.last_write.elapsed() >= AUTOSAVE_INTERVAL {
+            self.last_write = Instant::now();
+            self.unwritten = false;
+            if let Err(e) = self.write() {
+                warn!("{:?}", e);
+            }
+        }
+    }
+
+    fn snapshot(&self, world: &world::World) -> Result<()> {
+        let source = ron::ser::to_string_pretty(&world.scene()?, ron::ser::PrettyConfig::new())?;
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            *snapshot = Some(source);
+        }
+        Ok(())
+    }
+
+    fn write(&self) -> Result<()> {
+        let snapshot = self
+            .snapshot
+            .lock()
+            .map_err(|_| anyhow!("Autosave lock poisoned"))?;
+        if let Some(source) = snapshot.as_ref() {
+            write_atomic(&self.path, source)?;
+            info!("Autosaved the scene to {:?}", self.path);
+        }
+        Ok(())
+    }
+
+    /// Touches the lock of the slot once the interval passed, so other
+    /// editors keep away from it. Called every frame, whether autosaving or
+    /// not.
+    pub fn heartbeat(&mut self) {
+        if self.last_heartbeat.elapsed() < HEARTBEAT_INTERVAL {
+            return;
+        }
+        self.last_heartbeat = Instant::now();
+        let lock = lock_path(&self.path);
+        if let Err(e) = fs::write(&lock, std::process::id().to_string()) {
+            warn!("Could not refresh autosave lock {:?}: {}", lock, e);
+        }
+    }
+
+    /// Frees the slot for the next editor, on a clean exit.
+    pub fn release(&self) {
+        let lock = lock_path(&self.path);
+        if let Err(e) = fs::remove_file(&lock) {
+            warn!("Could not remove autosave lock {:?}: {}", lock, e);
+        }
+    }
+
+    /// Removes the autosave, on a clean exit or once the user decided
+    /// against recovering it.
+    pub fn discard(&self) {
+        if self.path.exists() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("Could not remove autosave {:?}: {}", self.path, e);
+            }
+        }
+    }
+}
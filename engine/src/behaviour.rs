@@ -0,0 +1,109 @@
+//! Gameplay written in Rust, attached to entities like scripts. Implement
+//! [`Behaviour`] and add it with `World::add_behaviour`, its callbacks run
+//! every frame after the fixed steps.
+
+use nalgebra::{Point3, Vector3};
+use ncollide3d::{pipeline::CollisionGroups, query::Ray};
+
+use crate::{input, physics, scripting::ScriptCommand, transform};
+
+pub trait Behaviour: Send + Sync + 'static {
+    /// Called once before the first update.
+    fn start(&mut self, _ctx: &mut ScriptCtx) {}
+
+    fn update(&mut self, ctx: &mut ScriptCtx, dt: f32);
+
+    /// Called for each entity the entity started touching this frame.
+    fn on_collision(&mut self, _ctx: &mut ScriptCtx, _other: legion::Entity) {}
+}
+
+struct Slot {
+    behaviour: Box<dyn Behaviour>,
+    started: bool,
+}
+
+/// Behaviours of an entity, run in the order they were added.
+#[derive(Default)]
+pub struct Behaviours(Vec<Slot>);
+
+impl Behaviours {
+    pub fn push<B: Behaviour>(&mut self, behaviour: B) {
+        self.0.push(Slot {
+            behaviour: Box::new(behaviour),
+            started: false,
+        });
+    }
+
+    /// Runs the callbacks of every behaviour.
+    pub fn run(&mut self, ctx: &mut ScriptCtx, dt: f32, collisions: &[legion::Entity]) {
+        for slot in self.0.iter_mut() {
+            if !slot.started {
+                slot.started = true;
+                slot.behaviour.start(ctx);
+            }
+            slot.behaviour.update(ctx, dt);
+            for other in collisions {
+                slot.behaviour.on_collision(ctx, *other);
+            }
+        }
+    }
+}
+
+/// What a behaviour can see and change. World changes are queued and
+/// applied once every behaviour ran.
+pub struct ScriptCtx<'a> {
+    pub entity: legion::Entity,
+    pub transform: &'a mut transform::Transform,
+    pub input: Option<&'a input::Input>,
+    physics: &'a physics::Physics,
+    commands: &'a mut Vec<ScriptCommand>,
+}
+
+impl<'a> ScriptCtx<'a> {
+    pub fn new(
+        entity: legion::Entity,
+        transform: &'a mut transform::Transform,
+        input: Option<&'a input::Input>,
+        physics: &'a physics::Physics,
+        commands: &'a mut Vec<ScriptCommand>,
+    ) -> Self {
+        Self {
+            entity,
+            transform,
+            input,
+            physics,
+            commands,
+        }
+    }
+
+    /// Nearest hit of `ray` other than the entity itself.
+    #[allow(unused)]
+    pub fn raycast(&self, ray: &Ray<f32>, max_toi: f32) -> Option<physics::RaycastHit> {
+        self.physics
+            .raycast_all(ray, max_toi, &CollisionGroups::new())
+            .into_iter()
+            .find(|hit| hit.entity != self.entity)
+    }
+
+    /// Spawns `model` at `position` after the behaviours ran.
+    #[allow(unused)]
+    pub fn spawn(&mut self, model: &str, position: Point3<f32>) {
+        self.commands.push(ScriptCommand::Spawn {
+            model: model.to_string(),
+            position: position.coords,
+        });
+    }
+
+    /// Removes `entity` after the behaviours ran.
+    #[allow(unused)]
+    pub fn despawn(&mut self, entity: legion::Entity) {
+        self.commands.push(ScriptCommand::Despawn(entity));
+    }
+
+    /// Moves the entity by `offset` in its parent's space.
+    #[allow(unused)]
+    pub fn translate(&mut self, offset: Vector3<f32>) {
+        let translation = self.transform.translation().vector + offset;
+        self.transform.set_position(translation.into());
+    }
+}
@@ -0,0 +1,39 @@
+use nalgebra::{Point3, Vector3};
+use ncollide3d::bounding_volume::{BoundingSphere, AABB};
+
+use crate::{render::model, transform};
+
+/// World space bounds of an entity's model, refreshed whenever its
+/// transform or model changes.
+#[derive(Debug, Clone)]
+pub struct Bounds {
+    pub aabb: AABB<f32>,
+    pub sphere: BoundingSphere<f32>,
+}
+
+impl Bounds {
+    pub fn new(geometry: &model::Geometry, transform: &transform::Transform) -> Self {
+        let local = geometry.local_aabb();
+        let scale = transform.world_scale();
+        let (a, b) = (
+            local.mins.coords.component_mul(&scale),
+            local.maxs.coords.component_mul(&scale),
+        );
+        // Negative scales flip the box
+        let scaled = AABB::new(Point3::from(a.inf(&b)), Point3::from(a.sup(&b)));
+
+        let isometry = transform.world_isometry();
+        Self {
+            aabb: scaled.transform_by(&isometry),
+            sphere: BoundingSphere::new(isometry * scaled.center(), scaled.half_extents().norm()),
+        }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        *self.sphere.center()
+    }
+
+    pub fn extents(&self) -> Vector3<f32> {
+        self.aabb.extents()
+    }
+}
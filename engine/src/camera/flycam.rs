@@ -1,72 +1,78 @@
 use std::time::Duration;
 
 use nalgebra::{Translation3, Vector2, Vector3};
-use winit::event::{ElementState, VirtualKeyCode};
 
 use super::Camera;
+use crate::input::Input;
 
+/// World units a scrolled line dollies the camera
+const DOLLY_STEP: f32 = 2.0;
+/// Zoom factor a scrolled line multiplies the view by
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 8.0;
+/// How fast scrolling catches up, higher is snappier
+const SCROLL_SMOOTHING: f32 = 12.0;
+const ACCELERATION: f32 = 8.0;
+const DAMPING: f32 = 5.0;
+const ROTATION_SMOOTHING: f32 = 20.0;
+
+/// What the scroll wheel does to the fly camera, toggled with F4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollMode {
+    /// Moves the camera forward and back
+    Dolly,
+    /// Narrows and widens the field of view
+    Fov,
+}
+
+/// Moves the camera along the `move_forward`, `move_right` and `move_up`
+/// axes, speeding up and slowing down smoothly unless `raw` is set.
 pub struct FlyCamController {
-    amount_left: f32,
-    amount_right: f32,
-    amount_forward: f32,
-    amount_backward: f32,
-    amount_up: f32,
-    amount_down: f32,
     speed: f32,
     rotate_horizontal: f32,
     rotate_vertical: f32,
     sensitivity: f32,
+    /// How fast the camera reaches full speed, higher is snappier
+    pub acceleration: f32,
+    /// How fast the camera stops once released, higher is snappier
+    pub damping: f32,
+    /// How fast the view catches up with the mouse, higher is snappier
+    pub rotation_smoothing: f32,
+    /// Moves and turns instantly, toggled with F7
+    pub raw: bool,
+    velocity: Vector3<f32>,
+    /// Yaw and pitch still to turn by
+    rotation: Vector2<f32>,
+    pub scroll_mode: ScrollMode,
+    /// Dolly distance still to travel
+    dolly: f32,
+    /// Zoom the view is easing towards
+    target_zoom: Option<f32>,
 }
 
 impl FlyCamController {
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
-            amount_left: 0.0,
-            amount_right: 0.0,
-            amount_forward: 0.0,
-            amount_backward: 0.0,
-            amount_up: 0.0,
-            amount_down: 0.0,
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
             speed,
             sensitivity,
+            acceleration: ACCELERATION,
+            damping: DAMPING,
+            rotation_smoothing: ROTATION_SMOOTHING,
+            raw: false,
+            velocity: Vector3::zeros(),
+            rotation: Vector2::zeros(),
+            scroll_mode: ScrollMode::Dolly,
+            dolly: 0.0,
+            target_zoom: None,
         }
     }
 
-    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
-        let amount = if state == ElementState::Pressed {
-            1.0
-        } else {
-            0.0
-        };
-        match key {
-            VirtualKeyCode::W | VirtualKeyCode::Up => {
-                self.amount_forward = amount;
-                true
-            }
-            VirtualKeyCode::S | VirtualKeyCode::Down => {
-                self.amount_backward = amount;
-                true
-            }
-            VirtualKeyCode::A | VirtualKeyCode::Left => {
-                self.amount_left = amount * 0.75;
-                true
-            }
-            VirtualKeyCode::D | VirtualKeyCode::Right => {
-                self.amount_right = amount * 0.75;
-                true
-            }
-            VirtualKeyCode::Space => {
-                self.amount_up = amount;
-                true
-            }
-            VirtualKeyCode::LShift => {
-                self.amount_down = amount;
-                true
-            }
-            _ => false,
-        }
+    /// Sets the top speed in units per second.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
@@ -74,26 +80,70 @@ impl FlyCamController {
         self.rotate_vertical = mouse_dy as f32;
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+    /// Dollies or zooms by `lines` scrolled, eased in over the next frames.
+    pub fn process_scroll(&mut self, camera: &Camera, lines: f32) {
+        match self.scroll_mode {
+            ScrollMode::Dolly => self.dolly += lines * DOLLY_STEP,
+            ScrollMode::Fov => {
+                let zoom = self
+                    .target_zoom
+                    .unwrap_or_else(|| camera.projection().zoom());
+                self.target_zoom = Some((zoom * ZOOM_STEP.powf(lines)).max(MIN_ZOOM).min(MAX_ZOOM));
+            }
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, input: &Input, dt: Duration) {
         let dt = dt.as_secs_f32();
+        let ease = 1.0 - (-SCROLL_SMOOTHING * dt).exp();
 
         let frame = camera.observer_frame();
         let forward = frame * Vector3::z();
         let right = frame * Vector3::x();
         let up = frame * Vector3::y();
 
-        let forward_disp = forward * (self.amount_forward - self.amount_backward);
-        let right_disp = right * -(self.amount_right - self.amount_left);
-        let up_disp = up * (self.amount_up - self.amount_down);
+        let forward_disp = forward * input.axis("move_forward");
+        let right_disp = right * -(input.axis("move_right") * 0.75);
+        let up_disp = up * input.axis("move_up");
+
+        let target = (forward_disp + right_disp + up_disp) * self.speed;
+        self.velocity = if self.raw {
+            target
+        } else {
+            let rate = if target == Vector3::zeros() {
+                self.damping
+            } else {
+                self.acceleration
+            };
+            self.velocity + (target - self.velocity) * (1.0 - (-rate * dt).exp())
+        };
+
+        let dolly = self.dolly * ease;
+        self.dolly -= dolly;
+
+        camera.translate_mut(&Translation3::from(self.velocity * dt + forward * dolly));
 
-        camera.translate_mut(&Translation3::from(
-            (forward_disp + right_disp + up_disp) * self.speed * dt,
-        ));
+        if let Some(target) = self.target_zoom {
+            let zoom = camera.projection().zoom();
+            if (target - zoom).abs() < 0.001 {
+                camera.set_zoom(target);
+                self.target_zoom = None;
+            } else {
+                camera.set_zoom(zoom + (target - zoom) * ease);
+            }
+        }
 
-        camera.rotate_mut(&Vector2::new(
+        self.rotation += Vector2::new(
             self.rotate_horizontal * self.sensitivity * dt,
             self.rotate_vertical * self.sensitivity * dt * 0.75,
-        ));
+        );
+        let rotation = if self.raw {
+            self.rotation
+        } else {
+            self.rotation * (1.0 - (-self.rotation_smoothing * dt).exp())
+        };
+        self.rotation -= rotation;
+        camera.rotate_mut(&rotation);
 
         self.rotate_horizontal = 0.0;
         self.rotate_vertical = 0.0;
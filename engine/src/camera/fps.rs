@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector2, Vector3};
+
+use super::Camera;
+use crate::input::Input;
+
+/// Looks through the eyes of a character, walking it along the
+/// `move_forward` and `move_right` axes and jumping on `jump`.
+pub struct FirstPersonController {
+    pub target: legion::Entity,
+    /// Eye height above the center of the character
+    pub eye_height: f32,
+    rotate_horizontal: f32,
+    rotate_vertical: f32,
+    sensitivity: f32,
+}
+
+impl FirstPersonController {
+    pub fn new(target: legion::Entity, eye_height: f32, sensitivity: f32) -> Self {
+        Self {
+            target,
+            eye_height,
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            sensitivity,
+        }
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.rotate_horizontal = mouse_dx as f32;
+        self.rotate_vertical = mouse_dy as f32;
+    }
+
+    /// Turns the camera, returning the direction to walk in on the ground
+    /// plane and whether to jump.
+    pub fn update_camera(
+        &mut self,
+        camera: &mut Camera,
+        input: &Input,
+        dt: Duration,
+    ) -> (Vector3<f32>, bool) {
+        let dt = dt.as_secs_f32();
+
+        camera.rotate_mut(&Vector2::new(
+            self.rotate_horizontal * self.sensitivity * dt,
+            self.rotate_vertical * self.sensitivity * dt * 0.75,
+        ));
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+
+        let frame = camera.observer_frame();
+        let flatten = |v: Vector3<f32>| {
+            Vector3::new(v.x, 0.0, v.z)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::zeros)
+        };
+        let forward = flatten(frame * Vector3::z());
+        let right = flatten(frame * Vector3::x());
+
+        let movement = forward * input.axis("move_forward") + right * -input.axis("move_right");
+
+        (movement, input.action_pressed("jump"))
+    }
+
+    /// Moves the eye to the character at `position`.
+    pub fn follow(&self, camera: &mut Camera, position: Point3<f32>) {
+        camera.set_eye(position + Vector3::y() * self.eye_height);
+    }
+}
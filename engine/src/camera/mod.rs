@@ -4,9 +4,15 @@ use nalgebra::{
 use ncollide3d::query::Ray;
 use projection::Projection;
 
+use crate::render::layer::RenderLayer;
+
 pub mod flycam;
+pub mod fps;
+pub mod orbit;
+pub mod path;
 pub mod projection;
 
+#[derive(Clone)]
 pub struct Camera {
     pub eye: Point3<f32>,
     yaw: f32,
@@ -18,6 +24,9 @@ pub struct Camera {
     coord_system: CoordSystemRh,
 
     pub view_proj: Matrix4<f32>,
+
+    /// Layers of the entities it draws
+    pub layers: RenderLayer,
 }
 
 impl Camera {
@@ -30,6 +39,7 @@ impl Camera {
             coord_system: CoordSystemRh::from_up_axis(Vector3::y_axis()),
             view_proj: one(),
             view: one(),
+            layers: RenderLayer::DEFAULT,
         };
 
         res.look_at(eye, at);
@@ -39,6 +49,30 @@ impl Camera {
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.projection.resize(width, height);
+        self.update_viewproj();
+    }
+
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.update_viewproj();
+    }
+
+    /// Looks at `center` from as far as it takes to see a sphere of
+    /// `radius` around it, keeping the view direction.
+    pub fn frame(&mut self, center: Point3<f32>, radius: f32) {
+        let direction = (self.at() - self.eye).normalize();
+        let distance = self.projection.fit(radius);
+        self.look_at(center - direction * distance, center);
+    }
+
+    /// Magnifies the view, see `Projection::set_zoom`.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.projection.set_zoom(zoom);
+        self.update_viewproj();
     }
 
     pub fn look_at(&mut self, eye: Point3<f32>, at: Point3<f32>) -> &mut Self {
@@ -63,6 +97,13 @@ impl Camera {
         self.coord_system.rotation_to_y_up.inverse() * Point3::new(ax, ay, az)
     }
 
+    /// Fraction of the screen height covered by a sphere, 1 or more once
+    /// it fills the view.
+    pub fn screen_size(&self, center: &Point3<f32>, radius: f32) -> f32 {
+        self.projection
+            .screen_size((center - self.eye).norm(), radius)
+    }
+
     pub fn update_viewproj(&mut self) -> &mut Self {
         self.view = self.view_transform().to_homogeneous();
         self.view_proj = self.projection.as_matrix() * self.view;
@@ -84,10 +125,58 @@ impl Camera {
         Isometry3::look_at_rh(&self.eye, &self.at(), &self.coord_system.up_axis)
     }
 
+    /// Direction to the right of the view.
+    pub fn right(&self) -> Vector3<f32> {
+        // The observer frame's x axis points to the left
+        -(self.observer_frame() * Vector3::x())
+    }
+
+    /// Direction up the view.
+    pub fn up(&self) -> Vector3<f32> {
+        self.observer_frame() * Vector3::y()
+    }
+
+    /// Corners of the volume the projection matrix keeps, near face first,
+    /// found by unprojecting the clip space box. The far face sits where
+    /// the matrix puts the far plane's depth.
+    pub fn frustum_corners(&self) -> Option<[Point3<f32>; 8]> {
+        let inverse = self.view_proj.try_inverse()?;
+        let far = self.far_depth();
+        let mut corners = [Point3::origin(); 8];
+        for (face, depth) in [0.0, far].iter().enumerate() {
+            for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .iter()
+                .enumerate()
+            {
+                corners[face * 4 + i] = inverse.transform_point(&Point3::new(*x, *y, *depth));
+            }
+        }
+        Some(corners)
+    }
+
     pub fn ray(&self) -> Ray<f32> {
         Ray::new(self.eye, self.observer_frame() * Vector3::z())
     }
 
+    /// Ray through the window position `(x, y)`, both from 0 to 1 with the
+    /// origin at the top left.
+    pub fn ray_at(&self, x: f32, y: f32) -> Option<Ray<f32>> {
+        let inverse = self.view_proj.try_inverse()?;
+        let (x, y) = (x * 2.0 - 1.0, 1.0 - y * 2.0);
+        let near = inverse.transform_point(&Point3::new(x, y, 0.0));
+        let far = inverse.transform_point(&Point3::new(x, y, self.far_depth()));
+        let direction = (far - near).try_normalize(f32::EPSILON)?;
+        Some(Ray::new(near, direction))
+    }
+
+    /// Clip space depth of the far plane
+    fn far_depth(&self) -> f32 {
+        self.projection
+            .as_matrix()
+            .transform_point(&Point3::new(0.0, 0.0, -self.projection.zfar()))
+            .z
+    }
+
     #[inline]
     pub fn rotate_mut(&mut self, disp: &Vector2<f32>) {
         self.yaw += disp.x;
@@ -116,6 +205,34 @@ impl Camera {
     }
 }
 
+/// Makes an entity a camera looking along the z axis of its transform.
+#[derive(Clone)]
+pub struct CameraComponent {
+    pub camera: Camera,
+    /// Seen through instead of the editor camera
+    pub active: bool,
+    /// Rendered into the camera preview window
+    pub preview: bool,
+}
+
+impl CameraComponent {
+    pub fn new(projection: Projection) -> Self {
+        Self {
+            camera: Camera::new(Point3::origin(), Point3::new(0.0, 0.0, 1.0), projection),
+            active: false,
+            preview: false,
+        }
+    }
+
+    /// The camera placed at `isometry`.
+    pub fn view_from(&self, isometry: &Isometry3<f32>) -> Camera {
+        let mut camera = self.camera.clone();
+        let eye = Point3::from(isometry.translation.vector);
+        camera.look_at(eye, eye + isometry.rotation * Vector3::z());
+        camera
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 struct CoordSystemRh {
     up_axis: Unit<Vector3<f32>>,
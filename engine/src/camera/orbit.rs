@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+
+use super::Camera;
+use crate::input::Input;
+
+const MIN_DISTANCE: f32 = 0.5;
+const MAX_DISTANCE: f32 = 500.0;
+/// Keeps the camera off the poles, where the up axis flips
+const PITCH_LIMIT: f32 = 0.01;
+/// Fraction of the distance a scroll step moves in
+const ZOOM_STEP: f32 = 0.1;
+/// Distances the focus pans while the mouse crosses the window
+const PAN_SPEED: f32 = 1.0;
+
+/// Orbits around a focus point while `look` is held, pans it on `pan` and
+/// zooms with the scroll wheel, magnifying orthographic views instead of
+/// moving closer.
+pub struct OrbitController {
+    pub focus: Point3<f32>,
+    pub distance: f32,
+    /// Around the up axis
+    yaw: f32,
+    /// From the up axis down to the camera
+    pitch: f32,
+    rotate_horizontal: f32,
+    rotate_vertical: f32,
+    /// Scrolled lines since the last update
+    scroll: f32,
+    sensitivity: f32,
+}
+
+impl OrbitController {
+    /// Orbits around the point `distance` in front of the camera, keeping
+    /// the current view.
+    pub fn from_camera(camera: &Camera, distance: f32, sensitivity: f32) -> Self {
+        let direction = (camera.at() - camera.eye).normalize();
+        let offset = -direction;
+        Self {
+            focus: camera.eye + direction * distance,
+            distance,
+            yaw: offset.z.atan2(offset.x),
+            pitch: offset.y.max(-1.0).min(1.0).acos(),
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            scroll: 0.0,
+            sensitivity,
+        }
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.rotate_horizontal = mouse_dx as f32;
+        self.rotate_vertical = mouse_dy as f32;
+    }
+
+    pub fn process_scroll(&mut self, lines: f32) {
+        self.scroll += lines;
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, input: &Input, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        if input.action("pan") {
+            let up = camera.observer_frame() * Vector3::y();
+            let pan =
+                camera.right() * -input.axis("look_horizontal") + up * input.axis("look_vertical");
+            self.focus += pan * self.distance * PAN_SPEED;
+        } else {
+            self.yaw += self.rotate_horizontal * self.sensitivity * dt;
+            self.pitch -= self.rotate_vertical * self.sensitivity * dt * 0.75;
+            self.pitch = self
+                .pitch
+                .max(PITCH_LIMIT)
+                .min(std::f32::consts::PI - PITCH_LIMIT);
+        }
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+
+        let zoom = std::mem::take(&mut self.scroll);
+        if zoom != 0.0 && camera.projection().is_orthographic() {
+            camera.set_zoom(camera.projection().zoom() * (1.0 + zoom * ZOOM_STEP));
+        } else if zoom != 0.0 {
+            self.distance = (self.distance * (1.0 - zoom * ZOOM_STEP))
+                .max(MIN_DISTANCE)
+                .min(MAX_DISTANCE);
+        }
+
+        let offset = Vector3::new(
+            self.yaw.cos() * self.pitch.sin(),
+            self.pitch.cos(),
+            self.yaw.sin() * self.pitch.sin(),
+        );
+        camera.look_at(self.focus + offset * self.distance, self.focus);
+    }
+}
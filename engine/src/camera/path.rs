@@ -0,0 +1,127 @@
+use nalgebra::{Isometry3, Point3, Translation3, UnitQuaternion};
+
+/// How the camera speeds up and slows down between two keys.
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKey {
+    /// Seconds from the start of the path
+    pub time: f32,
+    pub position: Point3<f32>,
+    /// Looks along the rotated z axis
+    pub rotation: UnitQuaternion<f32>,
+    /// Easing towards the next key
+    pub easing: Easing,
+}
+
+/// Keyframed camera track, played back with F6. Positions follow a
+/// Catmull-Rom spline through the keys, rotations are slerped.
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+    pub looping: bool,
+}
+
+impl CameraPath {
+    pub fn new(looping: bool) -> Self {
+        Self {
+            keys: Vec::new(),
+            looping,
+        }
+    }
+
+    /// Adds `key`, keeping the keys ordered by time.
+    pub fn push(&mut self, key: CameraKey) -> &mut Self {
+        let index = self
+            .keys
+            .iter()
+            .position(|other| other.time > key.time)
+            .unwrap_or_else(|| self.keys.len());
+        self.keys.insert(index, key);
+        self
+    }
+
+    #[allow(unused)]
+    pub fn keys(&self) -> &[CameraKey] {
+        &self.keys
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map(|key| key.time).unwrap_or(0.0)
+    }
+
+    /// Camera pose `time` seconds in, `None` once a path that doesn't loop
+    /// is over.
+    pub fn sample(&self, time: f32) -> Option<Isometry3<f32>> {
+        let first = self.keys.first()?;
+        let duration = self.duration();
+        let time = if self.looping && duration > 0.0 {
+            time.rem_euclid(duration)
+        } else if time > duration {
+            return None;
+        } else {
+            time
+        };
+        if self.keys.len() == 1 || time <= first.time {
+            return Some(pose(first.position, first.rotation));
+        }
+
+        let next = self
+            .keys
+            .iter()
+            .position(|key| key.time > time)
+            .unwrap_or(self.keys.len() - 1);
+        let prev = next - 1;
+        let (a, b) = (&self.keys[prev], &self.keys[next]);
+        let span = (b.time - a.time).max(f32::EPSILON);
+        let t = a.easing.apply(((time - a.time) / span).min(1.0));
+
+        // Neighbours of the segment, repeating the ends
+        let before = &self.keys[prev.saturating_sub(1)];
+        let after = &self.keys[(next + 1).min(self.keys.len() - 1)];
+        let position = catmull_rom(before.position, a.position, b.position, after.position, t);
+        let rotation = a
+            .rotation
+            .try_slerp(&b.rotation, t, f32::EPSILON)
+            .unwrap_or(a.rotation);
+        Some(pose(position, rotation))
+    }
+}
+
+fn pose(position: Point3<f32>, rotation: UnitQuaternion<f32>) -> Isometry3<f32> {
+    Isometry3::from_parts(Translation3::from(position.coords), rotation)
+}
+
+fn catmull_rom(
+    p0: Point3<f32>,
+    p1: Point3<f32>,
+    p2: Point3<f32>,
+    p3: Point3<f32>,
+    t: f32,
+) -> Point3<f32> {
+    let (t2, t3) = (t * t, t * t * t);
+    let coords = (p1.coords * 2.0
+        + (p2.coords - p0.coords) * t
+        + (p0.coords * 2.0 - p1.coords * 5.0 + p2.coords * 4.0 - p3.coords) * t2
+        + (p1.coords * 3.0 - p0.coords - p2.coords * 3.0 + p3.coords) * t3)
+        * 0.5;
+    Point3::from(coords)
+}
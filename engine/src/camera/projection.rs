@@ -1,18 +1,36 @@
-use nalgebra::{Matrix4, Perspective3};
+use nalgebra::{Matrix4, Orthographic3, Perspective3};
+
+const MIN_ZOOM: f32 = 0.01;
+/// Room left around a framed sphere
+const FRAMING_MARGIN: f32 = 1.2;
 
 #[allow(unused)]
-pub struct Projection {
-    fovy: f32,
-    znear: f32,
-    zfar: f32,
-    gpu_mat: Matrix4<f32>,
-    perspective: Perspective3<f32>,
+#[derive(Clone)]
+pub enum Projection {
+    /// Field of view `fovy` narrowed by `zoom`
+    Perspective {
+        fovy: f32,
+        zoom: f32,
+        znear: f32,
+        zfar: f32,
+        gpu_mat: Matrix4<f32>,
+        perspective: Perspective3<f32>,
+    },
+    /// Parallel view of `height` world units, divided by `zoom`
+    Orthographic {
+        height: f32,
+        zoom: f32,
+        aspect: f32,
+        znear: f32,
+        zfar: f32,
+    },
 }
 
 impl Projection {
     pub fn new(width: u32, height: u32, fovy: f32, znear: f32, zfar: f32) -> Self {
-        Self {
+        Projection::Perspective {
             fovy,
+            zoom: 1.0,
             znear,
             zfar,
             #[rustfmt::skip]
@@ -26,11 +44,116 @@ impl Projection {
         }
     }
 
+    /// Shows `view_height` world units from the bottom to the top of the
+    /// screen.
+    pub fn orthographic(width: u32, height: u32, view_height: f32, znear: f32, zfar: f32) -> Self {
+        Projection::Orthographic {
+            height: view_height,
+            zoom: 1.0,
+            aspect: width as f32 / height as f32,
+            znear,
+            zfar,
+        }
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.perspective.set_aspect(width as f32 / height as f32);
+        let new_aspect = width as f32 / height as f32;
+        match self {
+            Projection::Perspective { perspective, .. } => perspective.set_aspect(new_aspect),
+            Projection::Orthographic { aspect, .. } => *aspect = new_aspect,
+        }
+    }
+
+    pub fn zfar(&self) -> f32 {
+        match self {
+            Projection::Perspective { zfar, .. } | Projection::Orthographic { zfar, .. } => *zfar,
+        }
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        matches!(self, Projection::Orthographic { .. })
+    }
+
+    pub fn zoom(&self) -> f32 {
+        match self {
+            Projection::Perspective { zoom, .. } | Projection::Orthographic { zoom, .. } => *zoom,
+        }
+    }
+
+    /// Magnifies the view, narrowing the field of view of a perspective.
+    pub fn set_zoom(&mut self, new_zoom: f32) {
+        let new_zoom = new_zoom.max(MIN_ZOOM);
+        match self {
+            Projection::Perspective {
+                fovy,
+                zoom,
+                perspective,
+                ..
+            } => {
+                *zoom = new_zoom;
+                perspective.set_fovy(2.0 * ((*fovy / 2.0).tan() / new_zoom).atan());
+            }
+            Projection::Orthographic { zoom, .. } => *zoom = new_zoom,
+        }
+    }
+
+    /// Zooms an orthographic view to fit a sphere of `radius`, returning
+    /// how far from its center the eye should be.
+    pub fn fit(&mut self, radius: f32) -> f32 {
+        let radius = radius.max(f32::EPSILON) * FRAMING_MARGIN;
+        match self {
+            Projection::Perspective { fovy, zoom, .. } => {
+                radius * *zoom / (*fovy / 2.0).tan().abs()
+            }
+            Projection::Orthographic {
+                height,
+                zoom,
+                znear,
+                ..
+            } => {
+                *zoom = (*height / (radius * 2.0)).max(MIN_ZOOM);
+                radius * 2.0 + *znear
+            }
+        }
+    }
+
+    /// Fraction of the screen height covered by a sphere `distance` away
+    /// from the eye.
+    pub fn screen_size(&self, distance: f32, radius: f32) -> f32 {
+        match self {
+            Projection::Perspective { fovy, zoom, .. } => {
+                radius * zoom / (distance.max(f32::EPSILON) * (fovy / 2.0).tan())
+            }
+            Projection::Orthographic { height, zoom, .. } => radius * 2.0 * zoom / height,
+        }
     }
 
     pub fn as_matrix(&self) -> Matrix4<f32> {
-        self.gpu_mat * self.perspective.as_matrix()
+        match self {
+            Projection::Perspective {
+                gpu_mat,
+                perspective,
+                ..
+            } => gpu_mat * perspective.as_matrix(),
+            Projection::Orthographic {
+                height,
+                zoom,
+                aspect,
+                znear,
+                zfar,
+            } => {
+                let top = height / zoom / 2.0;
+                let right = top * aspect;
+                // From OpenGL's -1 to 1 depth to wgpu's 0 to 1
+                #[rustfmt::skip]
+                let depth = Matrix4::new(
+                    1.0, 0.0, 0.0, 0.0,
+                    0.0, 1.0, 0.0, 0.0,
+                    0.0, 0.0, 0.5, 0.5,
+                    0.0, 0.0, 0.0, 1.0,
+                );
+                depth * Orthographic3::new(-right, right, -top, top, *znear, *zfar).as_matrix()
+            }
+        }
     }
 }
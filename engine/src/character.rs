@@ -0,0 +1,206 @@
+//! Kinematic character controller. Characters are capsules moved by
+//! sweeping them through the collision world and sliding along whatever
+//! they hit, instead of being pushed around by the physics solver.
+
+use nalgebra::{Isometry3, Point3, Vector3};
+use ncollide3d::shape::{Capsule, ShapeHandle};
+
+use crate::{
+    inspect::Inspect,
+    physics::{Physics, GRAVITY},
+};
+
+/// Slides along surfaces tried per move before giving up
+const MAX_SLIDES: usize = 4;
+
+/// Gap kept between the capsule and what it touches, so the next sweep
+/// doesn't start inside it
+const SKIN_WIDTH: f32 = 0.01;
+
+/// Distance below the feet still counted as standing on the ground
+const GROUND_PROBE: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Inspect)]
+pub struct CharacterController {
+    pub radius: f32,
+    /// Total height, including both caps
+    pub height: f32,
+    /// Ledges up to this height are climbed without jumping
+    pub step_offset: f32,
+    /// Steepest walkable slope in radians
+    pub max_slope: f32,
+    pub speed: f32,
+    pub jump_speed: f32,
+    /// Direction to walk in, set by the input every frame
+    #[inspect(skip)]
+    pub movement: Vector3<f32>,
+    /// Jump on the next step if grounded
+    #[inspect(skip)]
+    pub jump: bool,
+    #[inspect(skip)]
+    pub vertical_velocity: f32,
+    #[inspect(skip)]
+    pub grounded: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            radius: 0.3,
+            height: 1.8,
+            step_offset: 0.3,
+            max_slope: std::f32::consts::FRAC_PI_4,
+            speed: 4.0,
+            jump_speed: 5.0,
+            movement: Vector3::zeros(),
+            jump: false,
+            vertical_velocity: 0.0,
+            grounded: false,
+        }
+    }
+}
+
+impl CharacterController {
+    /// Capsule centered on the transform's position.
+    pub fn shape(&self) -> ShapeHandle<f32> {
+        ShapeHandle::new(self.capsule())
+    }
+
+    fn capsule(&self) -> Capsule<f32> {
+        Capsule::new((self.height / 2.0 - self.radius).max(0.0), self.radius)
+    }
+
+    fn is_walkable(&self, normal: &Vector3<f32>) -> bool {
+        normal.y >= self.max_slope.cos()
+    }
+
+    /// Advances the character by `dt` seconds from `position`, returning
+    /// where it ends up. Updates the vertical velocity and ground state.
+    pub fn step(
+        &mut self,
+        physics: &Physics,
+        entity: legion::Entity,
+        position: Point3<f32>,
+        dt: f32,
+    ) -> Point3<f32> {
+        if self.grounded && self.jump {
+            self.vertical_velocity = self.jump_speed;
+            self.grounded = false;
+        }
+        self.jump = false;
+        self.vertical_velocity -= GRAVITY * dt;
+
+        let mut movement = Vector3::new(self.movement.x, 0.0, self.movement.z);
+        if movement.norm() > 1.0 {
+            movement.normalize_mut();
+        }
+        let horizontal = movement * self.speed * dt;
+        let vertical = Vector3::y() * self.vertical_velocity * dt;
+
+        let mut grounded = false;
+        let (moved, blocked) = self.slide(physics, entity, position, horizontal, &mut grounded);
+        let mut position = moved;
+
+        // Blocked while walking, try again from `step_offset` higher and
+        // keep whichever got further
+        if blocked && self.grounded && self.step_offset > 0.0 {
+            let up = Vector3::y() * self.step_offset;
+            let mut stepped_grounded = false;
+            let (raised, _) = self.slide(physics, entity, moved, up, &mut stepped_grounded);
+            let (forward, _) =
+                self.slide(physics, entity, raised, horizontal, &mut stepped_grounded);
+            let (lowered, _) = self.slide(
+                physics,
+                entity,
+                forward,
+                -Vector3::y() * (raised.y - moved.y),
+                &mut stepped_grounded,
+            );
+            if stepped_grounded && horizontal_distance(&moved, &lowered) > SKIN_WIDTH {
+                position = lowered;
+            }
+        }
+
+        let (moved, _) = self.slide(physics, entity, position, vertical, &mut grounded);
+        position = moved;
+
+        if !grounded && self.vertical_velocity <= 0.0 {
+            grounded = physics
+                .shape_cast(
+                    &self.capsule(),
+                    &isometry(&position),
+                    -Vector3::y(),
+                    GROUND_PROBE,
+                    Some(entity),
+                )
+                .map(|hit| self.is_walkable(&hit.normal))
+                .unwrap_or(false);
+        }
+
+        self.grounded = grounded;
+        if grounded && self.vertical_velocity < 0.0 {
+            self.vertical_velocity = 0.0;
+        }
+        position
+    }
+
+    /// Moves by `displacement`, sliding along anything in the way. Returns
+    /// the end position and whether something blocked the move.
+    fn slide(
+        &self,
+        physics: &Physics,
+        entity: legion::Entity,
+        mut position: Point3<f32>,
+        displacement: Vector3<f32>,
+        grounded: &mut bool,
+    ) -> (Point3<f32>, bool) {
+        let capsule = self.capsule();
+        let mut remaining = displacement;
+        let mut blocked = false;
+
+        for _ in 0..MAX_SLIDES {
+            let distance = remaining.norm();
+            if distance <= f32::EPSILON {
+                break;
+            }
+            let direction = remaining / distance;
+
+            let hit = match physics.shape_cast(
+                &capsule,
+                &isometry(&position),
+                direction,
+                distance + SKIN_WIDTH,
+                Some(entity),
+            ) {
+                Some(hit) => hit,
+                None => {
+                    position += remaining;
+                    break;
+                }
+            };
+
+            blocked = true;
+            if self.is_walkable(&hit.normal) {
+                *grounded = true;
+            }
+
+            let travel = (hit.distance - SKIN_WIDTH).max(0.0);
+            position += direction * travel;
+
+            // Keep the part of the move along the surface
+            remaining = direction * (distance - travel);
+            remaining -= hit.normal * remaining.dot(&hit.normal);
+        }
+
+        (position, blocked)
+    }
+}
+
+fn isometry(position: &Point3<f32>) -> Isometry3<f32> {
+    Isometry3::translation(position.x, position.y, position.z)
+}
+
+fn horizontal_distance(a: &Point3<f32>, b: &Point3<f32>) -> f32 {
+    let delta = b - a;
+    (delta.x * delta.x + delta.z * delta.z).sqrt()
+}
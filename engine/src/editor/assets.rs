@@ -0,0 +1,116 @@
+use imgui::{im_str, DragDropSource, ImString, Ui, WindowHoveredFlags};
+
+use super::{
+    layout::{self, Panel},
+    Editor,
+};
+use crate::{
+    locale::Locale,
+    render::thumbnail::{ThumbnailKind, Thumbnails, THUMBNAIL_SIZE},
+};
+
+/// Drag and drop payload name of models
+const MODEL_PAYLOAD: &str = "MODEL";
+
+/// Names of the loaded assets, as the asset browser lists them.
+#[derive(Debug, Clone, Default)]
+pub struct AssetList {
+    pub models: Vec<String>,
+    pub materials: Vec<String>,
+    pub textures: Vec<String>,
+    /// Loaded models spawned last, most recent first
+    pub recent: Vec<String>,
+}
+
+/// What the user did in the asset browser.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetAction {
+    /// Spawns the model where it was dropped into the scene
+    Spawn(String),
+}
+
+/// Draws the loaded assets with their thumbnails. Models are dragged out
+/// of the panel into the scene to spawn them.
+pub fn panel(
+    ui: &Ui,
+    locale: &Locale,
+    editor: &mut Editor,
+    assets: &AssetList,
+    thumbnails: &Thumbnails,
+) -> Vec<AssetAction> {
+    let mut actions = Vec::new();
+
+    layout::window(ui, Panel::Assets, &locale.label("assets.title")).build(ui, || {
+        if !assets.recent.is_empty() {
+            let header = im_str!("{}###assets.recent", locale.get("assets.recent"));
+            if imgui::CollapsingHeader::new(&header)
+                .default_open(true)
+                .build(ui)
+            {
+                // The same models are listed again below
+                let id = ui.push_id("recent");
+                for name in assets.recent.iter() {
+                    item(ui, editor, thumbnails, ThumbnailKind::Model, name);
+                }
+                id.pop(ui);
+            }
+        }
+        for (kind, key, names) in [
+            (ThumbnailKind::Model, "assets.models", &assets.models),
+            (
+                ThumbnailKind::Material,
+                "assets.materials",
+                &assets.materials,
+            ),
+            (ThumbnailKind::Texture, "assets.textures", &assets.textures),
+        ]
+        .iter()
+        {
+            let header = im_str!("{} ({})###{}", locale.get(key), names.len(), key);
+            if imgui::CollapsingHeader::new(&header)
+                .default_open(*kind == ThumbnailKind::Model)
+                .build(ui)
+            {
+                for name in names.iter() {
+                    item(ui, editor, thumbnails, *kind, name);
+                }
+            }
+        }
+    });
+
+    // Released over the scene rather than over one of the editor windows
+    if !ui.is_mouse_down(imgui::MouseButton::Left) {
+        if let Some(model) = editor.dragged_model.take() {
+            if !ui.is_window_hovered_with_flags(WindowHoveredFlags::ANY_WINDOW) {
+                actions.push(AssetAction::Spawn(model));
+            }
+        }
+    }
+    actions
+}
+
+fn item(ui: &Ui, editor: &mut Editor, thumbnails: &Thumbnails, kind: ThumbnailKind, name: &str) {
+    let size = [THUMBNAIL_SIZE as f32; 2];
+    match thumbnails.get(kind, name) {
+        Some(texture_id) => {
+            imgui::ImageButton::new(texture_id, size)
+                .frame_padding(0)
+                .build(ui);
+        }
+        // Not rendered yet
+        None => {
+            ui.button(&im_str!("...##{:?}{}", kind, name), size);
+        }
+    }
+
+    if kind == ThumbnailKind::Model {
+        if let Some(tooltip) = DragDropSource::new(&ImString::new(MODEL_PAYLOAD)).begin(ui) {
+            editor.dragged_model = Some(name.to_string());
+            ui.text(im_str!("{}", name));
+            tooltip.end(ui);
+        }
+    }
+
+    ui.same_line(0.0);
+    ui.text(im_str!("{}", name));
+}
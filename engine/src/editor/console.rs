@@ -0,0 +1,79 @@
+use imgui::{im_str, ComboBox, ImString, Ui};
+use log::{Level, LevelFilter};
+
+use super::{
+    layout::{self, Panel},
+    Editor,
+};
+use crate::{locale::Locale, logging::LogBuffer};
+
+/// Levels the console filters by, most severe first
+const LEVELS: [LevelFilter; 5] = [
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+fn level_color(level: Level) -> [f32; 4] {
+    match level {
+        Level::Error => [1.0, 0.4, 0.4, 1.0],
+        Level::Warn => [1.0, 0.8, 0.3, 1.0],
+        Level::Info => [1.0, 1.0, 1.0, 1.0],
+        Level::Debug | Level::Trace => [0.6, 0.6, 0.6, 1.0],
+    }
+}
+
+/// Draws the log records at or above the chosen level that contain the
+/// search text, following the newest while scrolled to the bottom.
+pub fn panel(ui: &Ui, locale: &Locale, editor: &mut Editor, log: &LogBuffer) {
+    layout::window(ui, Panel::Console, &locale.label("console.title")).build(ui, || {
+        let labels = LEVELS
+            .iter()
+            .map(|level| im_str!("{}", level))
+            .collect::<Vec<_>>();
+        let mut index = LEVELS
+            .iter()
+            .position(|level| *level == editor.log_level)
+            .unwrap_or(0);
+        ui.set_next_item_width(100.0);
+        if ComboBox::new(&locale.label("console.level")).build_simple(
+            ui,
+            &mut index,
+            labels.as_slice(),
+            &|label: &ImString| label.into(),
+        ) {
+            editor.log_level = LEVELS[index];
+        }
+        ui.same_line(0.0);
+        ui.set_next_item_width(200.0);
+        ui.input_text(&locale.label("console.search"), &mut editor.log_search)
+            .build();
+        ui.same_line(0.0);
+        if ui.button(&locale.label("console.clear"), [0.0, 0.0]) {
+            log.clear();
+        }
+        ui.separator();
+
+        imgui::ChildWindow::new(im_str!("log"))
+            .horizontal_scrollbar(true)
+            .build(ui, || {
+                let search = editor.log_search.to_str().to_lowercase();
+                if let Some(entries) = log.entries() {
+                    for entry in entries.iter().filter(|entry| {
+                        entry.level <= editor.log_level
+                            && (search.is_empty() || entry.message.to_lowercase().contains(&search))
+                    }) {
+                        ui.text_colored(
+                            level_color(entry.level),
+                            im_str!("[{}] {}: {}", entry.level, entry.target, entry.message),
+                        );
+                    }
+                }
+                if ui.scroll_y() >= ui.scroll_max_y() {
+                    ui.set_scroll_here_y_with_ratio(1.0);
+                }
+            });
+    });
+}
@@ -0,0 +1,211 @@
+use imgui::{
+    im_str, ComboBox, DragDropFlags, DragDropSource, DragDropTarget, ImString, TreeNode, Ui,
+};
+
+use super::{
+    layout::{self, Panel},
+    Editor, NAME_CAPACITY,
+};
+use crate::locale::Locale;
+
+/// Drag and drop payload name of entities
+const ENTITY_PAYLOAD: &str = "ENTITY";
+
+/// An entity as the hierarchy panel lists it.
+#[derive(Debug, Clone)]
+pub struct HierarchyEntry {
+    pub entity: legion::Entity,
+    pub name: Option<String>,
+    pub parent: Option<legion::Entity>,
+    pub children: Vec<legion::Entity>,
+    pub visible: bool,
+}
+
+impl HierarchyEntry {
+    fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{:?}", self.entity),
+        }
+    }
+}
+
+/// What the user did in the hierarchy panel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HierarchyAction {
+    Select(legion::Entity),
+    Rename(legion::Entity, String),
+    SetVisible(legion::Entity, bool),
+    /// Moves an entity under another, or to the root with `None`
+    Reparent(legion::Entity, Option<legion::Entity>),
+    /// Spawns an entity showing the model
+    Add(String),
+    /// Removes the selected entity and its children
+    Delete,
+    /// Copies the selected entity and its children
+    Duplicate,
+}
+
+/// Draws the entity tree below buttons to add, delete and duplicate
+/// entities. Entities are dragged onto each other to reparent them, or onto
+/// the root line to unparent them.
+pub fn panel(
+    ui: &Ui,
+    locale: &Locale,
+    editor: &mut Editor,
+    entries: &[HierarchyEntry],
+    selected: Option<legion::Entity>,
+    models: &[String],
+) -> Vec<HierarchyAction> {
+    let mut actions = Vec::new();
+
+    layout::window(ui, Panel::Hierarchy, &locale.label("hierarchy.title")).build(ui, || {
+        toolbar(ui, locale, editor, selected, models, &mut actions);
+        ui.separator();
+
+        ui.text(locale.get("hierarchy.root"));
+        drop_target(ui, editor, None, &mut actions);
+        ui.separator();
+
+        for entry in entries.iter().filter(|entry| entry.parent.is_none()) {
+            node(ui, editor, entries, entry, selected, &mut actions);
+        }
+
+        if let Some(selected) = selected {
+            if let Some(entry) = entries.iter().find(|entry| entry.entity == selected) {
+                ui.separator();
+                rename_field(ui, locale, editor, entry, &mut actions);
+            }
+        }
+    });
+
+    if !ui.is_mouse_down(imgui::MouseButton::Left) {
+        editor.dragged = None;
+    }
+    actions
+}
+
+fn node(
+    ui: &Ui,
+    editor: &mut Editor,
+    entries: &[HierarchyEntry],
+    entry: &HierarchyEntry,
+    selected: Option<legion::Entity>,
+    actions: &mut Vec<HierarchyAction>,
+) {
+    let mut visible = entry.visible;
+    if ui.checkbox(&im_str!("##visible{:?}", entry.entity), &mut visible) {
+        actions.push(HierarchyAction::SetVisible(entry.entity, visible));
+    }
+    ui.same_line(0.0);
+
+    let id = im_str!("{}##{:?}", entry.label(), entry.entity);
+    let token = TreeNode::new(&id)
+        .leaf(entry.children.is_empty())
+        .open_on_arrow(true)
+        .selected(selected == Some(entry.entity))
+        .push(ui);
+
+    if ui.is_item_clicked(imgui::MouseButton::Left) {
+        actions.push(HierarchyAction::Select(entry.entity));
+    }
+    if let Some(tooltip) = DragDropSource::new(&ImString::new(ENTITY_PAYLOAD)).begin(ui) {
+        editor.dragged = Some(entry.entity);
+        ui.text(im_str!("{}", entry.label()));
+        tooltip.end(ui);
+    }
+    drop_target(ui, editor, Some(entry.entity), actions);
+
+    if let Some(token) = token {
+        for child in entry
+            .children
+            .iter()
+            .filter_map(|child| entries.iter().find(|entry| entry.entity == *child))
+        {
+            node(ui, editor, entries, child, selected, actions);
+        }
+        token.pop(ui);
+    }
+}
+
+fn toolbar(
+    ui: &Ui,
+    locale: &Locale,
+    editor: &mut Editor,
+    selected: Option<legion::Entity>,
+    models: &[String],
+    actions: &mut Vec<HierarchyAction>,
+) {
+    if !models.is_empty() {
+        editor.add_model = editor.add_model.min(models.len() - 1);
+        let labels = models
+            .iter()
+            .map(|model| im_str!("{}", model))
+            .collect::<Vec<_>>();
+        ComboBox::new(im_str!("##add_model")).build_simple(
+            ui,
+            &mut editor.add_model,
+            labels.as_slice(),
+            &|label: &ImString| label.into(),
+        );
+        ui.same_line(0.0);
+        if ui.button(&locale.label("hierarchy.add"), [0.0, 0.0]) {
+            actions.push(HierarchyAction::Add(models[editor.add_model].clone()));
+        }
+    }
+
+    if selected.is_some() {
+        if ui.button(&locale.label("hierarchy.delete"), [0.0, 0.0]) {
+            actions.push(HierarchyAction::Delete);
+        }
+        ui.same_line(0.0);
+        if ui.button(&locale.label("hierarchy.duplicate"), [0.0, 0.0]) {
+            actions.push(HierarchyAction::Duplicate);
+        }
+    }
+}
+
+/// Reparents the dragged entity under `parent` when dropped on the last
+/// item.
+fn drop_target(
+    ui: &Ui,
+    editor: &mut Editor,
+    parent: Option<legion::Entity>,
+    actions: &mut Vec<HierarchyAction>,
+) {
+    if let Some(target) = DragDropTarget::new(ui) {
+        if target
+            .accept_payload_empty(&ImString::new(ENTITY_PAYLOAD), DragDropFlags::empty())
+            .is_some()
+        {
+            if let Some(dragged) = editor.dragged.take() {
+                if Some(dragged) != parent {
+                    actions.push(HierarchyAction::Reparent(dragged, parent));
+                }
+            }
+        }
+        target.pop();
+    }
+}
+
+fn rename_field(
+    ui: &Ui,
+    locale: &Locale,
+    editor: &mut Editor,
+    entry: &HierarchyEntry,
+    actions: &mut Vec<HierarchyAction>,
+) {
+    let (renamed, buffer) = &mut editor.rename;
+    if *renamed != Some(entry.entity) {
+        *renamed = Some(entry.entity);
+        *buffer = ImString::with_capacity(NAME_CAPACITY);
+        buffer.push_str(&entry.label());
+    }
+    if ui
+        .input_text(&locale.label("hierarchy.name"), buffer)
+        .enter_returns_true(true)
+        .build()
+    {
+        actions.push(HierarchyAction::Rename(entry.entity, buffer.to_string()));
+    }
+}
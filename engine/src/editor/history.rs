@@ -0,0 +1,368 @@
+use std::collections::VecDeque;
+
+use anyhow::*;
+
+use crate::{render::state, scene, world::World};
+
+/// Commands kept for undoing, the oldest are dropped past this
+const HISTORY_LIMIT: usize = 100;
+
+/// An entity and its descendants as they were when recorded.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Entities with the ids they had, parents are indices into the list
+    /// and the root comes first
+    entities: Vec<(legion::Entity, scene::EntityData)>,
+    /// Parent of the root
+    parent: Option<legion::Entity>,
+}
+
+impl Snapshot {
+    pub fn take(world: &World, entity: legion::Entity) -> Result<Self> {
+        let mut entities = Vec::new();
+        let mut stack = vec![(entity, None)];
+        while let Some((entity, parent)) = stack.pop() {
+            let mut data = world.entity_data(entity)?;
+            data.parent = parent;
+            let index = entities.len();
+            entities.push((entity, data));
+            stack.extend(
+                world
+                    .children(entity)
+                    .into_iter()
+                    .map(|child| (child, Some(index))),
+            );
+        }
+        Ok(Self {
+            entities,
+            parent: world.parent(entity),
+        })
+    }
+
+    fn root(&self) -> legion::Entity {
+        self.entities[0].0
+    }
+
+    /// Spawns the entities again, returning the ids they were recorded with
+    /// along with their new ones.
+    fn restore(
+        &self,
+        world: &mut World,
+        state: &state::WgpuState,
+    ) -> Result<Vec<(legion::Entity, legion::Entity)>> {
+        let mut spawned: Vec<(legion::Entity, legion::Entity)> = Vec::new();
+        for (old, data) in self.entities.iter() {
+            let entity = world.spawn(state, data)?;
+            if let Some(parent) = data.parent {
+                world.set_parent(entity, Some(spawned[parent].1))?;
+            }
+            spawned.push((*old, entity));
+        }
+        world.set_parent(spawned[0].1, self.parent)?;
+        world.propagate_transforms();
+        world.update_collision_world();
+        Ok(spawned)
+    }
+
+    fn remap(&mut self, old: legion::Entity, new: legion::Entity) {
+        for (entity, _) in self.entities.iter_mut() {
+            remap(entity, old, new);
+        }
+        if let Some(parent) = &mut self.parent {
+            remap(parent, old, new);
+        }
+    }
+}
+
+/// An undoable editor operation.
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Components of an entity changed, in the inspector or by renaming
+    Edit {
+        entity: legion::Entity,
+        before: scene::EntityData,
+        after: scene::EntityData,
+    },
+    /// Entities were added, duplicated or dropped into the scene
+    Spawn(Snapshot),
+    /// Entities were deleted
+    Remove(Snapshot),
+    Reparent {
+        entity: legion::Entity,
+        before: Option<legion::Entity>,
+        after: Option<legion::Entity>,
+    },
+}
+
+impl Command {
+    /// Does or undoes the command, returning the ids of entities that were
+    /// spawned again with their old ones.
+    fn run(
+        &self,
+        world: &mut World,
+        state: &state::WgpuState,
+        undo: bool,
+    ) -> Result<Vec<(legion::Entity, legion::Entity)>> {
+        match self {
+            Command::Edit {
+                entity,
+                before,
+                after,
+            } => {
+                world.apply_entity_data(*entity, if undo { before } else { after })?;
+                Ok(Vec::new())
+            }
+            Command::Spawn(snapshot) | Command::Remove(snapshot) => {
+                let spawn = matches!(self, Command::Spawn(_)) != undo;
+                if spawn {
+                    snapshot.restore(world, state)
+                } else {
+                    world.remove_entity(snapshot.root());
+                    Ok(Vec::new())
+                }
+            }
+            Command::Reparent {
+                entity,
+                before,
+                after,
+            } => {
+                world.set_parent(*entity, if undo { *before } else { *after })?;
+                world.propagate_transforms();
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    fn remap(&mut self, old: legion::Entity, new: legion::Entity) {
+        match self {
+            Command::Edit { entity, .. } => remap(entity, old, new),
+            Command::Spawn(snapshot) | Command::Remove(snapshot) => snapshot.remap(old, new),
+            Command::Reparent {
+                entity,
+                before,
+                after,
+            } => {
+                remap(entity, old, new);
+                if let Some(parent) = before {
+                    remap(parent, old, new);
+                }
+                if let Some(parent) = after {
+                    remap(parent, old, new);
+                }
+            }
+        }
+    }
+}
+
+fn remap(entity: &mut legion::Entity, old: legion::Entity, new: legion::Entity) {
+    if *entity == old {
+        *entity = new;
+    }
+}
+
+/// Editor operations that can be undone with Ctrl+Z and redone with
+/// Ctrl+Y. Entities spawned again by undoing get new ids, the commands
+/// still to come are remapped to them.
+pub struct History {
+    undo: VecDeque<Command>,
+    redo: Vec<Command>,
+    /// The last edit is still being dragged, further edits of the same
+    /// entity merge into it
+    open: bool,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            open: false,
+        }
+    }
+
+    /// Records a command that was just done.
+    pub fn push(&mut self, command: Command) {
+        self.redo.clear();
+        self.undo.push_back(command);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+        self.open = false;
+    }
+
+    /// Records an edit of `entity`, merged with the previous one while
+    /// `dragging` carries over from it.
+    pub fn edit(
+        &mut self,
+        entity: legion::Entity,
+        before: scene::EntityData,
+        after: scene::EntityData,
+        dragging: bool,
+    ) {
+        if self.open {
+            if let Some(Command::Edit {
+                entity: last,
+                after: last_after,
+                ..
+            }) = self.undo.back_mut()
+            {
+                if *last == entity {
+                    *last_after = after;
+                    self.open = dragging;
+                    return;
+                }
+            }
+        }
+        self.push(Command::Edit {
+            entity,
+            before,
+            after,
+        });
+        self.open = dragging;
+    }
+
+    /// Undoes the last command, returning whether there was one.
+    pub fn undo(&mut self, world: &mut World, state: &state::WgpuState) -> Result<bool> {
+        let command = match self.undo.pop_back() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        self.open = false;
+        let respawned = command.run(world, state, true)?;
+        self.redo.push(command);
+        self.remap(&respawned);
+        Ok(true)
+    }
+
+    /// Redoes the last undone command, returning whether there was one.
+    pub fn redo(&mut self, world: &mut World, state: &state::WgpuState) -> Result<bool> {
+        let command = match self.redo.pop() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        let respawned = command.run(world, state, false)?;
+        self.undo.push_back(command);
+        self.remap(&respawned);
+        Ok(true)
+    }
+
+    fn remap(&mut self, respawned: &[(legion::Entity, legion::Entity)]) {
+        for (old, new) in respawned.iter() {
+            for command in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+                command.remap(*old, *new);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefab::Prefab;
+
+    fn entities(count: usize) -> Vec<legion::Entity> {
+        let mut world = legion::World::default();
+        (0..count).map(|i| world.push((i,))).collect()
+    }
+
+    fn data(name: &str) -> scene::EntityData {
+        let mut data = Prefab {
+            model: String::from("cube"),
+            material: None,
+            transform: scene::TransformData::default(),
+            tag: None,
+        }
+        .instance("cube", None);
+        data.name = Some(String::from(name));
+        data
+    }
+
+    fn reparent(entity: legion::Entity) -> Command {
+        Command::Reparent {
+            entity,
+            before: None,
+            after: None,
+        }
+    }
+
+    #[test]
+    fn drag_edits_merge_into_one() {
+        let entity = entities(1)[0];
+        let mut history = History::new();
+        history.edit(entity, data("a"), data("b"), true);
+        history.edit(entity, data("b"), data("c"), true);
+        history.edit(entity, data("c"), data("d"), false);
+        assert_eq!(history.undo.len(), 1);
+        match history.undo.back() {
+            Some(Command::Edit { before, after, .. }) => {
+                assert_eq!(before.name.as_deref(), Some("a"));
+                assert_eq!(after.name.as_deref(), Some("d"));
+            }
+            command => panic!("Expected an edit, got {:?}", command),
+        }
+
+        // The drag ended, the next edit is a command of its own
+        history.edit(entity, data("d"), data("e"), false);
+        assert_eq!(history.undo.len(), 2);
+    }
+
+    #[test]
+    fn edits_of_other_entities_are_not_merged() {
+        let entities = entities(2);
+        let mut history = History::new();
+        history.edit(entities[0], data("a"), data("b"), true);
+        history.edit(entities[1], data("a"), data("b"), true);
+        assert_eq!(history.undo.len(), 2);
+    }
+
+    #[test]
+    fn push_clears_redo_and_drops_the_oldest() {
+        let entities = entities(HISTORY_LIMIT + 1);
+        let mut history = History::new();
+        history.redo.push(reparent(entities[0]));
+        for entity in entities.iter() {
+            history.push(reparent(*entity));
+        }
+        assert!(history.redo.is_empty());
+        assert_eq!(history.undo.len(), HISTORY_LIMIT);
+        match history.undo.front() {
+            Some(Command::Reparent { entity, .. }) => assert_eq!(*entity, entities[1]),
+            command => panic!("Expected a reparent, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn respawned_entities_are_remapped() {
+        let entities = entities(4);
+        let (old, new, parent) = (entities[0], entities[1], entities[2]);
+        let mut history = History::new();
+        history.push(Command::Reparent {
+            entity: old,
+            before: Some(old),
+            after: Some(parent),
+        });
+        history.redo.push(Command::Edit {
+            entity: old,
+            before: data("a"),
+            after: data("b"),
+        });
+        history.remap(&[(old, new), (entities[3], old)]);
+
+        match history.undo.back() {
+            Some(Command::Reparent {
+                entity,
+                before,
+                after,
+            }) => {
+                assert_eq!(*entity, new);
+                assert_eq!(*before, Some(new));
+                assert_eq!(*after, Some(parent));
+            }
+            command => panic!("Expected a reparent, got {:?}", command),
+        }
+        match history.redo.last() {
+            Some(Command::Edit { entity, .. }) => assert_eq!(*entity, new),
+            command => panic!("Expected an edit, got {:?}", command),
+        }
+    }
+}
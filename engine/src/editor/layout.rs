@@ -0,0 +1,71 @@
+//! Where the editor panels go the first time they are shown. Panels moved
+//! or resized after that are remembered in [`LAYOUT_FILE`].
+//!
+//! Panels are placed rather than docked. Docking is only exposed from imgui
+//! 0.7 on, which also needs newer imgui-wgpu and wgpu releases, so the
+//! dockspace is left to its own change along with that upgrade.
+
+use imgui::{Condition, ImStr, Ui};
+
+/// Panel positions and sizes, written by imgui every few seconds
+pub const LAYOUT_FILE: &str = "editor_layout.ini";
+
+const MARGIN: f32 = 10.0;
+/// Below the FPS counter of the overlay
+const TOP: f32 = 60.0;
+const SIDE_WIDTH: f32 = 300.0;
+const CONSOLE_HEIGHT: f32 = 220.0;
+/// Narrow windows let the console overlap the side columns instead
+const CONSOLE_MIN_WIDTH: f32 = 200.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    Hierarchy,
+    Assets,
+    Inspector,
+    Material,
+    Light,
+    Console,
+    Play,
+    Stats,
+}
+
+impl Panel {
+    /// Position and size in a window of `display` size, an empty size
+    /// leaves the panel fitting its contents. Hierarchy and assets share
+    /// the left column, the inspectors the right one, the console spans
+    /// the bottom between them.
+    pub fn placement(self, display: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let [width, height] = display;
+        let right = width - SIDE_WIDTH - MARGIN;
+        let column = (height - TOP - MARGIN * 2.0) / 2.0;
+        let center = SIDE_WIDTH + MARGIN * 2.0;
+        match self {
+            Panel::Hierarchy => ([MARGIN, TOP], [SIDE_WIDTH, column]),
+            Panel::Assets => ([MARGIN, TOP + column + MARGIN], [SIDE_WIDTH, column]),
+            Panel::Inspector => ([right, TOP], [0.0, 0.0]),
+            Panel::Material => ([right, TOP + column + MARGIN], [0.0, 0.0]),
+            Panel::Light => ([right, height - 150.0], [0.0, 0.0]),
+            Panel::Console => (
+                [center, height - CONSOLE_HEIGHT - MARGIN],
+                [
+                    (width - center * 2.0).max(CONSOLE_MIN_WIDTH),
+                    CONSOLE_HEIGHT,
+                ],
+            ),
+            Panel::Play => ([center, MARGIN], [0.0, 0.0]),
+            Panel::Stats => ([center, TOP], [0.0, 0.0]),
+        }
+    }
+}
+
+/// A window named `name` placed where `panel` goes until the user moves it.
+pub fn window<'a>(ui: &Ui, panel: Panel, name: &'a ImStr) -> imgui::Window<'a> {
+    let (position, size) = panel.placement(ui.io().display_size);
+    let window = imgui::Window::new(name).position(position, Condition::FirstUseEver);
+    if size == [0.0, 0.0] {
+        window
+    } else {
+        window.size(size, Condition::FirstUseEver)
+    }
+}
@@ -0,0 +1,57 @@
+use imgui::{ColorEdit, Ui};
+
+use super::layout::{self, Panel};
+use crate::locale::Locale;
+
+/// Largest intensity the slider goes up to
+const MAX_INTENSITY: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightType {
+    /// Shines from the direction of its position, everywhere alike
+    Directional,
+    /// Shines outwards from its position
+    Point,
+}
+
+/// The scene light as the editor shows it, the color written to the GPU is
+/// scaled by the intensity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSettings {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub ty: LightType,
+}
+
+/// Draws the light settings, returning whether any changed.
+pub fn panel(ui: &Ui, locale: &Locale, settings: &mut LightSettings) -> bool {
+    let mut changed = false;
+
+    layout::window(ui, Panel::Light, &locale.label("light.title"))
+        .always_auto_resize(true)
+        .build(ui, || {
+            changed |= ui.radio_button(
+                &locale.label("light.directional"),
+                &mut settings.ty,
+                LightType::Directional,
+            );
+            ui.same_line(0.0);
+            changed |= ui.radio_button(
+                &locale.label("light.point"),
+                &mut settings.ty,
+                LightType::Point,
+            );
+
+            let position = match settings.ty {
+                LightType::Directional => locale.label("light.direction"),
+                LightType::Point => locale.label("light.position"),
+            };
+            changed |= ui.input_float3(&position, &mut settings.position).build();
+            changed |= ColorEdit::new(&locale.label("light.color"), &mut settings.color).build(ui);
+            changed |= imgui::Slider::new(&locale.label("light.intensity"), 0.0..=MAX_INTENSITY)
+                .build(ui, &mut settings.intensity);
+        });
+
+    changed
+}
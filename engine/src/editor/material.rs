@@ -0,0 +1,126 @@
+use imgui::{im_str, ColorEdit, ComboBox, ImString, Ui};
+
+use super::layout::{self, Panel};
+use crate::{
+    locale::Locale,
+    render::thumbnail::{ThumbnailKind, Thumbnails},
+};
+
+/// String keys of the texture slot names, in the order materials bind them
+const SLOT_LABELS: [&str; 3] = [
+    "material.diffuse",
+    "material.normal",
+    "material.metallic_roughness",
+];
+/// Size texture previews are drawn at
+const PREVIEW_SIZE: f32 = 48.0;
+
+/// Where a material lives. Both are shared by every entity using them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaterialSource {
+    /// A material asset the entity is drawn with instead of its model's
+    Asset(String),
+    /// One of the materials of a model asset
+    Model(String, usize),
+}
+
+/// A material of the inspected entity.
+#[derive(Debug, Clone)]
+pub struct MaterialEntry {
+    pub name: String,
+    pub source: MaterialSource,
+    pub base_color: [f32; 4],
+    /// Cached texture in each slot, empty for materials owning their
+    /// textures
+    pub slots: Vec<Option<String>>,
+}
+
+/// What the user changed in the material panel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaterialAction {
+    SetBaseColor(MaterialSource, [f32; 4]),
+    /// Puts the loaded texture in a slot
+    SetTexture(MaterialSource, usize, String),
+}
+
+/// Draws the materials of the inspected entity with previews of their
+/// texture slots. `textures` are the loaded textures slots can be set to.
+pub fn panel(
+    ui: &Ui,
+    locale: &Locale,
+    materials: &[MaterialEntry],
+    textures: &[String],
+    thumbnails: &Thumbnails,
+) -> Vec<MaterialAction> {
+    let mut actions = Vec::new();
+
+    layout::window(ui, Panel::Material, &locale.label("material.title"))
+        .always_auto_resize(true)
+        .build(ui, || {
+            for (i, material) in materials.iter().enumerate() {
+                if i > 0 {
+                    ui.separator();
+                }
+                let source = match &material.source {
+                    MaterialSource::Asset(name) => {
+                        im_str!("{} ({})", name, locale.get("material.asset"))
+                    }
+                    MaterialSource::Model(model, _) => im_str!(
+                        "{} ({} {})",
+                        material.name,
+                        locale.get("material.from"),
+                        model
+                    ),
+                };
+                ui.text(source);
+
+                let mut color = material.base_color;
+                if ColorEdit::new(
+                    &im_str!("{}###base_color{}", locale.get("material.base_color"), i),
+                    &mut color,
+                )
+                .build(ui)
+                {
+                    actions.push(MaterialAction::SetBaseColor(material.source.clone(), color));
+                }
+
+                for (slot, texture) in material.slots.iter().enumerate() {
+                    let label =
+                        locale.get(SLOT_LABELS.get(slot).copied().unwrap_or("material.texture"));
+                    let preview = texture
+                        .as_ref()
+                        .and_then(|texture| thumbnails.get(ThumbnailKind::Texture, texture));
+                    match preview {
+                        Some(texture_id) => {
+                            imgui::Image::new(texture_id, [PREVIEW_SIZE; 2]).build(ui);
+                        }
+                        None => ui.text("..."),
+                    }
+                    ui.same_line(0.0);
+
+                    let mut index = texture
+                        .as_ref()
+                        .and_then(|texture| textures.iter().position(|name| name == texture))
+                        .unwrap_or(0);
+                    let labels = textures
+                        .iter()
+                        .map(|name| im_str!("{}", name))
+                        .collect::<Vec<_>>();
+                    if ComboBox::new(&im_str!("{}###slot{}_{}", label, i, slot)).build_simple(
+                        ui,
+                        &mut index,
+                        labels.as_slice(),
+                        &|label: &ImString| label.into(),
+                    ) {
+                        actions.push(MaterialAction::SetTexture(
+                            material.source.clone(),
+                            slot,
+                            textures[index].clone(),
+                        ));
+                    }
+                }
+            }
+        });
+
+    actions
+}
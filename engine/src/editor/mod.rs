@@ -0,0 +1,73 @@
+//! Editor panels. Panels only draw and report what the user asked for, the
+//! engine applies it to the world once the UI is built.
+
+use imgui::ImString;
+
+pub mod assets;
+pub mod console;
+pub mod hierarchy;
+pub mod history;
+pub mod layout;
+pub mod light;
+pub mod material;
+pub mod play;
+pub mod settings;
+pub mod stats;
+pub mod theme;
+
+/// Longest name the rename field takes
+const NAME_CAPACITY: usize = 64;
+
+/// The entity the inspector and gizmos work on, kept as a resource so it
+/// survives camera movement and systems can read it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Selection(pub Option<legion::Entity>);
+
+/// Editor state kept across frames.
+pub struct Editor {
+    /// Entity picked up in the hierarchy, dropped onto its new parent
+    dragged: Option<legion::Entity>,
+    /// Rename field contents and the entity they belong to
+    rename: (Option<legion::Entity>, ImString),
+    /// Model picked up in the asset browser, spawned where it is dropped
+    dragged_model: Option<String>,
+    /// Index of the model the hierarchy's add button spawns
+    add_model: usize,
+    /// Least severe level the console shows
+    log_level: log::LevelFilter,
+    /// Text console records must contain to be shown
+    log_search: ImString,
+    /// Action waiting for a key to be bound to it
+    rebinding: Option<String>,
+    /// Models spawned last, most recent first
+    recent_assets: Vec<String>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            dragged: None,
+            rename: (None, ImString::with_capacity(NAME_CAPACITY)),
+            dragged_model: None,
+            add_model: 0,
+            log_level: log::LevelFilter::Info,
+            log_search: ImString::with_capacity(NAME_CAPACITY),
+            rebinding: None,
+            recent_assets: Vec::new(),
+        }
+    }
+
+    pub fn recent_assets(&self) -> &[String] {
+        &self.recent_assets
+    }
+
+    /// Replaces the recently used assets, restoring a session.
+    pub fn set_recent_assets(&mut self, recent: Vec<String>) {
+        self.recent_assets = recent;
+    }
+
+    /// Lists `name` first among the recently used assets.
+    pub fn use_asset(&mut self, name: &str) {
+        crate::session::use_asset(&mut self.recent_assets, name);
+    }
+}
@@ -0,0 +1,61 @@
+use imgui::Ui;
+
+use super::layout::{self, Panel};
+use crate::{locale::Locale, schedule::PlayState};
+
+/// What the user pressed in the play toolbar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayAction {
+    /// Starts playing from the edited scene, or resumes a paused one
+    Play,
+    Pause,
+    /// Advances a paused simulation by one fixed step
+    Step,
+    /// Goes back to editing the scene as it was before playing
+    Stop,
+}
+
+/// Draws the play controls for the current `state`.
+pub fn toolbar(ui: &Ui, locale: &Locale, state: PlayState) -> Option<PlayAction> {
+    let mut action = None;
+
+    layout::window(ui, Panel::Play, &locale.label("play.title"))
+        .always_auto_resize(true)
+        .build(ui, || {
+            match state {
+                PlayState::Edit => {
+                    if ui.button(&locale.label("play.play"), [0.0, 0.0]) {
+                        action = Some(PlayAction::Play);
+                    }
+                }
+                PlayState::Play => {
+                    if ui.button(&locale.label("play.pause"), [0.0, 0.0]) {
+                        action = Some(PlayAction::Pause);
+                    }
+                }
+                PlayState::Paused => {
+                    if ui.button(&locale.label("play.resume"), [0.0, 0.0]) {
+                        action = Some(PlayAction::Play);
+                    }
+                    ui.same_line(0.0);
+                    if ui.button(&locale.label("play.step"), [0.0, 0.0]) {
+                        action = Some(PlayAction::Step);
+                    }
+                }
+            }
+            if state != PlayState::Edit {
+                ui.same_line(0.0);
+                if ui.button(&locale.label("play.stop"), [0.0, 0.0]) {
+                    action = Some(PlayAction::Stop);
+                }
+            }
+            ui.same_line(0.0);
+            ui.text(locale.get(match state {
+                PlayState::Edit => "play.edit",
+                PlayState::Play => "play.playing",
+                PlayState::Paused => "play.paused",
+            }));
+        });
+
+    action
+}
@@ -0,0 +1,242 @@
+use imgui::{im_str, ComboBox, ImString, Ui};
+use log::LevelFilter;
+use winit::event::VirtualKeyCode;
+
+use super::Editor;
+use crate::{
+    input::{Binding, InputMap},
+    locale::Locale,
+    render::post,
+    settings::{Antialiasing, Settings},
+};
+
+/// Listed first among the fonts, imgui's built in one
+const DEFAULT_FONT: &str = "(default)";
+/// Listed first among the color grading LUTs, grading off
+const NO_LUT: &str = "(none)";
+/// Levels the logger can keep records at, least verbose first
+const LOG_LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+/// Languages, themes, fonts and LUTs found in the resources and the post
+/// processing effects, listed when the settings window opens.
+#[derive(Debug, Clone, Default)]
+pub struct UiChoices {
+    pub effects: Vec<String>,
+    pub luts: Vec<String>,
+    pub languages: Vec<String>,
+    pub themes: Vec<String>,
+    pub fonts: Vec<String>,
+}
+
+/// Picks one of `names` with a combo, returning whether it changed.
+fn choose(ui: &Ui, label: &imgui::ImStr, names: &[String], selected: &mut String) -> bool {
+    let mut index = names.iter().position(|name| name == selected).unwrap_or(0);
+    let labels = names
+        .iter()
+        .map(|name| im_str!("{}", name))
+        .collect::<Vec<_>>();
+    if ComboBox::new(label).build_simple(ui, &mut index, labels.as_slice(), &|label: &ImString| {
+        label.into()
+    }) {
+        *selected = names[index].clone();
+        return true;
+    }
+    false
+}
+
+/// Draws the settings while `opened`, returning whether any changed.
+/// `pressed` is the key or button pressed this frame, bound to the action
+/// waiting for one. Escape cancels.
+pub fn panel(
+    ui: &Ui,
+    locale: &Locale,
+    editor: &mut Editor,
+    settings: &mut Settings,
+    map: &InputMap,
+    choices: &UiChoices,
+    pressed: Option<Binding>,
+    opened: &mut bool,
+) -> bool {
+    let mut changed = false;
+
+    if let (Some(action), Some(binding)) = (&editor.rebinding, pressed) {
+        if binding != Binding::Key(VirtualKeyCode::Escape) {
+            settings.bindings.insert(action.clone(), vec![binding]);
+            changed = true;
+        }
+        editor.rebinding = None;
+    }
+
+    imgui::Window::new(&locale.label("settings.title"))
+        .opened(opened)
+        .always_auto_resize(true)
+        .build(ui, || {
+            if imgui::CollapsingHeader::new(&locale.label("settings.graphics"))
+                .default_open(true)
+                .build(ui)
+            {
+                changed |= ui.checkbox(
+                    &locale.label("settings.vsync"),
+                    &mut settings.graphics.vsync,
+                );
+                changed |= imgui::Slider::new(&locale.label("settings.max_fps"), 0..=240)
+                    .build(ui, &mut settings.graphics.max_fps);
+
+                let mut index = Antialiasing::ALL
+                    .iter()
+                    .position(|aa| *aa == settings.graphics.antialiasing)
+                    .unwrap_or(0);
+                let labels = Antialiasing::ALL
+                    .iter()
+                    .map(|aa| locale.label(aa.key()))
+                    .collect::<Vec<_>>();
+                if ComboBox::new(&locale.label("settings.antialiasing")).build_simple(
+                    ui,
+                    &mut index,
+                    labels.as_slice(),
+                    &|label: &ImString| label.into(),
+                ) {
+                    settings.graphics.antialiasing = Antialiasing::ALL[index];
+                    changed = true;
+                }
+                changed |= ui.checkbox(
+                    &locale.label("settings.indirect_draw"),
+                    &mut settings.graphics.indirect_draw,
+                );
+                if settings.graphics.indirect_draw {
+                    changed |= ui.checkbox(
+                        &locale.label("settings.gpu_culling"),
+                        &mut settings.graphics.gpu_culling,
+                    );
+                }
+
+                ui.text(locale.get("settings.post_process"));
+                let mut luts = vec![NO_LUT.to_string()];
+                luts.extend(choices.luts.iter().cloned());
+                let mut lut = settings
+                    .graphics
+                    .color_lut
+                    .clone()
+                    .unwrap_or_else(|| NO_LUT.to_string());
+                if choose(ui, &locale.label("settings.color_lut"), &luts, &mut lut) {
+                    settings.graphics.color_lut = Some(lut).filter(|lut| lut != NO_LUT);
+                    changed = true;
+                }
+                // Anti-aliasing and the LUT above enable these
+                for effect in choices
+                    .effects
+                    .iter()
+                    .filter(|effect| *effect != post::FXAA && *effect != post::COLOR_GRADING)
+                {
+                    let effects = &mut settings.graphics.post_effects;
+                    let mut enabled = effects.contains(effect);
+                    if ui.checkbox(&locale.label(&format!("post.{}", effect)), &mut enabled) {
+                        effects.retain(|name| name != effect);
+                        if enabled {
+                            effects.push(effect.clone());
+                        }
+                        changed = true;
+                    }
+                }
+            }
+
+            if imgui::CollapsingHeader::new(&locale.label("settings.interface"))
+                .default_open(true)
+                .build(ui)
+            {
+                changed |= choose(
+                    ui,
+                    &locale.label("settings.language"),
+                    &choices.languages,
+                    &mut settings.ui.language,
+                );
+                changed |= choose(
+                    ui,
+                    &locale.label("settings.theme"),
+                    &choices.themes,
+                    &mut settings.ui.theme,
+                );
+
+                let mut fonts = vec![DEFAULT_FONT.to_string()];
+                fonts.extend(choices.fonts.iter().cloned());
+                let mut font = settings
+                    .ui
+                    .font
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_FONT.to_string());
+                if choose(ui, &locale.label("settings.font"), &fonts, &mut font) {
+                    settings.ui.font = Some(font).filter(|font| font != DEFAULT_FONT);
+                    changed = true;
+                }
+                changed |= imgui::Slider::new(&locale.label("settings.font_size"), 8.0..=32.0)
+                    .build(ui, &mut settings.ui.font_size);
+            }
+
+            if imgui::CollapsingHeader::new(&locale.label("settings.editor"))
+                .default_open(true)
+                .build(ui)
+            {
+                changed |= imgui::Slider::new(&locale.label("settings.camera_speed"), 0.5..=50.0)
+                    .build(ui, &mut settings.editor.camera_speed);
+                changed |= imgui::Slider::new(&locale.label("settings.grid_size"), 0.25..=10.0)
+                    .build(ui, &mut settings.editor.grid_size);
+            }
+
+            if imgui::CollapsingHeader::new(&locale.label("settings.logging")).build(ui) {
+                let levels = LOG_LEVELS
+                    .iter()
+                    .map(|level| level.to_string().to_lowercase())
+                    .collect::<Vec<_>>();
+                let mut level = settings.logging.level.to_string().to_lowercase();
+                if choose(ui, &locale.label("settings.log_level"), &levels, &mut level) {
+                    if let Ok(level) = level.parse() {
+                        settings.logging.level = level;
+                        changed = true;
+                    }
+                }
+            }
+
+            if imgui::CollapsingHeader::new(&locale.label("settings.input")).build(ui) {
+                for action in map.action_names() {
+                    let bindings = map
+                        .action_bindings(&action)
+                        .iter()
+                        .map(|binding| format!("{:?}", binding))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    ui.text(im_str!("{:<12} {}", action, bindings));
+                    ui.same_line(0.0);
+                    if editor.rebinding.as_deref() == Some(action.as_str()) {
+                        ui.text(locale.get("settings.press_key"));
+                    } else if ui.button(
+                        &im_str!("{}##{}", locale.get("settings.rebind"), action),
+                        [0.0, 0.0],
+                    ) {
+                        editor.rebinding = Some(action.clone());
+                    }
+                    if settings.bindings.contains_key(&action) {
+                        ui.same_line(0.0);
+                        if ui.button(
+                            &im_str!("{}##{}", locale.get("settings.reset"), action),
+                            [0.0, 0.0],
+                        ) {
+                            settings.bindings.remove(&action);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        });
+
+    if !*opened {
+        editor.rebinding = None;
+    }
+    changed
+}
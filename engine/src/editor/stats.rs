@@ -0,0 +1,76 @@
+use imgui::{im_str, Ui};
+
+use super::layout::{self, Panel};
+use crate::{locale::Locale, stats::Stats};
+
+/// Height of the frame time graph
+const GRAPH_HEIGHT: f32 = 60.0;
+
+fn megabytes(bytes: u64) -> f32 {
+    bytes as f32 / (1024.0 * 1024.0)
+}
+
+/// Draws the last finished frame's numbers with a graph of the latest
+/// frame times.
+pub fn overlay(ui: &Ui, locale: &Locale, stats: &Stats, entity_count: usize) {
+    layout::window(ui, Panel::Stats, &locale.label("stats.title"))
+        .always_auto_resize(true)
+        .bg_alpha(0.6)
+        .build(ui, || {
+            let frame_times = stats
+                .frame_times()
+                .iter()
+                .map(|seconds| seconds * 1000.0)
+                .collect::<Vec<_>>();
+            let latest = frame_times.last().copied().unwrap_or_default();
+            let worst = frame_times.iter().copied().fold(0.0, f32::max);
+            imgui::PlotLines::new(ui, im_str!("##frame times"), &frame_times)
+                .overlay_text(&im_str!(
+                    "{:.2} ms, {} {:.2} ms",
+                    latest,
+                    locale.get("stats.worst"),
+                    worst
+                ))
+                .scale_min(0.0)
+                .graph_size([300.0, GRAPH_HEIGHT])
+                .build();
+
+            let frame = &stats.last;
+            ui.text(im_str!(
+                "{}: {}",
+                locale.get("stats.draw_calls"),
+                frame.draw_calls
+            ));
+            ui.text(im_str!(
+                "{}: {}",
+                locale.get("stats.triangles"),
+                frame.triangles
+            ));
+            ui.text(im_str!(
+                "{}: {}",
+                locale.get("stats.entities"),
+                entity_count
+            ));
+            ui.text(im_str!(
+                "{}: {}",
+                locale.get("stats.threads"),
+                rayon::current_num_threads()
+            ));
+            ui.text(im_str!(
+                "{}: {:.1} MiB, {}: {:.1} MiB",
+                locale.get("stats.buffers"),
+                megabytes(stats.buffer_bytes()),
+                locale.get("stats.textures"),
+                megabytes(stats.texture_bytes()),
+            ));
+
+            ui.separator();
+            for (name, duration) in frame.timings.iter() {
+                ui.text(im_str!(
+                    "{:<24} {:>7.3} ms",
+                    name,
+                    duration.as_secs_f64() * 1000.0
+                ));
+            }
+        });
+}
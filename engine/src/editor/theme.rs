@@ -0,0 +1,81 @@
+//! Colors and fonts of the editor UI. Fonts are TTF files in the
+//! [`FONT_DIR`] resource folder, custom themes RON files in [`THEME_DIR`]
+//! mapping imgui style color names to RGBA, on top of the dark theme.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::*;
+use imgui::{FontSource, StyleColor};
+
+use crate::resources::Resources;
+
+pub const FONT_DIR: &str = "fonts";
+pub const THEME_DIR: &str = "themes";
+/// Themes imgui comes with, anything else is a file in `THEME_DIR`
+pub const BUILTIN_THEMES: [&str; 3] = ["dark", "light", "classic"];
+
+pub fn apply_theme(imgui: &mut imgui::Context, resources: &Resources, theme: &str) -> Result<()> {
+    let style = imgui.style_mut();
+    match theme {
+        "light" => {
+            style.use_light_colors();
+        }
+        "classic" => {
+            style.use_classic_colors();
+        }
+        "dark" => {
+            style.use_dark_colors();
+        }
+        custom => {
+            let source = resources.read(Path::new(THEME_DIR).join(custom))?;
+            let colors: BTreeMap<String, [f32; 4]> = ron::de::from_bytes(&source)
+                .with_context(|| format!("Could not parse theme {:?}", custom))?;
+            style.use_dark_colors();
+            for color in StyleColor::VARIANTS.iter() {
+                if let Some(value) = colors.get(&format!("{:?}", color)) {
+                    style[*color] = *value;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the fonts with `font` at `size` points, or with the default
+/// font, rasterized for `hidpi_factor`. The renderer's font texture has to
+/// be reloaded afterwards.
+pub fn build_fonts(
+    imgui: &mut imgui::Context,
+    resources: &Resources,
+    font: Option<&str>,
+    size: f32,
+    hidpi_factor: f64,
+) -> Result<()> {
+    let data = match font {
+        Some(font) => Some(resources.read(Path::new(FONT_DIR).join(font))?),
+        None => None,
+    };
+
+    let size_pixels = (size as f64 * hidpi_factor) as f32;
+    imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
+    let config = imgui::FontConfig {
+        oversample_h: 1,
+        pixel_snap_h: true,
+        size_pixels,
+        ..Default::default()
+    };
+
+    let mut fonts = imgui.fonts();
+    fonts.clear();
+    match &data {
+        Some(data) => fonts.add_font(&[FontSource::TtfData {
+            data,
+            size_pixels,
+            config: Some(config),
+        }]),
+        None => fonts.add_font(&[FontSource::DefaultFontData {
+            config: Some(config),
+        }]),
+    };
+    Ok(())
+}
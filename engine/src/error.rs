@@ -0,0 +1,106 @@
+//! Errors worth telling apart. Functions keep returning `anyhow::Result`
+//! with context added along the way, an [`EngineError`] at the root of the
+//! chain says what kind of failure it was. Callers branch on it with
+//! [`EngineError::find`], the editor shows its hint.
+
+use std::fmt;
+
+/// What failed, as far as a caller can act on it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    /// A resource file or a named asset that isn't loaded
+    AssetNotFound {
+        kind: &'static str,
+        name: String,
+    },
+    /// A shader that didn't pass validation
+    ShaderCompile {
+        shader: String,
+        message: String,
+    },
+    /// Something larger than the device allows
+    GpuLimit {
+        limit: &'static str,
+        requested: u64,
+        max: u64,
+    },
+    /// wgpu rejected an object when creating it
+    Validation {
+        object: String,
+        message: String,
+    },
+    EntityNotFound(legion::Entity),
+    /// The entity lacks a component the operation needs
+    ComponentMissing {
+        entity: legion::Entity,
+        component: &'static str,
+    },
+}
+
+impl EngineError {
+    pub fn asset_not_found<N: fmt::Display>(kind: &'static str, name: N) -> Self {
+        EngineError::AssetNotFound {
+            kind,
+            name: name.to_string(),
+        }
+    }
+
+    /// The engine error at the root of `error`, if any.
+    pub fn find(error: &anyhow::Error) -> Option<&EngineError> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            EngineError::AssetNotFound { .. } => {
+                "Check that the file is in a resource directory or add it to the scene's assets"
+            }
+            EngineError::ShaderCompile { .. } => {
+                "Rebuild the shaders and check the validation message"
+            }
+            EngineError::GpuLimit { .. } => "Use a smaller asset or a device with higher limits",
+            EngineError::Validation { .. } => "See the wgpu message for the offending field",
+            EngineError::EntityNotFound(_) => "The entity was removed, select another one",
+            EngineError::ComponentMissing { .. } => "Add the component in the inspector first",
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::AssetNotFound { kind, name } => write!(f, "{} {:?} not found", kind, name),
+            EngineError::ShaderCompile { shader, message } => {
+                write!(f, "Shader {:?} failed validation: {}", shader, message)
+            }
+            EngineError::GpuLimit {
+                limit,
+                requested,
+                max,
+            } => write!(
+                f,
+                "{} of {} exceeds the device limit of {}",
+                limit, requested, max
+            ),
+            EngineError::Validation { object, message } => {
+                write!(f, "Could not create {}: {}", object, message)
+            }
+            EngineError::EntityNotFound(entity) => write!(f, "Entity {:?} not found", entity),
+            EngineError::ComponentMissing { entity, component } => {
+                write!(f, "Entity {:?} has no {}", entity, component)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+/// `error` with its hint appended when it has one, for log messages the
+/// user reads in the console.
+pub fn describe(error: &anyhow::Error) -> String {
+    match EngineError::find(error) {
+        Some(engine_error) => format!("{:?}\n{}", error, engine_error.hint()),
+        None => format!("{:?}", error),
+    }
+}
@@ -0,0 +1,191 @@
+//! Typed event channels stored as resources. Events stay readable for the
+//! frame they were sent in and the one after, so systems in any stage see
+//! them once through an [`EventReader`].
+
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode};
+
+/// Channel for events of type `T`, see [`crate::world::World::add_event`].
+pub struct Events<T> {
+    previous: Vec<(u64, T)>,
+    current: Vec<(u64, T)>,
+    next_id: u64,
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push((self.next_id, event));
+        self.next_id += 1;
+    }
+
+    /// All events of this and the previous frame.
+    #[allow(unused)]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous
+            .iter()
+            .chain(self.current.iter())
+            .map(|(_, event)| event)
+    }
+
+    /// Drops the events of the previous frame, called at the start of a
+    /// frame.
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Remembers which events were already read, kept as system state.
+#[allow(unused)]
+pub struct EventReader<T> {
+    next_id: u64,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+#[allow(unused)]
+impl<T> EventReader<T> {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Events sent since the last call.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let next_id = self.next_id;
+        self.next_id = events.next_id;
+        events
+            .previous
+            .iter()
+            .chain(events.current.iter())
+            .filter(move |(id, _)| *id >= next_id)
+            .map(|(_, event)| event)
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Window input, mirrored from winit without its lifetimes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowEvent {
+    Resized { width: u32, height: u32 },
+    Focused(bool),
+    Key(VirtualKeyCode, ElementState),
+    MouseButton(MouseButton, ElementState),
+    CursorMoved { x: f64, y: f64 },
+    Scroll { x: f32, y: f32 },
+    CloseRequested,
+}
+
+impl WindowEvent {
+    pub fn from_winit(event: &winit::event::WindowEvent) -> Option<Self> {
+        use winit::event::WindowEvent as W;
+
+        Some(match event {
+            W::Resized(size) => WindowEvent::Resized {
+                width: size.width,
+                height: size.height,
+            },
+            W::ScaleFactorChanged { new_inner_size, .. } => WindowEvent::Resized {
+                width: new_inner_size.width,
+                height: new_inner_size.height,
+            },
+            W::Focused(focused) => WindowEvent::Focused(*focused),
+            W::KeyboardInput {
+                input:
+                    winit::event::KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => WindowEvent::Key(*key, *state),
+            W::MouseInput { button, state, .. } => WindowEvent::MouseButton(*button, *state),
+            W::CursorMoved { position, .. } => WindowEvent::CursorMoved {
+                x: position.x,
+                y: position.y,
+            },
+            W::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => WindowEvent::Scroll { x: *x, y: *y },
+                MouseScrollDelta::PixelDelta(position) => WindowEvent::Scroll {
+                    x: position.x as f32,
+                    y: position.y as f32,
+                },
+            },
+            W::CloseRequested => WindowEvent::CloseRequested,
+            _ => return None,
+        })
+    }
+}
+
+/// Contact or overlap between two colliders starting or ending. Each
+/// entity also gets it in its `physics::Contacts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionEvent {
+    CollisionStarted(legion::Entity, legion::Entity),
+    CollisionStopped(legion::Entity, legion::Entity),
+    /// Sensor overlaps, without a physical response
+    ProximityStarted(legion::Entity, legion::Entity),
+    ProximityStopped(legion::Entity, legion::Entity),
+}
+
+impl CollisionEvent {
+    pub fn entities(&self) -> (legion::Entity, legion::Entity) {
+        match *self {
+            CollisionEvent::CollisionStarted(a, b)
+            | CollisionEvent::CollisionStopped(a, b)
+            | CollisionEvent::ProximityStarted(a, b)
+            | CollisionEvent::ProximityStopped(a, b) => (a, b),
+        }
+    }
+
+    pub fn is_start(&self) -> bool {
+        match self {
+            CollisionEvent::CollisionStarted(..) | CollisionEvent::ProximityStarted(..) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Something entering or leaving a `physics::TriggerVolume`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerEvent {
+    Entered {
+        trigger: legion::Entity,
+        entity: legion::Entity,
+    },
+    Exited {
+        trigger: legion::Entity,
+        entity: legion::Entity,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetEvent {
+    /// Model finished loading or was reloaded after changing on disk
+    ModelLoaded(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityEvent {
+    Spawned(legion::Entity),
+    /// Sent for the entity and each of its removed children
+    Removed(legion::Entity),
+}
@@ -0,0 +1,41 @@
+//! Parent/child relations between entities. Children are positioned
+//! relative to their parent, `World::propagate_transforms` turns that into
+//! the world transform colliders and instance buffers use.
+
+use nalgebra::{Isometry3, Translation3, Vector3};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Parent(pub legion::Entity);
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Children(pub Vec<legion::Entity>);
+
+/// World transform of a child from its parent's world transform and its
+/// own local one. Scale is combined per axis, rotated non-uniform scales
+/// don't shear children.
+pub fn compose(
+    parent: (Isometry3<f32>, Vector3<f32>),
+    local: (Isometry3<f32>, Vector3<f32>),
+) -> (Isometry3<f32>, Vector3<f32>) {
+    let (parent_isometry, parent_scale) = parent;
+    let (local_isometry, local_scale) = local;
+
+    let translation = local_isometry
+        .translation
+        .vector
+        .component_mul(&parent_scale);
+    let isometry = parent_isometry
+        * Isometry3::from_parts(Translation3::from(translation), local_isometry.rotation);
+
+    (isometry, parent_scale.component_mul(&local_scale))
+}
+
+/// Inverse of [`compose`], the local isometry that puts a child at `world`
+/// under `parent`.
+pub fn decompose(parent: (Isometry3<f32>, Vector3<f32>), world: Isometry3<f32>) -> Isometry3<f32> {
+    let (parent_isometry, parent_scale) = parent;
+    let relative = parent_isometry.inverse() * world;
+    let translation = relative.translation.vector.component_div(&parent_scale);
+
+    Isometry3::from_parts(Translation3::from(translation), relative.rotation)
+}
@@ -0,0 +1,488 @@
+//! Keyboard, mouse and gamepad state, and the named actions and axes
+//! gameplay reads it through. Bindings can be changed at runtime through
+//! [`InputMap`].
+
+use std::collections::{HashMap, HashSet};
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode};
+
+/// Stick values closer to rest than this read as zero
+pub const DEAD_ZONE: f32 = 0.15;
+
+/// Fraction of the window a fully tilted look stick counts as moving the
+/// mouse across per frame
+const STICK_LOOK_SCALE: f32 = 0.02;
+
+/// Something an action or axis can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    /// Horizontal mouse movement as a fraction of the window width, only
+    /// meaningful for axes
+    MouseX,
+    /// Vertical mouse movement as a fraction of the window height, only
+    /// meaningful for axes
+    MouseY,
+    /// Scroll wheel lines, positive away from the user, only meaningful for
+    /// axes
+    Scroll,
+    /// Button of any gamepad, triggers report how far they are pulled
+    GamepadButton(Button),
+    /// Stick axis of any gamepad, from -1 to 1
+    GamepadAxis(Axis),
+}
+
+/// A change to the input state, as recorded and replayed by
+/// [`crate::replay`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key(VirtualKeyCode, ElementState),
+    Mouse(MouseButton, ElementState),
+    /// Cursor position from 0 to 1 across the window
+    Cursor(f64, f64),
+    /// Raw device motion, already divided by the window size
+    Motion(f64, f64),
+    /// Scrolled lines
+    Scroll(f32),
+    /// How far a gamepad button is held, 0 once released
+    GamepadButton(Button, f32),
+    GamepadAxis(Axis, f32),
+    GamepadDisconnected,
+}
+
+/// Named actions and axes and what drives them.
+#[derive(Debug, Clone, Default)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+    /// Each binding adds its scale to the axis while held
+    axes: HashMap<String, Vec<(Binding, f32)>>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bindings of the camera controllers.
+    pub fn with_defaults() -> Self {
+        use VirtualKeyCode::*;
+
+        let mut map = Self::new();
+        for (axis, positive, negative) in [
+            ("move_forward", [W, Up], [S, Down]),
+            ("move_right", [D, Right], [A, Left]),
+        ]
+        .iter()
+        {
+            for key in positive.iter() {
+                map.bind_axis(axis, Binding::Key(*key), 1.0);
+            }
+            for key in negative.iter() {
+                map.bind_axis(axis, Binding::Key(*key), -1.0);
+            }
+        }
+        map.bind_axis("move_up", Binding::Key(Space), 1.0);
+        map.bind_axis("move_up", Binding::Key(LShift), -1.0);
+        map.bind_axis("look_horizontal", Binding::MouseX, 1.0);
+        map.bind_axis("look_vertical", Binding::MouseY, 1.0);
+        map.bind_axis("zoom", Binding::Scroll, 1.0);
+        map.bind_action("look", Binding::Mouse(MouseButton::Left));
+        map.bind_action("pan", Binding::Mouse(MouseButton::Middle));
+        // Selects on release, unless the mouse was dragged to look around
+        map.bind_action("select", Binding::Mouse(MouseButton::Left));
+        map.bind_action("jump", Binding::Key(Space));
+        map.bind_action("grab_cursor", Binding::Key(Tab));
+        map.bind_action("focus", Binding::Key(F));
+        map.bind_action("delete", Binding::Key(Delete));
+
+        map.bind_axis("move_forward", Binding::GamepadAxis(Axis::LeftStickY), 1.0);
+        map.bind_axis("move_right", Binding::GamepadAxis(Axis::LeftStickX), 1.0);
+        map.bind_axis(
+            "move_up",
+            Binding::GamepadButton(Button::RightTrigger2),
+            1.0,
+        );
+        map.bind_axis(
+            "move_up",
+            Binding::GamepadButton(Button::LeftTrigger2),
+            -1.0,
+        );
+        map.bind_axis(
+            "look_horizontal",
+            Binding::GamepadAxis(Axis::RightStickX),
+            STICK_LOOK_SCALE,
+        );
+        map.bind_axis(
+            "look_vertical",
+            Binding::GamepadAxis(Axis::RightStickY),
+            -STICK_LOOK_SCALE,
+        );
+        // Tilting the look stick looks without holding anything
+        map.bind_action("look", Binding::GamepadAxis(Axis::RightStickX));
+        map.bind_action("look", Binding::GamepadAxis(Axis::RightStickY));
+        map.bind_action("jump", Binding::GamepadButton(Button::South));
+        map
+    }
+
+    pub fn bind_action(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes every binding of `action`.
+    #[allow(unused)]
+    pub fn clear_action(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    /// Replaces the bindings of `action` with `binding`.
+    #[allow(unused)]
+    pub fn rebind_action(&mut self, action: &str, binding: Binding) {
+        self.actions.insert(action.to_string(), vec![binding]);
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: Binding, scale: f32) {
+        let bindings = self.axes.entry(axis.to_string()).or_default();
+        bindings.retain(|(bound, _)| *bound != binding);
+        bindings.push((binding, scale));
+    }
+
+    #[allow(unused)]
+    pub fn clear_axis(&mut self, axis: &str) {
+        self.axes.remove(axis);
+    }
+
+    /// Names of the actions, sorted.
+    pub fn action_names(&self) -> Vec<String> {
+        let mut names = self.actions.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Replaces the bindings of `action`.
+    pub fn set_action_bindings(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.actions.insert(action.to_string(), bindings);
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[(Binding, f32)] {
+        self.axes.get(axis).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Whether any action or axis uses `binding`.
+    pub fn is_bound(&self, binding: Binding) -> bool {
+        self.actions
+            .values()
+            .any(|bindings| bindings.contains(&binding))
+            || self
+                .axes
+                .values()
+                .any(|bindings| bindings.iter().any(|(bound, _)| *bound == binding))
+    }
+}
+
+/// Keyboard and mouse state gathered from window events.
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub map: InputMap,
+    pub keys: HashSet<VirtualKeyCode>,
+    pub buttons: HashSet<MouseButton>,
+    /// Held gamepad buttons and how far, 1 for anything but triggers
+    pub gamepad_buttons: HashMap<Button, f32>,
+    pub gamepad_axes: HashMap<Axis, f32>,
+    /// Cursor position from 0 to 1 across the window, origin at the top left
+    pub mouse_position: (f64, f64),
+    /// Movement since the previous frame
+    pub mouse_delta: (f64, f64),
+    /// Scrolled lines since the previous frame
+    pub scroll_delta: f32,
+    /// While set, `mouse_delta` comes from raw device motion rather than
+    /// the cursor, which stays put when grabbed
+    pub relative_mouse: bool,
+    last_mouse_position: (f64, f64),
+    /// Raw device motion since the frame started
+    motion: (f64, f64),
+    scroll: f32,
+    /// Pressed since the last frame started, kept for one frame
+    pressed: HashSet<Binding>,
+    just_pressed: HashSet<Binding>,
+    /// Events applied since the last `take_recorded`, while recording
+    recorded: Option<Vec<InputEvent>>,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            map: InputMap::with_defaults(),
+            keys: HashSet::new(),
+            buttons: HashSet::new(),
+            gamepad_buttons: HashMap::new(),
+            gamepad_axes: HashMap::new(),
+            mouse_position: (0.0, 0.0),
+            mouse_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
+            relative_mouse: false,
+            last_mouse_position: (0.0, 0.0),
+            motion: (0.0, 0.0),
+            scroll: 0.0,
+            pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            recorded: None,
+        }
+    }
+}
+
+impl Input {
+    pub fn begin_frame(&mut self) {
+        let motion = std::mem::take(&mut self.motion);
+        self.mouse_delta = if self.relative_mouse {
+            motion
+        } else {
+            (
+                self.mouse_position.0 - self.last_mouse_position.0,
+                self.mouse_position.1 - self.last_mouse_position.1,
+            )
+        };
+        self.last_mouse_position = self.mouse_position;
+        self.scroll_delta = std::mem::take(&mut self.scroll);
+        self.just_pressed = std::mem::take(&mut self.pressed);
+    }
+
+    /// Keeps the events applied from now on for `take_recorded`.
+    pub fn start_recording(&mut self) {
+        self.recorded = Some(Vec::new());
+    }
+
+    /// Events applied since the last call, none unless recording.
+    pub fn take_recorded(&mut self) -> Vec<InputEvent> {
+        match &mut self.recorded {
+            Some(recorded) => std::mem::take(recorded),
+            None => Vec::new(),
+        }
+    }
+
+    /// Updates the state, every `process_` method ends up here.
+    pub fn apply(&mut self, event: InputEvent) {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(event);
+        }
+        match event {
+            InputEvent::Key(key, ElementState::Pressed) => {
+                if self.keys.insert(key) {
+                    self.pressed.insert(Binding::Key(key));
+                }
+            }
+            InputEvent::Key(key, ElementState::Released) => {
+                self.keys.remove(&key);
+            }
+            InputEvent::Mouse(button, ElementState::Pressed) => {
+                if self.buttons.insert(button) {
+                    self.pressed.insert(Binding::Mouse(button));
+                }
+            }
+            InputEvent::Mouse(button, ElementState::Released) => {
+                self.buttons.remove(&button);
+            }
+            InputEvent::Cursor(x, y) => self.mouse_position = (x, y),
+            InputEvent::Motion(x, y) => {
+                self.motion.0 += x;
+                self.motion.1 += y;
+            }
+            InputEvent::Scroll(lines) => self.scroll += lines,
+            InputEvent::GamepadButton(button, value) => {
+                if value > DEAD_ZONE {
+                    if self.gamepad_buttons.insert(button, value).is_none() {
+                        self.pressed.insert(Binding::GamepadButton(button));
+                    }
+                } else {
+                    self.gamepad_buttons.remove(&button);
+                }
+            }
+            InputEvent::GamepadAxis(axis, value) => {
+                if value.abs() > DEAD_ZONE {
+                    self.gamepad_axes.insert(axis, value);
+                } else {
+                    self.gamepad_axes.remove(&axis);
+                }
+            }
+            InputEvent::GamepadDisconnected => {
+                // Nothing stays held on a pad that's gone
+                self.gamepad_buttons.clear();
+                self.gamepad_axes.clear();
+            }
+        }
+    }
+
+    /// Returns whether the key is bound to an action or axis.
+    pub fn process_key(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        self.apply(InputEvent::Key(key, state));
+        self.map.is_bound(Binding::Key(key))
+    }
+
+    pub fn process_button(&mut self, button: MouseButton, state: ElementState) {
+        self.apply(InputEvent::Mouse(button, state));
+    }
+
+    /// Cursor position from 0 to 1 across the window.
+    pub fn process_cursor(&mut self, position: (f64, f64)) {
+        self.apply(InputEvent::Cursor(position.0, position.1));
+    }
+
+    /// Raw mouse movement from `DeviceEvent::MouseMotion`, already divided
+    /// by the window size.
+    pub fn process_motion(&mut self, delta: (f64, f64)) {
+        self.apply(InputEvent::Motion(delta.0, delta.1));
+    }
+
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.apply(InputEvent::Scroll(match delta {
+            MouseScrollDelta::LineDelta(_, lines) => *lines,
+            // Roughly a line per 20 pixels on touchpads
+            MouseScrollDelta::PixelDelta(position) => (position.y / 20.0) as f32,
+        }));
+    }
+
+    pub fn process_gamepad(&mut self, event: EventType) {
+        let event = match event {
+            EventType::ButtonPressed(button, _) => InputEvent::GamepadButton(button, 1.0),
+            EventType::ButtonChanged(button, value, _) => InputEvent::GamepadButton(button, value),
+            EventType::ButtonReleased(button, _) => InputEvent::GamepadButton(button, 0.0),
+            EventType::AxisChanged(axis, value, _) => InputEvent::GamepadAxis(axis, value),
+            EventType::Disconnected => InputEvent::GamepadDisconnected,
+            _ => return,
+        };
+        self.apply(event);
+    }
+
+    pub fn is_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Whether either control key is held, for shortcuts.
+    pub fn ctrl_held(&self) -> bool {
+        self.is_pressed(VirtualKeyCode::LControl) || self.is_pressed(VirtualKeyCode::RControl)
+    }
+
+    /// Whether either alt key is held, for shortcuts.
+    pub fn alt_held(&self) -> bool {
+        self.is_pressed(VirtualKeyCode::LAlt) || self.is_pressed(VirtualKeyCode::RAlt)
+    }
+
+    /// A key or mouse button pressed since the previous frame, for binding
+    /// it to an action.
+    pub fn pressed_binding(&self) -> Option<Binding> {
+        self.just_pressed
+            .iter()
+            .find(|binding| matches!(binding, Binding::Key(_) | Binding::Mouse(_)))
+            .copied()
+    }
+
+    /// Whether `key` was pressed since the previous frame.
+    pub fn key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.just_pressed.contains(&Binding::Key(key))
+    }
+
+    /// Whether `binding` is held, mouse movement counts while moving.
+    pub fn is_held(&self, binding: Binding) -> bool {
+        self.value(binding) != 0.0
+    }
+
+    fn value(&self, binding: Binding) -> f32 {
+        let held = |held: bool| if held { 1.0 } else { 0.0 };
+        match binding {
+            Binding::Key(key) => held(self.keys.contains(&key)),
+            Binding::Mouse(button) => held(self.buttons.contains(&button)),
+            Binding::MouseX => self.mouse_delta.0 as f32,
+            Binding::MouseY => self.mouse_delta.1 as f32,
+            Binding::Scroll => self.scroll_delta,
+            Binding::GamepadButton(button) => {
+                self.gamepad_buttons.get(&button).copied().unwrap_or(0.0)
+            }
+            Binding::GamepadAxis(axis) => self.gamepad_axes.get(&axis).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Whether any binding of `action` is held.
+    pub fn action(&self, action: &str) -> bool {
+        self.map
+            .action_bindings(action)
+            .iter()
+            .any(|binding| self.is_held(*binding))
+    }
+
+    /// Whether `action` was pressed since the previous frame.
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.map
+            .action_bindings(action)
+            .iter()
+            .any(|binding| self.just_pressed.contains(binding))
+    }
+
+    /// Sum of the bindings of `axis`, clamped to -1 to 1 unless it follows
+    /// the mouse or scroll wheel.
+    pub fn axis(&self, axis: &str) -> f32 {
+        let bindings = self.map.axis_bindings(axis);
+        let value = bindings
+            .iter()
+            .map(|(binding, scale)| self.value(*binding) * scale)
+            .sum::<f32>();
+        if bindings.iter().any(|(binding, _)| {
+            matches!(binding, Binding::MouseX | Binding::MouseY | Binding::Scroll)
+        }) {
+            value
+        } else {
+            value.max(-1.0).min(1.0)
+        }
+    }
+}
+
+/// Polls connected gamepads into `Input`.
+pub struct Gamepads {
+    /// `None` when the platform has no gamepad support
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    info!("Gamepad {} connected", gamepad.name());
+                }
+                Some(gilrs)
+            }
+            Err(e) => {
+                warn!("Gamepads are not supported: {}", e);
+                None
+            }
+        };
+        Self { gilrs }
+    }
+
+    /// Applies the gamepad events since the last call.
+    pub fn poll(&mut self, input: &mut Input) {
+        let gilrs = match &mut self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return,
+        };
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    info!("Gamepad {} connected", gilrs.gamepad(event.id).name())
+                }
+                EventType::Disconnected => info!("Gamepad disconnected"),
+                _ => {}
+            }
+            input.process_gamepad(event.event);
+        }
+    }
+}
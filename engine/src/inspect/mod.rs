@@ -1,6 +1,9 @@
 pub mod inspect_transform;
+pub mod registry;
 
+pub use imgui_inspect_derive::Inspect;
 pub use inspect_transform::InspectTransform;
+pub use registry::InspectorRegistry;
 
 pub trait IntoInspect {
     type Output;
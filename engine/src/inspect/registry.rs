@@ -0,0 +1,161 @@
+use std::{any::TypeId, collections::HashMap};
+
+use imgui::{im_str, Ui};
+use imgui_inspect::{InspectArgsStruct, InspectRenderStruct};
+use legion::{storage::Component, world::Entry};
+
+use super::{InspectTransform, IntoInspect};
+use crate::{
+    animation, audio, character, physics,
+    render::{billboard, decal, layer::RenderLayer, particles, reflection},
+    transform,
+};
+
+/// Draws the component of an entry under a label, `None` when the entry
+/// doesn't have it, otherwise whether it was changed.
+pub type RenderComponent = fn(&mut Entry, &'static str, &Ui) -> Option<bool>;
+
+struct Inspector {
+    label: &'static str,
+    render: RenderComponent,
+}
+
+/// Components the entity inspector shows, by type. Components deriving
+/// [`Inspect`](super::Inspect) are registered with
+/// [`InspectorRegistry::register`], others with a hand written renderer.
+pub struct InspectorRegistry {
+    inspectors: HashMap<TypeId, Inspector>,
+    /// Types in the order they were registered, the order they are drawn in
+    order: Vec<TypeId>,
+}
+
+impl InspectorRegistry {
+    pub fn new() -> Self {
+        Self {
+            inspectors: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// The engine's own components.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register_with::<transform::Transform>("Transform", render_transform);
+        registry.register_with::<animation::MorphWeights>("Morph targets", render_morph);
+        registry.register::<physics::RigidBody>("Rigid body");
+        registry.register::<character::CharacterController>("Character");
+        registry.register::<audio::AudioSource>("Audio source");
+        registry.register::<reflection::Mirror>("Mirror");
+        registry.register::<decal::Decal>("Decal");
+        registry.register::<particles::ParticleEmitter>("Particle emitter");
+        registry.register::<billboard::Billboard>("Billboard");
+        registry.register_with::<RenderLayer>("Render layers", render_layers);
+        registry
+    }
+
+    pub fn register<T: Component + InspectRenderStruct<T>>(&mut self, label: &'static str) {
+        self.register_with::<T>(label, render_derived::<T>);
+    }
+
+    /// Registers `T` with its own `render`, replacing any previous one.
+    pub fn register_with<T: Component>(&mut self, label: &'static str, render: RenderComponent) {
+        let type_id = TypeId::of::<T>();
+        if self
+            .inspectors
+            .insert(type_id, Inspector { label, render })
+            .is_none()
+        {
+            self.order.push(type_id);
+        }
+    }
+
+    /// Draws every registered component of `entry`, returning whether any
+    /// was changed.
+    pub fn render(&self, entry: &mut Entry, ui: &Ui) -> bool {
+        let mut changed = false;
+        for inspector in self.order.iter().map(|id| &self.inspectors[id]) {
+            if let Some(edited) = (inspector.render)(entry, inspector.label, ui) {
+                changed |= edited;
+            }
+        }
+        changed
+    }
+}
+
+fn render_derived<T: Component + InspectRenderStruct<T>>(
+    entry: &mut Entry,
+    label: &'static str,
+    ui: &Ui,
+) -> Option<bool> {
+    let component = entry.get_component_mut::<T>().ok()?;
+    Some(<T as InspectRenderStruct<T>>::render_mut(
+        &mut [component],
+        label,
+        ui,
+        &InspectArgsStruct::default(),
+    ))
+}
+
+fn render_transform(entry: &mut Entry, label: &'static str, ui: &Ui) -> Option<bool> {
+    let transform = entry.get_component_mut::<transform::Transform>().ok()?;
+    let mut inspect = transform.into_inspect();
+    let init_inspect = inspect.clone();
+    <InspectTransform as InspectRenderStruct<InspectTransform>>::render_mut(
+        &mut [&mut inspect],
+        label,
+        ui,
+        &InspectArgsStruct::default(),
+    );
+
+    if inspect == init_inspect {
+        return Some(false);
+    }
+    transform
+        .set_position(inspect.position())
+        .set_rotation(inspect.rotation())
+        .set_scale(inspect.scale());
+    transform.dirty = true;
+    Some(true)
+}
+
+/// Shown for every entity with a transform, the component is added once a
+/// layer is changed.
+fn render_layers(entry: &mut Entry, label: &'static str, ui: &Ui) -> Option<bool> {
+    entry.get_component::<transform::Transform>().ok()?;
+    let mut layers = entry
+        .get_component::<RenderLayer>()
+        .ok()
+        .copied()
+        .unwrap_or_default();
+    ui.text(label);
+    let mut changed = false;
+    for (layer, name) in RenderLayer::NAMED.iter() {
+        let mut on = layers.contains(*layer);
+        if ui.checkbox(&im_str!("{}##render_layer", name), &mut on) {
+            layers = if on {
+                layers | *layer
+            } else {
+                RenderLayer(layers.0 & !layer.0)
+            };
+            changed = true;
+        }
+    }
+    if changed {
+        entry.add_component(layers);
+    }
+    Some(changed)
+}
+
+fn render_morph(entry: &mut Entry, label: &'static str, ui: &Ui) -> Option<bool> {
+    let morph = entry.get_component_mut::<animation::MorphWeights>().ok()?;
+    ui.text(label);
+    let mut changed = false;
+    for i in 0..morph.weights.len() {
+        let mut weight = morph.weights[i];
+        if imgui::Slider::new(&im_str!("target {}", i), 0.0..=1.0).build(ui, &mut weight) {
+            morph.set_weight(i, weight);
+            changed = true;
+        }
+    }
+    Some(changed)
+}
@@ -0,0 +1,60 @@
+//! The Nodas engine: a wgpu renderer, a legion world with physics, audio
+//! and scripting, and the editor around them.
+//!
+//! [`App`] configures and runs it. Systems and lifecycle hooks are added
+//! directly or grouped into [`Plugin`]s:
+//!
+//! ```no_run
+//! use engine::{lifecycle::LifecycleEvent, plugin::DefaultPlugins, App};
+//!
+//! let mut app = App::from_args();
+//! app.add_plugin(DefaultPlugins);
+//! app.add_plugin(|app: &mut App| {
+//!     app.add_lifecycle_hook(|_world, event| {
+//!         if let LifecycleEvent::PlayStart = event {
+//!             log::info!("Playing");
+//!         }
+//!     });
+//! });
+//! app.run();
+//! ```
+
+mod app;
+
+pub mod animation;
+pub mod assets;
+pub mod audio;
+pub mod autosave;
+pub mod behaviour;
+pub mod bounds;
+pub mod camera;
+pub mod character;
+pub mod editor;
+pub mod error;
+pub mod events;
+pub mod hierarchy;
+pub mod input;
+pub mod inspect;
+pub mod lifecycle;
+pub mod locale;
+pub mod logging;
+pub mod net;
+pub mod physics;
+pub mod plugin;
+pub mod prefab;
+pub mod render;
+pub mod replay;
+pub mod resources;
+pub mod scene;
+pub mod schedule;
+pub mod scripting;
+pub mod session;
+pub mod settings;
+pub mod spatial;
+pub mod stats;
+pub mod terrain;
+pub mod transform;
+pub mod world;
+
+pub use app::{App, Options};
+pub use plugin::Plugin;
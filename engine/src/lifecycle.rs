@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use crate::world;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LifecycleEvent {
+    SceneLoaded,
+    PlayStart,
+    PlayStop,
+    Frame(Duration),
+}
+
+pub type Hook = Box<dyn FnMut(&mut world::World, LifecycleEvent)>;
+
+/// Callbacks run at fixed points of the engine lifecycle. The startup
+/// script of the current scene gets the same events, before the hooks.
+#[derive(Default)]
+pub struct Lifecycle {
+    hooks: Vec<Hook>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: FnMut(&mut world::World, LifecycleEvent) + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    pub fn dispatch(&mut self, world: &mut world::World, event: LifecycleEvent) {
+        world.run_startup_script(event);
+        for hook in self.hooks.iter_mut() {
+            hook(world, event);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! UI strings looked up by key in per language string tables, TOML files
+//! in the [`LOCALE_DIR`] resource folder. Tables nest, `[hierarchy]` with
+//! `title = "..."` is the key `hierarchy.title`.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::*;
+use imgui::{im_str, ImString};
+use log::info;
+
+use crate::resources::Resources;
+
+pub const LOCALE_DIR: &str = "locale";
+/// Language every other one falls back to for the keys it lacks
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// The string table of one language.
+#[derive(Debug, Clone, Default)]
+pub struct Locale {
+    language: String,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Locale {
+    pub fn load(resources: &Resources, language: &str) -> Result<Self> {
+        info!("Load language {:?}", language);
+        let fallback = read_table(resources, DEFAULT_LANGUAGE)?;
+        let strings = if language == DEFAULT_LANGUAGE {
+            HashMap::new()
+        } else {
+            read_table(resources, language)?
+        };
+        Ok(Self {
+            language: language.to_string(),
+            strings,
+            fallback,
+        })
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// The string for `key`, from the default language when this one lacks
+    /// it, the key itself when neither has it.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(String::as_str)
+            .unwrap_or(key)
+    }
+
+    /// The string for `key` as a widget or window label. The key is the
+    /// imgui id, so windows keep their layout and widgets their state when
+    /// the language changes.
+    pub fn label(&self, key: &str) -> ImString {
+        im_str!("{}###{}", self.get(key), key)
+    }
+}
+
+/// Languages with a string table in any search path, the default one
+/// first.
+pub fn languages(resources: &Resources) -> Vec<String> {
+    let mut languages = vec![DEFAULT_LANGUAGE.to_string()];
+    for file in resources.list(LOCALE_DIR, "toml") {
+        let language = file.trim_end_matches(".toml");
+        if language != DEFAULT_LANGUAGE {
+            languages.push(language.to_string());
+        }
+    }
+    languages
+}
+
+fn read_table(resources: &Resources, language: &str) -> Result<HashMap<String, String>> {
+    let source = resources.read(Path::new(LOCALE_DIR).join(format!("{}.toml", language)))?;
+    let table: toml::value::Table = toml::from_slice(&source)
+        .with_context(|| format!("Could not parse string table {:?}", language))?;
+    let mut strings = HashMap::new();
+    flatten("", &table, &mut strings);
+    Ok(strings)
+}
+
+fn flatten(prefix: &str, table: &toml::value::Table, strings: &mut HashMap<String, String>) {
+    for (key, value) in table.iter() {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(table) => flatten(&key, table, strings),
+            toml::Value::String(string) => {
+                strings.insert(key, string.clone());
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Log output kept in memory for the editor console, alongside the
+//! terminal and optionally a file. Levels are set per module by the
+//! settings, then the `NODAS_LOG` environment variable, and can be changed
+//! at runtime through a [`LogHandle`].
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use simplelog::{Config, TermLogger, TerminalMode};
+
+use crate::settings::LogSettings;
+
+/// Environment variable overriding the levels, `info,wgpu_core=warn` or
+/// just `debug`, like `RUST_LOG`
+pub const LOG_ENV: &str = "NODAS_LOG";
+
+/// Records kept for the console, the oldest are dropped past this
+pub const LOG_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+/// The latest log records, shared between the logger and the console.
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer(Arc<Mutex<VecDeque<LogEntry>>>);
+
+impl LogBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, entry: LogEntry) {
+        if let Ok(mut entries) = self.0.lock() {
+            if entries.len() == LOG_CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+    }
+
+    /// The records, oldest first. Nothing may be logged while they are held.
+    pub fn entries(&self) -> Option<MutexGuard<VecDeque<LogEntry>>> {
+        self.0.lock().ok()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.0.lock() {
+            entries.clear();
+        }
+    }
+}
+
+/// Levels records are kept at, the longest matching module decides.
+#[derive(Debug, Clone)]
+struct Filters {
+    level: LevelFilter,
+    /// Sorted by descending length so children come before their parents
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filters {
+    fn new(settings: &LogSettings) -> Self {
+        let mut filters = Self {
+            level: settings.level,
+            modules: Vec::new(),
+        };
+        for (module, level) in settings.modules.iter() {
+            filters.set_module(module, *level);
+        }
+        filters
+    }
+
+    fn set_module(&mut self, module: &str, level: LevelFilter) {
+        self.modules.retain(|(filtered, _)| filtered != module);
+        self.modules.push((module.to_string(), level));
+        self.modules
+            .sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+    }
+
+    /// Applies `level` or `module=level` entries separated by commas.
+    fn parse(&mut self, spec: &str) -> Result<()> {
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut parts = entry.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(module), Some(level)) => self.set_module(
+                    module,
+                    level
+                        .parse()
+                        .context(format!("Invalid log level {:?}", level))?,
+                ),
+                (Some(level), None) => {
+                    self.level = level
+                        .parse()
+                        .context(format!("Invalid log level {:?}", level))?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target.starts_with(module.as_str())
+                    && (target.len() == module.len() || target[module.len()..].starts_with("::"))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    /// The most verbose level any module is kept at.
+    fn max(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, std::cmp::max)
+    }
+}
+
+/// Changes the levels of the running logger.
+#[derive(Debug, Clone)]
+pub struct LogHandle(Arc<RwLock<Filters>>);
+
+impl LogHandle {
+    /// Level of modules without a filter of their own.
+    pub fn level(&self) -> LevelFilter {
+        self.0
+            .read()
+            .map_or(LevelFilter::Info, |filters| filters.level)
+    }
+
+    pub fn set_level(&self, level: LevelFilter) {
+        if let Ok(mut filters) = self.0.write() {
+            filters.level = level;
+            log::set_max_level(filters.max());
+        }
+    }
+}
+
+/// A log file moved aside once it grows too large, `game.log` becoming
+/// `game.log.1`, that one `game.log.2` and so on.
+struct LogFile {
+    path: PathBuf,
+    file: fs::File,
+    /// Bytes in the current file
+    size: u64,
+    max_size: u64,
+    /// Rotated files kept besides the current one
+    max_files: usize,
+}
+
+impl LogFile {
+    fn open(path: &Path, max_size: u64, max_files: usize) -> Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("Could not open log file {:?}", path))?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.path.display(), index))
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.max_files > 0 {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            if let Err(e) = self.rotate() {
+                eprintln!("Could not rotate log file {:?}: {}", self.path, e);
+            }
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+}
+
+/// Sends the records the filters let through to the terminal, the
+/// console and the log file.
+struct Logger {
+    filters: Arc<RwLock<Filters>>,
+    terminal: Box<TermLogger>,
+    buffer: LogBuffer,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filters.read().map_or(false, |filters| {
+            metadata.level() <= filters.level_for(metadata.target())
+        })
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.terminal.log(record);
+        let message = record.args().to_string();
+        if let Some(file) = &self.file {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let line = format!(
+                "{}.{:03} [{}] {}: {}\n",
+                time.as_secs(),
+                time.subsec_millis(),
+                record.level(),
+                record.target(),
+                message
+            );
+            if let Ok(mut file) = file.lock() {
+                file.write(&line);
+            }
+        }
+        self.buffer.push(LogEntry {
+            level: record.level(),
+            target: record.target().to_string(),
+            message,
+        });
+    }
+
+    fn flush(&self) {
+        self.terminal.flush();
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+/// Logs records to the terminal, into `buffer` and to the settings' file
+/// at the levels of the settings, overridden by [`LOG_ENV`] and then
+/// `level` when given.
+pub fn init(
+    settings: &LogSettings,
+    level: Option<LevelFilter>,
+    buffer: LogBuffer,
+) -> Result<LogHandle> {
+    let mut filters = Filters::new(settings);
+    let env_error = std::env::var(LOG_ENV)
+        .ok()
+        .and_then(|spec| filters.parse(&spec).err());
+    if let Some(level) = level {
+        filters.level = level;
+    }
+    let max = filters.max();
+    let filters = Arc::new(RwLock::new(filters));
+
+    let file = match &settings.file {
+        Some(path) => Some(Mutex::new(LogFile::open(
+            path,
+            settings.max_file_size,
+            settings.max_files,
+        )?)),
+        None => None,
+    };
+    log::set_boxed_logger(Box::new(Logger {
+        filters: filters.clone(),
+        terminal: TermLogger::new(LevelFilter::Trace, Config::default(), TerminalMode::Mixed),
+        buffer,
+        file,
+    }))?;
+    log::set_max_level(max);
+
+    if let Some(e) = env_error {
+        log::warn!("Ignoring the rest of {}: {:?}", LOG_ENV, e);
+    }
+    Ok(LogHandle(filters))
+}
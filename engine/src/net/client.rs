@@ -0,0 +1,370 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Instant,
+};
+
+use anyhow::*;
+use log::{info, warn};
+use nalgebra::{Isometry3, Vector3};
+
+use super::{Message, NetId, Parts, TransformState, HELLO_INTERVAL, INTERPOLATION_DELAY, TIMEOUT};
+use crate::{assets, scene};
+
+/// What the server sent that takes the world to apply.
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// The entities of the local scene make way for the server's, which
+    /// use the models of the manifest
+    Connected(assets::AssetManifest),
+    Spawn {
+        id: NetId,
+        parent: Option<NetId>,
+        data: scene::EntityData,
+    },
+    Despawn(legion::Entity),
+}
+
+/// An entity mirrored from the server.
+struct Remote {
+    entity: legion::Entity,
+    parent: Option<NetId>,
+    /// Server time and transform, oldest first
+    snapshots: VecDeque<(f64, TransformState)>,
+}
+
+pub struct Client {
+    socket: UdpSocket,
+    server: SocketAddr,
+    connected: bool,
+    /// Session of the server welcome connected with
+    session: Option<u64>,
+    /// Parts of a welcome arrived so far, by session
+    welcome: Parts<assets::AssetManifest>,
+    /// Parts of an entity list arrived so far, by list
+    listing: Parts<Vec<NetId>>,
+    /// Nothing arrived for a while, the server may still answer
+    lost: bool,
+    last_hello: Option<Instant>,
+    last_heard: Instant,
+    start: Instant,
+    /// Server time minus local time, from the least delayed snapshot
+    clock: Option<f64>,
+    remotes: HashMap<NetId, Remote>,
+    /// Spawns asked for and when, so they aren't asked for every snapshot
+    requested: HashMap<NetId, Instant>,
+}
+
+impl Client {
+    /// Connects to the server at `addr`, like `127.0.0.1:7777`. The
+    /// connection completes once the server answers.
+    pub fn connect(addr: &str) -> Result<Self> {
+        let server = addr
+            .to_socket_addrs()
+            .context(format!("Could not resolve {}", addr))?
+            .next()
+            .ok_or_else(|| anyhow!("{} resolves to no address", addr))?;
+        let socket = super::bind(if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        })?;
+        info!("Connecting to {}", server);
+        let now = Instant::now();
+        Ok(Self {
+            socket,
+            server,
+            connected: false,
+            session: None,
+            welcome: Parts::default(),
+            listing: Parts::default(),
+            lost: false,
+            last_hello: None,
+            last_heard: now,
+            start: now,
+            clock: None,
+            remotes: HashMap::new(),
+            requested: HashMap::new(),
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected && !self.lost
+    }
+
+    pub fn entity(&self, id: NetId) -> Option<legion::Entity> {
+        self.remotes.get(&id).map(|remote| remote.entity)
+    }
+
+    /// Mirrors `id` with `entity` from now on.
+    pub fn insert(&mut self, id: NetId, entity: legion::Entity, parent: Option<NetId>) {
+        self.requested.remove(&id);
+        self.remotes.insert(
+            id,
+            Remote {
+                entity,
+                parent,
+                snapshots: VecDeque::new(),
+            },
+        );
+    }
+
+    /// Entities whose parent is `id`, for parenting the ones that spawned
+    /// before it.
+    pub fn children(&self, id: NetId) -> Vec<legion::Entity> {
+        self.remotes
+            .values()
+            .filter(|remote| remote.parent == Some(id))
+            .map(|remote| remote.entity)
+            .collect()
+    }
+
+    /// Handles what the server sent and keeps the connection alive.
+    pub fn update(&mut self) -> Vec<ClientEvent> {
+        let mut events = Vec::new();
+        let now = Instant::now();
+        if self
+            .last_hello
+            .map_or(true, |hello| now.duration_since(hello) >= HELLO_INTERVAL)
+        {
+            self.last_hello = Some(now);
+            super::send(
+                &self.socket,
+                self.server,
+                &Message::Hello {
+                    welcomed: self.connected,
+                },
+            );
+        }
+
+        for (addr, message) in super::receive(&self.socket) {
+            if addr != self.server {
+                continue;
+            }
+            self.last_heard = now;
+            if self.lost {
+                info!("Heard from {} again", self.server);
+                self.lost = false;
+            }
+            match message {
+                Message::Welcome {
+                    session,
+                    part,
+                    parts,
+                    assets,
+                } => {
+                    // Welcomes keep coming until the next hello
+                    if self.connected && self.session == Some(session) {
+                        continue;
+                    }
+                    let assets = match self.receive_welcome(session, part, parts, assets) {
+                        Some(assets) => assets,
+                        None => continue,
+                    };
+                    info!("Connected to {}", self.server);
+                    self.connected = true;
+                    self.session = Some(session);
+                    // Tells the server the welcome arrived right away
+                    self.last_hello = None;
+                    self.clock = None;
+                    self.requested.clear();
+                    self.listing = Parts::default();
+                    events.extend(
+                        self.remotes
+                            .drain()
+                            .map(|(_, remote)| ClientEvent::Despawn(remote.entity)),
+                    );
+                    events.push(ClientEvent::Connected(assets));
+                }
+                Message::Goodbye => {
+                    info!("{} closed the connection", self.server);
+                    self.connected = false;
+                    self.session = None;
+                }
+                Message::Spawn { id, parent, data } if self.connected => {
+                    if !self.remotes.contains_key(&id) {
+                        events.push(ClientEvent::Spawn { id, parent, data });
+                    }
+                }
+                Message::Despawn(id) => {
+                    if let Some(remote) = self.remotes.remove(&id) {
+                        events.push(ClientEvent::Despawn(remote.entity));
+                    }
+                }
+                Message::Snapshot { time, transforms } if self.connected => {
+                    let local = self.start.elapsed().as_secs_f64();
+                    let clock = self.clock.get_or_insert(time - local);
+                    *clock = clock.max(time - local);
+                    for transform in transforms {
+                        match self.remotes.get_mut(&transform.id) {
+                            Some(remote) => {
+                                // Reordered datagrams arrive too late to matter
+                                if remote
+                                    .snapshots
+                                    .back()
+                                    .map_or(true, |(last, _)| *last < time)
+                                {
+                                    remote.snapshots.push_back((time, transform));
+                                }
+                            }
+                            None => self.request(transform.id, now),
+                        }
+                    }
+                }
+                Message::Entities {
+                    list,
+                    part,
+                    parts,
+                    ids,
+                } if self.connected => {
+                    // Entities count as gone only once the whole list is in
+                    let ids = match self.listing.receive(list, part, parts, ids) {
+                        Some(parts) => parts.into_iter().flatten().collect::<Vec<_>>(),
+                        None => continue,
+                    };
+                    let listed = ids.iter().copied().collect::<HashSet<_>>();
+                    let stale = self
+                        .remotes
+                        .keys()
+                        .filter(|id| !listed.contains(id))
+                        .copied()
+                        .collect::<Vec<_>>();
+                    for id in stale {
+                        if let Some(remote) = self.remotes.remove(&id) {
+                            events.push(ClientEvent::Despawn(remote.entity));
+                        }
+                    }
+                    for id in ids {
+                        if !self.remotes.contains_key(&id) {
+                            self.request(id, now);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if self.connected && !self.lost && now.duration_since(self.last_heard) >= TIMEOUT {
+            warn!("Lost the connection to {}", self.server);
+            self.lost = true;
+        }
+        events
+    }
+
+    /// Keeps `part` of the welcome of `session`, the whole manifest once
+    /// every part arrived.
+    fn receive_welcome(
+        &mut self,
+        session: u64,
+        part: usize,
+        parts: usize,
+        assets: assets::AssetManifest,
+    ) -> Option<assets::AssetManifest> {
+        let mut manifest = assets::AssetManifest::default();
+        for assets in self.welcome.receive(session, part, parts, assets)? {
+            manifest.models.extend(assets.models);
+            manifest.sounds.extend(assets.sounds);
+            manifest.textures.extend(assets.textures);
+        }
+        Some(manifest)
+    }
+
+    fn request(&mut self, id: NetId, now: Instant) {
+        let due = self
+            .requested
+            .get(&id)
+            .map_or(true, |asked| now.duration_since(*asked) >= HELLO_INTERVAL);
+        if due {
+            self.requested.insert(id, now);
+            super::send(&self.socket, self.server, &Message::Request(id));
+        }
+    }
+
+    /// Where every mirrored entity is at the time drawn, between the
+    /// snapshots around it. Entities past their last snapshot stay there.
+    pub fn interpolate(&mut self) -> Vec<(legion::Entity, Isometry3<f32>, Vector3<f32>)> {
+        let clock = match self.clock {
+            Some(clock) => clock,
+            None => return Vec::new(),
+        };
+        let time = self.start.elapsed().as_secs_f64() + clock - INTERPOLATION_DELAY.as_secs_f64();
+
+        let mut transforms = Vec::with_capacity(self.remotes.len());
+        for remote in self.remotes.values_mut() {
+            // Keep the last snapshot at or before the time drawn
+            while remote.snapshots.len() > 1 && remote.snapshots[1].0 <= time {
+                remote.snapshots.pop_front();
+            }
+            let (from_time, from) = match remote.snapshots.front() {
+                Some(front) => *front,
+                None => continue,
+            };
+            let (isometry, scale) = match remote.snapshots.get(1) {
+                Some((to_time, to)) if from_time <= time => {
+                    let t = ((time - from_time) / (to_time - from_time)) as f32;
+                    let (from_isometry, to_isometry) = (from.isometry(), to.isometry());
+                    let translation = from_isometry
+                        .translation
+                        .vector
+                        .lerp(&to_isometry.translation.vector, t);
+                    (
+                        Isometry3::from_parts(
+                            translation.into(),
+                            from_isometry.rotation.slerp(&to_isometry.rotation, t),
+                        ),
+                        from.scale().lerp(&to.scale(), t),
+                    )
+                }
+                _ => (from.isometry(), from.scale()),
+            };
+            transforms.push((remote.entity, isometry, scale));
+        }
+        transforms
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if self.connected {
+            super::send(&self.socket, self.server, &Message::Goodbye);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(model: &str) -> assets::AssetManifest {
+        let mut manifest = assets::AssetManifest::default();
+        manifest
+            .models
+            .insert(String::from(model), assets::ModelSource::file(model));
+        manifest
+    }
+
+    #[test]
+    fn welcome_completes_with_every_part() {
+        let mut client = Client::connect("127.0.0.1:7777").unwrap();
+        assert_eq!(client.receive_welcome(1, 1, 2, part("b")), None);
+        let manifest = client.receive_welcome(1, 0, 2, part("a")).unwrap();
+        assert_eq!(manifest.models.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn welcome_of_a_restarted_server_starts_over() {
+        let mut client = Client::connect("127.0.0.1:7777").unwrap();
+        assert_eq!(client.receive_welcome(1, 0, 2, part("old")), None);
+        assert_eq!(client.receive_welcome(2, 0, 2, part("a")), None);
+        let manifest = client.receive_welcome(2, 1, 2, part("b")).unwrap();
+        assert!(!manifest.models.contains_key("old"));
+        assert_eq!(manifest.models.len(), 2);
+    }
+
+    #[test]
+    fn welcome_part_out_of_range_is_dropped() {
+        let mut client = Client::connect("127.0.0.1:7777").unwrap();
+        assert_eq!(client.receive_welcome(1, 3, 1, part("a")), None);
+        assert!(client.receive_welcome(1, 0, 1, part("a")).is_some());
+    }
+}
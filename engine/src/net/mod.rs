@@ -0,0 +1,375 @@
+//! Replication of a world to other engine instances over UDP. A server
+//! sends every entity with a model to its clients as it spawns, followed by
+//! snapshots of their transforms at a fixed rate. Clients mirror the
+//! entities and draw them a little in the past, between the two snapshots
+//! around that time, so they move smoothly however the packets arrive.
+//!
+//! Datagrams may be lost or reordered. The server welcomes a client again
+//! for every hello saying the welcome hasn't arrived yet. Spawns a client
+//! missed are asked for again once a snapshot names the entity, and the
+//! server lists the entities it replicates every second, so despawns it
+//! missed catch up. Welcomes and lists too long for one datagram are split
+//! into numbered parts, which clients collect until they have them all.
+
+pub mod client;
+pub mod server;
+
+pub use client::Client;
+pub use server::Server;
+
+use std::{
+    collections::BTreeSet,
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use anyhow::*;
+use log::warn;
+use nalgebra::{Isometry3, Quaternion, Translation3, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{assets, scene, transform};
+
+/// How often the server sends transform snapshots
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the server lists the entities it replicates
+pub const LIST_INTERVAL: Duration = Duration::from_secs(1);
+/// How often a client says hello, to connect and then to stay connected
+pub const HELLO_INTERVAL: Duration = Duration::from_secs(1);
+/// Silence after which the other end counts as gone
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+/// How far behind the server clients draw, two snapshots and some jitter
+pub const INTERPOLATION_DELAY: Duration = Duration::from_millis(120);
+/// Transforms per snapshot datagram, keeping them under the usual MTU
+const SNAPSHOT_CHUNK: usize = 8;
+/// Models, sounds or textures per welcome datagram, for the same reason
+const MANIFEST_CHUNK: usize = 8;
+/// Ids per entity list datagram, for the same reason
+const LIST_CHUNK: usize = 64;
+/// Largest spawn sent, a few IP fragments at most. Entities with more data
+/// aren't replicated.
+pub const MAX_SPAWN: usize = 4096;
+/// Largest datagram read
+const MAX_DATAGRAM: usize = 65507;
+
+/// Identifies a replicated entity on the server and every client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NetId(pub u64);
+
+/// Local transform of a replicated entity at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TransformState {
+    pub id: NetId,
+    pub translation: [f32; 3],
+    /// Quaternion as `[i, j, k, w]`, interpolated without the euler angles'
+    /// wrap around
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl TransformState {
+    pub fn new(id: NetId, transform: &transform::Transform) -> Self {
+        let isometry = transform.isometry();
+        Self {
+            id,
+            translation: isometry.translation.vector.into(),
+            rotation: isometry.rotation.coords.into(),
+            scale: transform.scale().into(),
+        }
+    }
+
+    pub fn isometry(&self) -> Isometry3<f32> {
+        let [i, j, k, w] = self.rotation;
+        Isometry3::from_parts(
+            Translation3::from(Vector3::from(self.translation)),
+            UnitQuaternion::from_quaternion(Quaternion::new(w, i, j, k)),
+        )
+    }
+
+    pub fn scale(&self) -> Vector3<f32> {
+        Vector3::from(self.scale)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Message {
+    /// Client to server, connecting and then as a keep alive. The server
+    /// welcomes the client again until `welcomed` says it got every part.
+    Hello {
+        welcomed: bool,
+    },
+    /// Either way, the sender is leaving
+    Goodbye,
+    /// Server to a client once it connected, with part `part` of `parts` of
+    /// the manifest listing the models its entities use. `session` tells
+    /// the welcomes of a restarted server apart.
+    Welcome {
+        session: u64,
+        part: usize,
+        parts: usize,
+        assets: assets::AssetManifest,
+    },
+    /// A replicated entity, its parent given by id
+    Spawn {
+        id: NetId,
+        parent: Option<NetId>,
+        data: scene::EntityData,
+    },
+    Despawn(NetId),
+    /// Transforms at `time` seconds since the server started
+    Snapshot {
+        time: f64,
+        transforms: Vec<TransformState>,
+    },
+    /// Part `part` of `parts` of the list `list` of every entity the
+    /// server replicates
+    Entities {
+        list: u64,
+        part: usize,
+        parts: usize,
+        ids: Vec<NetId>,
+    },
+    /// Client to server, for the spawn of an entity it doesn't know
+    Request(NetId),
+}
+
+impl Message {
+    /// The spawn of the replicated `id`, unless it takes more than
+    /// [`MAX_SPAWN`] bytes.
+    pub fn spawn(id: NetId, parent: Option<NetId>, data: scene::EntityData) -> Result<Self> {
+        let spawn = Message::Spawn { id, parent, data };
+        let size = ron::ser::to_string(&spawn)?.len();
+        ensure!(
+            size <= MAX_SPAWN,
+            "Spawn of {} bytes is over the limit of {}",
+            size,
+            MAX_SPAWN
+        );
+        Ok(spawn)
+    }
+}
+
+/// `manifest` in parts of at most [`MANIFEST_CHUNK`] models, sounds or
+/// textures, at least one so an empty manifest still makes a welcome.
+fn split_manifest(manifest: &assets::AssetManifest) -> Vec<assets::AssetManifest> {
+    let models = manifest.models.iter().collect::<Vec<_>>();
+    let sounds = manifest.sounds.iter().collect::<Vec<_>>();
+    let textures = manifest.textures.iter().collect::<Vec<_>>();
+    let mut parts = models
+        .chunks(MANIFEST_CHUNK)
+        .map(|chunk| assets::AssetManifest {
+            models: chunk
+                .iter()
+                .map(|(name, source)| ((*name).clone(), (*source).clone()))
+                .collect(),
+            ..Default::default()
+        })
+        .chain(sounds.chunks(MANIFEST_CHUNK).map(|chunk| {
+            assets::AssetManifest {
+                sounds: chunk
+                    .iter()
+                    .map(|(name, path)| ((*name).clone(), (*path).clone()))
+                    .collect(),
+                ..Default::default()
+            }
+        }))
+        .chain(textures.chunks(MANIFEST_CHUNK).map(|chunk| {
+            assets::AssetManifest {
+                textures: chunk
+                    .iter()
+                    .map(|path| (*path).clone())
+                    .collect::<BTreeSet<_>>(),
+                ..Default::default()
+            }
+        }))
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        parts.push(assets::AssetManifest::default());
+    }
+    parts
+}
+
+/// `ids` in parts of at most [`LIST_CHUNK`], at least one so an empty list
+/// still despawns every entity.
+fn split_list(ids: &[NetId]) -> Vec<Vec<NetId>> {
+    let mut parts = ids
+        .chunks(LIST_CHUNK)
+        .map(|chunk| chunk.to_vec())
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        parts.push(Vec::new());
+    }
+    parts
+}
+
+/// The parts of a message split over several datagrams, kept until every
+/// one of them arrived.
+struct Parts<T> {
+    /// Message the parts belong to and the ones received
+    pending: Option<(u64, Vec<Option<T>>)>,
+}
+
+impl<T> Parts<T> {
+    /// Keeps part `part` of `parts` of the message `key`, every part in
+    /// order once they all arrived. A part of another message drops the
+    /// ones kept so far.
+    fn receive(&mut self, key: u64, part: usize, parts: usize, value: T) -> Option<Vec<T>> {
+        let started = self.pending.as_ref().map_or(false, |(pending, received)| {
+            *pending == key && received.len() == parts
+        });
+        if !started {
+            self.pending = Some((key, (0..parts).map(|_| None).collect()));
+        }
+        let received = &mut self.pending.as_mut()?.1;
+        *received.get_mut(part)? = Some(value);
+        if received.iter().any(Option::is_none) {
+            return None;
+        }
+        Some(self.pending.take()?.1.into_iter().flatten().collect())
+    }
+}
+
+impl<T> Default for Parts<T> {
+    fn default() -> Self {
+        Self { pending: None }
+    }
+}
+
+fn bind(addr: &str) -> Result<UdpSocket> {
+    let socket = UdpSocket::bind(addr).context(format!("Could not bind {}", addr))?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+fn send(socket: &UdpSocket, addr: SocketAddr, message: &Message) {
+    let result = ron::ser::to_string(message)
+        .map_err(Error::from)
+        .and_then(|source| Ok(socket.send_to(source.as_bytes(), addr)?));
+    if let Err(e) = result {
+        warn!("Could not send to {}: {:?}", addr, e);
+    }
+}
+
+/// Every datagram waiting on `socket`, skipping the ones that don't parse.
+fn receive(socket: &UdpSocket) -> Vec<(SocketAddr, Message)> {
+    let mut messages = Vec::new();
+    let mut buffer = vec![0; MAX_DATAGRAM];
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((len, addr)) => match std::str::from_utf8(&buffer[..len])
+                .map_err(Error::from)
+                .and_then(|source| Ok(ron::de::from_str(source)?))
+            {
+                Ok(message) => messages.push((addr, message)),
+                Err(e) => warn!("Malformed datagram from {}: {:?}", addr, e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            // A client that went away without a goodbye makes some
+            // platforms report the connection reset, nothing to read then
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+            Err(e) => {
+                warn!("Could not receive: {}", e);
+                break;
+            }
+        }
+    }
+    messages
+}
+
+/// Which end of the replication this instance is.
+pub enum Net {
+    Server(Server),
+    Client(Client),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(models: usize, sounds: usize, textures: usize) -> assets::AssetManifest {
+        assets::AssetManifest {
+            models: (0..models)
+                .map(|i| {
+                    let name = format!("model{}", i);
+                    let source = assets::ModelSource::file(format!("{}.obj", name));
+                    (name, source)
+                })
+                .collect(),
+            sounds: (0..sounds)
+                .map(|i| (format!("sound{}", i), format!("sound{}.ogg", i).into()))
+                .collect(),
+            textures: (0..textures)
+                .map(|i| format!("texture{}.png", i).into())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn empty_manifest_makes_one_part() {
+        let parts = split_manifest(&assets::AssetManifest::default());
+        assert_eq!(parts, vec![assets::AssetManifest::default()]);
+    }
+
+    #[test]
+    fn manifest_parts_hold_everything_once() {
+        let whole = manifest(MANIFEST_CHUNK * 2 + 1, 3, MANIFEST_CHUNK + 1);
+        let parts = split_manifest(&whole);
+        assert_eq!(parts.len(), 3 + 1 + 2);
+
+        let mut joined = assets::AssetManifest::default();
+        for part in parts.iter() {
+            let size = part.models.len() + part.sounds.len() + part.textures.len();
+            assert!(size > 0 && size <= MANIFEST_CHUNK);
+            joined.models.extend(part.models.clone());
+            joined.sounds.extend(part.sounds.clone());
+            joined.textures.extend(part.textures.clone());
+        }
+        assert_eq!(joined, whole);
+    }
+
+    #[test]
+    fn empty_list_makes_one_part() {
+        assert_eq!(split_list(&[]), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn list_parts_keep_the_order() {
+        let ids = (0..LIST_CHUNK as u64 + 1).map(NetId).collect::<Vec<_>>();
+        let parts = split_list(&ids);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts.concat(), ids);
+    }
+
+    #[test]
+    fn parts_complete_in_any_order() {
+        let mut parts = Parts::default();
+        assert_eq!(parts.receive(1, 2, 3, "c"), None);
+        assert_eq!(parts.receive(1, 0, 3, "a"), None);
+        assert_eq!(parts.receive(1, 0, 3, "a"), None);
+        assert_eq!(parts.receive(1, 1, 3, "b"), Some(vec!["a", "b", "c"]));
+    }
+
+    #[test]
+    fn parts_of_another_message_start_over() {
+        let mut parts = Parts::default();
+        assert_eq!(parts.receive(1, 0, 2, "old"), None);
+        assert_eq!(parts.receive(2, 1, 2, "b"), None);
+        assert_eq!(parts.receive(2, 0, 2, "a"), Some(vec!["a", "b"]));
+        assert_eq!(parts.receive(3, 5, 2, "out of range"), None);
+    }
+
+    #[test]
+    fn oversized_spawns_are_refused() {
+        let mut entity = crate::prefab::Prefab {
+            model: String::from("crate.obj"),
+            material: None,
+            transform: scene::TransformData::default(),
+            tag: None,
+        }
+        .instance("crate", None);
+        assert!(Message::spawn(NetId(0), None, entity.clone()).is_ok());
+
+        entity.name = Some("x".repeat(MAX_SPAWN));
+        assert!(Message::spawn(NetId(0), None, entity).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{SocketAddr, UdpSocket},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::*;
+use log::info;
+
+use crate::assets;
+
+use super::{
+    Message, NetId, TransformState, LIST_INTERVAL, SNAPSHOT_CHUNK, SNAPSHOT_INTERVAL, TIMEOUT,
+};
+
+/// What clients asked for that takes the world to answer.
+#[derive(Debug, Clone, Copy)]
+pub enum ServerEvent {
+    /// Needs a welcome and the spawn of every replicated entity, again for
+    /// each hello until the welcome arrived
+    Joined(SocketAddr),
+    /// Needs the spawn of one entity it missed
+    Requested(SocketAddr, NetId),
+}
+
+pub struct Server {
+    socket: UdpSocket,
+    /// When each client was last heard from
+    clients: HashMap<SocketAddr, Instant>,
+    /// Every entity replicated, by the order they spawned in
+    entities: BTreeMap<NetId, legion::Entity>,
+    next_id: u64,
+    /// Sent with the welcome, a different one each time the server starts
+    session: u64,
+    start: Instant,
+    last_snapshot: Instant,
+    last_list: Instant,
+    /// Number of the next entity list, which clients tell the parts of
+    /// different lists apart by
+    next_list: u64,
+}
+
+impl Server {
+    /// Serves on `addr`, like `0.0.0.0:7777`.
+    pub fn bind(addr: &str) -> Result<Self> {
+        let socket = super::bind(addr)?;
+        info!("Serving the world on {}", socket.local_addr()?);
+        let now = Instant::now();
+        let session = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        Ok(Self {
+            socket,
+            clients: HashMap::new(),
+            entities: BTreeMap::new(),
+            next_id: 0,
+            session,
+            start: now,
+            last_snapshot: now,
+            last_list: now,
+            next_list: 0,
+        })
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Replicates `entity` from now on, the world sends its spawn.
+    pub fn replicate(&mut self, entity: legion::Entity) -> NetId {
+        let id = NetId(self.next_id);
+        self.next_id += 1;
+        self.entities.insert(id, entity);
+        id
+    }
+
+    pub fn entity(&self, id: NetId) -> Option<legion::Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    /// Every replicated entity with its id, by the order they spawned in.
+    pub fn entities(&self) -> impl Iterator<Item = (NetId, legion::Entity)> + '_ {
+        self.entities.iter().map(|(id, entity)| (*id, *entity))
+    }
+
+    /// Handles what the clients sent, despawns the entities `exists` no
+    /// longer finds and lists the rest when due.
+    pub fn update<F: Fn(legion::Entity) -> bool>(&mut self, exists: F) -> Vec<ServerEvent> {
+        let mut events = Vec::new();
+        let now = Instant::now();
+        for (addr, message) in super::receive(&self.socket) {
+            match message {
+                Message::Hello { welcomed } => {
+                    let joined = self.clients.insert(addr, now).is_none();
+                    if joined {
+                        info!("Client {} connected", addr);
+                    }
+                    if joined || !welcomed {
+                        events.push(ServerEvent::Joined(addr));
+                    }
+                }
+                Message::Goodbye => {
+                    if self.clients.remove(&addr).is_some() {
+                        info!("Client {} disconnected", addr);
+                    }
+                }
+                Message::Request(id) if self.clients.contains_key(&addr) => {
+                    self.clients.insert(addr, now);
+                    events.push(ServerEvent::Requested(addr, id));
+                }
+                _ => {}
+            }
+        }
+        self.clients.retain(|addr, heard| {
+            let alive = now.duration_since(*heard) < TIMEOUT;
+            if !alive {
+                info!("Client {} timed out", addr);
+            }
+            alive
+        });
+
+        let removed = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| !exists(**entity))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in removed {
+            self.entities.remove(&id);
+            self.broadcast(&Message::Despawn(id));
+        }
+
+        if now.duration_since(self.last_list) >= LIST_INTERVAL {
+            self.last_list = now;
+            self.list_entities();
+        }
+        events
+    }
+
+    /// Sends every client the list of replicated entities, split over as
+    /// many datagrams as it takes.
+    fn list_entities(&mut self) {
+        let list = self.next_list;
+        self.next_list += 1;
+        let ids = self.entities.keys().copied().collect::<Vec<_>>();
+        let parts = super::split_list(&ids);
+        let count = parts.len();
+        for (part, ids) in parts.into_iter().enumerate() {
+            self.broadcast(&Message::Entities {
+                list,
+                part,
+                parts: count,
+                ids,
+            });
+        }
+    }
+
+    pub fn send_to(&self, addr: SocketAddr, message: &Message) {
+        super::send(&self.socket, addr, message);
+    }
+
+    /// Sends `manifest` to `addr`, split over as many welcomes as it takes.
+    pub fn welcome(&self, addr: SocketAddr, manifest: &assets::AssetManifest) {
+        let parts = super::split_manifest(manifest);
+        let count = parts.len();
+        for (part, assets) in parts.into_iter().enumerate() {
+            self.send_to(
+                addr,
+                &Message::Welcome {
+                    session: self.session,
+                    part,
+                    parts: count,
+                    assets,
+                },
+            );
+        }
+    }
+
+    pub fn broadcast(&self, message: &Message) {
+        for addr in self.clients.keys() {
+            super::send(&self.socket, *addr, message);
+        }
+    }
+
+    /// Whether the next snapshot is due, restarting the interval if so.
+    pub fn snapshot_due(&mut self) -> bool {
+        let due = self.last_snapshot.elapsed() >= SNAPSHOT_INTERVAL;
+        if due {
+            self.last_snapshot = Instant::now();
+        }
+        due
+    }
+
+    /// Sends `transforms` to every client, split over as many datagrams as
+    /// they take.
+    pub fn send_snapshot(&self, transforms: &[TransformState]) {
+        if self.clients.is_empty() {
+            return;
+        }
+        let time = self.start.elapsed().as_secs_f64();
+        for chunk in transforms.chunks(SNAPSHOT_CHUNK) {
+            self.broadcast(&Message::Snapshot {
+                time,
+                transforms: chunk.to_vec(),
+            });
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.broadcast(&Message::Goodbye);
+    }
+}
@@ -0,0 +1,767 @@
+//! Rigid body simulation. Every entity with a model gets a body, kinematic
+//! unless it has a dynamic [`RigidBody`], and a collider shaped like its
+//! model. Dynamic bodies write their pose back into the `Transform` after
+//! each fixed step, kinematic ones follow their `Transform`.
+
+use std::collections::HashMap;
+
+use nalgebra::{Isometry3, Point3, Vector3};
+use ncollide3d::{
+    bounding_volume::AABB,
+    pipeline::{CollisionGroups, ContactEvent},
+    query::{self, Proximity, Ray},
+    shape::{Ball, Capsule, Compound, ConvexHull, Cuboid, Shape, ShapeHandle},
+};
+use nphysics3d::{
+    algebra::{Force3, ForceType, Inertia3, Velocity3},
+    force_generator::DefaultForceGeneratorSet,
+    joint::DefaultJointConstraintSet,
+    object::{
+        Body, BodyPartHandle, BodyStatus, ColliderDesc, DefaultBodyHandle, DefaultBodySet,
+        DefaultColliderHandle, DefaultColliderSet, Ground, RigidBodyDesc,
+    },
+    world::{DefaultGeometricalWorld, DefaultMechanicalWorld},
+};
+use serde::{Deserialize, Serialize};
+
+use log::warn;
+
+use crate::{events::CollisionEvent, inspect::Inspect, render::model::Geometry};
+
+/// Downward acceleration in m/s²
+pub const GRAVITY: f32 = 9.81;
+
+/// Layer of entities without `CollisionLayers`
+pub const DEFAULT_LAYER: &str = "default";
+
+/// Number of collision groups ncollide supports
+pub const MAX_LAYERS: usize = 30;
+
+/// Transforms closer than this to their body are not written back to it
+const POSE_EPSILON: f32 = 1e-4;
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyType {
+    /// Moved by forces and collisions
+    Dynamic,
+    /// Follows its `Transform`, pushes dynamic bodies out of the way
+    Kinematic,
+    /// Never moves
+    Static,
+}
+
+impl BodyType {
+    fn status(self) -> BodyStatus {
+        match self {
+            BodyType::Dynamic => BodyStatus::Dynamic,
+            BodyType::Kinematic => BodyStatus::Kinematic,
+            BodyType::Static => BodyStatus::Static,
+        }
+    }
+}
+
+/// How an entity takes part in the simulation, entities pushed without
+/// one get a kinematic body.
+#[derive(Debug, Clone, Copy, PartialEq, Inspect)]
+pub struct RigidBody {
+    #[inspect(skip)]
+    pub body_type: BodyType,
+    pub mass: f32,
+    #[inspect(skip)]
+    handle: Option<DefaultBodyHandle>,
+}
+
+impl RigidBody {
+    #[allow(unused)]
+    pub fn dynamic(mass: f32) -> Self {
+        Self {
+            body_type: BodyType::Dynamic,
+            mass,
+            handle: None,
+        }
+    }
+
+    pub fn kinematic() -> Self {
+        Self {
+            body_type: BodyType::Kinematic,
+            mass: 0.0,
+            handle: None,
+        }
+    }
+
+    pub fn handle(&self) -> Option<DefaultBodyHandle> {
+        self.handle
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub entity: legion::Entity,
+    /// Distance along the ray, in units of its direction
+    pub distance: f32,
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>,
+    /// Mesh of the entity's model that was hit, filled in by
+    /// `World::raycast`
+    pub mesh: Option<usize>,
+}
+
+/// Collider of an entity, added along with its body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collider(pub DefaultColliderHandle);
+
+/// What an entity's collider is built from. Anything but `TriMesh` is much
+/// cheaper to move and test against.
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColliderShape {
+    /// Box around the model
+    Aabb,
+    ConvexHull,
+    /// Sphere around the model
+    Sphere,
+    /// Upright capsule fitting the model's box
+    Capsule,
+    /// Exact model triangles, the default
+    TriMesh,
+}
+
+impl Default for ColliderShape {
+    fn default() -> Self {
+        ColliderShape::TriMesh
+    }
+}
+
+impl ColliderShape {
+    pub const ALL: [ColliderShape; 5] = [
+        ColliderShape::Aabb,
+        ColliderShape::ConvexHull,
+        ColliderShape::Sphere,
+        ColliderShape::Capsule,
+        ColliderShape::TriMesh,
+    ];
+
+    pub fn shape_handle(self, geometry: &Geometry, scale: Vector3<f32>) -> ShapeHandle<f32> {
+        let aabb = geometry.local_aabb();
+        let center = aabb.center().coords.component_mul(&scale);
+        let half_extents = aabb.half_extents().component_mul(&scale).abs();
+        // Primitive shapes are centered on the origin, move them onto the
+        // model
+        let centered = |shape: ShapeHandle<f32>| {
+            if center == Vector3::zeros() {
+                shape
+            } else {
+                ShapeHandle::new(Compound::new(vec![(
+                    Isometry3::translation(center.x, center.y, center.z),
+                    shape,
+                )]))
+            }
+        };
+
+        match self {
+            ColliderShape::Aabb => centered(ShapeHandle::new(Cuboid::new(half_extents))),
+            ColliderShape::Sphere => centered(ShapeHandle::new(Ball::new(half_extents.norm()))),
+            ColliderShape::Capsule => {
+                let radius = half_extents.x.max(half_extents.z);
+                let half_height = (half_extents.y - radius).max(0.0);
+                centered(ShapeHandle::new(Capsule::new(half_height, radius)))
+            }
+            ColliderShape::ConvexHull => {
+                match ConvexHull::try_from_points(&geometry.points(scale)) {
+                    Some(hull) => ShapeHandle::new(hull),
+                    None => {
+                        warn!("Could not build convex hull, using the bounding box");
+                        centered(ShapeHandle::new(Cuboid::new(half_extents)))
+                    }
+                }
+            }
+            ColliderShape::TriMesh => ShapeHandle::new(geometry.scaled(scale)),
+        }
+    }
+}
+
+/// Model shapes already built for a scale, shared by every entity using
+/// the same model at that scale.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ShapeKey {
+    model: String,
+    shape: ColliderShape,
+    scale: [u32; 3],
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerShape {
+    Box {
+        half_extents: Vector3<f32>,
+    },
+    Sphere {
+        radius: f32,
+    },
+    /// Uses the entity's model and `ColliderShape`
+    Mesh,
+}
+
+/// Sensor reporting entities entering and leaving it, without pushing
+/// them. Triggers other than `Mesh` don't need a model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerVolume {
+    pub shape: TriggerShape,
+}
+
+impl TriggerVolume {
+    /// Shape scaled by `scale`, `None` for mesh triggers.
+    pub fn shape_handle(&self, scale: Vector3<f32>) -> Option<ShapeHandle<f32>> {
+        match self.shape {
+            TriggerShape::Box { half_extents } => Some(ShapeHandle::new(Cuboid::new(
+                half_extents.component_mul(&scale),
+            ))),
+            TriggerShape::Sphere { radius } => {
+                Some(ShapeHandle::new(Ball::new(radius * scale.amax())))
+            }
+            TriggerShape::Mesh => None,
+        }
+    }
+}
+
+/// Named layers an entity is in and the layers it collides with, turned
+/// into ncollide `CollisionGroups` when its collider is made.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollisionLayers {
+    pub member_of: Vec<String>,
+    /// `None` collides with every layer
+    pub collides_with: Option<Vec<String>>,
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self {
+            member_of: vec![String::from(DEFAULT_LAYER)],
+            collides_with: None,
+        }
+    }
+}
+
+impl CollisionLayers {
+    #[allow(unused)]
+    pub fn new(layers: &[&str]) -> Self {
+        Self {
+            member_of: layers.iter().map(|layer| String::from(*layer)).collect(),
+            collides_with: None,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn with_collides_with(mut self, layers: &[&str]) -> Self {
+        self.collides_with = Some(layers.iter().map(|layer| String::from(*layer)).collect());
+        self
+    }
+
+    pub fn is_member(&self, layer: &str) -> bool {
+        self.member_of.iter().any(|member| member == layer)
+    }
+
+    pub fn collides_with(&self, layer: &str) -> bool {
+        match &self.collides_with {
+            Some(layers) => layers.iter().any(|other| other == layer),
+            None => true,
+        }
+    }
+
+    pub fn set_member(&mut self, layer: &str, member: bool) {
+        self.member_of.retain(|other| other != layer);
+        if member {
+            self.member_of.push(String::from(layer));
+        }
+    }
+
+    /// `all` are the known layers, used when this collided with every
+    /// layer so far.
+    pub fn set_collides_with(&mut self, layer: &str, collides: bool, all: &[String]) {
+        let layers = self.collides_with.get_or_insert_with(|| all.to_vec());
+        layers.retain(|other| other != layer);
+        if collides {
+            layers.push(String::from(layer));
+        }
+    }
+}
+
+/// Maps layer names to collision group indices in the order they are
+/// first used.
+#[derive(Debug, Clone)]
+pub struct LayerNames {
+    names: Vec<String>,
+}
+
+impl LayerNames {
+    pub fn new() -> Self {
+        Self {
+            names: vec![String::from(DEFAULT_LAYER)],
+        }
+    }
+
+    /// Index of `name`, registering it if needed. `None` once all
+    /// `MAX_LAYERS` are taken.
+    pub fn index(&mut self, name: &str) -> Option<usize> {
+        if let Some(index) = self.names.iter().position(|other| other == name) {
+            return Some(index);
+        }
+        if self.names.len() == MAX_LAYERS {
+            warn!("No collision layer left for {:?}", name);
+            return None;
+        }
+        self.names.push(String::from(name));
+        Some(self.names.len() - 1)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn groups(&mut self, layers: &CollisionLayers) -> CollisionGroups {
+        let membership = layers
+            .member_of
+            .iter()
+            .filter_map(|layer| self.index(layer))
+            .collect::<Vec<_>>();
+        let groups = CollisionGroups::new().with_membership(&membership);
+
+        match &layers.collides_with {
+            Some(collides_with) => {
+                let whitelist = collides_with
+                    .iter()
+                    .filter_map(|layer| self.index(layer))
+                    .collect::<Vec<_>>();
+                groups.with_whitelist(&whitelist)
+            }
+            None => groups,
+        }
+    }
+}
+
+impl Default for LayerNames {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entities an entity is touching or overlapping, updated after every
+/// fixed step.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contacts {
+    pub touching: Vec<legion::Entity>,
+    /// Contacts that started this frame
+    pub started: Vec<legion::Entity>,
+    /// Contacts that ended this frame
+    pub stopped: Vec<legion::Entity>,
+}
+
+impl Contacts {
+    pub fn begin_frame(&mut self) {
+        self.started.clear();
+        self.stopped.clear();
+    }
+
+    pub fn apply(&mut self, other: legion::Entity, started: bool) {
+        if started {
+            if !self.touching.contains(&other) {
+                self.touching.push(other);
+            }
+            self.started.push(other);
+        } else {
+            self.touching.retain(|entity| *entity != other);
+            self.stopped.push(other);
+        }
+    }
+
+    #[allow(unused)]
+    pub fn is_touching(&self, other: legion::Entity) -> bool {
+        self.touching.contains(&other)
+    }
+}
+
+pub struct Physics {
+    mechanical_world: DefaultMechanicalWorld<f32>,
+    geometrical_world: DefaultGeometricalWorld<f32>,
+    bodies: DefaultBodySet<f32>,
+    colliders: DefaultColliderSet<f32>,
+    joint_constraints: DefaultJointConstraintSet<f32>,
+    force_generators: DefaultForceGeneratorSet<f32>,
+    /// Static colliders like terrain hang off this
+    ground: DefaultBodyHandle,
+    pub layers: LayerNames,
+    shapes: HashMap<ShapeKey, ShapeHandle<f32>>,
+    /// Colliders were added, removed or changed since the last `maintain`
+    dirty: bool,
+}
+
+impl Physics {
+    pub fn new(gravity: Vector3<f32>) -> Self {
+        let mut bodies = DefaultBodySet::new();
+        let ground = bodies.insert(Ground::new());
+
+        Self {
+            mechanical_world: DefaultMechanicalWorld::new(gravity),
+            geometrical_world: DefaultGeometricalWorld::new(),
+            bodies,
+            colliders: DefaultColliderSet::new(),
+            joint_constraints: DefaultJointConstraintSet::new(),
+            force_generators: DefaultForceGeneratorSet::new(),
+            ground,
+            layers: LayerNames::new(),
+            shapes: HashMap::new(),
+            dirty: false,
+        }
+    }
+
+    /// Collider for an entity using `model`, the trigger's own shape wins
+    /// over the collider shape. Model shapes are cached per scale.
+    pub fn model_shape(
+        &mut self,
+        model: &str,
+        geometry: &Geometry,
+        scale: Vector3<f32>,
+        shape: Option<ColliderShape>,
+        trigger: Option<TriggerVolume>,
+    ) -> ShapeHandle<f32> {
+        if let Some(handle) = trigger.and_then(|trigger| trigger.shape_handle(scale)) {
+            return handle;
+        }
+
+        let shape = shape.unwrap_or_default();
+        let key = ShapeKey {
+            model: model.to_string(),
+            shape,
+            scale: [scale.x.to_bits(), scale.y.to_bits(), scale.z.to_bits()],
+        };
+        self.shapes
+            .entry(key)
+            .or_insert_with(|| shape.shape_handle(geometry, scale))
+            .clone()
+    }
+
+    /// Drops the cached shapes of `model`, after it was reloaded.
+    pub fn forget_model_shapes(&mut self, model: &str) {
+        self.shapes.retain(|key, _| key.model != model);
+    }
+
+    #[allow(unused)]
+    pub fn gravity(&self) -> Vector3<f32> {
+        self.mechanical_world.gravity
+    }
+
+    #[allow(unused)]
+    pub fn set_gravity(&mut self, gravity: Vector3<f32>) {
+        self.mechanical_world.gravity = gravity;
+    }
+
+    pub fn set_timestep(&mut self, dt: f32) {
+        self.mechanical_world.set_timestep(dt);
+    }
+
+    /// Adds a body described by `body` with a collider of `shape`, filling
+    /// in the body handle. Sensors only report overlaps.
+    pub fn add(
+        &mut self,
+        entity: legion::Entity,
+        body: &mut RigidBody,
+        isometry: Isometry3<f32>,
+        shape: ShapeHandle<f32>,
+        groups: CollisionGroups,
+        sensor: bool,
+    ) -> Collider {
+        let mut desc = RigidBodyDesc::new()
+            .position(isometry)
+            .status(body.body_type.status())
+            .user_data(entity);
+        if body.is_dynamic() {
+            // Meshes have no volume to derive the inertia from, use the one
+            // of their bounding box
+            let aabb = shape.local_aabb();
+            let extents = aabb.half_extents() * 2.0;
+            let sq = extents.component_mul(&extents);
+            desc = desc.local_inertia(Inertia3::new(
+                body.mass,
+                Vector3::new(sq.y + sq.z, sq.x + sq.z, sq.x + sq.y) * (body.mass / 12.0),
+            ));
+        }
+        let handle = self.bodies.insert(desc.build());
+        body.handle = Some(handle);
+
+        let collider = ColliderDesc::new(shape)
+            .collision_groups(groups)
+            .sensor(sensor)
+            .user_data(entity)
+            .build(BodyPartHandle(handle, 0));
+        self.dirty = true;
+        Collider(self.colliders.insert(collider))
+    }
+
+    /// Adds a collider that never moves, like terrain.
+    pub fn add_static(
+        &mut self,
+        entity: legion::Entity,
+        isometry: Isometry3<f32>,
+        shape: ShapeHandle<f32>,
+    ) -> Collider {
+        let collider = ColliderDesc::new(shape)
+            .position(isometry)
+            .user_data(entity)
+            .build(BodyPartHandle(self.ground, 0));
+        self.dirty = true;
+        Collider(self.colliders.insert(collider))
+    }
+
+    /// Teleports the body of `body`.
+    pub fn set_pose(&mut self, body: &RigidBody, isometry: Isometry3<f32>) {
+        if let Some(body) = body
+            .handle
+            .and_then(|handle| self.bodies.rigid_body_mut(handle))
+        {
+            // Dynamic bodies come back through their transform every step,
+            // only move them when something else did
+            let position = body.position();
+            if (position.translation.vector - isometry.translation.vector).norm() > POSE_EPSILON
+                || position.rotation.angle_to(&isometry.rotation) > POSE_EPSILON
+            {
+                body.set_position(isometry);
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Swaps the shape of `collider`, skipped when it already uses `shape`.
+    pub fn set_shape(&mut self, collider: &Collider, shape: ShapeHandle<f32>) {
+        if let Some(collider) = self.colliders.get_mut(collider.0) {
+            if !std::ptr::eq(collider.shape_handle().as_ref(), shape.as_ref()) {
+                collider.set_shape(shape);
+                self.dirty = true;
+            }
+        }
+    }
+
+    pub fn set_collision_groups(&mut self, collider: &Collider, groups: CollisionGroups) {
+        if let Some(collider) = self.colliders.get_mut(collider.0) {
+            collider.set_collision_groups(groups);
+        }
+    }
+
+    pub fn body_position(&self, body: &RigidBody) -> Option<Isometry3<f32>> {
+        let body = self.bodies.rigid_body(body.handle?)?;
+        Some(*body.position())
+    }
+
+    pub fn apply_force(&mut self, body: &RigidBody, force: Vector3<f32>, force_type: ForceType) {
+        if let Some(body) = body
+            .handle
+            .and_then(|handle| self.bodies.rigid_body_mut(handle))
+        {
+            body.apply_force(0, &Force3::linear(force), force_type, true);
+        }
+    }
+
+    pub fn apply_torque(&mut self, body: &RigidBody, torque: Vector3<f32>, force_type: ForceType) {
+        if let Some(body) = body
+            .handle
+            .and_then(|handle| self.bodies.rigid_body_mut(handle))
+        {
+            body.apply_force(0, &Force3::torque(torque), force_type, true);
+        }
+    }
+
+    pub fn set_velocity(&mut self, body: &RigidBody, linear: Vector3<f32>) {
+        if let Some(body) = body
+            .handle
+            .and_then(|handle| self.bodies.rigid_body_mut(handle))
+        {
+            let angular = body.velocity().angular;
+            body.set_velocity(Velocity3::new(linear, angular));
+        }
+    }
+
+    pub fn remove(&mut self, colliders: &[Collider], bodies: &[DefaultBodyHandle]) {
+        for collider in colliders {
+            self.colliders.remove(collider.0);
+        }
+        for body in bodies {
+            self.bodies.remove(*body);
+        }
+        self.dirty = true;
+    }
+
+    /// Advances the simulation by one timestep.
+    pub fn step(&mut self) {
+        self.mechanical_world.step(
+            &mut self.geometrical_world,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.joint_constraints,
+            &mut self.force_generators,
+        );
+        self.dirty = false;
+    }
+
+    /// Registers added and moved colliders without stepping, so queries
+    /// see them right away.
+    pub fn maintain(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.mechanical_world.maintain(
+            &mut self.geometrical_world,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.joint_constraints,
+        );
+        self.dirty = false;
+    }
+
+    fn entity(&self, collider: DefaultColliderHandle) -> Option<legion::Entity> {
+        self.colliders
+            .get(collider)?
+            .user_data()?
+            .downcast_ref::<legion::Entity>()
+            .copied()
+    }
+
+    /// Contacts and overlaps started and stopped during the last step.
+    pub fn collision_events(&self) -> Vec<CollisionEvent> {
+        let contacts = self
+            .geometrical_world
+            .contact_events()
+            .iter()
+            .filter_map(|event| match *event {
+                ContactEvent::Started(a, b) => Some(CollisionEvent::CollisionStarted(
+                    self.entity(a)?,
+                    self.entity(b)?,
+                )),
+                ContactEvent::Stopped(a, b) => Some(CollisionEvent::CollisionStopped(
+                    self.entity(a)?,
+                    self.entity(b)?,
+                )),
+            });
+        let proximities = self
+            .geometrical_world
+            .proximity_events()
+            .iter()
+            .filter_map(|event| {
+                let a = self.entity(event.collider1)?;
+                let b = self.entity(event.collider2)?;
+                match (event.prev_status, event.new_status) {
+                    (Proximity::Intersecting, Proximity::Intersecting) => None,
+                    (_, Proximity::Intersecting) => Some(CollisionEvent::ProximityStarted(a, b)),
+                    (Proximity::Intersecting, _) => Some(CollisionEvent::ProximityStopped(a, b)),
+                    _ => None,
+                }
+            });
+
+        contacts.chain(proximities).collect()
+    }
+
+    /// Every collider `ray` hits within `max_toi`, nearest first.
+    pub fn raycast_all(
+        &self,
+        ray: &Ray<f32>,
+        max_toi: f32,
+        groups: &CollisionGroups,
+    ) -> Vec<RaycastHit> {
+        let mut hits = self
+            .geometrical_world
+            .interferences_with_ray(&self.colliders, ray, max_toi, groups)
+            .filter_map(|(handle, _, intersection)| {
+                Some(RaycastHit {
+                    entity: self.entity(handle)?,
+                    distance: intersection.toi,
+                    point: ray.point_at(intersection.toi),
+                    normal: intersection.normal,
+                    mesh: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        hits
+    }
+
+    pub fn raycast(&self, ray: &Ray<f32>, max_toi: f32) -> Option<RaycastHit> {
+        self.raycast_all(ray, max_toi, &CollisionGroups::new())
+            .into_iter()
+            .next()
+    }
+
+    /// Sweeps a sphere of `radius` from `origin` along `direction`, the
+    /// hit point is the sphere's center when it touches.
+    pub fn sphere_cast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        radius: f32,
+        max_toi: f32,
+    ) -> Option<RaycastHit> {
+        let start = Isometry3::translation(origin.x, origin.y, origin.z);
+        self.shape_cast(&Ball::new(radius), &start, direction, max_toi, None)
+    }
+
+    /// Sweeps `shape` from `start` along `direction`, ignoring sensors and
+    /// the colliders of `exclude`. The hit point is the shape's origin when
+    /// it touches.
+    pub fn shape_cast(
+        &self,
+        shape: &dyn Shape<f32>,
+        start: &Isometry3<f32>,
+        direction: Vector3<f32>,
+        max_toi: f32,
+        exclude: Option<legion::Entity>,
+    ) -> Option<RaycastHit> {
+        let origin = Point3::from(start.translation.vector);
+
+        self.colliders
+            .iter()
+            .filter(|(_, collider)| !collider.is_sensor())
+            .filter_map(|(handle, collider)| {
+                let entity = self.entity(handle)?;
+                if Some(entity) == exclude {
+                    return None;
+                }
+                let toi = query::time_of_impact(
+                    start,
+                    &direction,
+                    shape,
+                    collider.position(),
+                    &Vector3::zeros(),
+                    collider.shape(),
+                    max_toi,
+                    0.0,
+                )?;
+                Some(RaycastHit {
+                    entity,
+                    distance: toi.toi,
+                    point: origin + direction * toi.toi,
+                    normal: -toi.normal1.into_inner(),
+                    mesh: None,
+                })
+            })
+            .min_by(|a, b| {
+                a.distance
+                    .partial_cmp(&b.distance)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+
+    /// Entities whose collider's bounding box overlaps `aabb`.
+    pub fn aabb_overlap(&self, aabb: &AABB<f32>) -> Vec<legion::Entity> {
+        self.geometrical_world
+            .interferences_with_aabb(&self.colliders, aabb, &CollisionGroups::new())
+            .filter_map(|(handle, _)| self.entity(handle))
+            .collect()
+    }
+}
@@ -0,0 +1,104 @@
+//! Features added to an [`App`] as a unit. A plugin registers whatever it
+//! needs in [`Plugin::build`]: systems, lifecycle hooks, layouts, pipelines
+//! and model formats.
+//!
+//! Physics, audio, networking and the editor windows are switched on by
+//! the plugins below, [`DefaultPlugins`] adds them all. Their state and
+//! systems still live in the world and the app, which run them only once
+//! the plugin turned them on.
+
+use log::error;
+
+use crate::App;
+
+pub trait Plugin {
+    /// Adds the plugin to `app`, once whatever the number of times it is
+    /// added.
+    fn build(&self, app: &mut App);
+
+    /// Tells plugins apart, the type name unless overridden.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// Closures are plugins too, for the ones not worth a type.
+impl<F: Fn(&mut App)> Plugin for F {
+    fn build(&self, app: &mut App) {
+        self(app)
+    }
+}
+
+/// Steps the rigid bodies on every fixed update. Colliders
+/// still answer raycasts and picking without it.
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_setup(|world| world.set_physics_simulated(true));
+    }
+}
+
+/// Opens the audio output, sound sources stay silent without it.
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_setup(|world| world.enable_audio());
+    }
+}
+
+/// Draws the editor windows over the scene.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.enable_editor();
+    }
+}
+
+/// Replicates the world to other instances, or mirrors the world of
+/// another one, over UDP.
+#[derive(Debug, Clone)]
+pub enum NetPlugin {
+    /// Serves on the address
+    Serve(String),
+    /// Connects to the server on the address
+    Connect(String),
+}
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let net = self.clone();
+        app.add_setup(move |world| {
+            let result = match &net {
+                NetPlugin::Serve(addr) => world.serve(addr),
+                NetPlugin::Connect(addr) => world.connect(addr),
+            };
+            if let Err(e) = result {
+                error!("Could not start networking: {:?}", e);
+            }
+        });
+    }
+}
+
+/// Everything the editor binary runs with, networking when `--serve` or
+/// `--connect` asks for it.
+pub struct DefaultPlugins;
+
+impl Plugin for DefaultPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(PhysicsPlugin)
+            .add_plugin(AudioPlugin)
+            .add_plugin(EditorPlugin);
+        let options = app.options();
+        let net = match (&options.serve, &options.connect) {
+            (Some(addr), _) => Some(NetPlugin::Serve(addr.clone())),
+            (None, Some(addr)) => Some(NetPlugin::Connect(addr.clone())),
+            (None, None) => None,
+        };
+        if let Some(net) = net {
+            app.add_plugin(net);
+        }
+    }
+}
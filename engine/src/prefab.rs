@@ -0,0 +1,104 @@
+//! Reusable entity templates. Instances remember their prefab and take its
+//! model and material on load, so scene files pick up changes to the prefab,
+//! while keeping their own transform and components.
+
+use serde::{Deserialize, Serialize};
+
+use crate::scene::{EntityData, TransformData};
+
+/// Marks an entity as an instance of the named prefab.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct PrefabInstance(pub String);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prefab {
+    pub model: String,
+    #[serde(default)]
+    pub material: Option<String>,
+    /// Used by instances that don't override it
+    #[serde(default)]
+    pub transform: TransformData,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+impl Prefab {
+    /// Components of an instance of the prefab `name`.
+    pub fn instance(&self, name: &str, transform: Option<TransformData>) -> EntityData {
+        EntityData {
+            model: self.model.clone(),
+            material: self.material.clone(),
+            transform: transform.unwrap_or(self.transform),
+            prefab: Some(String::from(name)),
+            parent: None,
+            name: None,
+            tag: self.tag.clone(),
+            collider: None,
+            layers: None,
+            script: None,
+            label: None,
+            mirror: None,
+            particles: None,
+            render_layer: None,
+            billboard: None,
+            visible: None,
+        }
+    }
+
+    /// Components of the saved instance `data` of the prefab `name`, the
+    /// prefab supplying the model, material and whatever `data` left unset.
+    pub fn merge(&self, name: &str, data: &EntityData) -> EntityData {
+        EntityData {
+            model: self.model.clone(),
+            material: self.material.clone(),
+            prefab: Some(String::from(name)),
+            tag: data.tag.clone().or_else(|| self.tag.clone()),
+            ..data.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefab() -> Prefab {
+        Prefab {
+            model: String::from("crate.obj"),
+            material: Some(String::from("wood")),
+            transform: TransformData::default(),
+            tag: Some(String::from("prop")),
+        }
+    }
+
+    #[test]
+    fn merge_keeps_instance_components() {
+        let mut saved = prefab().instance("crate", None);
+        saved.model = String::from("old.obj");
+        saved.transform.translation = [1.0, 2.0, 3.0];
+        saved.name = Some(String::from("Crate 2"));
+        saved.script = Some(String::from("crate.rhai"));
+        saved.visible = Some(false);
+        saved.tag = Some(String::from("loot"));
+
+        let merged = prefab().merge("crate", &saved);
+        assert_eq!(merged.model, "crate.obj");
+        assert_eq!(merged.transform.translation, [1.0, 2.0, 3.0]);
+        assert_eq!(merged.name.as_deref(), Some("Crate 2"));
+        assert_eq!(merged.script.as_deref(), Some("crate.rhai"));
+        assert_eq!(merged.visible, Some(false));
+        assert_eq!(merged.tag.as_deref(), Some("loot"));
+    }
+
+    #[test]
+    fn merge_falls_back_to_prefab() {
+        let mut saved = prefab().instance("crate", None);
+        saved.material = None;
+        saved.tag = None;
+
+        let merged = prefab().merge("crate", &saved);
+        assert_eq!(merged.material.as_deref(), Some("wood"));
+        assert_eq!(merged.tag.as_deref(), Some("prop"));
+        assert_eq!(merged.prefab.as_deref(), Some("crate"));
+    }
+}
@@ -0,0 +1,407 @@
+//! Camera facing quads drawn in place of distant entities, keeping large
+//! scenes cheap. A billboard shows a texture asset, or an impostor: a
+//! picture of the entity's model rendered offscreen the first time it is
+//! needed. The world decides which entities are far enough once a frame,
+//! every view then draws the same billboards facing its own camera.
+
+use std::{collections::HashMap, ops::Range};
+
+use anyhow::*;
+use log::{error, info};
+use nalgebra::{Point3, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    binding::{self, BufferGroup, TextureBinding},
+    renderpass, state,
+    texture::Texture,
+    traits::{Binding, DrawBillboards, DrawModel, Vertex},
+    Layouts, Pipelines,
+};
+use crate::{
+    assets,
+    camera::{self, projection::Projection},
+    inspect::Inspect,
+    transform,
+};
+
+/// Width and height of an impostor in pixels
+pub const IMPOSTOR_SIZE: u32 = 128;
+/// New impostors captured per frame, so a scene full of models doesn't
+/// stall
+const IMPOSTORS_PER_FRAME: usize = 4;
+/// Narrow, so impostors show their model almost without perspective
+const IMPOSTOR_FOVY: f32 = 0.2;
+
+/// Draws the entity as a billboard once the camera is `distance` away.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Inspect)]
+pub struct Billboard {
+    /// Resource path of the texture shown, empty for an impostor of the
+    /// entity's model
+    #[serde(default)]
+    pub texture: String,
+    /// The texture at `texture` once the world has loaded it
+    #[serde(skip)]
+    #[inspect(skip)]
+    pub handle: Option<assets::Handle<Texture>>,
+    /// Distance from the camera past which the billboard replaces the
+    /// model, 0 to always show it
+    #[serde(default = "Billboard::default_distance")]
+    #[inspect_slider(min_value = 0.0, max_value = 1000.0)]
+    pub distance: f32,
+    /// Width and height in world units, 0 to fit the entity
+    #[serde(default)]
+    pub size: f32,
+}
+
+impl Billboard {
+    fn default_distance() -> f32 {
+        50.0
+    }
+}
+
+impl Default for Billboard {
+    fn default() -> Self {
+        Self {
+            texture: String::new(),
+            handle: None,
+            distance: Billboard::default_distance(),
+            size: 0.0,
+        }
+    }
+}
+
+/// What a billboard shows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BillboardTexture {
+    /// A texture asset
+    Asset(assets::Handle<Texture>),
+    /// The impostor of a model
+    Impostor(String),
+}
+
+/// A billboard drawn this frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BillboardInstance {
+    pub center: Point3<f32>,
+    /// World units
+    pub size: f32,
+    pub texture: BillboardTexture,
+}
+
+/// Projection an impostor of a model with a bounding sphere of `radius` is
+/// captured with, and the eye's distance from the sphere's center.
+fn impostor_projection(radius: f32) -> (Projection, f32) {
+    let mut projection = Projection::new(IMPOSTOR_SIZE, IMPOSTOR_SIZE, IMPOSTOR_FOVY, 1.0, 2.0);
+    let distance = projection.fit(radius);
+    let projection = Projection::new(
+        IMPOSTOR_SIZE,
+        IMPOSTOR_SIZE,
+        IMPOSTOR_FOVY,
+        (distance - radius * 2.0).max(0.01),
+        distance + radius * 2.0,
+    );
+    (projection, distance)
+}
+
+/// World size the impostor of a model with a bounding sphere of `radius`
+/// covers, the sphere and the room left around it.
+pub fn impostor_size(radius: f32) -> f32 {
+    let (projection, distance) = impostor_projection(radius);
+    radius * 2.0 / projection.screen_size(distance, radius)
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct BillboardVertex {
+    center: Vector3<f32>,
+    /// Offset from the center along the camera's right and up
+    corner: Vector2<f32>,
+    tex_coords: Vector2<f32>,
+}
+
+unsafe impl bytemuck::Pod for BillboardVertex {}
+unsafe impl bytemuck::Zeroable for BillboardVertex {}
+
+impl Vertex for BillboardVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<BillboardVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float3,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float2,
+                },
+            ],
+        }
+    }
+}
+
+/// A model rendered once, from the front, on a transparent background.
+struct Impostor {
+    binding: TextureBinding,
+    _texture: Texture,
+    /// Kept with the texture, the capture may not have run yet
+    _depth_texture: Texture,
+    _uniforms: binding::Buffer,
+    _uniform_group: BufferGroup,
+}
+
+enum BatchTexture {
+    /// Id of a texture asset
+    Asset(u64),
+    Impostor(String),
+}
+
+/// Billboards of one frame, sorted into draws of one texture each, and the
+/// impostors captured so far. Upload them once before the passes that draw
+/// them.
+pub struct BillboardRenderer {
+    impostors: HashMap<String, Impostor>,
+    /// Identity transform the captured models are drawn with
+    transform: transform::Transform,
+    buffer: Option<binding::Buffer>,
+    /// Vertices the buffer can hold
+    capacity: usize,
+    batches: Vec<(BatchTexture, Range<u32>)>,
+    /// Bindings of the texture assets drawn last frame by texture id
+    bindings: HashMap<u64, TextureBinding>,
+}
+
+impl BillboardRenderer {
+    pub fn new(state: &state::WgpuState) -> Self {
+        Self {
+            impostors: HashMap::new(),
+            transform: transform::Transform::new(state, "impostor_transform"),
+            buffer: None,
+            capacity: 0,
+            batches: Vec::new(),
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Captures the impostors `billboards` are missing, a few per frame,
+    /// and writes the billboards to the GPU. Impostors of unloaded models
+    /// are dropped, billboards whose texture isn't there yet are left out.
+    /// `uniforms` builds the camera uniforms of a capture.
+    pub fn upload<A: bytemuck::Pod>(
+        &mut self,
+        state: &state::WgpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &Pipelines,
+        layouts: &Layouts,
+        assets: &assets::AssetManager,
+        light: &BufferGroup,
+        uniforms: impl Fn(&camera::Camera) -> A,
+        billboards: &mut [BillboardInstance],
+    ) {
+        self.impostors
+            .retain(|name, _| assets.models.contains(name.as_str()));
+        let mut missing = billboards
+            .iter()
+            .filter_map(|billboard| match &billboard.texture {
+                BillboardTexture::Impostor(model) if !self.impostors.contains_key(model) => {
+                    Some(model.clone())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        for model in missing.into_iter().take(IMPOSTORS_PER_FRAME) {
+            if let Err(e) = self.capture(
+                state, encoder, pipelines, layouts, assets, light, &uniforms, &model,
+            ) {
+                error!("Could not capture impostor of {:?}: {:?}", model, e);
+            }
+        }
+
+        billboards.sort_by(|a, b| a.texture.cmp(&b.texture));
+        self.batches.clear();
+        let mut vertices = Vec::new();
+        let mut current: Option<&BillboardTexture> = None;
+        for billboard in billboards.iter() {
+            if current != Some(&billboard.texture) {
+                let texture = match &billboard.texture {
+                    BillboardTexture::Asset(handle) => match assets.textures.get(handle) {
+                        Some(texture) => {
+                            self.bindings.entry(texture.id()).or_insert_with(|| {
+                                let name = assets.textures.name(handle).unwrap_or("billboard");
+                                TextureBinding::new_ref(state, name, &layouts.texture, &[texture])
+                            });
+                            BatchTexture::Asset(texture.id())
+                        }
+                        None => continue,
+                    },
+                    BillboardTexture::Impostor(model) if self.impostors.contains_key(model) => {
+                        BatchTexture::Impostor(model.clone())
+                    }
+                    BillboardTexture::Impostor(_) => continue,
+                };
+                current = Some(&billboard.texture);
+                let start = vertices.len() as u32;
+                self.batches.push((texture, start..start));
+            }
+
+            let half = billboard.size / 2.0;
+            let center = billboard.center.coords;
+            let corner = |x: f32, y: f32, u: f32, v: f32| BillboardVertex {
+                center,
+                corner: Vector2::new(x, y),
+                tex_coords: Vector2::new(u, v),
+            };
+            let top_left = corner(-half, half, 0.0, 0.0);
+            let top_right = corner(half, half, 1.0, 0.0);
+            let bottom_left = corner(-half, -half, 0.0, 1.0);
+            let bottom_right = corner(half, -half, 1.0, 1.0);
+            vertices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                bottom_right,
+                top_left,
+                bottom_right,
+                top_right,
+            ]);
+            if let Some((_, range)) = self.batches.last_mut() {
+                range.end = vertices.len() as u32;
+            }
+        }
+
+        let batches = &self.batches;
+        self.bindings.retain(|id, _| {
+            batches
+                .iter()
+                .any(|(batch, _)| matches!(batch, BatchTexture::Asset(batch) if batch == id))
+        });
+
+        if vertices.is_empty() {
+            return;
+        }
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            vertices.resize(self.capacity, vertices[0]);
+            self.buffer = Some(binding::Buffer::new_init(
+                state,
+                "billboards",
+                &vertices,
+                binding::BufferUsage::DynamicVertex,
+            ));
+        } else if let Some(buffer) = &self.buffer {
+            buffer.write(state, &vertices);
+        }
+    }
+
+    /// Renders `model` from the front, framed on its bounding sphere, to
+    /// the texture of a new impostor.
+    fn capture<A: bytemuck::Pod>(
+        &mut self,
+        state: &state::WgpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &Pipelines,
+        layouts: &Layouts,
+        assets: &assets::AssetManager,
+        light: &BufferGroup,
+        uniforms: &impl Fn(&camera::Camera) -> A,
+        model: &str,
+    ) -> Result<()> {
+        let model_asset = assets
+            .models
+            .get_by_name(model)
+            .context("Model is not loaded")?;
+        let sphere = model_asset.geometry.bounding_sphere();
+        let center = *sphere.center();
+        let (projection, distance) = impostor_projection(sphere.radius());
+        let camera = camera::Camera::new(center + Vector3::z() * distance, center, projection);
+
+        let label = format!("impostor {}", model);
+        let texture =
+            Texture::render_target(state, &label, IMPOSTOR_SIZE, IMPOSTOR_SIZE, state.format());
+        let depth_texture =
+            Texture::create_depth_texture_sized(state, &label, IMPOSTOR_SIZE, IMPOSTOR_SIZE);
+        let uniform_buffer = binding::Buffer::new_init(
+            state,
+            label.as_str(),
+            &[uniforms(&camera)],
+            binding::BufferUsage::Uniform,
+        );
+        let uniform_group =
+            BufferGroup::from_buffer(state, label.as_str(), &layouts.uniforms, &[&uniform_buffer]);
+        info!("Capture impostor of {:?}", model);
+
+        {
+            // Left transparent around the model, billboards drop those
+            // pixels
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] = &[&(
+                &texture.view,
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.0,
+                    g: 0.0,
+                    b: 0.0,
+                    a: 0.0,
+                }),
+            )];
+            let depth_attachment: &dyn renderpass::IntoDepthAttachment =
+                &(&depth_texture.view, wgpu::LoadOp::Clear(1.0));
+
+            let mut render_pass =
+                renderpass::render_pass(encoder, color_attachments, depth_attachment);
+            render_pass.set_pipeline(pipelines.forward.get(model_asset.geometry.index_format()));
+            render_pass.bind_buffer(1, self.transform.buffer(state));
+            render_pass.draw_model_lod(model_asset, 0, None, &uniform_group, light);
+        }
+
+        let binding = TextureBinding::new_ref(state, label.as_str(), &layouts.texture, &[&texture]);
+        self.impostors.insert(
+            model.to_string(),
+            Impostor {
+                binding,
+                _texture: texture,
+                _depth_texture: depth_texture,
+                _uniforms: uniform_buffer,
+                _uniform_group: uniform_group,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl<'a, 'b> DrawBillboards<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_billboards(&mut self, billboards: &'b BillboardRenderer, uniforms: &'b BufferGroup) {
+        let buffer = match &billboards.buffer {
+            Some(buffer) if !billboards.batches.is_empty() => buffer,
+            _ => return,
+        };
+        self.bind_vertex_buffer(0, buffer);
+        self.bind_group(0, uniforms);
+        for (texture, range) in billboards.batches.iter() {
+            let binding = match texture {
+                BatchTexture::Asset(texture) => match billboards.bindings.get(texture) {
+                    Some(binding) => binding,
+                    None => continue,
+                },
+                BatchTexture::Impostor(model) => match billboards.impostors.get(model) {
+                    Some(impostor) => &impostor.binding,
+                    None => continue,
+                },
+            };
+            self.bind_textures(1, binding);
+            self.draw(range.clone(), 0..1);
+        }
+    }
+}
@@ -1,7 +1,17 @@
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
 use log::info;
 use wgpu::util::DeviceExt;
 
 use super::{state, texture, traits::Binding};
+use crate::stats;
 
 #[derive(Debug)]
 pub enum BufferUsage {
@@ -9,6 +19,13 @@ pub enum BufferUsage {
     Uniform,
     Index,
     Transform,
+    Storage,
+    /// Vertices rewritten every frame
+    DynamicVertex,
+    /// Draw arguments written by compute shaders
+    Indirect,
+    /// Vertices written by compute shaders
+    ComputedVertex,
 }
 
 impl From<BufferUsage> for wgpu::BufferUsage {
@@ -18,16 +35,105 @@ impl From<BufferUsage> for wgpu::BufferUsage {
             BufferUsage::Uniform => wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
             BufferUsage::Index => wgpu::BufferUsage::INDEX,
             BufferUsage::Transform => wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            BufferUsage::Storage => wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            BufferUsage::DynamicVertex => wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            BufferUsage::Indirect => {
+                wgpu::BufferUsage::INDIRECT
+                    | wgpu::BufferUsage::STORAGE
+                    | wgpu::BufferUsage::COPY_DST
+            }
+            BufferUsage::ComputedVertex => {
+                wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST
+            }
+        }
+    }
+}
+
+/// Identifies a buffer, texture or layout in the bind group cache, never
+/// reused.
+pub(crate) fn next_resource_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A bind group layout along with the id the bind group cache tells it
+/// apart by.
+#[derive(Debug)]
+pub struct BindGroupLayout {
+    layout: wgpu::BindGroupLayout,
+    id: u64,
+}
+
+impl BindGroupLayout {
+    pub fn new(layout: wgpu::BindGroupLayout) -> Self {
+        Self {
+            layout,
+            id: next_resource_id(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Deref for BindGroupLayout {
+    type Target = wgpu::BindGroupLayout;
+
+    fn deref(&self) -> &Self::Target {
+        &self.layout
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct BindGroupKey {
+    layout: u64,
+    /// Ids of the resources in binding order
+    resources: Vec<u64>,
+}
+
+/// Bind groups by layout and resources, so binding the same resources
+/// again reuses the group instead of creating another. Groups are held
+/// weakly and go away with the last binding using them.
+#[derive(Default)]
+pub struct BindGroupCache {
+    groups: Mutex<HashMap<BindGroupKey, Weak<wgpu::BindGroup>>>,
+}
+
+impl BindGroupCache {
+    fn get_or_create(
+        &self,
+        layout: &BindGroupLayout,
+        resources: Vec<u64>,
+        create: impl FnOnce() -> wgpu::BindGroup,
+    ) -> Arc<wgpu::BindGroup> {
+        let key = BindGroupKey {
+            layout: layout.id(),
+            resources,
+        };
+        let mut groups = self
+            .groups
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(group) = groups.get(&key).and_then(Weak::upgrade) {
+            return group;
         }
+        groups.retain(|_, group| group.strong_count() > 0);
+        let group = Arc::new(create());
+        groups.insert(key, Arc::downgrade(&group));
+        group
     }
 }
 
 pub struct Buffer {
     pub buffer: wgpu::Buffer,
+    /// Bytes counted towards `stats::BUFFER_BYTES`
+    size: u64,
+    id: u64,
 }
 
 impl Buffer {
-    pub fn new_init<A: bytemuck::Pod, L: Into<Option<&'a str>>>(
+    pub fn new_init<'a, A: bytemuck::Pod, L: Into<Option<&'a str>>>(
         state: &state::WgpuState,
         label: L,
         data: &[A],
@@ -35,135 +141,165 @@ impl Buffer {
     ) -> Self {
         let label = label.into();
         info!("Init {:?} buffer {:?}", &usage, &label.unwrap_or(""));
+        let contents: &[u8] = bytemuck::cast_slice(&data);
+        let size = contents.len() as u64;
+        stats::track_bytes(&stats::BUFFER_BYTES, size);
         Self {
             buffer: state
                 .device()
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: label,
                     usage: usage.into(),
-                    contents: bytemuck::cast_slice(&data),
+                    contents,
                 }),
+            size,
+            id: next_resource_id(),
         }
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     pub fn write<A: bytemuck::Pod>(&self, state: &state::WgpuState, data: &[A]) {
         state.write_buffer(&self.buffer, data);
     }
 }
 
-impl From<&&'a Buffer> for wgpu::BindingResource<'a> {
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        stats::untrack_bytes(&stats::BUFFER_BYTES, self.size);
+    }
+}
+
+impl<'a> From<&&'a Buffer> for wgpu::BindingResource<'a> {
     fn from(buf: &&'a Buffer) -> Self {
         wgpu::BindingResource::Buffer(buf.buffer.slice(..))
     }
 }
 
+/// Buffers bound together, shared with every group binding the same
+/// buffers to the same layout.
 pub struct BufferGroup {
-    pub bind_group: wgpu::BindGroup,
+    pub bind_group: Arc<wgpu::BindGroup>,
     pub label: String,
 }
 
 impl BufferGroup {
-    pub fn from_buffer<T: Into<Option<&'a str>>>(
+    pub fn from_buffer<'a, T: Into<Option<&'a str>>>(
         state: &state::WgpuState,
         label: T,
-        layout: &wgpu::BindGroupLayout,
+        layout: &BindGroupLayout,
         group: &[&Buffer],
     ) -> Self {
         let label = label.into();
-        info!("Create buffer group {:?}", &label.unwrap_or(""));
+        let resources = group.iter().map(|buf| buf.id).collect();
         Self {
-            bind_group: state
-                .device()
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: label,
-                    layout,
-                    entries: group
-                        .iter()
-                        .enumerate()
-                        .map(|(i, buf)| wgpu::BindGroupEntry {
-                            binding: i as u32,
-                            resource: buf.into(),
+            bind_group: state.bind_groups().get_or_create(layout, resources, || {
+                info!("Create buffer group {:?}", &label.unwrap_or(""));
+                state.errors().scope("bind group", label, || {
+                    state
+                        .device()
+                        .create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: label,
+                            layout,
+                            entries: group
+                                .iter()
+                                .enumerate()
+                                .map(|(i, buf)| wgpu::BindGroupEntry {
+                                    binding: i as u32,
+                                    resource: buf.into(),
+                                })
+                                .collect::<Vec<_>>()
+                                .as_slice(),
                         })
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                }),
+                })
+            }),
             label: String::from(label.unwrap_or("")),
         }
     }
 }
 
+/// Textures bound together, shared like a `BufferGroup`.
 pub struct TextureBinding {
-    pub bind_group: wgpu::BindGroup,
+    pub bind_group: Arc<wgpu::BindGroup>,
     pub label: String,
 }
 
 impl TextureBinding {
-    pub fn new<T: Into<Option<&'a str>>>(
+    pub fn new<'a, T: Into<Option<&'a str>>>(
         state: &state::WgpuState,
         label: T,
-        layout: &wgpu::BindGroupLayout,
+        layout: &BindGroupLayout,
         textures: &[texture::Texture],
     ) -> Self {
-        let label = label.into();
-        Self {
-            bind_group: state
-                .device()
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label,
-                    layout,
-                    entries: textures
-                        .iter()
-                        .enumerate()
-                        .flat_map(|(i, tex)| {
-                            vec![
-                                wgpu::BindGroupEntry {
-                                    binding: (i * 2) as u32,
-                                    resource: wgpu::BindingResource::TextureView(&tex.view),
-                                },
-                                wgpu::BindGroupEntry {
-                                    binding: (i * 2 + 1) as u32,
-                                    resource: wgpu::BindingResource::Sampler(&tex.sampler),
-                                },
-                            ]
-                        })
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                }),
-            label: String::from(label.unwrap_or("")),
-        }
+        Self::new_ref(
+            state,
+            label,
+            layout,
+            textures.iter().collect::<Vec<_>>().as_slice(),
+        )
+    }
+
+    pub fn new_ref<'a, T: Into<Option<&'a str>>>(
+        state: &state::WgpuState,
+        label: T,
+        layout: &BindGroupLayout,
+        textures: &[&texture::Texture],
+    ) -> Self {
+        Self::with_buffers(state, label, layout, textures, &[])
     }
 
-    pub fn new_ref<T: Into<Option<&'a str>>>(
+    /// Texture and sampler pairs first, followed by `buffers` in the
+    /// bindings after them.
+    pub fn with_buffers<'a, T: Into<Option<&'a str>>>(
         state: &state::WgpuState,
         label: T,
-        layout: &wgpu::BindGroupLayout,
+        layout: &BindGroupLayout,
         textures: &[&texture::Texture],
+        buffers: &[&Buffer],
     ) -> Self {
         let label: Option<&str> = label.into();
+        let texture_bindings = (textures.len() * 2) as u32;
+        let resources = textures
+            .iter()
+            .map(|tex| tex.id())
+            .chain(buffers.iter().map(|buf| buf.id))
+            .collect();
         Self {
-            bind_group: state
-                .device()
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label,
-                    layout,
-                    entries: textures
-                        .iter()
-                        .enumerate()
-                        .flat_map(|(i, tex)| {
-                            vec![
-                                wgpu::BindGroupEntry {
-                                    binding: (i * 2) as u32,
-                                    resource: wgpu::BindingResource::TextureView(&tex.view),
-                                },
-                                wgpu::BindGroupEntry {
-                                    binding: (i * 2 + 1) as u32,
-                                    resource: wgpu::BindingResource::Sampler(&tex.sampler),
-                                },
-                            ]
+            bind_group: state.bind_groups().get_or_create(layout, resources, || {
+                state.errors().scope("bind group", label, || {
+                    state
+                        .device()
+                        .create_bind_group(&wgpu::BindGroupDescriptor {
+                            label,
+                            layout,
+                            entries: textures
+                                .iter()
+                                .enumerate()
+                                .flat_map(|(i, tex)| {
+                                    vec![
+                                        wgpu::BindGroupEntry {
+                                            binding: (i * 2) as u32,
+                                            resource: wgpu::BindingResource::TextureView(&tex.view),
+                                        },
+                                        wgpu::BindGroupEntry {
+                                            binding: (i * 2 + 1) as u32,
+                                            resource: wgpu::BindingResource::Sampler(&tex.sampler),
+                                        },
+                                    ]
+                                })
+                                .chain(buffers.iter().enumerate().map(|(i, buf)| {
+                                    wgpu::BindGroupEntry {
+                                        binding: texture_bindings + i as u32,
+                                        resource: buf.into(),
+                                    }
+                                }))
+                                .collect::<Vec<_>>()
+                                .as_slice(),
                         })
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                }),
+                })
+            }),
             label: String::from(label.unwrap_or("")),
         }
     }
@@ -193,3 +329,38 @@ where
         self.set_index_buffer(buffer.buffer.slice(..));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn key(layout: u64, resources: &[u64]) -> BindGroupKey {
+        BindGroupKey {
+            layout,
+            resources: resources.to_vec(),
+        }
+    }
+
+    #[test]
+    fn resource_ids_are_never_reused() {
+        let ids = (0..100).map(|_| next_resource_id()).collect::<Vec<_>>();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn keys_match_on_layout_and_resources() {
+        assert_eq!(key(1, &[2, 3]), key(1, &[2, 3]));
+        assert_ne!(key(1, &[2, 3]), key(4, &[2, 3]));
+        assert_ne!(key(1, &[2, 3]), key(1, &[2]));
+    }
+
+    #[test]
+    fn keys_tell_the_binding_order_apart() {
+        let keys = vec![key(1, &[2, 3]), key(1, &[3, 2]), key(1, &[2, 3])]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(keys.len(), 2);
+    }
+}
@@ -0,0 +1,316 @@
+use std::{convert::TryInto, path::Path};
+
+use anyhow::*;
+use log::info;
+
+/// Texture data already in a GPU block format, with every mip level stored
+/// in the file.
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Mip levels from largest to smallest
+    pub levels: Vec<Vec<u8>>,
+}
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const KTX2_IDENTIFIER: &[u8] = &[
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+impl CompressedImage {
+    pub fn is_compressed_path<P: AsRef<Path>>(path: P) -> bool {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .as_deref()
+        {
+            Some("dds") | Some("ktx2") => true,
+            _ => false,
+        }
+    }
+
+    /// Loads a DDS or KTX2 container, `srgb` picks the color space for
+    /// legacy DDS files which don't record one.
+    pub fn load<P: AsRef<Path>>(path: P, srgb: bool) -> Result<Self> {
+        info!("Load compressed texture {:?}", path.as_ref());
+        let bytes = std::fs::read(path.as_ref())
+            .context(format!("Could not read texture {:?}", path.as_ref()))?;
+        Self::from_bytes(&bytes, srgb).context(format!("Invalid texture {:?}", path.as_ref()))
+    }
+
+    pub fn from_bytes(bytes: &[u8], srgb: bool) -> Result<Self> {
+        if bytes.starts_with(KTX2_IDENTIFIER) {
+            parse_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            parse_dds(bytes, srgb)
+        } else {
+            bail!("Unknown texture container")
+        }
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    pub fn is_block_compressed(&self) -> bool {
+        block_size(self.format).0 > 1
+    }
+
+    /// Block dimension in texels and size of one block in bytes.
+    pub fn block_size(&self) -> (u32, u32) {
+        block_size(self.format)
+    }
+}
+
+fn block_size(format: wgpu::TextureFormat) -> (u32, u32) {
+    use wgpu::TextureFormat::*;
+
+    match format {
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb | Bc4RUnorm | Bc4RSnorm => (4, 8),
+        Bc2RgbaUnorm | Bc2RgbaUnormSrgb | Bc3RgbaUnorm | Bc3RgbaUnormSrgb | Bc5RgUnorm
+        | Bc5RgSnorm | Bc6hRgbUfloat | Bc6hRgbSfloat | Bc7RgbaUnorm | Bc7RgbaUnormSrgb => (4, 16),
+        _ => (1, 4),
+    }
+}
+
+/// Mip levels down to 1x1 of a `width` by `height` image, the most a file
+/// may store.
+fn max_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Byte size of mip `level` of a `width` by `height` image.
+fn level_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let (block, bytes) = block_size(format);
+    let blocks_x = ((width >> level).max(1) + block - 1) / block;
+    let blocks_y = ((height >> level).max(1) + block - 1) / block;
+    (blocks_x * blocks_y * bytes) as usize
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    let slice = bytes
+        .get(offset..offset + 4)
+        .context("Unexpected end of texture data")?;
+    Ok(u32::from_le_bytes(slice.try_into()?))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64> {
+    let slice = bytes
+        .get(offset..offset + 8)
+        .context("Unexpected end of texture data")?;
+    Ok(u64::from_le_bytes(slice.try_into()?))
+}
+
+fn parse_dds(bytes: &[u8], srgb: bool) -> Result<CompressedImage> {
+    use wgpu::TextureFormat::*;
+
+    const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+    const DDPF_FOURCC: u32 = 0x4;
+
+    ensure!(bytes.starts_with(DDS_MAGIC), "Not a DDS file");
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    // Without the flag the count is left over from whatever wrote the file
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28)?.max(1).min(max_levels(width, height))
+    } else {
+        1
+    };
+    let pixel_flags = read_u32(bytes, 80)?;
+    let four_cc = bytes.get(84..88).context("Truncated DDS header")?;
+
+    ensure!(
+        pixel_flags & DDPF_FOURCC != 0,
+        "Only block compressed DDS files are supported"
+    );
+
+    let (format, mut offset) = match four_cc {
+        b"DXT1" => (if srgb { Bc1RgbaUnormSrgb } else { Bc1RgbaUnorm }, 128),
+        b"DXT3" => (if srgb { Bc2RgbaUnormSrgb } else { Bc2RgbaUnorm }, 128),
+        b"DXT5" => (if srgb { Bc3RgbaUnormSrgb } else { Bc3RgbaUnorm }, 128),
+        b"ATI1" | b"BC4U" => (Bc4RUnorm, 128),
+        b"BC4S" => (Bc4RSnorm, 128),
+        b"ATI2" | b"BC5U" => (Bc5RgUnorm, 128),
+        b"BC5S" => (Bc5RgSnorm, 128),
+        b"DX10" => {
+            let format = match read_u32(bytes, 128)? {
+                71 => Bc1RgbaUnorm,
+                72 => Bc1RgbaUnormSrgb,
+                74 => Bc2RgbaUnorm,
+                75 => Bc2RgbaUnormSrgb,
+                77 => Bc3RgbaUnorm,
+                78 => Bc3RgbaUnormSrgb,
+                80 => Bc4RUnorm,
+                81 => Bc4RSnorm,
+                83 => Bc5RgUnorm,
+                84 => Bc5RgSnorm,
+                95 => Bc6hRgbUfloat,
+                96 => Bc6hRgbSfloat,
+                98 => Bc7RgbaUnorm,
+                99 => Bc7RgbaUnormSrgb,
+                dxgi => bail!("Unsupported DXGI format {}", dxgi),
+            };
+            ensure!(
+                read_u32(bytes, 140)? <= 1,
+                "DDS texture arrays are not supported"
+            );
+            (format, 148)
+        }
+        other => bail!(
+            "Unsupported DDS format {:?}",
+            String::from_utf8_lossy(other)
+        ),
+    };
+
+    let mut levels = Vec::new();
+    for level in 0..mip_count {
+        let size = level_size(format, width, height, level);
+        let data = bytes
+            .get(offset..offset + size)
+            .context("DDS mip chain is truncated")?;
+        levels.push(data.to_vec());
+        offset += size;
+    }
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage> {
+    use wgpu::TextureFormat::*;
+
+    let format = match read_u32(bytes, 12)? {
+        37 => Rgba8Unorm,
+        43 => Rgba8UnormSrgb,
+        131 | 133 => Bc1RgbaUnorm,
+        132 | 134 => Bc1RgbaUnormSrgb,
+        135 => Bc2RgbaUnorm,
+        136 => Bc2RgbaUnormSrgb,
+        137 => Bc3RgbaUnorm,
+        138 => Bc3RgbaUnormSrgb,
+        139 => Bc4RUnorm,
+        140 => Bc4RSnorm,
+        141 => Bc5RgUnorm,
+        142 => Bc5RgSnorm,
+        143 => Bc6hRgbUfloat,
+        144 => Bc6hRgbSfloat,
+        145 => Bc7RgbaUnorm,
+        146 => Bc7RgbaUnormSrgb,
+        0 => bail!("KTX2 files without a Vulkan format (Basis) are not supported"),
+        vk => bail!("Unsupported KTX2 format {}", vk),
+    };
+
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?.max(1);
+    ensure!(
+        read_u32(bytes, 28)? <= 1,
+        "3D KTX2 textures are not supported"
+    );
+    ensure!(
+        read_u32(bytes, 32)? <= 1 && read_u32(bytes, 36)? == 1,
+        "KTX2 arrays and cubemaps are not supported"
+    );
+    let level_count = read_u32(bytes, 40)?.max(1).min(max_levels(width, height));
+    ensure!(
+        read_u32(bytes, 44)? == 0,
+        "Supercompressed KTX2 files are not supported"
+    );
+
+    // The level index follows the fixed size header
+    let levels = (0..level_count as usize)
+        .map(|level| {
+            let offset = read_u64(bytes, 80 + level * 24)? as usize;
+            let length = read_u64(bytes, 80 + level * 24 + 8)? as usize;
+            ensure!(
+                length == level_size(format, width, height, level as u32),
+                "KTX2 level {} has an unexpected size",
+                level
+            );
+            Ok(bytes
+                .get(offset..offset + length)
+                .context("KTX2 level data is truncated")?
+                .to_vec())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DXT1 file header for a `width` by `height` image, without data.
+    fn dds_header(width: u32, height: u32, flags: u32, mip_count: u32) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        bytes[8..12].copy_from_slice(&flags.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&mip_count.to_le_bytes());
+        bytes[80..84].copy_from_slice(&0x4u32.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DXT1");
+        bytes
+    }
+
+    #[test]
+    fn level_sizes_round_up_to_blocks() {
+        let format = wgpu::TextureFormat::Bc1RgbaUnorm;
+        assert_eq!(level_size(format, 8, 8, 0), 4 * 8);
+        assert_eq!(level_size(format, 8, 8, 1), 8);
+        assert_eq!(level_size(format, 8, 8, 3), 8);
+        assert_eq!(max_levels(8, 8), 4);
+        assert_eq!(max_levels(1, 1), 1);
+        assert_eq!(max_levels(0, 0), 1);
+    }
+
+    #[test]
+    fn parses_dds_mip_chain() {
+        let mut bytes = dds_header(8, 8, 0x20000, 4);
+        bytes.extend(vec![0; 32 + 8 + 8 + 8]);
+        let image = CompressedImage::from_bytes(&bytes, false).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc1RgbaUnorm);
+        assert_eq!(image.mip_level_count(), 4);
+        assert_eq!(image.levels[0].len(), 32);
+    }
+
+    #[test]
+    fn dds_mip_count_needs_its_flag() {
+        let mut bytes = dds_header(8, 8, 0, 4);
+        bytes.extend(vec![0; 32]);
+        let image = CompressedImage::from_bytes(&bytes, true).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc1RgbaUnormSrgb);
+        assert_eq!(image.mip_level_count(), 1);
+    }
+
+    #[test]
+    fn dds_mip_count_is_clamped() {
+        let mut bytes = dds_header(8, 8, 0x20000, 40);
+        bytes.extend(vec![0; 32 + 8 + 8 + 8]);
+        let image = CompressedImage::from_bytes(&bytes, false).unwrap();
+        assert_eq!(image.mip_level_count(), 4);
+    }
+
+    #[test]
+    fn rejects_bad_dds() {
+        let mut bytes = dds_header(8, 8, 0, 1);
+        assert!(CompressedImage::from_bytes(&bytes[..100], false).is_err());
+        bytes[..4].copy_from_slice(b"XXXX");
+        assert!(parse_dds(&bytes, false).is_err());
+        let truncated = dds_header(8, 8, 0, 1);
+        assert!(CompressedImage::from_bytes(&truncated, false).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+use super::{
+    binding::{BufferGroup, TextureBinding},
+    traits::ComputePassExt,
+};
+
+pub fn compute_pass<'a>(encoder: &'a mut wgpu::CommandEncoder) -> wgpu::ComputePass<'a> {
+    encoder.begin_compute_pass()
+}
+
+impl<'a, 'b> ComputePassExt<'a, 'b> for wgpu::ComputePass<'a>
+where
+    'b: 'a,
+{
+    fn bind_textures(&mut self, index: u32, textures: &'b TextureBinding) {
+        self.set_bind_group(index, &textures.bind_group, &[]);
+    }
+
+    fn bind_group(&mut self, index: u32, group: &'b BufferGroup) {
+        self.set_bind_group(index, &group.bind_group, &[]);
+    }
+
+    fn dispatch_pipeline(
+        &mut self,
+        pipeline: &'b wgpu::ComputePipeline,
+        workgroups: (u32, u32, u32),
+    ) {
+        self.set_pipeline(pipeline);
+        self.dispatch(workgroups.0, workgroups.1, workgroups.2);
+    }
+}
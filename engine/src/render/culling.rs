@@ -0,0 +1,140 @@
+//! Frustum culling of indirectly drawn instances on the GPU. A compute
+//! shader tests the bounds of every instance of the indirect batches and
+//! packs the visible ones into the instance buffer, counting them in the
+//! draw arguments. Occlusion against a depth pyramid would go in the same
+//! pass.
+
+use anyhow::*;
+
+use super::{
+    binding::{self, BufferGroup},
+    compute, state,
+    traits::ComputePassExt,
+};
+use crate::{spatial::Frustum, transform::InstanceRaw};
+
+/// Local size of the culling shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// An instance of an indirect batch as the culling shader reads it.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CullInstance {
+    pub instance: InstanceRaw,
+    /// World space bounds, w is 1 when the instance has them and 0 when it
+    /// is always drawn
+    pub center: [f32; 4],
+    pub half_extents: [f32; 4],
+    /// x: index of the batch
+    pub batch: [u32; 4],
+}
+
+unsafe impl bytemuck::Pod for CullInstance {}
+unsafe impl bytemuck::Zeroable for CullInstance {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CullUniforms {
+    planes: [[f32; 4]; 6],
+    instance_count: u32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for CullUniforms {}
+unsafe impl bytemuck::Zeroable for CullUniforms {}
+
+fn storage_entry(binding: u32, readonly: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::StorageBuffer {
+            dynamic: false,
+            min_binding_size: None,
+            readonly,
+        },
+        count: None,
+    }
+}
+
+/// The culling pipeline, shared by every view drawn indirectly.
+pub struct Culling {
+    layout: binding::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl Culling {
+    pub fn new(state: &state::WgpuState) -> Result<Self> {
+        let layout = state.create_layout(
+            "culling",
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1, true),
+                storage_entry(2, true),
+                storage_entry(3, false),
+                storage_entry(4, false),
+            ],
+        );
+        let pipeline_layout = state.create_pipeline_layout("culling", &[&layout])?;
+        let pipeline =
+            state.create_compute_pipeline(&pipeline_layout, "culling", "cull.comp.spv")?;
+        Ok(Self { layout, pipeline })
+    }
+
+    /// Binds the buffers of one view: its uniforms, the instances to test,
+    /// the batch ranges, the draw arguments and the visible instances.
+    pub fn bind(
+        &self,
+        state: &state::WgpuState,
+        uniforms: &binding::Buffer,
+        instances: &binding::Buffer,
+        batches: &binding::Buffer,
+        arguments: &binding::Buffer,
+        visible: &binding::Buffer,
+    ) -> BufferGroup {
+        BufferGroup::from_buffer(
+            state,
+            "culling",
+            &self.layout,
+            &[uniforms, instances, batches, arguments, visible],
+        )
+    }
+
+    /// Uniforms for culling `instance_count` instances against `frustum`.
+    pub fn uniforms(frustum: &Frustum, instance_count: u32) -> CullUniforms {
+        let mut planes = [[0.0; 4]; 6];
+        for (plane, frustum_plane) in planes.iter_mut().zip(frustum.planes().iter()) {
+            *plane = (*frustum_plane).into();
+        }
+        CullUniforms {
+            planes,
+            instance_count,
+            _padding: [0; 3],
+        }
+    }
+
+    /// Records the culling of `instance_count` instances bound by `group`.
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        group: &BufferGroup,
+        instance_count: u32,
+    ) {
+        if instance_count == 0 {
+            return;
+        }
+        let mut compute_pass = compute::compute_pass(encoder);
+        compute_pass.bind_group(0, group);
+        compute_pass.dispatch_pipeline(
+            &self.pipeline,
+            ((instance_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1),
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use nalgebra::{Point3, Vector3};
+use ncollide3d::query::Ray;
+
+use super::{
+    binding::{self, BufferGroup},
+    state,
+    traits::{Binding, DrawDebugLines, Vertex},
+};
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DebugVertex {
+    position: Vector3<f32>,
+    color: Vector3<f32>,
+}
+
+unsafe impl bytemuck::Pod for DebugVertex {}
+unsafe impl bytemuck::Zeroable for DebugVertex {}
+
+impl Vertex for DebugVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float3,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float3,
+                },
+            ],
+        }
+    }
+}
+
+/// Edges of a box given as its corners, near face first, counter
+/// clockwise from the bottom left.
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 0),
+    (4, 5),
+    (5, 6),
+    (6, 7),
+    (7, 4),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Lines queued during a frame and drawn over the scene. Queue them, then
+/// `upload` once before the passes that draw them.
+pub struct DebugLines {
+    vertices: Vec<DebugVertex>,
+    buffer: Option<binding::Buffer>,
+    /// Vertices the buffer can hold
+    capacity: usize,
+    /// Vertices uploaded for this frame
+    count: u32,
+}
+
+impl DebugLines {
+    pub fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            buffer: None,
+            capacity: 0,
+            count: 0,
+        }
+    }
+
+    pub fn line(&mut self, from: Point3<f32>, to: Point3<f32>, color: Vector3<f32>) {
+        self.vertices.push(DebugVertex {
+            position: from.coords,
+            color,
+        });
+        self.vertices.push(DebugVertex {
+            position: to.coords,
+            color,
+        });
+    }
+
+    pub fn ray(&mut self, ray: &Ray<f32>, length: f32, color: Vector3<f32>) {
+        self.line(ray.origin, ray.point_at(length), color);
+    }
+
+    /// Three axis aligned lines crossing at `point`.
+    pub fn cross(&mut self, point: Point3<f32>, size: f32, color: Vector3<f32>) {
+        let half = size / 2.0;
+        for axis in [Vector3::x(), Vector3::y(), Vector3::z()].iter() {
+            self.line(point - axis * half, point + axis * half, color);
+        }
+    }
+
+    /// Edges of a box or frustum from its eight corners, see `BOX_EDGES`.
+    pub fn corners(&mut self, corners: &[Point3<f32>; 8], color: Vector3<f32>) {
+        for (from, to) in BOX_EDGES.iter() {
+            self.line(corners[*from], corners[*to], color);
+        }
+    }
+
+    /// Writes the queued lines to the GPU and starts queueing the next
+    /// frame's.
+    pub fn upload(&mut self, state: &state::WgpuState) {
+        self.count = self.vertices.len() as u32;
+        if self.vertices.is_empty() {
+            return;
+        }
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            let mut data = self.vertices.clone();
+            data.resize(
+                self.capacity,
+                DebugVertex {
+                    position: Vector3::zeros(),
+                    color: Vector3::zeros(),
+                },
+            );
+            self.buffer = Some(binding::Buffer::new_init(
+                state,
+                "debug_lines",
+                &data,
+                binding::BufferUsage::DynamicVertex,
+            ));
+        } else if let Some(buffer) = &self.buffer {
+            buffer.write(state, &self.vertices);
+        }
+        self.vertices.clear();
+    }
+}
+
+impl<'a, 'b> DrawDebugLines<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_debug_lines(&mut self, lines: &'b DebugLines, uniforms: &'b BufferGroup) {
+        let buffer = match &lines.buffer {
+            Some(buffer) if lines.count > 0 => buffer,
+            _ => return,
+        };
+        self.bind_vertex_buffer(0, buffer);
+        self.bind_group(0, uniforms);
+        self.draw(0..lines.count, 0..1);
+    }
+}
@@ -0,0 +1,380 @@
+//! Textures projected onto the scene inside box volumes. Each decal draws
+//! the inside of its box, finds the scene's surface behind every pixel
+//! from the depth buffer and shows its texture where that surface lies in
+//! the box. Decals sharing a texture are drawn together.
+
+use std::{collections::HashMap, ops::Range};
+
+use nalgebra::{Matrix4, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    binding::{self, BufferGroup, TextureBinding},
+    state,
+    texture::Texture,
+    traits::{Binding, DrawDecals, Vertex},
+};
+use crate::{
+    assets::{Assets, Handle},
+    inspect::Inspect,
+};
+
+/// Projects a texture down the y axis of the entity's transform, onto what
+/// lies in the unit box around it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Inspect)]
+pub struct Decal {
+    /// Resource path of the texture, its x runs along the box's x and its
+    /// y along z
+    pub texture: String,
+    /// The texture at `texture` once the world has loaded it
+    #[serde(skip)]
+    #[inspect(skip)]
+    pub handle: Option<Handle<Texture>>,
+    #[serde(default = "Decal::opaque")]
+    #[inspect_slider(min_value = 0.0, max_value = 1.0)]
+    pub opacity: f32,
+}
+
+impl Decal {
+    fn opaque() -> f32 {
+        1.0
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DecalUniforms {
+    view_proj: Matrix4<f32>,
+    /// Turns window positions and depth back into world positions
+    inverse_view_proj: Matrix4<f32>,
+}
+
+unsafe impl bytemuck::Pod for DecalUniforms {}
+unsafe impl bytemuck::Zeroable for DecalUniforms {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DecalVertex {
+    position: Vector3<f32>,
+}
+
+unsafe impl bytemuck::Pod for DecalVertex {}
+unsafe impl bytemuck::Zeroable for DecalVertex {}
+
+impl Vertex for DecalVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        wgpu::VertexBufferDescriptor {
+            stride: std::mem::size_of::<DecalVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[wgpu::VertexAttributeDescriptor {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float3,
+            }],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DecalInstance {
+    model: Matrix4<f32>,
+    /// From world space into the unit box
+    inverse_model: Matrix4<f32>,
+    opacity: f32,
+}
+
+unsafe impl bytemuck::Pod for DecalInstance {}
+unsafe impl bytemuck::Zeroable for DecalInstance {}
+
+impl Vertex for DecalInstance {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        const COLUMN: wgpu::BufferAddress = mem::size_of::<[f32; 4]>() as wgpu::BufferAddress;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<DecalInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN * 2,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN * 3,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN * 4,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN * 5,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN * 6,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN * 7,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: COLUMN * 8,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float,
+                },
+            ],
+        }
+    }
+}
+
+/// Unit cube around the origin wound inwards, so with back faces culled
+/// only its far side is drawn, also while the camera is inside it.
+fn inward_cube() -> Vec<DecalVertex> {
+    let mut vertices = Vec::with_capacity(36);
+    for axis in 0..3 {
+        for sign in [-1.0, 1.0].iter() {
+            let mut normal = Vector3::zeros();
+            normal[axis] = *sign;
+            let mut u = Vector3::zeros();
+            u[(axis + 1) % 3] = 1.0;
+            let v = normal.cross(&u);
+            let corner = |a: f32, b: f32| DecalVertex {
+                position: (normal + u * a + v * b) * 0.5,
+            };
+            // Counter-clockwise seen from outside, reversed
+            let quad = [
+                corner(-1.0, -1.0),
+                corner(1.0, -1.0),
+                corner(1.0, 1.0),
+                corner(-1.0, 1.0),
+            ];
+            vertices.extend_from_slice(&[quad[0], quad[2], quad[1], quad[0], quad[3], quad[2]]);
+        }
+    }
+    vertices
+}
+
+/// The depth texture bound with a sampler reading depth values rather than
+/// comparing them.
+fn depth_binding(
+    state: &state::WgpuState,
+    layout: &binding::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    depth: &Texture,
+) -> TextureBinding {
+    TextureBinding {
+        bind_group: std::sync::Arc::new(state.errors().scope(
+            "bind group",
+            Some("decal_depth"),
+            || {
+                state
+                    .device()
+                    .create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("decal_depth"),
+                        layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(&depth.view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(sampler),
+                            },
+                        ],
+                    })
+            },
+        )),
+        label: String::from("decal_depth"),
+    }
+}
+
+/// Decals of one frame, sorted into draws of one texture each. Upload them
+/// once before the pass that draws them, which can't write the depth
+/// texture they read.
+pub struct DecalRenderer {
+    cube: binding::Buffer,
+    uniforms: binding::Buffer,
+    uniform_group: BufferGroup,
+    depth_sampler: wgpu::Sampler,
+    depth_group: TextureBinding,
+    instances: Option<binding::Buffer>,
+    /// Instances the buffer can hold
+    capacity: usize,
+    /// Texture id and instances of each draw
+    batches: Vec<(u64, Range<u32>)>,
+    /// Bindings of the textures drawn last frame by texture id
+    bindings: HashMap<u64, TextureBinding>,
+}
+
+impl DecalRenderer {
+    /// `texture_layout` binds the scene's `depth` texture and the decal
+    /// textures.
+    pub fn new(
+        state: &state::WgpuState,
+        uniforms_layout: &binding::BindGroupLayout,
+        texture_layout: &binding::BindGroupLayout,
+        depth: &Texture,
+    ) -> Self {
+        let cube = binding::Buffer::new_init(
+            state,
+            "decal_cube",
+            &inward_cube(),
+            binding::BufferUsage::Vertex,
+        );
+        let uniforms = binding::Buffer::new_init(
+            state,
+            "decal_uniforms",
+            &[DecalUniforms {
+                view_proj: Matrix4::identity(),
+                inverse_view_proj: Matrix4::identity(),
+            }],
+            binding::BufferUsage::Uniform,
+        );
+        let uniform_group =
+            BufferGroup::from_buffer(state, "decal_uniforms", uniforms_layout, &[&uniforms]);
+        let depth_sampler = state.device().create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("decal_depth"),
+            ..Default::default()
+        });
+        let depth_group = depth_binding(state, texture_layout, &depth_sampler, depth);
+        Self {
+            cube,
+            uniforms,
+            uniform_group,
+            depth_sampler,
+            depth_group,
+            instances: None,
+            capacity: 0,
+            batches: Vec::new(),
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Reads the scene's depth from `depth` from now on, after it was
+    /// recreated.
+    pub fn set_depth(
+        &mut self,
+        state: &state::WgpuState,
+        texture_layout: &binding::BindGroupLayout,
+        depth: &Texture,
+    ) {
+        self.depth_group = depth_binding(state, texture_layout, &self.depth_sampler, depth);
+    }
+
+    /// Writes `decals`, each with the world matrix of its box, to the GPU
+    /// for a scene drawn with `view_proj`. Decals whose texture isn't
+    /// loaded are left out.
+    pub fn upload(
+        &mut self,
+        state: &state::WgpuState,
+        view_proj: Matrix4<f32>,
+        decals: &mut [(Matrix4<f32>, Decal)],
+        textures: &Assets<Texture>,
+        layout: &binding::BindGroupLayout,
+    ) {
+        self.uniforms.write(
+            state,
+            &[DecalUniforms {
+                view_proj,
+                inverse_view_proj: view_proj.try_inverse().unwrap_or_else(Matrix4::identity),
+            }],
+        );
+
+        decals.sort_by(|(_, a), (_, b)| a.texture.cmp(&b.texture));
+        self.batches.clear();
+        let mut instances = Vec::new();
+        let mut current = None;
+        for (model, decal) in decals.iter() {
+            let texture = match decal
+                .handle
+                .as_ref()
+                .and_then(|handle| textures.get(handle))
+            {
+                Some(texture) => texture,
+                None => continue,
+            };
+            // A box flattened to nothing covers no surface
+            let inverse_model = match model.try_inverse() {
+                Some(inverse) => inverse,
+                None => continue,
+            };
+            if current != Some(texture.id()) {
+                current = Some(texture.id());
+                self.bindings.entry(texture.id()).or_insert_with(|| {
+                    TextureBinding::new_ref(state, decal.texture.as_str(), layout, &[texture])
+                });
+                let start = instances.len() as u32;
+                self.batches.push((texture.id(), start..start));
+            }
+            instances.push(DecalInstance {
+                model: *model,
+                inverse_model,
+                opacity: decal.opacity,
+            });
+            if let Some((_, range)) = self.batches.last_mut() {
+                range.end = instances.len() as u32;
+            }
+        }
+
+        let batches = &self.batches;
+        self.bindings
+            .retain(|id, _| batches.iter().any(|(batch, _)| batch == id));
+
+        if instances.is_empty() {
+            return;
+        }
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            instances.resize(self.capacity, instances[0]);
+            self.instances = Some(binding::Buffer::new_init(
+                state,
+                "decals",
+                &instances,
+                binding::BufferUsage::DynamicVertex,
+            ));
+        } else if let Some(buffer) = &self.instances {
+            buffer.write(state, &instances);
+        }
+    }
+}
+
+impl<'a, 'b> DrawDecals<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_decals(&mut self, decals: &'b DecalRenderer) {
+        let instances = match &decals.instances {
+            Some(instances) if !decals.batches.is_empty() => instances,
+            _ => return,
+        };
+        self.bind_vertex_buffer(0, &decals.cube);
+        self.bind_vertex_buffer(1, instances);
+        self.bind_group(0, &decals.uniform_group);
+        self.bind_textures(1, &decals.depth_group);
+        for (texture, range) in decals.batches.iter() {
+            if let Some(texture) = decals.bindings.get(texture) {
+                self.bind_textures(2, texture);
+                self.draw(0..36, range.clone());
+            }
+        }
+    }
+}
@@ -45,10 +45,10 @@ pub struct Framebuffer {
 }
 
 impl Framebuffer {
-    pub fn new<T: Into<Option<&'a str>>>(
+    pub fn new<'a, T: Into<Option<&'a str>>>(
         state: &state::WgpuState,
         label: T,
-        layout: &wgpu::BindGroupLayout,
+        layout: &binding::BindGroupLayout,
         textures: &[&texture::Texture],
     ) -> Self {
         let label = label.into();
@@ -80,7 +80,7 @@ impl Framebuffer {
             index_buffer: binding::Buffer::new_init(
                 state,
                 label,
-                &[0, 1, 2, 0, 2, 3],
+                &[0u16, 1, 2, 0, 2, 3],
                 BufferUsage::Index,
             ),
             textures: binding::TextureBinding::new_ref(state, label, layout, textures),
@@ -90,7 +90,7 @@ impl Framebuffer {
     pub fn update_textures(
         &mut self,
         state: &state::WgpuState,
-        layout: &wgpu::BindGroupLayout,
+        layout: &binding::BindGroupLayout,
         textures: &[&texture::Texture],
     ) {
         self.textures = binding::TextureBinding::new_ref(state, None, layout, textures)
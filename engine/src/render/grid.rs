@@ -50,23 +50,44 @@ struct GridData {
     color: Vector4<f32>,
 }
 
+impl GridData {
+    fn new(cell_size: f32) -> Self {
+        Self {
+            // In texture coordinates, which run at half the world's rate
+            size: cell_size * TEX_COORD / (SIZE * 2.0),
+            _padding: [0u32; 3],
+            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
 unsafe impl bytemuck::Pod for GridData {}
 unsafe impl bytemuck::Zeroable for GridData {}
 
 pub struct Grid {
     pub vertex_buffer: binding::Buffer,
     pub index_buffer: binding::Buffer,
+    data_buffer: binding::Buffer,
     pub grid_group: binding::BufferGroup,
 }
 
+/// Default world units between grid lines
+pub const CELL_SIZE: f32 = 2.0;
+
 impl Grid {
-    pub fn new<T: Into<Option<&'a str>>>(
+    pub fn new<'a, T: Into<Option<&'a str>>>(
         state: &state::WgpuState,
         label: T,
-        layout: &wgpu::BindGroupLayout,
+        layout: &binding::BindGroupLayout,
     ) -> Self {
         let label = label.into();
         info!("Create grid {:?}", &label.unwrap_or(""));
+        let data_buffer = binding::Buffer::new_init(
+            &state,
+            "grid",
+            &[GridData::new(CELL_SIZE)],
+            binding::BufferUsage::Uniform,
+        );
         Self {
             vertex_buffer: binding::Buffer::new_init(
                 state,
@@ -94,26 +115,18 @@ impl Grid {
             index_buffer: binding::Buffer::new_init(
                 state,
                 label,
-                &[0, 1, 2, 1, 3, 2],
+                &[0u16, 1, 2, 1, 3, 2],
                 BufferUsage::Index,
             ),
-            grid_group: binding::BufferGroup::from_buffer(
-                &state,
-                "grid",
-                &layout,
-                &[&binding::Buffer::new_init(
-                    &state,
-                    "grid",
-                    &[GridData {
-                        size: 1.0,
-                        _padding: [0u32; 3],
-                        color: Vector4::new(1.0, 1.0, 1.0, 1.0),
-                    }],
-                    binding::BufferUsage::Uniform,
-                )],
-            ),
+            grid_group: binding::BufferGroup::from_buffer(&state, "grid", &layout, &[&data_buffer]),
+            data_buffer,
         }
     }
+
+    /// Sets the world units between grid lines.
+    pub fn set_cell_size(&self, state: &state::WgpuState, cell_size: f32) {
+        self.data_buffer.write(state, &[GridData::new(cell_size)]);
+    }
 }
 
 impl<'a, 'b> DrawGrid<'a, 'b> for wgpu::RenderPass<'a>
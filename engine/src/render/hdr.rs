@@ -0,0 +1,132 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use anyhow::*;
+use log::info;
+
+/// Linear float image, kept at full precision until upload.
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl HdrImage {
+    pub fn is_hdr_path<P: AsRef<Path>>(path: P) -> bool {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .as_deref()
+        {
+            Some("hdr") | Some("exr") => true,
+            _ => false,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        info!("Load HDR image {:?}", path.as_ref());
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        match extension.as_deref() {
+            Some("hdr") => Self::load_radiance(path),
+            #[cfg(feature = "exr")]
+            Some("exr") => Self::load_exr(path),
+            _ => bail!("Unsupported HDR format {:?}", path.as_ref()),
+        }
+    }
+
+    pub fn load_radiance<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())
+            .context(format!("Could not open HDR image {:?}", path.as_ref()))?;
+        let decoder = image::codecs::hdr::HdrDecoder::new(BufReader::new(file))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()?
+            .into_iter()
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0])
+            .collect();
+
+        Ok(Self {
+            width: metadata.width,
+            height: metadata.height,
+            pixels,
+        })
+    }
+
+    #[cfg(feature = "exr")]
+    pub fn load_exr<P: AsRef<Path>>(path: P) -> Result<Self> {
+        use exr::prelude::*;
+
+        let image = read_first_rgba_layer_from_file(
+            path.as_ref(),
+            |resolution, _channels: &RgbaChannels| {
+                (
+                    resolution.width(),
+                    vec![[0.0, 0.0, 0.0, 1.0]; resolution.width() * resolution.height()],
+                )
+            },
+            |(width, pixels): &mut (usize, Vec<[f32; 4]>),
+             position,
+             (r, g, b, a): (f32, f32, f32, f32)| {
+                pixels[position.y() * *width + position.x()] = [r, g, b, a];
+            },
+        )
+        .map_err(|e| anyhow!("Could not read EXR image {:?}: {}", path.as_ref(), e))?;
+
+        let size = image.layer_data.size;
+        Ok(Self {
+            width: size.width() as u32,
+            height: size.height() as u32,
+            pixels: image.layer_data.channel_data.pixels.1,
+        })
+    }
+
+    /// Pixels converted to half floats for `Rgba16Float` textures.
+    pub fn to_rgba16f(&self) -> Vec<[u16; 4]> {
+        self.pixels
+            .iter()
+            .map(|pixel| {
+                [
+                    f32_to_f16(pixel[0]),
+                    f32_to_f16(pixel[1]),
+                    f32_to_f16(pixel[2]),
+                    f32_to_f16(pixel[3]),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Truncating conversion, rounding isn't worth it for color data. Values out
+/// of range become infinity and tiny values flush to zero.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinity, NaN keeps a mantissa bit
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal half, shift the implicit leading one in
+        let mantissa = mantissa | 0x80_0000;
+        sign | (mantissa >> (14 - exponent)) as u16
+    } else {
+        sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16
+    }
+}
@@ -0,0 +1,131 @@
+//! Rendering without a window. Frames are drawn into an offscreen target
+//! and read back, to render scenes from the command line or compare them
+//! against golden images.
+
+use anyhow::*;
+use futures::executor::block_on;
+use log::info;
+
+use super::{state, texture::Texture};
+
+/// A color target and depth texture the size of the state's frames, with
+/// a buffer to copy the color back into.
+pub struct Offscreen {
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub depth_texture: Texture,
+    readback: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    /// Bytes of a row in `readback`, padded to what copies need
+    padded_row: u32,
+}
+
+impl Offscreen {
+    pub fn new(state: &state::WgpuState) -> Self {
+        let (width, height) = (state.width(), state.height());
+        info!("Create offscreen target ({}x{})", width, height);
+        let texture = state.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: state.format(),
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = Texture::create_depth_texture(state, "offscreen_depth");
+
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = (width * 4 + alignment - 1) / alignment * alignment;
+        let readback = state.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offscreen_readback"),
+            size: (padded_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            texture,
+            view,
+            depth_texture,
+            readback,
+            width,
+            height,
+            padded_row,
+        }
+    }
+
+    /// Waits for everything submitted so far and reads the target back.
+    pub fn read(&self, state: &state::WgpuState) -> Result<image::RgbaImage> {
+        let mut encoder = state.encoder();
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+            },
+            wgpu::BufferCopyView {
+                buffer: &self.readback,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: self.padded_row,
+                    rows_per_image: self.height,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            },
+        );
+        state.queue().submit(std::iter::once(encoder.finish()));
+
+        let slice = self.readback.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        state.device().poll(wgpu::Maintain::Wait);
+        block_on(mapping).map_err(|_| anyhow!("Could not map the offscreen readback"))?;
+
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(self.padded_row as usize) {
+                pixels.extend_from_slice(&row[..(self.width * 4) as usize]);
+            }
+        }
+        self.readback.unmap();
+
+        match state.format() {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                for pixel in pixels.chunks_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {}
+            format => bail!("Can't read back {:?} targets", format),
+        }
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .context("Offscreen pixels don't fill the image")
+    }
+}
+
+/// Mean difference of the channels of `a` and `b` from 0 to 1, none when
+/// their sizes differ.
+pub fn difference(a: &image::RgbaImage, b: &image::RgbaImage) -> Option<f32> {
+    if a.dimensions() != b.dimensions() {
+        return None;
+    }
+    let total = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw().iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).abs() as u64)
+        .sum::<u64>();
+    let channels = a.as_raw().len().max(1) as f32;
+    Some(total as f32 / channels / 255.0)
+}
@@ -0,0 +1,239 @@
+//! Argument and instance buffers for indirect drawing. Entities sharing a
+//! model, detail level and material are drawn as one batch, each mesh of it
+//! with a single `draw_indexed_indirect`, so the draws submitted grow with
+//! the batches rather than the entities. With culling on the GPU the
+//! instance counts are only known to the GPU.
+
+use super::{
+    binding::{self, BufferGroup},
+    culling::{CullInstance, Culling},
+    state,
+};
+use crate::{spatial::Frustum, transform::InstanceRaw};
+
+/// Arguments of one `draw_indexed_indirect`, as wgpu reads them.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DrawIndexedIndirect {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+unsafe impl bytemuck::Pod for DrawIndexedIndirect {}
+unsafe impl bytemuck::Zeroable for DrawIndexedIndirect {}
+
+impl DrawIndexedIndirect {
+    /// Offset of the arguments at `index` in the buffer.
+    pub fn offset(index: usize) -> wgpu::BufferAddress {
+        (index * std::mem::size_of::<Self>()) as wgpu::BufferAddress
+    }
+}
+
+/// Entities drawn as one batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndirectBatch {
+    pub model: String,
+    pub lod: usize,
+    pub material: Option<String>,
+    /// Index of the arguments of its first mesh, one follows for each
+    pub first_argument: usize,
+    pub mesh_count: usize,
+    /// Slot of its first instance in the instance buffer
+    pub first_instance: u32,
+    /// Instances before culling
+    pub instance_count: u32,
+}
+
+/// A buffer growing with the largest frame seen.
+#[derive(Default)]
+struct GrowingBuffer {
+    buffer: Option<binding::Buffer>,
+    /// Elements the buffer can hold
+    capacity: usize,
+}
+
+impl GrowingBuffer {
+    /// Writes `data`, replacing the buffer with one twice as large when it
+    /// doesn't fit. Returns whether it was replaced.
+    fn write<A: bytemuck::Pod>(
+        &mut self,
+        state: &state::WgpuState,
+        label: &str,
+        data: &[A],
+        usage: fn() -> binding::BufferUsage,
+    ) -> bool {
+        if data.is_empty() {
+            return false;
+        }
+        if data.len() > self.capacity {
+            self.capacity = data.len().next_power_of_two();
+            let mut padded = data.to_vec();
+            padded.resize(self.capacity, data[0]);
+            self.buffer = Some(binding::Buffer::new_init(state, label, &padded, usage()));
+            true
+        } else if let Some(buffer) = &self.buffer {
+            buffer.write(state, data);
+            false
+        } else {
+            false
+        }
+    }
+
+    /// Makes room for `len` elements without writing any.
+    fn reserve<A: bytemuck::Pod>(
+        &mut self,
+        state: &state::WgpuState,
+        label: &str,
+        len: usize,
+        usage: fn() -> binding::BufferUsage,
+    ) -> bool {
+        if len <= self.capacity {
+            return false;
+        }
+        self.capacity = len.next_power_of_two();
+        let zeroed: Vec<A> = vec![bytemuck::Zeroable::zeroed(); self.capacity];
+        self.buffer = Some(binding::Buffer::new_init(state, label, &zeroed, usage()));
+        true
+    }
+}
+
+/// Buffers of one view drawn indirectly, written once per frame before the
+/// pass drawing from them.
+#[derive(Default)]
+pub struct IndirectDraws {
+    batches: Vec<IndirectBatch>,
+    /// Model matrices bound in place of single transforms, packed by the
+    /// culling shader when culling on the GPU
+    instances: GrowingBuffer,
+    arguments: GrowingBuffer,
+    /// Inputs of the culling shader
+    cull_uniforms: Option<binding::Buffer>,
+    cull_instances: GrowingBuffer,
+    cull_batches: GrowingBuffer,
+    /// Binds the buffers above, recreated whenever one of them is
+    cull_group: Option<BufferGroup>,
+}
+
+impl IndirectDraws {
+    /// Writes the instances of every batch, one batch after the other, and
+    /// the arguments drawing them. With `culling` the instances are tested
+    /// against the frustum first, recorded to `encoder`.
+    pub fn upload(
+        &mut self,
+        state: &state::WgpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        culling: Option<(&Culling, &Frustum)>,
+        batches: Vec<IndirectBatch>,
+        instances: &[CullInstance],
+        arguments: &[DrawIndexedIndirect],
+    ) {
+        self.batches = batches;
+        let (culling, frustum) = match culling {
+            Some(culling) => culling,
+            None => {
+                let raw = instances
+                    .iter()
+                    .map(|instance| instance.instance)
+                    .collect::<Vec<_>>();
+                self.instances.write(state, "indirect_instances", &raw, || {
+                    binding::BufferUsage::ComputedVertex
+                });
+                self.arguments
+                    .write(state, "indirect_arguments", arguments, || {
+                        binding::BufferUsage::Indirect
+                    });
+                return;
+            }
+        };
+
+        // The culling shader counts the visible instances
+        let arguments = arguments
+            .iter()
+            .map(|arguments| DrawIndexedIndirect {
+                instance_count: 0,
+                ..*arguments
+            })
+            .collect::<Vec<_>>();
+        let ranges = self
+            .batches
+            .iter()
+            .map(|batch| {
+                [
+                    batch.first_argument as u32,
+                    batch.mesh_count as u32,
+                    batch.first_instance,
+                    0,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let uniforms = Culling::uniforms(frustum, instances.len() as u32);
+
+        let mut rebind = self.instances.reserve::<InstanceRaw>(
+            state,
+            "indirect_instances",
+            instances.len(),
+            || binding::BufferUsage::ComputedVertex,
+        );
+        rebind |= self
+            .arguments
+            .write(state, "indirect_arguments", &arguments, || {
+                binding::BufferUsage::Indirect
+            });
+        rebind |= self
+            .cull_instances
+            .write(state, "cull_instances", instances, || {
+                binding::BufferUsage::Storage
+            });
+        rebind |= self.cull_batches.write(state, "cull_batches", &ranges, || {
+            binding::BufferUsage::Storage
+        });
+        match &self.cull_uniforms {
+            Some(buffer) => buffer.write(state, &[uniforms]),
+            None => {
+                self.cull_uniforms = Some(binding::Buffer::new_init(
+                    state,
+                    "cull_uniforms",
+                    &[uniforms],
+                    binding::BufferUsage::Uniform,
+                ));
+                rebind = true;
+            }
+        }
+
+        let buffers = (
+            &self.cull_uniforms,
+            &self.cull_instances.buffer,
+            &self.cull_batches.buffer,
+            &self.arguments.buffer,
+            &self.instances.buffer,
+        );
+        if let (Some(uniforms), Some(instances), Some(batches), Some(arguments), Some(visible)) =
+            buffers
+        {
+            if rebind || self.cull_group.is_none() {
+                self.cull_group =
+                    Some(culling.bind(state, uniforms, instances, batches, arguments, visible));
+            }
+        }
+        if let Some(group) = &self.cull_group {
+            culling.dispatch(encoder, group, instances.len() as u32);
+        }
+    }
+
+    /// Batches of the last upload.
+    pub fn batches(&self) -> &[IndirectBatch] {
+        &self.batches
+    }
+
+    /// Instance buffer to bind in place of a single transform.
+    pub fn instances(&self) -> Option<&binding::Buffer> {
+        self.instances.buffer.as_ref()
+    }
+
+    pub fn arguments(&self) -> Option<&binding::Buffer> {
+        self.arguments.buffer.as_ref()
+    }
+}
@@ -0,0 +1,59 @@
+//! Render layers separate what each camera draws. An entity's
+//! [`RenderLayer`] says which layers it is on, a camera's which ones it
+//! sees, and the entity is drawn when they share one. Entities without the
+//! component are on [`RenderLayer::DEFAULT`].
+
+use std::ops::{BitOr, BitOrAssign};
+
+use serde::{Deserialize, Serialize};
+
+/// Bitmask of layers, on an entity or seen by a camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RenderLayer(pub u32);
+
+impl RenderLayer {
+    pub const NONE: RenderLayer = RenderLayer(0);
+    /// The scene itself
+    pub const DEFAULT: RenderLayer = RenderLayer(1);
+    /// Helpers only the editor camera sees
+    pub const EDITOR: RenderLayer = RenderLayer(1 << 1);
+    /// Kept apart from the scene for cameras drawing an interface
+    pub const UI: RenderLayer = RenderLayer(1 << 2);
+    pub const ALL: RenderLayer = RenderLayer(u32::MAX);
+
+    /// Named layers and their names, as the inspector offers them.
+    pub const NAMED: [(RenderLayer, &'static str); 3] = [
+        (RenderLayer::DEFAULT, "default"),
+        (RenderLayer::EDITOR, "editor"),
+        (RenderLayer::UI, "ui"),
+    ];
+
+    /// Whether any layer is in both masks.
+    pub fn intersects(self, other: RenderLayer) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn contains(self, other: RenderLayer) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for RenderLayer {
+    fn default() -> Self {
+        RenderLayer::DEFAULT
+    }
+}
+
+impl BitOr for RenderLayer {
+    type Output = RenderLayer;
+
+    fn bitor(self, other: RenderLayer) -> RenderLayer {
+        RenderLayer(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for RenderLayer {
+    fn bitor_assign(&mut self, other: RenderLayer) {
+        self.0 |= other.0;
+    }
+}
@@ -1,28 +1,400 @@
+pub mod billboard;
 pub mod binding;
+pub mod compressed;
+pub mod compute;
+pub mod culling;
+pub mod debug;
+pub mod decal;
 pub mod frame;
 pub mod grid;
+pub mod hdr;
+pub mod headless;
+pub mod indirect;
+pub mod layer;
 pub mod model;
+pub mod particles;
+pub mod post;
+pub mod reflection;
 pub mod renderpass;
+pub mod sprite;
 pub mod state;
+pub mod text;
 pub mod texture;
+pub mod thumbnail;
 pub mod traits;
+pub mod validation;
+pub mod viewport;
+pub mod window;
+
+use std::collections::HashMap;
+
+use anyhow::*;
+
+use crate::transform::InstanceRaw;
+use traits::Vertex;
 
 pub struct Layouts {
-    pub material: wgpu::BindGroupLayout,
-    pub uniforms: wgpu::BindGroupLayout,
-    pub light: wgpu::BindGroupLayout,
-    pub frame: wgpu::BindGroupLayout,
-    pub grid: wgpu::BindGroupLayout,
+    pub material: binding::BindGroupLayout,
+    pub uniforms: binding::BindGroupLayout,
+    pub light: binding::BindGroupLayout,
+    pub frame: binding::BindGroupLayout,
+    pub grid: binding::BindGroupLayout,
+    pub joints: binding::BindGroupLayout,
+    pub morph: binding::BindGroupLayout,
+    pub material_array: binding::BindGroupLayout,
+    pub terrain: binding::BindGroupLayout,
+    pub texture: binding::BindGroupLayout,
+    /// Added by plugins, by name
+    pub custom: HashMap<String, binding::BindGroupLayout>,
+}
+
+impl Layouts {
+    pub fn new(state: &state::WgpuState) -> Self {
+        Self {
+            material: material_layout(state),
+            uniforms: uniforms_layout(state),
+            light: light_layout(state),
+            frame: frame_layout(state),
+            grid: grid_layout(state),
+            joints: joints_layout(state),
+            morph: morph_layout(state),
+            material_array: material_array_layout(state),
+            terrain: terrain_layout(state),
+            texture: texture_layout(state),
+            custom: HashMap::new(),
+        }
+    }
 }
 
 pub struct Pipelines {
-    pub forward: wgpu::RenderPipeline,
-    pub light: wgpu::RenderPipeline,
+    pub forward: IndexedPipeline,
+    pub skinned: IndexedPipeline,
+    pub morph: IndexedPipeline,
+    pub material_array: IndexedPipeline,
+    pub light: IndexedPipeline,
+    pub terrain: wgpu::RenderPipeline,
     pub depth: wgpu::RenderPipeline,
     pub grid: wgpu::RenderPipeline,
+    pub debug: wgpu::RenderPipeline,
+    pub text: wgpu::RenderPipeline,
+    pub sprite: wgpu::RenderPipeline,
+    pub decal: wgpu::RenderPipeline,
+    pub billboard: wgpu::RenderPipeline,
+    /// Added by plugins, by name
+    pub custom: HashMap<String, wgpu::RenderPipeline>,
 }
 
-pub fn frame_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
+impl Pipelines {
+    /// Pipelines drawing into targets of the swapchain's format.
+    pub fn new(state: &state::WgpuState, layouts: &Layouts) -> Result<Self> {
+        let forward_layout = state.create_pipeline_layout(
+            "forward",
+            &[&layouts.material, &layouts.uniforms, &layouts.light],
+        )?;
+
+        let skinned_layout = state.create_pipeline_layout(
+            "skinned",
+            &[
+                &layouts.material,
+                &layouts.uniforms,
+                &layouts.light,
+                &layouts.joints,
+            ],
+        )?;
+
+        let morph_layout = state.create_pipeline_layout(
+            "morph",
+            &[
+                &layouts.material,
+                &layouts.uniforms,
+                &layouts.light,
+                &layouts.morph,
+            ],
+        )?;
+
+        let material_array_layout = state.create_pipeline_layout(
+            "material_array",
+            &[&layouts.material_array, &layouts.uniforms, &layouts.light],
+        )?;
+
+        let terrain_layout = state.create_pipeline_layout(
+            "terrain",
+            &[&layouts.terrain, &layouts.uniforms, &layouts.light],
+        )?;
+
+        let light_layout =
+            state.create_pipeline_layout("light", &[&layouts.uniforms, &layouts.light])?;
+
+        let depth_layout =
+            state.create_pipeline_layout("depth", &[&layouts.frame, &layouts.uniforms])?;
+
+        let grid_layout =
+            state.create_pipeline_layout("grid", &[&layouts.uniforms, &layouts.grid])?;
+
+        let forward = state.create_indexed_pipeline(
+            &forward_layout,
+            "forward_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            (texture::Texture::DEPTH_FORMAT, true),
+            &[model::ModelVertex::desc(), InstanceRaw::desc()],
+            "shader.vert.spv",
+            "shader.frag.spv",
+            true,
+        )?;
+
+        let skinned = state.create_indexed_pipeline(
+            &skinned_layout,
+            "skinned_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            (texture::Texture::DEPTH_FORMAT, true),
+            &[
+                model::ModelVertex::desc(),
+                InstanceRaw::desc(),
+                model::SkinVertex::desc(),
+            ],
+            "skinned.vert.spv",
+            "shader.frag.spv",
+            true,
+        )?;
+
+        let morph = state.create_indexed_pipeline(
+            &morph_layout,
+            "morph_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            (texture::Texture::DEPTH_FORMAT, true),
+            &[model::ModelVertex::desc(), InstanceRaw::desc()],
+            "morph.vert.spv",
+            "shader.frag.spv",
+            true,
+        )?;
+
+        let material_array = state.create_indexed_pipeline(
+            &material_array_layout,
+            "material_array_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            (texture::Texture::DEPTH_FORMAT, true),
+            &[
+                model::ModelVertex::desc(),
+                InstanceRaw::desc(),
+                model::LayerInstance::desc(),
+            ],
+            "material_array.vert.spv",
+            "material_array.frag.spv",
+            true,
+        )?;
+
+        let terrain = state.create_render_pipeline(
+            &terrain_layout,
+            "terrain_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            (texture::Texture::DEPTH_FORMAT, true),
+            &[model::ModelVertex::desc()],
+            wgpu::IndexFormat::Uint32,
+            "terrain.vert.spv",
+            "terrain.frag.spv",
+            true,
+        )?;
+
+        let light_pipeline = state.create_indexed_pipeline(
+            &light_layout,
+            "light_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            (texture::Texture::DEPTH_FORMAT, true),
+            &[model::ModelVertex::desc()],
+            "light.vert.spv",
+            "light.frag.spv",
+            true,
+        )?;
+
+        let depth_pipeline = state.create_render_pipeline(
+            &depth_layout,
+            "depth_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            None,
+            &[frame::FrameVertex::desc()],
+            wgpu::IndexFormat::Uint16,
+            "depth_frame.vert.spv",
+            "depth_frame.frag.spv",
+            true,
+        )?;
+
+        let grid_pipeline = state.create_render_pipeline(
+            &grid_layout,
+            "grid_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            (texture::Texture::DEPTH_FORMAT, false),
+            &[grid::GridVertex::desc()],
+            wgpu::IndexFormat::Uint16,
+            "grid.vert.spv",
+            "grid.frag.spv",
+            false,
+        )?;
+
+        let debug_layout = state.create_pipeline_layout("debug", &[&layouts.uniforms])?;
+
+        let debug_pipeline = state.create_line_pipeline(
+            &debug_layout,
+            "debug_pipeline",
+            state.format(),
+            (texture::Texture::DEPTH_FORMAT, false),
+            &[debug::DebugVertex::desc()],
+            "debug.vert.spv",
+            "debug.frag.spv",
+        )?;
+
+        let text_layout =
+            state.create_pipeline_layout("text", &[&layouts.uniforms, &layouts.texture])?;
+
+        let text_pipeline = state.create_render_pipeline(
+            &text_layout,
+            "text_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            (texture::Texture::DEPTH_FORMAT, false),
+            &[text::TextVertex::desc()],
+            wgpu::IndexFormat::Uint16,
+            "text.vert.spv",
+            "text.frag.spv",
+            false,
+        )?;
+
+        let sprite_layout =
+            state.create_pipeline_layout("sprite", &[&layouts.uniforms, &layouts.texture])?;
+
+        let sprite_pipeline = state.create_render_pipeline(
+            &sprite_layout,
+            "sprite_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            (texture::Texture::DEPTH_FORMAT, false),
+            &[sprite::SpriteVertex::desc()],
+            wgpu::IndexFormat::Uint16,
+            "sprite.vert.spv",
+            "sprite.frag.spv",
+            false,
+        )?;
+
+        let decal_layout = state.create_pipeline_layout(
+            "decal",
+            &[&layouts.uniforms, &layouts.texture, &layouts.texture],
+        )?;
+
+        // Drawn without a depth attachment, they read the depth instead
+        let decal_pipeline = state.create_render_pipeline(
+            &decal_layout,
+            "decal_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            None,
+            &[decal::DecalVertex::desc(), decal::DecalInstance::desc()],
+            wgpu::IndexFormat::Uint16,
+            "decal.vert.spv",
+            "decal.frag.spv",
+            true,
+        )?;
+
+        let billboard_layout =
+            state.create_pipeline_layout("billboard", &[&layouts.uniforms, &layouts.texture])?;
+
+        let billboard_pipeline = state.create_render_pipeline(
+            &billboard_layout,
+            "billboard_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            (texture::Texture::DEPTH_FORMAT, true),
+            &[billboard::BillboardVertex::desc()],
+            wgpu::IndexFormat::Uint16,
+            "billboard.vert.spv",
+            "billboard.frag.spv",
+            false,
+        )?;
+
+        Ok(Self {
+            forward,
+            skinned,
+            morph,
+            material_array,
+            light: light_pipeline,
+            terrain,
+            depth: depth_pipeline,
+            grid: grid_pipeline,
+            debug: debug_pipeline,
+            text: text_pipeline,
+            sprite: sprite_pipeline,
+            decal: decal_pipeline,
+            billboard: billboard_pipeline,
+            custom: HashMap::new(),
+        })
+    }
+}
+
+pub struct IndexedPipeline {
+    pub uint16: wgpu::RenderPipeline,
+    pub uint32: wgpu::RenderPipeline,
+}
+
+impl IndexedPipeline {
+    pub fn get(&self, format: wgpu::IndexFormat) -> &wgpu::RenderPipeline {
+        match format {
+            wgpu::IndexFormat::Uint16 => &self.uint16,
+            wgpu::IndexFormat::Uint32 => &self.uint32,
+        }
+    }
+}
+
+pub fn frame_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
     state.create_layout(
         "framebuffer",
         &[
@@ -46,7 +418,7 @@ pub fn frame_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
     )
 }
 
-pub fn material_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
+pub fn material_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
     state.create_layout(
         "material",
         &[
@@ -82,11 +454,116 @@ pub fn material_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
                 ty: wgpu::BindingType::Sampler { comparison: false },
                 count: None,
             },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: true,
+                    dimension: wgpu::TextureViewDimension::D2,
+                    component_type: wgpu::TextureComponentType::Float,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    )
+}
+
+pub fn material_array_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
+    state.create_layout(
+        "material_array",
+        &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2Array,
+                    component_type: wgpu::TextureComponentType::Float,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2Array,
+                    component_type: wgpu::TextureComponentType::Float,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+                count: None,
+            },
+        ],
+    )
+}
+
+pub fn terrain_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
+    state.create_layout(
+        "terrain",
+        &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2,
+                    component_type: wgpu::TextureComponentType::Float,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2Array,
+                    component_type: wgpu::TextureComponentType::Float,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+                count: None,
+            },
         ],
     )
 }
 
-pub fn uniforms_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
+pub fn uniforms_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
     state.create_layout(
         "uniforms",
         &[wgpu::BindGroupLayoutEntry {
@@ -101,7 +578,7 @@ pub fn uniforms_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
     )
 }
 
-pub fn light_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
+pub fn light_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
     state.create_layout(
         "light",
         &[wgpu::BindGroupLayoutEntry {
@@ -116,7 +593,7 @@ pub fn light_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
     )
 }
 
-pub fn grid_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
+pub fn grid_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
     state.create_layout(
         "grid",
         &[wgpu::BindGroupLayoutEntry {
@@ -130,3 +607,72 @@ pub fn grid_layout(state: &state::WgpuState) -> wgpu::BindGroupLayout {
         }],
     )
 }
+
+/// A single texture and its sampler, the glyph atlas, a sprite or decal
+/// texture or the depth decals read.
+pub fn texture_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
+    state.create_layout(
+        "texture",
+        &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    dimension: wgpu::TextureViewDimension::D2,
+                    component_type: wgpu::TextureComponentType::Float,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+                count: None,
+            },
+        ],
+    )
+}
+
+pub fn joints_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
+    state.create_layout(
+        "joints",
+        &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::VERTEX,
+            ty: wgpu::BindingType::StorageBuffer {
+                dynamic: false,
+                min_binding_size: None,
+                readonly: true,
+            },
+            count: None,
+        }],
+    )
+}
+
+pub fn morph_layout(state: &state::WgpuState) -> binding::BindGroupLayout {
+    state.create_layout(
+        "morph",
+        &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::StorageBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                    readonly: true,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    )
+}
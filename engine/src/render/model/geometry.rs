@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
 use log::info;
-use nalgebra::Vector3;
+use nalgebra::{Point2, Point3, Vector3, Vector4};
 use ncollide3d::{bounding_volume::BoundingVolume, query::RayCast};
 use ncollide3d::{
-    bounding_volume::AABB,
+    bounding_volume::{BoundingSphere, AABB},
     math::Isometry,
     partitioning::{BVHImpl, BVT},
     query::{ContactPrediction, ContactPreprocessor},
@@ -16,77 +16,127 @@ use crate::render::{
     state,
 };
 
-use super::ModelVertex;
+use super::{tangent, ModelVertex, MorphDelta, SkinVertex};
 
 #[derive(Clone)]
 pub struct Mesh {
     pub(super) name: String,
     pub(super) vertex_buffer: Arc<Buffer>,
     pub(super) index_buffer: Arc<Buffer>,
-    pub(super) num_elements: u32,
+    pub(super) skin_buffer: Option<Arc<Buffer>>,
+    /// Header of vertex and target count followed by the target-major
+    /// deltas, empty targets for meshes without blend shapes.
+    pub(crate) morph_buffer: Arc<Buffer>,
+    pub(super) morph_targets: usize,
+    pub(crate) num_elements: u32,
     pub(super) material: usize,
 }
 
 #[derive(Clone)]
 pub struct Geometry {
-    pub(super) meshes: Vec<Mesh>,
+    pub(crate) meshes: Vec<Mesh>,
+    pub(super) index_format: wgpu::IndexFormat,
     pub(super) colliders: Vec<TriMesh<f32>>,
     pub(super) bvt: BVT<usize, AABB<f32>>,
 }
 
+/// CPU side mesh data produced by the model loaders, before upload.
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    pub skin: Option<Vec<SkinVertex>>,
+    /// One delta per vertex for each morph target
+    pub morph_targets: Vec<Vec<MorphDelta>>,
+    pub material: usize,
+}
+
+impl MeshData {
+    pub fn from_obj(m: tobj::Model) -> Self {
+        let mut vertices = Vec::new();
+        let vertex_count = m.mesh.positions.len() / 3;
+        let has_colors = m.mesh.vertex_color.len() == vertex_count * 3;
+        let has_normals = m.mesh.normals.len() == vertex_count * 3;
+        let has_tex_coords = m.mesh.texcoords.len() == vertex_count * 2;
+        for i in 0..vertex_count {
+            let tex_coords: Point2<f32> = if has_tex_coords {
+                [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]].into()
+            } else {
+                Point2::origin()
+            };
+            let normal = if has_normals {
+                Vector3::new(
+                    m.mesh.normals[i * 3],
+                    m.mesh.normals[i * 3 + 1],
+                    m.mesh.normals[i * 3 + 2],
+                )
+            } else {
+                Vector3::zeros()
+            };
+            let color = if has_colors {
+                Vector4::new(
+                    m.mesh.vertex_color[i * 3],
+                    m.mesh.vertex_color[i * 3 + 1],
+                    m.mesh.vertex_color[i * 3 + 2],
+                    1.0,
+                )
+            } else {
+                Vector4::new(1.0, 1.0, 1.0, 1.0)
+            };
+            vertices.push(ModelVertex {
+                position: [
+                    m.mesh.positions[i * 3],
+                    m.mesh.positions[i * 3 + 1],
+                    m.mesh.positions[i * 3 + 2],
+                ]
+                .into(),
+                tex_coords,
+                normal,
+                tangent: [0.0; 3].into(),
+                bitangent: [0.0; 3].into(),
+                color,
+                // OBJ has no second UV set, lightmaps fall back to the first
+                tex_coords2: tex_coords,
+            });
+        }
+
+        if !has_normals {
+            info!("Mesh {:?} has no normals, generating them", m.name);
+            tangent::generate_normals(&mut vertices, &m.mesh.indices);
+        }
+        tangent::generate_tangents(&mut vertices, &m.mesh.indices);
+
+        Self {
+            name: m.name,
+            vertices,
+            indices: m.mesh.indices,
+            skin: None,
+            morph_targets: Vec::new(),
+            material: m.mesh.material_id.unwrap_or(0),
+        }
+    }
+}
+
 impl Geometry {
-    pub fn new(state: &state::WgpuState, obj_models: Vec<tobj::Model>) -> Self {
+    pub fn from_meshes(state: &state::WgpuState, mesh_data: Vec<MeshData>) -> Self {
         let mut meshes = Vec::new();
 
+        // Every mesh of a model is drawn with the same pipeline, so only use
+        // 16 bit indices when all of them fit.
+        let index_format = if mesh_data
+            .iter()
+            .all(|m| m.vertices.len() <= u16::MAX as usize + 1)
+        {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        };
+
         let mut colliders = Vec::new();
-        for m in obj_models {
+        for m in mesh_data {
             info!("Load mesh {:?}", m.name);
-            let mut vertices = Vec::new();
-            for i in 0..m.mesh.positions.len() / 3 {
-                vertices.push(ModelVertex {
-                    position: [
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ]
-                    .into(),
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]].into(),
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ]
-                    .into(),
-                    tangent: [0.0; 3].into(),
-                    bitangent: [0.0; 3].into(),
-                });
-            }
-
-            let indices = &m.mesh.indices;
-
-            for c in indices.chunks(3) {
-                let v0 = vertices[c[0] as usize];
-                let v1 = vertices[c[1] as usize];
-                let v2 = vertices[c[2] as usize];
-
-                let delta_pos1 = v1.position - v0.position;
-                let delta_pos2 = v2.position - v0.position;
-
-                let delta_uv1 = v1.tex_coords - v0.tex_coords;
-                let delta_uv2 = v2.tex_coords - v0.tex_coords;
-
-                let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-                let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-                let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * r;
-
-                vertices[c[0] as usize].tangent = tangent.into();
-                vertices[c[1] as usize].tangent = tangent.into();
-                vertices[c[2] as usize].tangent = tangent.into();
-
-                vertices[c[0] as usize].bitangent = bitangent.into();
-                vertices[c[1] as usize].bitangent = bitangent.into();
-                vertices[c[2] as usize].bitangent = bitangent.into();
-            }
+            let vertices = &m.vertices;
+            let indices = &m.indices;
 
             let shape = TriMesh::new(
                 vertices
@@ -103,16 +153,54 @@ impl Geometry {
             colliders.push(shape);
 
             let vertex_buffer =
-                Buffer::new_init(state, m.name.as_str(), &vertices, BufferUsage::Vertex);
-            let index_buffer =
-                Buffer::new_init(state, m.name.as_str(), &m.mesh.indices, BufferUsage::Index);
+                Buffer::new_init(state, m.name.as_str(), vertices, BufferUsage::Vertex);
+            let index_buffer = match index_format {
+                wgpu::IndexFormat::Uint16 => Buffer::new_init(
+                    state,
+                    m.name.as_str(),
+                    &indices.iter().map(|&i| i as u16).collect::<Vec<_>>(),
+                    BufferUsage::Index,
+                ),
+                wgpu::IndexFormat::Uint32 => {
+                    Buffer::new_init(state, m.name.as_str(), indices, BufferUsage::Index)
+                }
+            };
+
+            let skin_buffer = m.skin.as_ref().map(|skin| {
+                Arc::new(Buffer::new_init(
+                    state,
+                    m.name.as_str(),
+                    skin,
+                    BufferUsage::Vertex,
+                ))
+            });
+
+            let header = [
+                f32::from_bits(vertices.len() as u32),
+                f32::from_bits(m.morph_targets.len() as u32),
+                0.0,
+                0.0,
+            ];
+            let morph_data = std::iter::once(header)
+                .chain(
+                    m.morph_targets
+                        .iter()
+                        .flatten()
+                        .flat_map(|delta| vec![delta.position, delta.normal]),
+                )
+                .collect::<Vec<_>>();
+            let morph_buffer =
+                Buffer::new_init(state, m.name.as_str(), &morph_data, BufferUsage::Storage);
 
             meshes.push(Mesh {
                 name: m.name,
                 vertex_buffer: Arc::new(vertex_buffer),
                 index_buffer: Arc::new(index_buffer),
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
+                skin_buffer,
+                morph_buffer: Arc::new(morph_buffer),
+                morph_targets: m.morph_targets.len(),
+                num_elements: m.indices.len() as u32,
+                material: m.material,
             });
         }
 
@@ -126,11 +214,62 @@ impl Geometry {
 
         Self {
             meshes,
+            index_format,
             colliders,
             bvt,
         }
     }
 
+    /// Model space box around all meshes, the root of the mesh BVT.
+    pub fn local_aabb(&self) -> AABB<f32> {
+        self.bvt
+            .root_bounding_volume()
+            .cloned()
+            .unwrap_or_else(|| self.aabb(&Isometry::identity()))
+    }
+
+    /// Model space sphere around all meshes.
+    pub fn bounding_sphere(&self) -> BoundingSphere<f32> {
+        let aabb = self.local_aabb();
+        BoundingSphere::new(aabb.center(), aabb.half_extents().norm())
+    }
+
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        self.index_format
+    }
+
+    pub fn morph_target_count(&self) -> usize {
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.morph_targets)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn mesh_count(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn triangle_count(&self) -> u64 {
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.num_elements as u64 / 3)
+            .sum()
+    }
+
+    pub fn is_skinned(&self) -> bool {
+        self.meshes.iter().any(|mesh| mesh.skin_buffer.is_some())
+    }
+
+    /// Vertex positions of every mesh multiplied by `scale`.
+    pub fn points(&self, scale: Vector3<f32>) -> Vec<Point3<f32>> {
+        self.colliders
+            .iter()
+            .flat_map(|collider| collider.points().iter())
+            .map(|point| Point3::from(point.coords.component_mul(&scale)))
+            .collect()
+    }
+
     pub fn scaled(&self, scale: Vector3<f32>) -> Self {
         let mut clone = self.clone();
         let colliders = clone
@@ -222,11 +361,32 @@ impl RayCast<f32> for Geometry {
         max_toi: f32,
         solid: bool,
     ) -> Option<ncollide3d::query::RayIntersection<f32>> {
-        for collider in self.colliders.iter() {
-            if let Some(intersection) = collider.toi_and_normal_with_ray(m, ray, max_toi, solid) {
-                return Some(intersection);
-            }
-        }
-        None
+        self.mesh_with_ray(m, ray, max_toi, solid)
+            .map(|(_, intersection)| intersection)
+    }
+}
+
+impl Geometry {
+    /// Nearest intersection of `ray` along with the index of the mesh hit.
+    pub fn mesh_with_ray(
+        &self,
+        m: &Isometry<f32>,
+        ray: &ncollide3d::query::Ray<f32>,
+        max_toi: f32,
+        solid: bool,
+    ) -> Option<(usize, ncollide3d::query::RayIntersection<f32>)> {
+        self.colliders
+            .iter()
+            .enumerate()
+            .filter_map(|(index, collider)| {
+                collider
+                    .toi_and_normal_with_ray(m, ray, max_toi, solid)
+                    .map(|intersection| (index, intersection))
+            })
+            .min_by(|(_, a), (_, b)| {
+                a.toi
+                    .partial_cmp(&b.toi)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
     }
 }
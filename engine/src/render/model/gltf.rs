@@ -0,0 +1,566 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::*;
+use log::info;
+use nalgebra::{Matrix3, Matrix4, Point2, Point3, Quaternion, UnitQuaternion, Vector3, Vector4};
+
+use crate::{animation, error::EngineError, render::texture};
+
+use super::{tangent, MaterialData, MeshData, ModelData, ModelVertex, MorphDelta, SkinVertex};
+
+/// Loads a glTF 2.0 (`.gltf` or `.glb`) file. Node transforms of the default
+/// scene are baked into the vertices, since models are drawn with a single
+/// instance transform.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ModelData> {
+    let (document, buffers, images) = ::gltf::import(path.as_ref())
+        .context(format!("Could not import glTF {:?}", path.as_ref()))?;
+
+    let mut materials = Vec::new();
+    for material in document.materials() {
+        materials.push(load_material(path.as_ref(), &images, &material)?);
+    }
+    // Primitives without a material use the glTF default material
+    let default_material = materials.len();
+    materials.push(MaterialData {
+        name: String::from("default"),
+        diffuse: None,
+        normal: None,
+        metallic_roughness: None,
+        base_color: [1.0, 1.0, 1.0, 1.0],
+        metallic: 1.0,
+        roughness: 1.0,
+    });
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .context("glTF file has no scenes")?;
+
+    let mut meshes = Vec::new();
+    for node in scene.nodes() {
+        load_node(
+            &node,
+            Matrix4::identity(),
+            &buffers,
+            default_material,
+            &mut meshes,
+        )?;
+    }
+
+    let skeleton = document
+        .skins()
+        .next()
+        .map(|skin| load_skeleton(&document, &skin, &buffers));
+    if let Some((skeleton, _)) = &skeleton {
+        check_skin(skeleton, &meshes)?;
+    }
+    let animations = match &skeleton {
+        Some((_, joint_map)) => document
+            .animations()
+            .map(|animation| load_animation(&animation, &buffers, joint_map))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    // External buffers and images can change independently of the file
+    let folder = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+    let external = |uri: &str| {
+        if uri.starts_with("data:") {
+            None
+        } else {
+            Some(folder.join(uri))
+        }
+    };
+    let sources = std::iter::once(path.as_ref().to_path_buf())
+        .chain(
+            document
+                .buffers()
+                .filter_map(|buffer| match buffer.source() {
+                    ::gltf::buffer::Source::Uri(uri) => external(uri),
+                    ::gltf::buffer::Source::Bin => None,
+                }),
+        )
+        .chain(document.images().filter_map(|image| match image.source() {
+            ::gltf::image::Source::Uri { uri, .. } => external(uri),
+            ::gltf::image::Source::View { .. } => None,
+        }))
+        .collect();
+
+    Ok(ModelData {
+        meshes,
+        materials,
+        skeleton: skeleton.map(|(skeleton, _)| skeleton),
+        animations,
+        sources,
+        lods: Vec::new(),
+    })
+}
+
+fn joint_pose(node: &::gltf::Node) -> animation::JointPose {
+    let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+    animation::JointPose {
+        translation: translation.into(),
+        rotation: UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)),
+        scale: scale.into(),
+    }
+}
+
+/// Builds the skeleton of `skin`, returning it with the mapping from glTF
+/// node index to joint index.
+fn load_skeleton(
+    document: &::gltf::Document,
+    skin: &::gltf::Skin,
+    buffers: &[::gltf::buffer::Data],
+) -> (animation::Skeleton, HashMap<usize, usize>) {
+    let mut parents = HashMap::new();
+    for node in document.nodes() {
+        for child in node.children() {
+            parents.insert(child.index(), node.index());
+        }
+    }
+
+    let joint_map = skin
+        .joints()
+        .enumerate()
+        .map(|(joint, node)| (node.index(), joint))
+        .collect::<HashMap<_, _>>();
+
+    let inverse_binds = skin
+        .reader(|buffer| Some(&buffers[buffer.index()]))
+        .read_inverse_bind_matrices()
+        .map(|matrices| matrices.map(Matrix4::from).collect::<Vec<_>>());
+
+    let joints = skin
+        .joints()
+        .enumerate()
+        .map(|(i, node)| animation::Joint {
+            name: node
+                .name()
+                .map(String::from)
+                .unwrap_or_else(|| format!("joint_{}", i)),
+            parent: parents
+                .get(&node.index())
+                .and_then(|parent| joint_map.get(parent))
+                .cloned(),
+            inverse_bind: inverse_binds
+                .as_ref()
+                .and_then(|m| m.get(i).cloned())
+                .unwrap_or_else(Matrix4::identity),
+        })
+        .collect::<Vec<_>>();
+
+    let rest = animation::Pose {
+        joints: skin.joints().map(|node| joint_pose(&node)).collect(),
+    };
+
+    info!("Load skeleton with {} joints", joints.len());
+    (animation::Skeleton::new(joints, rest), joint_map)
+}
+
+/// Fails for skins the joint buffer can't hold, or whose vertices refer to
+/// joints the skeleton doesn't have, which the shader would read past.
+fn check_skin(skeleton: &animation::Skeleton, meshes: &[MeshData]) -> Result<()> {
+    let joints = skeleton.joints.len();
+    if joints > animation::MAX_JOINTS {
+        return Err(EngineError::GpuLimit {
+            limit: "Skin joints",
+            requested: joints as u64,
+            max: animation::MAX_JOINTS as u64,
+        }
+        .into());
+    }
+    let out_of_range = meshes
+        .iter()
+        .filter_map(|mesh| mesh.skin.as_ref())
+        .flatten()
+        .flat_map(|vertex| vertex.joints.iter())
+        .find(|joint| **joint as usize >= joints);
+    if let Some(joint) = out_of_range {
+        bail!("Vertex refers to joint {} of a skin with {}", joint, joints);
+    }
+    Ok(())
+}
+
+fn load_animation(
+    animation: &::gltf::Animation,
+    buffers: &[::gltf::buffer::Data],
+    joint_map: &HashMap<usize, usize>,
+) -> animation::AnimationClip {
+    use ::gltf::animation::{util::ReadOutputs, Interpolation};
+
+    let name = animation
+        .name()
+        .map(String::from)
+        .unwrap_or_else(|| format!("animation_{}", animation.index()));
+    info!("Load animation {:?}", name);
+
+    let mut channels = Vec::new();
+    for channel in animation.channels() {
+        let joint = match joint_map.get(&channel.target().node().index()) {
+            Some(joint) => *joint,
+            None => continue,
+        };
+        let sampler = channel.sampler();
+        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let times = match reader.read_inputs() {
+            Some(inputs) => inputs.collect::<Vec<_>>(),
+            None => continue,
+        };
+
+        // Cubic spline samplers store (in tangent, value, out tangent)
+        // triples, only the values are kept.
+        let (interpolation, stride, offset) = match sampler.interpolation() {
+            Interpolation::Step => (animation::Interpolation::Step, 1, 0),
+            Interpolation::Linear => (animation::Interpolation::Linear, 1, 0),
+            Interpolation::CubicSpline => (animation::Interpolation::Linear, 3, 1),
+        };
+
+        let keyframes = match reader.read_outputs() {
+            Some(ReadOutputs::Translations(values)) => animation::Keyframes::Translation(
+                values
+                    .skip(offset)
+                    .step_by(stride)
+                    .map(Vector3::from)
+                    .collect(),
+            ),
+            Some(ReadOutputs::Rotations(values)) => animation::Keyframes::Rotation(
+                values
+                    .into_f32()
+                    .skip(offset)
+                    .step_by(stride)
+                    .map(|[x, y, z, w]| {
+                        UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z))
+                    })
+                    .collect(),
+            ),
+            Some(ReadOutputs::Scales(values)) => animation::Keyframes::Scale(
+                values
+                    .skip(offset)
+                    .step_by(stride)
+                    .map(Vector3::from)
+                    .collect(),
+            ),
+            _ => continue,
+        };
+
+        channels.push(animation::Channel {
+            joint,
+            interpolation,
+            times,
+            keyframes,
+        });
+    }
+
+    let duration = channels
+        .iter()
+        .filter_map(|channel| channel.times.last())
+        .cloned()
+        .fold(0.0, f32::max);
+
+    animation::AnimationClip {
+        name,
+        duration,
+        channels,
+    }
+}
+
+fn load_node(
+    node: &::gltf::Node,
+    parent: Matrix4<f32>,
+    buffers: &[::gltf::buffer::Data],
+    default_material: usize,
+    meshes: &mut Vec<MeshData>,
+) -> Result<()> {
+    let local: Matrix4<f32> = node.transform().matrix().into();
+    let world = parent * local;
+    // Skinned meshes are positioned by their joints, not by the node
+    let mesh_transform = if node.skin().is_some() {
+        Matrix4::identity()
+    } else {
+        world
+    };
+
+    if let Some(mesh) = node.mesh() {
+        let name = mesh
+            .name()
+            .or(node.name())
+            .map(String::from)
+            .unwrap_or_else(|| format!("mesh_{}", mesh.index()));
+
+        for (i, primitive) in mesh.primitives().enumerate() {
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let material = primitive.material().index().unwrap_or(default_material);
+
+            meshes.push(load_primitive(
+                format!("{}_{}", name, i),
+                &reader,
+                material,
+                &mesh_transform,
+            )?);
+        }
+    }
+
+    for child in node.children() {
+        load_node(&child, world, buffers, default_material, meshes)?;
+    }
+
+    Ok(())
+}
+
+fn load_primitive<'a, 's, F>(
+    name: String,
+    reader: &::gltf::mesh::Reader<'a, 's, F>,
+    material: usize,
+    transform: &Matrix4<f32>,
+) -> Result<MeshData>
+where
+    F: Clone + Fn(::gltf::Buffer<'a>) -> Option<&'s [u8]>,
+{
+    info!("Load glTF primitive {:?}", name);
+    let normal_matrix: Matrix3<f32> = transform
+        .fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0)
+        .into_owned()
+        .try_inverse()
+        .map(|m| m.transpose())
+        .unwrap_or_else(Matrix3::identity);
+
+    let positions = reader
+        .read_positions()
+        .context(format!("Primitive {:?} has no positions", name))?
+        .collect::<Vec<_>>();
+    let normals = reader.read_normals().map(|n| n.collect::<Vec<_>>());
+    let tex_coords = reader
+        .read_tex_coords(0)
+        .map(|t| t.into_f32().collect::<Vec<_>>());
+    let tex_coords2 = reader
+        .read_tex_coords(1)
+        .map(|t| t.into_f32().collect::<Vec<_>>());
+    let colors = reader
+        .read_colors(0)
+        .map(|c| c.into_rgba_f32().collect::<Vec<_>>());
+
+    let mut vertices = positions
+        .iter()
+        .enumerate()
+        .map(|(i, position)| {
+            let tex_coords: Point2<f32> = tex_coords
+                .as_ref()
+                .map(|t| t[i].into())
+                .unwrap_or_else(Point2::origin);
+            ModelVertex {
+                position: transform.transform_point(&Point3::from(*position)),
+                tex_coords,
+                normal: normals
+                    .as_ref()
+                    .map(|n| (normal_matrix * Vector3::from(n[i])).normalize())
+                    .unwrap_or_else(Vector3::zeros),
+                tangent: Vector3::zeros(),
+                bitangent: Vector3::zeros(),
+                color: colors
+                    .as_ref()
+                    .map(|c| Vector4::from(c[i]))
+                    .unwrap_or_else(|| Vector4::new(1.0, 1.0, 1.0, 1.0)),
+                tex_coords2: tex_coords2
+                    .as_ref()
+                    .map(|t| t[i].into())
+                    .unwrap_or(tex_coords),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let joints = reader
+        .read_joints(0)
+        .map(|j| j.into_u16().collect::<Vec<_>>());
+    let weights = reader
+        .read_weights(0)
+        .map(|w| w.into_f32().collect::<Vec<_>>());
+    let skin = match (joints, weights) {
+        (Some(joints), Some(weights)) => Some(
+            joints
+                .iter()
+                .zip(weights.iter())
+                .map(|(j, w)| SkinVertex {
+                    joints: [j[0] as u32, j[1] as u32, j[2] as u32, j[3] as u32],
+                    weights: *w,
+                })
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    let morph_targets = reader
+        .read_morph_targets()
+        .take(animation::morph::MAX_MORPH_TARGETS)
+        .map(|(positions, normals, _)| {
+            let positions = positions.map(|p| p.collect::<Vec<_>>());
+            let normals = normals.map(|n| n.collect::<Vec<_>>());
+            (0..vertices.len())
+                .map(|i| {
+                    let position = positions
+                        .as_ref()
+                        .map(|p| transform.transform_vector(&Vector3::from(p[i])))
+                        .unwrap_or_else(Vector3::zeros);
+                    let normal = normals
+                        .as_ref()
+                        .map(|n| normal_matrix * Vector3::from(n[i]))
+                        .unwrap_or_else(Vector3::zeros);
+                    MorphDelta {
+                        position: [position.x, position.y, position.z, 0.0],
+                        normal: [normal.x, normal.y, normal.z, 0.0],
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let indices = reader
+        .read_indices()
+        .map(|i| i.into_u32().collect::<Vec<_>>())
+        .unwrap_or_else(|| (0..vertices.len() as u32).collect());
+
+    if normals.is_none() {
+        info!("Primitive {:?} has no normals, generating them", name);
+        tangent::generate_normals(&mut vertices, &indices);
+    }
+    tangent::generate_tangents(&mut vertices, &indices);
+
+    Ok(MeshData {
+        name,
+        vertices,
+        indices,
+        skin,
+        morph_targets,
+        material,
+    })
+}
+
+fn load_material(
+    path: &Path,
+    images: &[::gltf::image::Data],
+    material: &::gltf::Material,
+) -> Result<MaterialData> {
+    let name = material
+        .name()
+        .map(String::from)
+        .unwrap_or_else(|| format!("material_{}", material.index().unwrap_or(0)));
+    let pbr = material.pbr_metallic_roughness();
+
+    // Embedded images are labelled by file and index so materials sharing
+    // an image share the uploaded texture
+    let image_label = |index: usize| format!("{}#{}", path.display(), index);
+
+    let diffuse = match pbr.base_color_texture() {
+        Some(info) => {
+            let index = info.texture().source().index();
+            Some(
+                image_texture(&images[index], &image_label(index), false)?
+                    .with_sampler(sampler_desc(&info.texture().sampler())),
+            )
+        }
+        None => None,
+    };
+
+    let normal = match material.normal_texture() {
+        Some(normal) => {
+            let index = normal.texture().source().index();
+            Some(
+                image_texture(&images[index], &image_label(index), true)?
+                    .with_sampler(sampler_desc(&normal.texture().sampler())),
+            )
+        }
+        None => None,
+    };
+
+    let metallic_roughness = match pbr.metallic_roughness_texture() {
+        Some(info) => {
+            let index = info.texture().source().index();
+            Some(
+                image_texture(&images[index], &image_label(index), true)?
+                    .with_sampler(sampler_desc(&info.texture().sampler())),
+            )
+        }
+        None => None,
+    };
+
+    // The factors scale the maps in the shader, textured or not
+    Ok(MaterialData {
+        name,
+        diffuse,
+        normal,
+        metallic_roughness,
+        base_color: pbr.base_color_factor(),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+    })
+}
+
+fn sampler_desc(sampler: &::gltf::texture::Sampler) -> texture::SamplerDesc {
+    use ::gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        _ => wgpu::FilterMode::Linear,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => {
+            (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::NearestMipmapLinear) => {
+            (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear)
+        }
+        Some(MinFilter::Linear) | Some(MinFilter::LinearMipmapNearest) => {
+            (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::LinearMipmapLinear) | None => {
+            (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+        }
+    };
+
+    texture::SamplerDesc {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        ..Default::default()
+    }
+}
+
+fn image_texture(
+    data: &::gltf::image::Data,
+    label: &str,
+    is_normal_map: bool,
+) -> Result<texture::TextureData> {
+    use ::gltf::image::Format;
+
+    let image = match data.format {
+        Format::R8G8B8A8 => {
+            image::RgbaImage::from_raw(data.width, data.height, data.pixels.clone())
+                .map(image::DynamicImage::ImageRgba8)
+        }
+        Format::R8G8B8 => image::RgbImage::from_raw(data.width, data.height, data.pixels.clone())
+            .map(image::DynamicImage::ImageRgb8),
+        Format::R8G8 => {
+            image::GrayAlphaImage::from_raw(data.width, data.height, data.pixels.clone())
+                .map(image::DynamicImage::ImageLumaA8)
+        }
+        Format::R8 => image::GrayImage::from_raw(data.width, data.height, data.pixels.clone())
+            .map(image::DynamicImage::ImageLuma8),
+        format => bail!("Unsupported glTF image format {:?} in {}", format, label),
+    }
+    .context(format!("Invalid glTF image data in {}", label))?;
+
+    Ok(texture::TextureData {
+        label: String::from(label),
+        pixels: texture::TexturePixels::Image(image),
+        is_normal_map,
+        sampler: texture::SamplerDesc::default(),
+    })
+}
@@ -0,0 +1,136 @@
+//! Lower detail versions of a model, picked per entity from how much of the
+//! screen it covers. Levels are either authored files or generated with a
+//! vertex clustering decimator.
+
+use std::collections::HashMap;
+
+use log::info;
+use nalgebra::{Point3, Vector3};
+
+use super::{tangent, Geometry, MeshData, ModelVertex};
+
+/// Screen heights below which the generated levels are used.
+pub const DEFAULT_SCREEN_SIZES: [f32; 3] = [0.25, 0.1, 0.04];
+
+/// Grid resolution along the longest side for the first generated level,
+/// halved for every level after it.
+const BASE_CLUSTER_CELLS: f32 = 32.0;
+
+/// Meshes of a detail level before upload.
+pub struct LodData {
+    pub meshes: Vec<MeshData>,
+    pub screen_size: f32,
+}
+
+pub struct Lod {
+    pub geometry: Geometry,
+    /// Fraction of the screen height below which this level is drawn
+    pub screen_size: f32,
+}
+
+/// Generates one level per entry of `screen_sizes`, each coarser than the
+/// one before. Skinned and morphed meshes are left out, they can't be
+/// decimated without their weights.
+pub fn generate(meshes: &[MeshData], screen_sizes: &[f32]) -> Vec<LodData> {
+    screen_sizes
+        .iter()
+        .enumerate()
+        .map(|(level, &screen_size)| {
+            let cells = BASE_CLUSTER_CELLS / 2f32.powi(level as i32);
+            LodData {
+                meshes: meshes
+                    .iter()
+                    .filter(|mesh| mesh.skin.is_none() && mesh.morph_targets.is_empty())
+                    .map(|mesh| decimate(mesh, cells))
+                    .filter(|mesh| !mesh.indices.is_empty())
+                    .collect(),
+                screen_size,
+            }
+        })
+        .collect()
+}
+
+/// Merges all vertices falling in the same grid cell, `cells` being the
+/// number of cells along the longest side of the mesh. Vertices facing
+/// different ways are kept apart so hard edges survive.
+pub fn decimate(mesh: &MeshData, cells: f32) -> MeshData {
+    let (min, max) = mesh.vertices.iter().fold(
+        (Vector3::repeat(f32::MAX), Vector3::repeat(f32::MIN)),
+        |(min, max), v| (min.inf(&v.position.coords), max.sup(&v.position.coords)),
+    );
+    let cell_size = ((max - min).max() / cells.max(1.0)).max(f32::EPSILON);
+
+    let mut clusters: HashMap<(i32, i32, i32, usize), u32> = HashMap::new();
+    let mut sums: Vec<(ModelVertex, Vector3<f32>, f32)> = Vec::new();
+    let remap = mesh
+        .vertices
+        .iter()
+        .map(|v| {
+            let cell = (v.position.coords - min) / cell_size;
+            let key = (
+                cell.x as i32,
+                cell.y as i32,
+                cell.z as i32,
+                dominant_axis(&v.normal),
+            );
+            *clusters.entry(key).or_insert_with(|| {
+                sums.push((*v, Vector3::zeros(), 0.0));
+                sums.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (v, &cluster) in mesh.vertices.iter().zip(remap.iter()) {
+        let (_, position, count) = &mut sums[cluster as usize];
+        *position += v.position.coords;
+        *count += 1.0;
+    }
+
+    let mut vertices = sums
+        .into_iter()
+        .map(|(mut vertex, position, count)| {
+            vertex.position = Point3::from(position / count);
+            vertex
+        })
+        .collect::<Vec<_>>();
+
+    let indices = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|c| {
+            [
+                remap[c[0] as usize],
+                remap[c[1] as usize],
+                remap[c[2] as usize],
+            ]
+        })
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .flat_map(|triangle| triangle.to_vec())
+        .collect::<Vec<_>>();
+
+    tangent::generate_tangents(&mut vertices, &indices);
+    info!(
+        "Decimated mesh {:?} from {} to {} triangles",
+        mesh.name,
+        mesh.indices.len() / 3,
+        indices.len() / 3
+    );
+
+    MeshData {
+        name: format!("{} (lod)", mesh.name),
+        vertices,
+        indices,
+        skin: None,
+        morph_targets: Vec::new(),
+        material: mesh.material,
+    }
+}
+
+fn dominant_axis(normal: &Vector3<f32>) -> usize {
+    let axis = normal.iamax();
+    if normal[axis] < 0.0 {
+        axis + 3
+    } else {
+        axis
+    }
+}
@@ -1,10 +1,72 @@
+use anyhow::*;
 use log::info;
 
-use crate::render::{binding, state, texture};
+use crate::{
+    assets,
+    render::{
+        binding::{self, BufferUsage},
+        reflection, state, texture,
+    },
+};
+
+/// Set in [`MaterialUniform::map_flags`] for every map the material provides.
+pub const DIFFUSE_MAP: u32 = 1;
+pub const NORMAL_MAP: u32 = 2;
+/// The diffuse map is the reflection target, sampled at window positions
+pub const REFLECTION_MAP: u32 = 4;
+/// Roughness in the green and metalness in the blue channel, as in glTF
+pub const METALLIC_ROUGHNESS_MAP: u32 = 8;
+
+/// Roughness of materials that don't give one, the highlight the forward
+/// shader always had
+pub const DEFAULT_ROUGHNESS: f32 = 0.5;
+
+/// Material textures decoded on a loader thread, missing maps are replaced
+/// by fallback textures on upload.
+pub struct MaterialData {
+    pub name: String,
+    pub diffuse: Option<texture::TextureData>,
+    pub normal: Option<texture::TextureData>,
+    pub metallic_roughness: Option<texture::TextureData>,
+    /// Multiplied with the diffuse map, or used on its own without one
+    pub base_color: [f32; 4],
+    /// Multiplied with the metallic-roughness map, or used on their own
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MaterialUniform {
+    pub base_color: [f32; 4],
+    pub map_flags: u32,
+    pub metallic: f32,
+    pub roughness: f32,
+    _padding: u32,
+}
+
+unsafe impl bytemuck::Pod for MaterialUniform {}
+unsafe impl bytemuck::Zeroable for MaterialUniform {}
+
+impl MaterialUniform {
+    pub fn new(base_color: [f32; 4], metallic: f32, roughness: f32, map_flags: u32) -> Self {
+        Self {
+            base_color,
+            map_flags,
+            metallic,
+            roughness,
+            _padding: 0,
+        }
+    }
+}
 
 pub struct Material {
     pub(super) name: String,
     pub(super) textures: binding::TextureBinding,
+    uniform: MaterialUniform,
+    uniform_buffer: binding::Buffer,
+    /// Keeps cached textures alive while the material uses them
+    texture_handles: Vec<assets::Handle<texture::Texture>>,
 }
 
 impl Material {
@@ -13,17 +75,219 @@ impl Material {
         name: &str,
         diffuse_texture: texture::Texture,
         normal_texture: texture::Texture,
-        material_layout: &wgpu::BindGroupLayout,
+        metallic_roughness_texture: texture::Texture,
+        material_layout: &binding::BindGroupLayout,
     ) -> Self {
         info!("Create material {:?}", name);
+        let uniform = MaterialUniform::new(
+            [1.0; 4],
+            1.0,
+            1.0,
+            DIFFUSE_MAP | NORMAL_MAP | METALLIC_ROUGHNESS_MAP,
+        );
+        let uniform_buffer =
+            binding::Buffer::new_init(state, name, &[uniform], BufferUsage::Uniform);
         Self {
             name: String::from(name),
-            textures: binding::TextureBinding::new(
+            textures: binding::TextureBinding::with_buffers(
                 state,
                 Some(name),
                 material_layout,
-                &[diffuse_texture, normal_texture],
+                &[
+                    &diffuse_texture,
+                    &normal_texture,
+                    &metallic_roughness_texture,
+                ],
+                &[&uniform_buffer],
             ),
+            uniform,
+            uniform_buffer,
+            texture_handles: Vec::new(),
+        }
+    }
+
+    /// Creates the material from decoded textures, uploading only those not
+    /// already in `textures`. Missing maps bind a 1x1 fallback texture and
+    /// are left out of the map flags.
+    pub fn from_data(
+        state: &state::WgpuState,
+        data: &MaterialData,
+        textures: &mut assets::Assets<texture::Texture>,
+        material_layout: &binding::BindGroupLayout,
+    ) -> Result<Self> {
+        info!("Create material {:?}", data.name);
+        let fallback_diffuse = texture::TextureData::solid([1.0, 1.0, 1.0, 1.0], false);
+        let fallback_normal = texture::TextureData::solid([0.5, 0.5, 1.0, 1.0], true);
+        let fallback_metallic_roughness = texture::TextureData::solid([1.0, 1.0, 1.0, 1.0], true);
+
+        let mut map_flags = 0;
+        if data.diffuse.is_some() {
+            map_flags |= DIFFUSE_MAP;
         }
+        if data.normal.is_some() {
+            map_flags |= NORMAL_MAP;
+        }
+        if data.metallic_roughness.is_some() {
+            map_flags |= METALLIC_ROUGHNESS_MAP;
+        }
+
+        let texture_handles = [
+            data.diffuse.as_ref().unwrap_or(&fallback_diffuse),
+            data.normal.as_ref().unwrap_or(&fallback_normal),
+            data.metallic_roughness
+                .as_ref()
+                .unwrap_or(&fallback_metallic_roughness),
+        ]
+        .iter()
+        .map(|texture_data| {
+            textures.get_or_load(texture_data.cache_key(), || {
+                texture::Texture::from_data(state, texture_data)
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+        Self::with_handles(
+            state,
+            &data.name,
+            textures,
+            texture_handles,
+            MaterialUniform::new(data.base_color, data.metallic, data.roughness, map_flags),
+            material_layout,
+        )
+    }
+
+    /// Material showing the cached texture `key` as its diffuse map, used to
+    /// preview textures on their own.
+    pub fn from_cached_texture(
+        state: &state::WgpuState,
+        key: &str,
+        textures: &mut assets::Assets<texture::Texture>,
+        material_layout: &binding::BindGroupLayout,
+    ) -> Result<Self> {
+        let diffuse = textures
+            .handle(key)
+            .with_context(|| format!("Texture {:?} is not loaded", key))?;
+        let fallback_normal = texture::TextureData::solid([0.5, 0.5, 1.0, 1.0], true);
+        let normal = textures.get_or_load(fallback_normal.cache_key(), || {
+            texture::Texture::from_data(state, &fallback_normal)
+        })?;
+        let fallback_metallic_roughness = texture::TextureData::solid([1.0, 1.0, 1.0, 1.0], true);
+        let metallic_roughness = textures
+            .get_or_load(fallback_metallic_roughness.cache_key(), || {
+                texture::Texture::from_data(state, &fallback_metallic_roughness)
+            })?;
+
+        Self::with_handles(
+            state,
+            key,
+            textures,
+            vec![diffuse, normal, metallic_roughness],
+            MaterialUniform::new([1.0; 4], 0.0, DEFAULT_ROUGHNESS, DIFFUSE_MAP),
+            material_layout,
+        )
+    }
+
+    fn with_handles(
+        state: &state::WgpuState,
+        name: &str,
+        textures: &assets::Assets<texture::Texture>,
+        texture_handles: Vec<assets::Handle<texture::Texture>>,
+        uniform: MaterialUniform,
+        material_layout: &binding::BindGroupLayout,
+    ) -> Result<Self> {
+        let bound = texture_handles
+            .iter()
+            .map(|handle| textures.get(handle).context("Texture missing from cache"))
+            .collect::<Result<Vec<_>>>()?;
+
+        let uniform_buffer =
+            binding::Buffer::new_init(state, name, &[uniform], BufferUsage::Uniform);
+
+        Ok(Self {
+            name: name.to_string(),
+            textures: binding::TextureBinding::with_buffers(
+                state,
+                Some(name),
+                material_layout,
+                &bound,
+                &[&uniform_buffer],
+            ),
+            uniform,
+            uniform_buffer,
+            texture_handles,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn uniform(&self) -> &MaterialUniform {
+        &self.uniform
+    }
+
+    pub fn set_base_color(&mut self, state: &state::WgpuState, base_color: [f32; 4]) {
+        self.uniform.base_color = base_color;
+        self.uniform_buffer.write(state, &[self.uniform]);
+    }
+
+    /// Cached textures used by the material, empty for materials created
+    /// from owned textures.
+    pub fn texture_handles(&self) -> &[assets::Handle<texture::Texture>] {
+        &self.texture_handles
+    }
+
+    /// Puts the cached texture `handle` in `slot`, 0 for the diffuse map, 1
+    /// for the normal map and 2 for the metallic-roughness map, and rebinds
+    /// the material.
+    pub fn set_texture(
+        &mut self,
+        state: &state::WgpuState,
+        slot: usize,
+        handle: assets::Handle<texture::Texture>,
+        textures: &assets::Assets<texture::Texture>,
+        material_layout: &binding::BindGroupLayout,
+    ) -> Result<()> {
+        if slot >= self.texture_handles.len() {
+            bail!("Material {:?} has no texture slot {}", self.name, slot);
+        }
+        if slot == 0 {
+            self.uniform.map_flags |= DIFFUSE_MAP;
+            if textures.name(&handle) == Some(reflection::REFLECTION_TEXTURE) {
+                self.uniform.map_flags |= REFLECTION_MAP;
+            } else {
+                self.uniform.map_flags &= !REFLECTION_MAP;
+            }
+        } else if slot == 1 {
+            self.uniform.map_flags |= NORMAL_MAP;
+        } else {
+            self.uniform.map_flags |= METALLIC_ROUGHNESS_MAP;
+        }
+        self.texture_handles[slot] = handle;
+        self.uniform_buffer.write(state, &[self.uniform]);
+        self.rebind(state, textures, material_layout)
+    }
+
+    /// Binds the material's textures again, after any of them was replaced
+    /// in `textures`.
+    pub fn rebind(
+        &mut self,
+        state: &state::WgpuState,
+        textures: &assets::Assets<texture::Texture>,
+        material_layout: &binding::BindGroupLayout,
+    ) -> Result<()> {
+        let bound = self
+            .texture_handles
+            .iter()
+            .map(|handle| textures.get(handle).context("Texture missing from cache"))
+            .collect::<Result<Vec<_>>>()?;
+        self.textures = binding::TextureBinding::with_buffers(
+            state,
+            Some(self.name.as_str()),
+            material_layout,
+            &bound,
+            &[&self.uniform_buffer],
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+use log::info;
+
+use crate::render::{
+    binding::{self, BufferUsage},
+    state, texture,
+    traits::Vertex,
+};
+
+use super::MaterialData;
+
+/// Many materials packed into the layers of one diffuse and one normal
+/// array texture, so meshes using any of them share a single bind group.
+/// Every layer has the same size, images are scaled to fit.
+pub struct MaterialArray {
+    pub(super) name: String,
+    pub(super) textures: binding::TextureBinding,
+    layers: HashMap<String, u32>,
+    layer_size: u32,
+}
+
+impl MaterialArray {
+    pub fn new(
+        state: &state::WgpuState,
+        name: &str,
+        materials: &[MaterialData],
+        layer_size: u32,
+        layout: &binding::BindGroupLayout,
+    ) -> Result<Self> {
+        info!(
+            "Create material array {:?} with {} layers",
+            name,
+            materials.len()
+        );
+
+        let mut diffuse = Vec::new();
+        let mut normal = Vec::new();
+        let mut layers = HashMap::new();
+        for (layer, material) in materials.iter().enumerate() {
+            let mut diffuse_layer = layer_image(material.diffuse.as_ref(), [1.0; 4], layer_size)?;
+            // Base colors are baked in, the array shader has no per layer
+            // uniforms
+            for pixel in diffuse_layer.pixels_mut() {
+                for (channel, factor) in pixel.0.iter_mut().zip(material.base_color.iter()) {
+                    *channel = (*channel as f32 * factor) as u8;
+                }
+            }
+            diffuse.push(diffuse_layer);
+            normal.push(layer_image(
+                material.normal.as_ref(),
+                [0.5, 0.5, 1.0, 1.0],
+                layer_size,
+            )?);
+            layers.insert(material.name.clone(), layer as u32);
+        }
+
+        let sampler = texture::SamplerDesc::repeat();
+        let diffuse = texture::Texture::from_layers(
+            state,
+            &diffuse,
+            Some(format!("{} diffuse", name).as_str()),
+            false,
+            &sampler,
+        )?;
+        let normal = texture::Texture::from_layers(
+            state,
+            &normal,
+            Some(format!("{} normal", name).as_str()),
+            true,
+            &sampler,
+        )?;
+
+        Ok(Self {
+            name: String::from(name),
+            textures: binding::TextureBinding::new(state, name, layout, &[diffuse, normal]),
+            layers,
+            layer_size,
+        })
+    }
+
+    pub fn layer(&self, material: &str) -> Option<u32> {
+        self.layers.get(material).copied()
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn layer_size(&self) -> u32 {
+        self.layer_size
+    }
+}
+
+/// `data` scaled to a `size` square, or a solid `fallback` color without it.
+pub(crate) fn layer_image(
+    data: Option<&texture::TextureData>,
+    fallback: [f32; 4],
+    size: u32,
+) -> Result<image::RgbaImage> {
+    let data = match data {
+        Some(data) => data,
+        None => {
+            let color = fallback
+                .iter()
+                .map(|c| (c * 255.0) as u8)
+                .collect::<Vec<_>>();
+            return Ok(image::RgbaImage::from_pixel(
+                size,
+                size,
+                image::Rgba([color[0], color[1], color[2], color[3]]),
+            ));
+        }
+    };
+
+    match &data.pixels {
+        texture::TexturePixels::Image(image) => Ok(image::imageops::resize(
+            &image.to_rgba8(),
+            size,
+            size,
+            image::imageops::FilterType::Triangle,
+        )),
+        _ => bail!(
+            "Only 8 bit images can be packed into material arrays, {} is not",
+            data.label
+        ),
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct LayerInstance {
+    layer: u32,
+}
+
+unsafe impl bytemuck::Pod for LayerInstance {}
+unsafe impl bytemuck::Zeroable for LayerInstance {}
+
+impl Vertex for LayerInstance {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<LayerInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[wgpu::VertexAttributeDescriptor {
+                offset: 0,
+                shader_location: 13,
+                format: wgpu::VertexFormat::Uint,
+            }],
+        }
+    }
+}
+
+/// Per entity layer of a [`MaterialArray`], bound as an instance buffer.
+pub struct ArrayLayer {
+    pub array: String,
+    layer: u32,
+    buffer: binding::Buffer,
+}
+
+impl ArrayLayer {
+    pub fn new(state: &state::WgpuState, array: &MaterialArray, material: &str) -> Result<Self> {
+        let layer = array.layer(material).context(format!(
+            "Material {:?} is not in array {:?}",
+            material, array.name
+        ))?;
+        Ok(Self {
+            array: array.name.clone(),
+            layer,
+            buffer: binding::Buffer::new_init(
+                state,
+                material,
+                &[LayerInstance { layer }],
+                BufferUsage::Transform,
+            ),
+        })
+    }
+
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    pub fn set_layer(&mut self, state: &state::WgpuState, layer: u32) {
+        self.layer = layer;
+        self.buffer.write(state, &[LayerInstance { layer }]);
+    }
+
+    pub fn buffer(&self) -> &binding::Buffer {
+        &self.buffer
+    }
+}
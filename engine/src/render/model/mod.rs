@@ -1,63 +1,232 @@
 pub mod geometry;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+pub mod lod;
 pub mod material;
+pub mod material_array;
+pub mod primitives;
+pub mod tangent;
 pub mod vertex;
 
-pub use geometry::{Geometry, Mesh};
-pub use material::Material;
-pub use vertex::ModelVertex;
+pub use geometry::{Geometry, Mesh, MeshData};
+pub use lod::{Lod, LodData};
+pub use material::{Material, MaterialData, MaterialUniform};
+pub use material_array::{ArrayLayer, LayerInstance, MaterialArray};
+pub use primitives::Primitive;
+pub use vertex::{ModelVertex, MorphDelta, SkinVertex};
 
 use anyhow::*;
 use log::info;
-use std::{ops::Range, path::Path};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{animation, assets};
 
 use super::{
-    binding, state, texture,
+    binding,
+    indirect::DrawIndexedIndirect,
+    state, texture,
     traits::{Binding, DrawLight, DrawModel},
 };
 
 pub struct Model {
     pub geometry: Geometry,
+    /// Lower detail levels, ordered from the most detailed
+    pub lods: Vec<Lod>,
     pub materials: Vec<Material>,
+    pub skeleton: Option<animation::Skeleton>,
+    pub animations: Vec<animation::AnimationClip>,
 }
 
-impl Model {
-    pub fn load<P: AsRef<Path>>(
-        state: &state::WgpuState,
-        material_layout: &wgpu::BindGroupLayout,
-        path: P,
-    ) -> Result<Self> {
+/// Everything a model needs before touching the GPU, so files can be parsed
+/// and decoded on loader threads.
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+    pub skeleton: Option<animation::Skeleton>,
+    pub animations: Vec<animation::AnimationClip>,
+    /// Files the model was built from, the model file itself first. Empty
+    /// for generated models.
+    pub sources: Vec<PathBuf>,
+    pub lods: Vec<LodData>,
+}
+
+impl ModelData {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         info!("Load model {:?}", path.as_ref());
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        match extension.as_deref() {
+            Some("obj") => Self::load_obj(path),
+            #[cfg(feature = "gltf")]
+            Some("gltf") | Some("glb") => self::gltf::load(path),
+            _ => bail!("Unsupported model format {:?}", path.as_ref()),
+        }
+    }
+
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Self> {
         let (obj_models, obj_materials) = tobj::load_obj(path.as_ref(), true)?;
 
         // We're assuming that the texture files are stored with the obj file
         let containing_folder = path.as_ref().parent().context("Directory has no parent")?;
 
+        let mut sources = vec![path.as_ref().to_path_buf()];
+        // tobj doesn't report which material libraries it read
+        for line in BufReader::new(File::open(path.as_ref())?).lines() {
+            if let Some(library) = line?.strip_prefix("mtllib ") {
+                sources.push(containing_folder.join(library.trim()));
+            }
+        }
+
         let mut materials = Vec::new();
         for mat in obj_materials {
-            let diffuse_path = mat.diffuse_texture;
-            let diffuse_texture =
-                texture::Texture::load(state, containing_folder.join(diffuse_path), false)?;
+            let diffuse = match mat.diffuse_texture.as_str() {
+                "" => None,
+                path => {
+                    sources.push(containing_folder.join(path));
+                    Some(texture::TextureData::load(
+                        containing_folder.join(path),
+                        false,
+                    )?)
+                }
+            };
 
-            let normal_path = mat.normal_texture;
-            let normal_texture =
-                texture::Texture::load(state, containing_folder.join(normal_path), true)?;
+            let normal = match mat.normal_texture.as_str() {
+                "" => None,
+                path => {
+                    sources.push(containing_folder.join(path));
+                    Some(texture::TextureData::load(
+                        containing_folder.join(path),
+                        true,
+                    )?)
+                }
+            };
 
-            materials.push(Material::new(
-                state,
-                &mat.name,
-                diffuse_texture,
-                normal_texture,
-                material_layout,
-            ));
-        }
+            // Kd only tints untextured materials, textured ones would get
+            // darker than they used to
+            let base_color = match diffuse {
+                Some(_) => [1.0, 1.0, 1.0, mat.dissolve],
+                None => [mat.diffuse[0], mat.diffuse[1], mat.diffuse[2], mat.dissolve],
+            };
 
-        let geometry = Geometry::new(state, obj_models);
+            materials.push(MaterialData {
+                name: mat.name,
+                diffuse,
+                normal,
+                metallic_roughness: None,
+                base_color,
+                metallic: 0.0,
+                roughness: material::DEFAULT_ROUGHNESS,
+            });
+        }
 
         Ok(Self {
-            geometry,
+            meshes: obj_models.into_iter().map(MeshData::from_obj).collect(),
             materials,
+            skeleton: None,
+            animations: Vec::new(),
+            sources,
+            lods: Vec::new(),
         })
     }
+
+    /// Adds decimated levels drawn below each of `screen_sizes`.
+    pub fn with_generated_lods(mut self, screen_sizes: &[f32]) -> Self {
+        self.lods = lod::generate(&self.meshes, screen_sizes);
+        self
+    }
+}
+
+impl Model {
+    pub fn load<P: AsRef<Path>>(
+        state: &state::WgpuState,
+        material_layout: &binding::BindGroupLayout,
+        textures: &mut assets::Assets<texture::Texture>,
+        path: P,
+    ) -> Result<Self> {
+        Self::from_data(state, material_layout, textures, ModelData::load(path)?)
+    }
+
+    /// Uploads `data`, sharing textures already in `textures` with other
+    /// models.
+    pub fn from_data(
+        state: &state::WgpuState,
+        material_layout: &binding::BindGroupLayout,
+        textures: &mut assets::Assets<texture::Texture>,
+        data: ModelData,
+    ) -> Result<Self> {
+        let materials = data
+            .materials
+            .iter()
+            .map(|material| Material::from_data(state, material, textures, material_layout))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut model = Self {
+            geometry: Geometry::from_meshes(state, data.meshes),
+            lods: Vec::new(),
+            materials,
+            skeleton: data.skeleton,
+            animations: data.animations,
+        };
+        for lod in data.lods {
+            model.add_lod(state, lod)?;
+        }
+        Ok(model)
+    }
+
+    /// Uploads another detail level, its meshes index into the materials of
+    /// this model.
+    pub fn add_lod(&mut self, state: &state::WgpuState, lod: LodData) -> Result<()> {
+        if lod.meshes.is_empty() {
+            return Ok(());
+        }
+        if let Some(mesh) = lod
+            .meshes
+            .iter()
+            .find(|mesh| mesh.material >= self.materials.len())
+        {
+            bail!(
+                "Detail level mesh {:?} uses missing material {}",
+                mesh.name,
+                mesh.material
+            );
+        }
+
+        self.lods.push(Lod {
+            geometry: Geometry::from_meshes(state, lod.meshes),
+            screen_size: lod.screen_size,
+        });
+        self.lods.sort_by(|a, b| {
+            b.screen_size
+                .partial_cmp(&a.screen_size)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(())
+    }
+
+    /// The level to draw when the model covers `screen_size` of the screen
+    /// height, 0 being the full detail geometry.
+    pub fn select_lod(&self, screen_size: f32) -> usize {
+        self.lods
+            .iter()
+            .take_while(|lod| screen_size < lod.screen_size)
+            .count()
+    }
+
+    pub fn lod_geometry(&self, lod: usize) -> &Geometry {
+        match lod {
+            0 => &self.geometry,
+            _ => &self.lods[(lod - 1).min(self.lods.len() - 1)].geometry,
+        }
+    }
 }
 
 impl<'a, 'b> DrawModel<'a, 'b> for wgpu::RenderPass<'a>
@@ -83,6 +252,9 @@ where
         light: &'b binding::BufferGroup,
     ) {
         self.bind_vertex_buffer(0, &mesh.vertex_buffer);
+        if let Some(skin_buffer) = &mesh.skin_buffer {
+            self.bind_vertex_buffer(2, skin_buffer);
+        }
         self.bind_index_buffer(&mesh.index_buffer);
         self.bind_material(0, &material);
         self.bind_group(1, &uniforms);
@@ -112,6 +284,20 @@ where
         }
     }
 
+    fn draw_model_lod(
+        &mut self,
+        model: &'b Model,
+        lod: usize,
+        material: Option<&'b Material>,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    ) {
+        for mesh in &model.lod_geometry(lod).meshes {
+            let material = material.unwrap_or(&model.materials[mesh.material]);
+            self.draw_mesh(mesh, material, uniforms, light);
+        }
+    }
+
     fn draw_model_with_material(
         &mut self,
         model: &'b Model,
@@ -135,9 +321,86 @@ where
         }
     }
 
+    fn draw_model_indirect(
+        &mut self,
+        model: &'b Model,
+        lod: usize,
+        material: Option<&'b Material>,
+        arguments: &'b binding::Buffer,
+        first: usize,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    ) {
+        for (i, mesh) in model.lod_geometry(lod).meshes.iter().enumerate() {
+            let material = material.unwrap_or(&model.materials[mesh.material]);
+            self.bind_vertex_buffer(0, &mesh.vertex_buffer);
+            self.bind_index_buffer(&mesh.index_buffer);
+            self.bind_material(0, &material);
+            self.bind_group(1, &uniforms);
+            self.bind_group(2, &light);
+            self.draw_indexed_indirect(&arguments.buffer, DrawIndexedIndirect::offset(first + i));
+        }
+    }
+
+    fn draw_model_morphed(
+        &mut self,
+        model: &'b Model,
+        morph: &'b animation::MorphWeights,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    ) {
+        for (mesh, group) in model.geometry.meshes.iter().zip(morph.groups.iter()) {
+            let material = &model.materials[mesh.material];
+            self.bind_group(3, group);
+            self.draw_mesh(mesh, material, uniforms, light);
+        }
+    }
+
+    fn draw_model_skinned(
+        &mut self,
+        model: &'b Model,
+        material: Option<&'b Material>,
+        skinned: &'b wgpu::RenderPipeline,
+        rigid: &'b wgpu::RenderPipeline,
+        joints: &'b binding::BufferGroup,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    ) {
+        for mesh in &model.geometry.meshes {
+            if mesh.skin_buffer.is_some() {
+                self.set_pipeline(skinned);
+                self.bind_group(3, joints);
+            } else {
+                self.set_pipeline(rigid);
+            }
+            let material = material.unwrap_or(&model.materials[mesh.material]);
+            self.draw_mesh(mesh, material, uniforms, light);
+        }
+    }
+
+    fn draw_model_array(
+        &mut self,
+        model: &'b Model,
+        lod: usize,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    ) {
+        for mesh in &model.lod_geometry(lod).meshes {
+            self.bind_vertex_buffer(0, &mesh.vertex_buffer);
+            self.bind_index_buffer(&mesh.index_buffer);
+            self.bind_group(1, &uniforms);
+            self.bind_group(2, &light);
+            self.draw_indexed(0..mesh.num_elements, 0, 0..1);
+        }
+    }
+
     fn bind_material(&mut self, index: u32, material: &'b Material) {
         self.bind_textures(index, &material.textures);
     }
+
+    fn bind_material_array(&mut self, index: u32, array: &'b MaterialArray) {
+        self.bind_textures(index, &array.textures);
+    }
 }
 
 impl<'a, 'b> DrawLight<'a, 'b> for wgpu::RenderPass<'a>
@@ -0,0 +1,336 @@
+//! Meshes generated at runtime, mostly for test scenes that shouldn't need
+//! model files. All primitives are centered on the origin with +y up.
+
+use std::f32::consts::PI;
+
+use nalgebra::{Point2, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::render::state;
+
+use super::{
+    material::DEFAULT_ROUGHNESS, tangent, Geometry, MaterialData, MeshData, ModelData, ModelVertex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Primitive {
+    Cube {
+        size: f32,
+    },
+    Sphere {
+        radius: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Plane {
+        width: f32,
+        depth: f32,
+        subdivisions: u32,
+    },
+    Capsule {
+        radius: f32,
+        height: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+}
+
+impl Primitive {
+    pub fn mesh_data(&self) -> MeshData {
+        match *self {
+            Self::Cube { size } => cube(size),
+            Self::Sphere {
+                radius,
+                segments,
+                rings,
+            } => sphere(radius, segments, rings),
+            Self::Plane {
+                width,
+                depth,
+                subdivisions,
+            } => plane(width, depth, subdivisions),
+            Self::Capsule {
+                radius,
+                height,
+                segments,
+                rings,
+            } => capsule(radius, height, segments, rings),
+            Self::Cylinder {
+                radius,
+                height,
+                segments,
+            } => cylinder(radius, height, segments),
+        }
+    }
+
+    pub fn geometry(&self, state: &state::WgpuState) -> Geometry {
+        Geometry::from_meshes(state, vec![self.mesh_data()])
+    }
+
+    /// Model data with a single untextured white material.
+    pub fn model_data(&self) -> ModelData {
+        ModelData {
+            meshes: vec![self.mesh_data()],
+            materials: vec![MaterialData {
+                name: String::from("default"),
+                diffuse: None,
+                normal: None,
+                metallic_roughness: None,
+                base_color: [1.0, 1.0, 1.0, 1.0],
+                metallic: 0.0,
+                roughness: DEFAULT_ROUGHNESS,
+            }],
+            skeleton: None,
+            animations: Vec::new(),
+            sources: Vec::new(),
+            lods: Vec::new(),
+        }
+    }
+}
+
+fn vertex(position: Point3<f32>, normal: Vector3<f32>, tex_coords: Point2<f32>) -> ModelVertex {
+    ModelVertex::new(position, tex_coords, normal, tex_coords)
+}
+
+fn mesh(name: &str, mut vertices: Vec<ModelVertex>, indices: Vec<u32>) -> MeshData {
+    tangent::generate_tangents(&mut vertices, &indices);
+    MeshData {
+        name: String::from(name),
+        vertices,
+        indices,
+        skin: None,
+        morph_targets: Vec::new(),
+        material: 0,
+    }
+}
+
+pub fn cube(size: f32) -> MeshData {
+    let half = size / 2.0;
+    // Face normal and the direction of +v on the face, u follows from them
+    let faces = [
+        (Vector3::x(), Vector3::y()),
+        (-Vector3::x(), Vector3::y()),
+        (Vector3::z(), Vector3::y()),
+        (-Vector3::z(), Vector3::y()),
+        (Vector3::y(), -Vector3::z()),
+        (-Vector3::y(), Vector3::z()),
+    ];
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (normal, up) in faces.iter() {
+        let right = up.cross(normal);
+        let center = Point3::from(normal * half);
+        let base = vertices.len() as u32;
+        let corners = [
+            (-right - up, Point2::new(0.0, 1.0)),
+            (right - up, Point2::new(1.0, 1.0)),
+            (right + up, Point2::new(1.0, 0.0)),
+            (-right + up, Point2::new(0.0, 0.0)),
+        ];
+        for (offset, uv) in corners.iter() {
+            vertices.push(vertex(center + offset * half, *normal, *uv));
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    mesh("cube", vertices, indices)
+}
+
+pub fn plane(width: f32, depth: f32, subdivisions: u32) -> MeshData {
+    let cells = subdivisions + 1;
+    let mut vertices = Vec::new();
+    for z in 0..=cells {
+        for x in 0..=cells {
+            let u = x as f32 / cells as f32;
+            let v = z as f32 / cells as f32;
+            vertices.push(vertex(
+                Point3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth),
+                Vector3::y(),
+                Point2::new(u, v),
+            ));
+        }
+    }
+
+    let mut indices = Vec::new();
+    let row = cells + 1;
+    for z in 0..cells {
+        for x in 0..cells {
+            let a = z * row + x;
+            let b = a + 1;
+            let c = a + row;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+    }
+
+    mesh("plane", vertices, indices)
+}
+
+/// Profile point of a surface of revolution around the y axis.
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    /// Normal in the radial plane, x outwards
+    normal: (f32, f32),
+}
+
+/// Sweeps `profile`, ordered bottom to top, around the y axis. The seam
+/// duplicates its vertices so u wraps from 0 to 1.
+fn lathe(
+    profile: &[ProfilePoint],
+    segments: u32,
+    vertices: &mut Vec<ModelVertex>,
+    indices: &mut Vec<u32>,
+) {
+    let segments = segments.max(3);
+    let base = vertices.len() as u32;
+    let bottom = profile.first().map(|p| p.y).unwrap_or(0.0);
+    let top = profile.last().map(|p| p.y).unwrap_or(0.0);
+    let height = (top - bottom).max(f32::EPSILON);
+
+    for point in profile {
+        for j in 0..=segments {
+            let u = j as f32 / segments as f32;
+            let (sin, cos) = (u * 2.0 * PI).sin_cos();
+            vertices.push(vertex(
+                Point3::new(point.radius * cos, point.y, point.radius * sin),
+                Vector3::new(point.normal.0 * cos, point.normal.1, point.normal.0 * sin),
+                Point2::new(u, 1.0 - (point.y - bottom) / height),
+            ));
+        }
+    }
+
+    let row = segments + 1;
+    for i in 0..profile.len().saturating_sub(1) as u32 {
+        for j in 0..segments {
+            let a = base + i * row + j;
+            let b = a + 1;
+            let c = a + row;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, c, d, a, d, b]);
+        }
+    }
+}
+
+/// Rings from the bottom pole to the top one of a hemisphere pair split at
+/// `y_offset` below and above the equator.
+fn sphere_profile(radius: f32, rings: u32, y_offset: f32) -> Vec<ProfilePoint> {
+    let rings = rings.max(2);
+    let mut profile = Vec::new();
+    for i in 0..=rings {
+        // Polar angle from the bottom pole
+        let angle = PI - PI * i as f32 / rings as f32;
+        let (sin, cos) = angle.sin_cos();
+        let offset = if i * 2 < rings {
+            -y_offset
+        } else if i * 2 > rings {
+            y_offset
+        } else {
+            0.0
+        };
+        if i * 2 == rings && y_offset > 0.0 {
+            // Split the equator so the straight section has its own rings
+            for &y in [-y_offset, y_offset].iter() {
+                profile.push(ProfilePoint {
+                    radius: radius * sin,
+                    y: radius * cos + y,
+                    normal: (sin, cos),
+                });
+            }
+            continue;
+        }
+        profile.push(ProfilePoint {
+            radius: radius * sin,
+            y: radius * cos + offset,
+            normal: (sin, cos),
+        });
+    }
+    profile
+}
+
+pub fn sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    lathe(
+        &sphere_profile(radius, rings, 0.0),
+        segments,
+        &mut vertices,
+        &mut indices,
+    );
+    mesh("sphere", vertices, indices)
+}
+
+/// `height` is the length of the straight section, the caps add `radius`
+/// on either end. `rings` is rounded up to an even count so both caps
+/// meet the straight section.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = (rings.max(2) + 1) / 2 * 2;
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    lathe(
+        &sphere_profile(radius, rings, height / 2.0),
+        segments,
+        &mut vertices,
+        &mut indices,
+    );
+    mesh("capsule", vertices, indices)
+}
+
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    lathe(
+        &[
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: (1.0, 0.0),
+            },
+            ProfilePoint {
+                radius,
+                y: half,
+                normal: (1.0, 0.0),
+            },
+        ],
+        segments,
+        &mut vertices,
+        &mut indices,
+    );
+
+    // Caps get their own vertices for the hard edge
+    for &(y, normal) in [(half, 1.0), (-half, -1.0)].iter() {
+        let center = vertices.len() as u32;
+        vertices.push(vertex(
+            Point3::new(0.0, y, 0.0),
+            Vector3::new(0.0, normal, 0.0),
+            Point2::new(0.5, 0.5),
+        ));
+        for j in 0..segments {
+            let (sin, cos) = (j as f32 / segments as f32 * 2.0 * PI).sin_cos();
+            vertices.push(vertex(
+                Point3::new(radius * cos, y, radius * sin),
+                Vector3::new(0.0, normal, 0.0),
+                Point2::new(0.5 + cos * 0.5, 0.5 + sin * 0.5),
+            ));
+        }
+        for j in 0..segments {
+            let current = center + 1 + j;
+            let next = center + 1 + (j + 1) % segments;
+            if normal > 0.0 {
+                indices.extend_from_slice(&[center, next, current]);
+            } else {
+                indices.extend_from_slice(&[center, current, next]);
+            }
+        }
+    }
+
+    mesh("cylinder", vertices, indices)
+}
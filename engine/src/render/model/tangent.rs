@@ -0,0 +1,197 @@
+use nalgebra::Vector3;
+
+use super::ModelVertex;
+
+/// UV triangles with a smaller signed area than this are treated as
+/// degenerate and don't contribute to the tangent frame.
+const DEGENERATE_UV_AREA: f32 = 1e-8;
+
+/// Area weighted smooth normals, used when a mesh ships without any.
+pub fn generate_normals(vertices: &mut [ModelVertex], indices: &[u32]) {
+    for v in vertices.iter_mut() {
+        v.normal = Vector3::zeros();
+    }
+
+    for c in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (c[0] as usize, c[1] as usize, c[2] as usize);
+        let p0 = vertices[i0].position;
+        let face_normal = (vertices[i1].position - p0).cross(&(vertices[i2].position - p0));
+
+        vertices[i0].normal += face_normal;
+        vertices[i1].normal += face_normal;
+        vertices[i2].normal += face_normal;
+    }
+
+    for v in vertices.iter_mut() {
+        v.normal = v
+            .normal
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+    }
+}
+
+/// Accumulates per-triangle tangents on every vertex they touch, then
+/// orthonormalizes the result against the vertex normal (Gram-Schmidt) and
+/// rebuilds the bitangent with the correct handedness, the same way
+/// MikkTSpace derives its per-vertex frame.
+pub fn generate_tangents(vertices: &mut [ModelVertex], indices: &[u32]) {
+    for v in vertices.iter_mut() {
+        v.tangent = Vector3::zeros();
+        v.bitangent = Vector3::zeros();
+    }
+
+    for c in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (c[0] as usize, c[1] as usize, c[2] as usize);
+        let v0 = vertices[i0];
+        let v1 = vertices[i1];
+        let v2 = vertices[i2];
+
+        let delta_pos1 = v1.position - v0.position;
+        let delta_pos2 = v2.position - v0.position;
+
+        let delta_uv1 = v1.tex_coords - v0.tex_coords;
+        let delta_uv2 = v2.tex_coords - v0.tex_coords;
+
+        let det = delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x;
+        if det.abs() < DEGENERATE_UV_AREA {
+            continue;
+        }
+
+        let r = 1.0 / det;
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * r;
+
+        for &i in &[i0, i1, i2] {
+            vertices[i].tangent += tangent;
+            vertices[i].bitangent += bitangent;
+        }
+    }
+
+    for v in vertices.iter_mut() {
+        let normal = v.normal;
+        let tangent = (v.tangent - normal * normal.dot(&v.tangent))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| any_orthogonal(&normal));
+        let handedness = if normal.cross(&tangent).dot(&v.bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        v.tangent = tangent;
+        v.bitangent = normal.cross(&tangent) * handedness;
+    }
+}
+
+/// Fallback tangent for vertices whose triangles all had degenerate UVs.
+fn any_orthogonal(normal: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    (axis - normal * normal.dot(&axis)).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Point2, Point3};
+
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    /// Unit quad in the xy plane facing +z, u running along x and v along
+    /// -y as image rows do.
+    fn quad() -> (Vec<ModelVertex>, Vec<u32>) {
+        let vertex = |x: f32, y: f32| {
+            ModelVertex::new(
+                Point3::new(x, y, 0.0),
+                Point2::new(x, 1.0 - y),
+                Vector3::z(),
+                Point2::origin(),
+            )
+        };
+        let vertices = vec![
+            vertex(0.0, 0.0),
+            vertex(1.0, 0.0),
+            vertex(1.0, 1.0),
+            vertex(0.0, 1.0),
+        ];
+        (vertices, vec![0, 1, 2, 0, 2, 3])
+    }
+
+    fn assert_near(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).norm() < EPSILON, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn normals_face_counter_clockwise_winding() {
+        let (mut vertices, indices) = quad();
+        for v in vertices.iter_mut() {
+            v.normal = Vector3::x();
+        }
+        generate_normals(&mut vertices, &indices);
+        for v in vertices.iter() {
+            assert_near(v.normal, Vector3::z());
+        }
+    }
+
+    #[test]
+    fn unused_vertices_get_an_up_normal() {
+        let (mut vertices, _) = quad();
+        generate_normals(&mut vertices, &[]);
+        assert_near(vertices[0].normal, Vector3::y());
+    }
+
+    #[test]
+    fn tangents_follow_the_uvs() {
+        let (mut vertices, indices) = quad();
+        generate_tangents(&mut vertices, &indices);
+        for v in vertices.iter() {
+            assert_near(v.tangent, Vector3::x());
+            assert_near(v.bitangent, -Vector3::y());
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_the_bitangent() {
+        let (mut vertices, indices) = quad();
+        for v in vertices.iter_mut() {
+            v.tex_coords.y = 1.0 - v.tex_coords.y;
+        }
+        generate_tangents(&mut vertices, &indices);
+        for v in vertices.iter() {
+            assert_near(v.tangent, Vector3::x());
+            assert_near(v.bitangent, Vector3::y());
+        }
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_tilted_normals() {
+        let (mut vertices, indices) = quad();
+        let normal = Vector3::new(0.3, 0.0, 1.0).normalize();
+        for v in vertices.iter_mut() {
+            v.normal = normal;
+        }
+        generate_tangents(&mut vertices, &indices);
+        for v in vertices.iter() {
+            assert!(v.tangent.dot(&normal).abs() < EPSILON);
+            assert!((v.tangent.norm() - 1.0).abs() < EPSILON);
+            assert!(v.bitangent.dot(&v.tangent).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_fall_back_to_any_orthogonal_tangent() {
+        let (mut vertices, indices) = quad();
+        for v in vertices.iter_mut() {
+            v.tex_coords = Point2::origin();
+        }
+        generate_tangents(&mut vertices, &indices);
+        for v in vertices.iter() {
+            assert!(v.tangent.dot(&v.normal).abs() < EPSILON);
+            assert!((v.tangent.norm() - 1.0).abs() < EPSILON);
+        }
+    }
+}
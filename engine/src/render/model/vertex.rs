@@ -1,4 +1,4 @@
-use nalgebra::{Point2, Point3, Vector3};
+use nalgebra::{Point2, Point3, Vector3, Vector4};
 
 use crate::render::traits::Vertex;
 
@@ -10,11 +10,33 @@ pub struct ModelVertex {
     pub(super) normal: Vector3<f32>,
     pub(super) tangent: Vector3<f32>,
     pub(super) bitangent: Vector3<f32>,
+    pub(super) color: Vector4<f32>,
+    pub(super) tex_coords2: Point2<f32>,
 }
 
 unsafe impl bytemuck::Pod for ModelVertex {}
 unsafe impl bytemuck::Zeroable for ModelVertex {}
 
+impl ModelVertex {
+    /// White vertex, tangents are left for `tangent::generate_tangents`.
+    pub fn new(
+        position: Point3<f32>,
+        tex_coords: Point2<f32>,
+        normal: Vector3<f32>,
+        tex_coords2: Point2<f32>,
+    ) -> Self {
+        Self {
+            position,
+            tex_coords,
+            normal,
+            tangent: Vector3::zeros(),
+            bitangent: Vector3::zeros(),
+            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            tex_coords2,
+        }
+    }
+}
+
 impl Vertex for ModelVertex {
     fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
         use std::mem;
@@ -47,7 +69,63 @@ impl Vertex for ModelVertex {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float3,
                 },
+                // 5..=8 are taken by the instance matrix
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 18]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float2,
+                },
+            ],
+        }
+    }
+}
+
+/// Joint influences of a skinned vertex, uploaded as a separate vertex
+/// stream so unskinned meshes keep the plain `ModelVertex` layout.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SkinVertex {
+    pub(super) joints: [u32; 4],
+    pub(super) weights: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for SkinVertex {}
+unsafe impl bytemuck::Zeroable for SkinVertex {}
+
+impl Vertex for SkinVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<SkinVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Uint4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[u32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float4,
+                },
             ],
         }
     }
 }
+
+/// Offset of a vertex for one morph target, padded to std430 layout.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MorphDelta {
+    pub(super) position: [f32; 4],
+    pub(super) normal: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for MorphDelta {}
+unsafe impl bytemuck::Zeroable for MorphDelta {}
@@ -0,0 +1,396 @@
+//! Particles simulated on the GPU. Emitters are gathered every frame and a
+//! compute shader spawns their new particles into a ring of slots, moves
+//! the living ones and lists them for an indirect draw, so the CPU never
+//! sees single particles. When the ring is full the oldest slots are
+//! reused.
+
+use anyhow::*;
+use nalgebra::{Isometry3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    binding::{self, BufferGroup},
+    compute, state,
+    texture::Texture,
+    traits::{Binding, ComputePassExt, DrawParticles},
+};
+use crate::inspect::Inspect;
+
+/// Particles alive at once across all emitters
+pub const PARTICLE_CAPACITY: u32 = 1 << 18;
+/// Emitters spawning in one frame, further ones wait for the next
+const MAX_EMITTERS: usize = 256;
+/// Local size of the simulation shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Emits particles along the y axis of the entity's transform.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Inspect)]
+#[serde(default)]
+pub struct ParticleEmitter {
+    /// Particles per second
+    #[inspect_slider(min_value = 0.0, max_value = 10000.0)]
+    pub rate: f32,
+    /// Seconds each particle lives
+    pub lifetime: f32,
+    pub speed: f32,
+    /// Widest angle from the y axis particles leave at, in radians
+    #[inspect_slider(min_value = 0.0, max_value = 3.1415927)]
+    pub spread: f32,
+    pub size: f32,
+    /// Pulls particles down, in units per second squared
+    pub gravity: f32,
+    /// Faded out over each particle's life, blended additively
+    #[inspect(skip)]
+    pub color: [f32; 4],
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            rate: 100.0,
+            lifetime: 2.0,
+            speed: 2.0,
+            spread: 0.3,
+            size: 0.1,
+            gravity: 1.0,
+            color: [1.0, 0.6, 0.2, 1.0],
+        }
+    }
+}
+
+impl ParticleEmitter {
+    /// Particles to spawn in the frame from `elapsed - delta` to
+    /// `elapsed` seconds, keeping fractions of a particle for later frames.
+    fn spawn_count(&self, delta: f32, elapsed: f32) -> u32 {
+        let before = ((elapsed - delta) * self.rate).floor();
+        ((elapsed * self.rate).floor() - before).max(0.0) as u32
+    }
+}
+
+/// Layout of a particle in the shaders, only ever touched by them.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Particle {
+    position: [f32; 3],
+    age: f32,
+    velocity: [f32; 3],
+    lifetime: f32,
+    color: [f32; 4],
+    size: f32,
+    gravity: f32,
+    _padding: [f32; 2],
+}
+
+unsafe impl bytemuck::Pod for Particle {}
+unsafe impl bytemuck::Zeroable for Particle {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct GpuEmitter {
+    /// w: spread
+    position: [f32; 4],
+    /// w: speed
+    direction: [f32; 4],
+    color: [f32; 4],
+    /// Lifetime, size and gravity
+    params: [f32; 4],
+    /// First of this frame's particles, how many and the random seed
+    spawn: [u32; 4],
+}
+
+unsafe impl bytemuck::Pod for GpuEmitter {}
+unsafe impl bytemuck::Zeroable for GpuEmitter {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SimulationUniforms {
+    delta: f32,
+    time: f32,
+    /// Slot of the first particle spawned this frame
+    cursor: u32,
+    spawn_count: u32,
+    emitter_count: u32,
+    capacity: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Pod for SimulationUniforms {}
+unsafe impl bytemuck::Zeroable for SimulationUniforms {}
+
+/// Arguments of `draw_indirect`, the simulation shader counts the
+/// instances.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DrawArguments {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+}
+
+unsafe impl bytemuck::Pod for DrawArguments {}
+unsafe impl bytemuck::Zeroable for DrawArguments {}
+
+/// Vertices of the quad drawn for each particle
+const QUAD_VERTICES: u32 = 6;
+
+fn storage_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStage,
+    readonly: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::StorageBuffer {
+            dynamic: false,
+            min_binding_size: None,
+            readonly,
+        },
+        count: None,
+    }
+}
+
+/// Every particle and the pipelines simulating and drawing them.
+pub struct ParticleSystem {
+    uniforms: binding::Buffer,
+    emitters: binding::Buffer,
+    draw: binding::Buffer,
+    /// Particles and emitters as the simulation shader sees them
+    simulation_group: BufferGroup,
+    /// Particles and the living ones as the vertex shader sees them
+    render_group: BufferGroup,
+    simulation_pipeline: wgpu::ComputePipeline,
+    pipeline: wgpu::RenderPipeline,
+    /// Slot the next spawned particle goes to
+    cursor: u32,
+    /// Varies the particles spawned from frame to frame
+    seed: u32,
+    /// Keep the storage alive, the groups above bind them
+    _particles: binding::Buffer,
+    _alive: binding::Buffer,
+}
+
+impl ParticleSystem {
+    /// `uniforms_layout` binds the camera the particles face.
+    pub fn new(
+        state: &state::WgpuState,
+        uniforms_layout: &binding::BindGroupLayout,
+    ) -> Result<Self> {
+        let capacity = PARTICLE_CAPACITY as usize;
+        // Zeroed particles have lived their whole lifetime
+        let dead: Vec<Particle> = vec![bytemuck::Zeroable::zeroed(); capacity];
+        let particles =
+            binding::Buffer::new_init(state, "particles", &dead, binding::BufferUsage::Storage);
+        let alive = binding::Buffer::new_init(
+            state,
+            "particles_alive",
+            &vec![0u32; capacity],
+            binding::BufferUsage::Storage,
+        );
+        let emitters: Vec<GpuEmitter> = vec![bytemuck::Zeroable::zeroed(); MAX_EMITTERS];
+        let emitters = binding::Buffer::new_init(
+            state,
+            "particle_emitters",
+            &emitters,
+            binding::BufferUsage::Storage,
+        );
+        let uniforms = binding::Buffer::new_init(
+            state,
+            "particle_simulation",
+            &[SimulationUniforms {
+                delta: 0.0,
+                time: 0.0,
+                cursor: 0,
+                spawn_count: 0,
+                emitter_count: 0,
+                capacity: PARTICLE_CAPACITY,
+                _padding: [0; 2],
+            }],
+            binding::BufferUsage::Uniform,
+        );
+        let draw = binding::Buffer::new_init(
+            state,
+            "particle_draw",
+            &[DrawArguments {
+                vertex_count: QUAD_VERTICES,
+                instance_count: 0,
+                first_vertex: 0,
+                first_instance: 0,
+            }],
+            binding::BufferUsage::Indirect,
+        );
+
+        let simulation_layout = state.create_layout(
+            "particle_simulation",
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1, wgpu::ShaderStage::COMPUTE, false),
+                storage_entry(2, wgpu::ShaderStage::COMPUTE, true),
+                storage_entry(3, wgpu::ShaderStage::COMPUTE, false),
+                storage_entry(4, wgpu::ShaderStage::COMPUTE, false),
+            ],
+        );
+        let render_layout = state.create_layout(
+            "particles",
+            &[
+                storage_entry(0, wgpu::ShaderStage::VERTEX, true),
+                storage_entry(1, wgpu::ShaderStage::VERTEX, true),
+            ],
+        );
+        let simulation_group = BufferGroup::from_buffer(
+            state,
+            "particle_simulation",
+            &simulation_layout,
+            &[&uniforms, &particles, &emitters, &alive, &draw],
+        );
+        let render_group =
+            BufferGroup::from_buffer(state, "particles", &render_layout, &[&particles, &alive]);
+
+        let simulation_pipeline_layout =
+            state.create_pipeline_layout("particle_simulation", &[&simulation_layout])?;
+        let simulation_pipeline = state.create_compute_pipeline(
+            &simulation_pipeline_layout,
+            "particle_simulation",
+            "particles.comp.spv",
+        )?;
+
+        let pipeline_layout =
+            state.create_pipeline_layout("particles", &[uniforms_layout, &render_layout])?;
+        // Added to the scene without writing depth, so they need no sorting
+        let pipeline = state.create_render_pipeline(
+            &pipeline_layout,
+            "particle_pipeline",
+            state.format(),
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            (Texture::DEPTH_FORMAT, false),
+            &[],
+            wgpu::IndexFormat::Uint16,
+            "particle.vert.spv",
+            "particle.frag.spv",
+            false,
+        )?;
+
+        Ok(Self {
+            uniforms,
+            emitters,
+            draw,
+            simulation_group,
+            render_group,
+            simulation_pipeline,
+            pipeline,
+            cursor: 0,
+            seed: 0,
+            _particles: particles,
+            _alive: alive,
+        })
+    }
+
+    /// Records the simulation of the frame from `elapsed - delta` to
+    /// `elapsed` seconds, spawning from `emitters` placed at their
+    /// isometry. Run it before the pass drawing the particles.
+    pub fn simulate(
+        &mut self,
+        state: &state::WgpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        delta: f32,
+        elapsed: f32,
+        emitters: &[(Isometry3<f32>, ParticleEmitter)],
+    ) {
+        let mut spawning = Vec::new();
+        let mut spawn_count = 0;
+        for (isometry, emitter) in emitters.iter() {
+            if spawning.len() == MAX_EMITTERS {
+                break;
+            }
+            // More than fit are cut off rather than overwriting this frame's
+            let count = emitter
+                .spawn_count(delta, elapsed)
+                .min(PARTICLE_CAPACITY - spawn_count);
+            if count == 0 {
+                continue;
+            }
+            let position = isometry.translation.vector;
+            let direction = isometry.rotation * Vector3::y();
+            spawning.push(GpuEmitter {
+                position: [position.x, position.y, position.z, emitter.spread],
+                direction: [direction.x, direction.y, direction.z, emitter.speed],
+                color: emitter.color,
+                params: [emitter.lifetime, emitter.size, emitter.gravity, 0.0],
+                spawn: [
+                    spawn_count,
+                    count,
+                    self.seed.wrapping_add(spawning.len() as u32),
+                    0,
+                ],
+            });
+            spawn_count += count;
+        }
+
+        self.uniforms.write(
+            state,
+            &[SimulationUniforms {
+                delta,
+                time: elapsed,
+                cursor: self.cursor,
+                spawn_count,
+                emitter_count: spawning.len() as u32,
+                capacity: PARTICLE_CAPACITY,
+                _padding: [0; 2],
+            }],
+        );
+        if !spawning.is_empty() {
+            self.emitters.write(state, &spawning);
+        }
+        self.draw.write(
+            state,
+            &[DrawArguments {
+                vertex_count: QUAD_VERTICES,
+                instance_count: 0,
+                first_vertex: 0,
+                first_instance: 0,
+            }],
+        );
+
+        {
+            let mut compute_pass = compute::compute_pass(encoder);
+            compute_pass.bind_group(0, &self.simulation_group);
+            compute_pass.dispatch_pipeline(
+                &self.simulation_pipeline,
+                (PARTICLE_CAPACITY / WORKGROUP_SIZE, 1, 1),
+            );
+        }
+
+        self.cursor = (self.cursor + spawn_count) % PARTICLE_CAPACITY;
+        self.seed = self.seed.wrapping_add(MAX_EMITTERS as u32);
+    }
+}
+
+impl<'a, 'b> DrawParticles<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_particles(&mut self, particles: &'b ParticleSystem, uniforms: &'b BufferGroup) {
+        self.set_pipeline(&particles.pipeline);
+        self.bind_group(0, uniforms);
+        self.bind_group(1, &particles.render_group);
+        self.draw_indirect(&particles.draw.buffer, 0);
+    }
+}
@@ -0,0 +1,479 @@
+//! Screen space effects run one after another on the rendered scene. The
+//! scene is drawn into an offscreen target instead of the swapchain while
+//! any effect is enabled, each effect then draws a fullscreen triangle
+//! sampling the previous one's output, the last one into the swapchain.
+//!
+//! Temporal anti-aliasing runs before the effects. The scene is drawn with
+//! its projection jittered by a sub-pixel offset each frame and blended
+//! into the history of the frames before, clamped to the colors around
+//! each pixel so moving edges don't leave trails.
+//!
+//! Effects can sample a texture of their own besides their input, color
+//! grading looks colors up in a LUT texture loaded from [`LUT_DIR`].
+
+use anyhow::*;
+use log::info;
+use nalgebra::Vector2;
+
+use super::{
+    binding::{self, BufferGroup, TextureBinding},
+    renderpass, state,
+    texture::{SamplerDesc, Texture, TextureData},
+    traits::Binding,
+};
+use crate::assets::{Assets, Handle};
+
+/// Vertex shader every effect shares, a triangle covering the screen
+const VERTEX_SHADER: &str = "post.vert.spv";
+/// Name of the FXAA effect, enabled through the anti-aliasing setting
+pub const FXAA: &str = "fxaa";
+/// Name of the color grading effect, enabled while a LUT is picked
+pub const COLOR_GRADING: &str = "color_grading";
+/// Resource folder of color grading LUTs. A LUT of size N is a strip of N
+/// slices of N by N pixels, red across each slice, green down and blue
+/// from slice to slice.
+pub const LUT_DIR: &str = "luts";
+/// Effects the engine comes with, in the order they run, their strength
+/// and whether they sample a texture. Each has its fragment shader in
+/// `<name>.frag`.
+pub const BUILTIN_EFFECTS: [(&str, f32, bool); 5] = [
+    ("tonemap", 1.0, false),
+    (COLOR_GRADING, 1.0, true),
+    (FXAA, 1.0, false),
+    ("vignette", 0.5, false),
+    ("chromatic_aberration", 2.0, false),
+];
+/// Weight of the current frame against the history
+const TAA_BLEND: f32 = 0.1;
+/// Frames the jitter pattern repeats after
+const JITTER_FRAMES: u32 = 8;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct EffectUniforms {
+    /// Size of one pixel of the input in texture coordinates
+    texel_size: [f32; 2],
+    /// Seconds since the engine started
+    time: f32,
+    strength: f32,
+}
+
+unsafe impl bytemuck::Pod for EffectUniforms {}
+unsafe impl bytemuck::Zeroable for EffectUniforms {}
+
+/// A texture asset an effect samples in set 2.
+struct EffectTexture {
+    name: String,
+    /// Keeps the asset loaded while the effect uses it
+    _handle: Handle<Texture>,
+    binding: TextureBinding,
+}
+
+pub struct Effect {
+    pub name: String,
+    pub enabled: bool,
+    /// How strongly the effect applies, what it scales is up to the shader
+    pub strength: f32,
+    pipeline: wgpu::RenderPipeline,
+    uniforms: binding::Buffer,
+    uniform_group: BufferGroup,
+    /// Whether the shader samples a texture, the effect is skipped until
+    /// it has one
+    textured: bool,
+    texture: Option<EffectTexture>,
+}
+
+impl Effect {
+    /// Whether the effect runs this frame.
+    fn is_active(&self) -> bool {
+        self.enabled && (!self.textured || self.texture.is_some())
+    }
+}
+
+/// Resolved frames of temporal anti-aliasing, one is read while the other
+/// is written.
+struct Taa {
+    pipeline: wgpu::RenderPipeline,
+    history: [Texture; 2],
+    history_groups: [TextureBinding; 2],
+    uniforms: binding::Buffer,
+    uniform_group: BufferGroup,
+    /// The history doesn't hold a frame yet
+    reset: bool,
+}
+
+pub struct PostProcess {
+    effects: Vec<Effect>,
+    /// The scene goes into the first, effects ping pong between them
+    targets: [Texture; 2],
+    target_groups: [TextureBinding; 2],
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Layout of effects sampling a texture besides their input
+    textured_layout: wgpu::PipelineLayout,
+    taa: Taa,
+    taa_enabled: bool,
+    /// Draws the input unchanged, when anti-aliasing runs without effects
+    copy: wgpu::RenderPipeline,
+    /// Counts applied frames, picks the jitter and the history to write
+    frame: u32,
+}
+
+/// The `index`th number of the Halton sequence in `base`, from 0 to 1.
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+fn targets(
+    state: &state::WgpuState,
+    layout: &binding::BindGroupLayout,
+) -> ([Texture; 2], [TextureBinding; 2]) {
+    let target =
+        |label| Texture::render_target(state, label, state.width(), state.height(), state.format());
+    let targets = [target("post_target_a"), target("post_target_b")];
+    let groups = [
+        TextureBinding::new_ref(state, "post_target_a", layout, &[&targets[0]]),
+        TextureBinding::new_ref(state, "post_target_b", layout, &[&targets[1]]),
+    ];
+    (targets, groups)
+}
+
+/// Draws a fullscreen triangle with `pipeline` into `destination`.
+fn fullscreen_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    destination: &'a wgpu::TextureView,
+    pipeline: &'a wgpu::RenderPipeline,
+    groups: &[&'a TextureBinding],
+    uniforms: &'a BufferGroup,
+) {
+    let color_attachments: &[&dyn renderpass::IntoColorAttachment] =
+        &[&(destination, wgpu::LoadOp::Clear(wgpu::Color::BLACK))];
+    let mut render_pass = renderpass::render_pass(encoder, color_attachments, None);
+    render_pass.set_pipeline(pipeline);
+    render_pass.bind_textures(0, groups[0]);
+    render_pass.bind_group(1, uniforms);
+    for (i, group) in groups.iter().enumerate().skip(1) {
+        render_pass.bind_textures(i as u32 + 1, *group);
+    }
+    render_pass.draw(0..3, 0..1);
+}
+
+impl PostProcess {
+    /// An empty stack. `texture_layout` binds an effect's input,
+    /// `uniforms_layout` its settings.
+    pub fn new(
+        state: &state::WgpuState,
+        texture_layout: &binding::BindGroupLayout,
+        uniforms_layout: &binding::BindGroupLayout,
+    ) -> Result<Self> {
+        let (history, history_groups) = targets(state, texture_layout);
+        let (targets, target_groups) = targets(state, texture_layout);
+        let pipeline_layout =
+            state.create_pipeline_layout("post", &[texture_layout, uniforms_layout])?;
+        let effect_pipeline = |layout, name, fragment_shader| {
+            state.create_render_pipeline(
+                layout,
+                name,
+                state.format(),
+                wgpu::BlendDescriptor::REPLACE,
+                wgpu::BlendDescriptor::REPLACE,
+                None,
+                &[],
+                wgpu::IndexFormat::Uint16,
+                VERTEX_SHADER,
+                fragment_shader,
+                false,
+            )
+        };
+
+        // Temporal anti-aliasing samples the history like a textured effect
+        let textured_layout = state.create_pipeline_layout(
+            "post_textured",
+            &[texture_layout, uniforms_layout, texture_layout],
+        )?;
+        let uniforms = binding::Buffer::new_init(
+            state,
+            "taa",
+            &[EffectUniforms {
+                texel_size: [0.0; 2],
+                time: 0.0,
+                strength: TAA_BLEND,
+            }],
+            binding::BufferUsage::Uniform,
+        );
+        let taa = Taa {
+            pipeline: effect_pipeline(&textured_layout, "taa", "taa.frag.spv")?,
+            history,
+            history_groups,
+            uniform_group: BufferGroup::from_buffer(state, "taa", uniforms_layout, &[&uniforms]),
+            uniforms,
+            reset: true,
+        };
+
+        Ok(Self {
+            effects: Vec::new(),
+            targets,
+            target_groups,
+            copy: effect_pipeline(&pipeline_layout, "copy", "copy.frag.spv")?,
+            pipeline_layout,
+            textured_layout,
+            taa,
+            taa_enabled: false,
+            frame: 0,
+        })
+    }
+
+    /// Adds an effect after the others, disabled. `fragment_shader` samples
+    /// `t_input` in set 0 and reads `EffectUniforms` from set 1, a
+    /// `textured` one samples the texture set with `set_texture` in set 2.
+    pub fn push(
+        &mut self,
+        state: &state::WgpuState,
+        uniforms_layout: &binding::BindGroupLayout,
+        name: &str,
+        fragment_shader: &str,
+        strength: f32,
+        textured: bool,
+    ) -> Result<()> {
+        info!("Add post effect {:?}", name);
+        let pipeline = state.create_render_pipeline(
+            if textured {
+                &self.textured_layout
+            } else {
+                &self.pipeline_layout
+            },
+            name,
+            state.format(),
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            None,
+            &[],
+            wgpu::IndexFormat::Uint16,
+            VERTEX_SHADER,
+            fragment_shader,
+            false,
+        )?;
+        let uniforms = binding::Buffer::new_init(
+            state,
+            name,
+            &[EffectUniforms {
+                texel_size: [0.0; 2],
+                time: 0.0,
+                strength,
+            }],
+            binding::BufferUsage::Uniform,
+        );
+        let uniform_group = BufferGroup::from_buffer(state, name, uniforms_layout, &[&uniforms]);
+        self.effects.push(Effect {
+            name: name.to_string(),
+            enabled: false,
+            strength,
+            pipeline,
+            uniforms,
+            uniform_group,
+            textured,
+            texture: None,
+        });
+        Ok(())
+    }
+
+    /// Name of the texture the effect `effect` samples.
+    pub fn texture(&self, effect: &str) -> Option<&str> {
+        self.effects
+            .iter()
+            .find(|e| e.name == effect)
+            .and_then(|e| e.texture.as_ref())
+            .map(|texture| texture.name.as_str())
+    }
+
+    /// Has the effect `effect` sample the texture at the resource path
+    /// `name` from now on, loading it into `textures` unless it is there
+    /// already. `None` leaves it without one, which skips it.
+    pub fn set_texture(
+        &mut self,
+        state: &state::WgpuState,
+        textures: &mut Assets<Texture>,
+        texture_layout: &binding::BindGroupLayout,
+        effect: &str,
+        name: Option<&str>,
+    ) -> Result<()> {
+        let effect = self
+            .effects
+            .iter_mut()
+            .find(|e| e.name == effect)
+            .context(format!("No post effect {:?}", effect))?;
+        effect.texture = None;
+        let name = match name {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+
+        let handle = textures.get_or_load(name, || {
+            let path = state.resources().path(name)?;
+            // Lookup tables hold colors as authored, filtered between
+            // entries but never across slices by mips
+            let data = TextureData::load(path, true)?.with_sampler(SamplerDesc {
+                min_filter: wgpu::FilterMode::Linear,
+                anisotropy: None,
+                ..Default::default()
+            });
+            Texture::from_data(state, &data)
+        })?;
+        let texture = textures
+            .get(&handle)
+            .context(format!("Texture {:?} is not loaded", name))?;
+        effect.texture = Some(EffectTexture {
+            name: name.to_string(),
+            binding: TextureBinding::new_ref(state, name, texture_layout, &[texture]),
+            _handle: handle,
+        });
+        Ok(())
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    /// Enables the effects named in `names` and disables the rest, the
+    /// order they run in stays the order they were pushed in.
+    pub fn set_enabled(&mut self, names: &[String]) {
+        for effect in self.effects.iter_mut() {
+            effect.enabled = names.contains(&effect.name);
+        }
+    }
+
+    pub fn set_taa(&mut self, enabled: bool) {
+        if enabled && !self.taa_enabled {
+            self.taa.reset = true;
+        }
+        self.taa_enabled = enabled;
+    }
+
+    /// Offset to move this frame's projection by in clip space, within a
+    /// pixel, while temporal anti-aliasing is on.
+    pub fn jitter(&self, state: &state::WgpuState) -> Option<Vector2<f32>> {
+        if !self.taa_enabled {
+            return None;
+        }
+        let index = self.frame % JITTER_FRAMES + 1;
+        let pixel = Vector2::new(halton(index, 2) - 0.5, halton(index, 3) - 0.5);
+        // Clip space spans two units across the screen
+        Some(Vector2::new(
+            pixel.x * 2.0 / state.width() as f32,
+            pixel.y * 2.0 / state.height() as f32,
+        ))
+    }
+
+    /// Whether the scene has to be drawn into `target` rather than the
+    /// swapchain.
+    pub fn is_active(&self) -> bool {
+        self.taa_enabled || self.effects.iter().any(Effect::is_active)
+    }
+
+    /// Where the scene is drawn while any effect is enabled.
+    pub fn target(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+
+    /// Recreates the targets at the swapchain's size.
+    pub fn resize(&mut self, state: &state::WgpuState, texture_layout: &binding::BindGroupLayout) {
+        let (history, history_groups) = targets(state, texture_layout);
+        let (targets, target_groups) = targets(state, texture_layout);
+        self.target_groups = target_groups;
+        self.taa.history_groups = history_groups;
+        let [a, b] = std::mem::replace(&mut self.targets, targets);
+        let [history_a, history_b] = std::mem::replace(&mut self.taa.history, history);
+        for texture in vec![a, b, history_a, history_b] {
+            state.texture_pool().release(texture);
+        }
+        self.taa.reset = true;
+    }
+
+    /// Resolves temporal anti-aliasing and runs the enabled effects on the
+    /// scene in `target`, the last one drawing into `output`.
+    pub fn apply(
+        &mut self,
+        state: &state::WgpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        time: f32,
+    ) {
+        let enabled = self
+            .effects
+            .iter()
+            .filter(|effect| effect.is_active())
+            .collect::<Vec<_>>();
+        let texel_size = [1.0 / state.width() as f32, 1.0 / state.height() as f32];
+        let mut source = &self.target_groups[0];
+        // Target the next effect draws into
+        let mut free = 1;
+
+        if self.taa_enabled {
+            let taa = &self.taa;
+            taa.uniforms.write(
+                state,
+                &[EffectUniforms {
+                    texel_size,
+                    time,
+                    strength: if taa.reset { 1.0 } else { TAA_BLEND },
+                }],
+            );
+            let (write, read) = ((self.frame % 2) as usize, ((self.frame + 1) % 2) as usize);
+            fullscreen_pass(
+                encoder,
+                &taa.history[write].view,
+                &taa.pipeline,
+                &[source, &taa.history_groups[read]],
+                &taa.uniform_group,
+            );
+            source = &taa.history_groups[write];
+            if enabled.is_empty() {
+                fullscreen_pass(encoder, output, &self.copy, &[source], &taa.uniform_group);
+            }
+        }
+
+        for (i, effect) in enabled.iter().enumerate() {
+            effect.uniforms.write(
+                state,
+                &[EffectUniforms {
+                    texel_size,
+                    time,
+                    strength: effect.strength,
+                }],
+            );
+
+            let destination = if i + 1 == enabled.len() {
+                output
+            } else {
+                &self.targets[free].view
+            };
+            match &effect.texture {
+                Some(texture) => fullscreen_pass(
+                    encoder,
+                    destination,
+                    &effect.pipeline,
+                    &[source, &texture.binding],
+                    &effect.uniform_group,
+                ),
+                None => fullscreen_pass(
+                    encoder,
+                    destination,
+                    &effect.pipeline,
+                    &[source],
+                    &effect.uniform_group,
+                ),
+            }
+            source = &self.target_groups[free];
+            free = 1 - free;
+        }
+
+        self.taa.reset = false;
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
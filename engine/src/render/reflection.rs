@@ -0,0 +1,190 @@
+//! Planar reflections. The scene is drawn a second time, mirrored across
+//! the plane of a [`Mirror`] entity and clipped at it, into a target the
+//! size of the window. The target is the texture asset
+//! [`REFLECTION_TEXTURE`], a material with it as its diffuse map samples it
+//! at each pixel's window position rather than its texture coordinates.
+
+use log::info;
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+
+use super::{binding, state, texture::Texture};
+use crate::{
+    assets::{Assets, Handle},
+    camera::Camera,
+    inspect::Inspect,
+};
+
+/// Name of the reflection target among the texture assets
+pub const REFLECTION_TEXTURE: &str = "reflection";
+
+/// Makes the entity's local xz plane a mirror, facing along its y axis.
+/// Only the first mirror is reflected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Inspect)]
+pub struct Mirror {
+    /// How far in front of the surface the reflection is clipped, keeps
+    /// what touches the mirror from showing in it
+    #[serde(default)]
+    #[inspect_slider(min_value = 0.0, max_value = 1.0)]
+    pub offset: f32,
+}
+
+/// A plane through `point` facing along the unit vector `normal`.
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>,
+}
+
+impl Plane {
+    /// Distance of `point` in front of the plane, negative behind it.
+    pub fn distance(&self, point: &Point3<f32>) -> f32 {
+        (point - self.point).dot(&self.normal)
+    }
+
+    /// Mirror image of `point`.
+    pub fn reflect(&self, point: &Point3<f32>) -> Point3<f32> {
+        point - self.normal * 2.0 * self.distance(point)
+    }
+
+    /// `(a, b, c, d)` with `ax + by + cz + d = 0` on the plane.
+    fn coefficients(&self) -> Vector4<f32> {
+        let n = self.normal;
+        Vector4::new(n.x, n.y, n.z, -n.dot(&self.point.coords))
+    }
+
+    fn reflection_matrix(&self) -> Matrix4<f32> {
+        let n = self.normal;
+        let d = -n.dot(&self.point.coords);
+        #[rustfmt::skip]
+        let matrix = Matrix4::new(
+            1.0 - 2.0 * n.x * n.x, -2.0 * n.x * n.y, -2.0 * n.x * n.z, -2.0 * n.x * d,
+            -2.0 * n.y * n.x, 1.0 - 2.0 * n.y * n.y, -2.0 * n.y * n.z, -2.0 * n.y * d,
+            -2.0 * n.z * n.x, -2.0 * n.z * n.y, 1.0 - 2.0 * n.z * n.z, -2.0 * n.z * d,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        matrix
+    }
+}
+
+/// `camera` moved to its mirror image, for culling and level of detail of
+/// the reflected scene.
+pub fn mirror_camera(camera: &Camera, plane: &Plane) -> Camera {
+    let mut mirrored = camera.clone();
+    mirrored.look_at(plane.reflect(&camera.eye), plane.reflect(&camera.at()));
+    mirrored
+}
+
+/// View projection drawing the scene mirrored across `plane` where
+/// `camera` sees it reflected, with anything behind `clip` cut off.
+///
+/// Mirroring turns triangles around, so the image is flipped horizontally
+/// as well to keep their winding and back faces culled. Materials flip it
+/// back when sampling.
+pub fn view_proj(camera: &Camera, plane: &Plane, clip: &Plane) -> Matrix4<f32> {
+    let flip = Matrix4::new_nonuniform_scaling(&Vector3::new(-1.0, 1.0, 1.0));
+    let mut matrix = flip * camera.view_proj * plane.reflection_matrix();
+    let inverse = match matrix.try_inverse() {
+        Some(inverse) => inverse,
+        None => return matrix,
+    };
+
+    // Oblique near plane: depth is made 0 on the clip plane, and the far
+    // plane goes through the corner of the view opposite it
+    let coefficients = clip.coefficients();
+    let clip_space = inverse.transpose() * coefficients;
+    let corner = inverse * Vector4::new(clip_space.x.signum(), clip_space.y.signum(), 1.0, 1.0);
+    let scale = coefficients.dot(&corner);
+    if scale.abs() > f32::EPSILON {
+        matrix.set_row(2, &(coefficients / scale).transpose());
+    }
+    matrix
+}
+
+/// Target the reflection is drawn into, with its own camera uniforms like
+/// a viewport.
+pub struct Reflection {
+    /// Drawn into by the reflection pass, the texture itself is an asset
+    view: wgpu::TextureView,
+    /// Keeps the target loaded
+    _handle: Handle<Texture>,
+    pub depth_texture: Texture,
+    uniform_buffer: binding::Buffer,
+    pub uniform_group: binding::BufferGroup,
+}
+
+/// Creates a target the size of the window, replacing the asset of an
+/// earlier one.
+fn create_target(
+    state: &state::WgpuState,
+    textures: &mut Assets<Texture>,
+) -> (wgpu::TextureView, Handle<Texture>, Texture) {
+    info!(
+        "Create reflection target ({}x{})",
+        state.width(),
+        state.height()
+    );
+    let target = Texture::render_target(
+        state,
+        REFLECTION_TEXTURE,
+        state.width(),
+        state.height(),
+        state.format(),
+    );
+    let view = target
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    let handle = textures.insert(REFLECTION_TEXTURE, target);
+    let depth_texture = Texture::create_depth_texture(state, "reflection_depth");
+    (view, handle, depth_texture)
+}
+
+impl Reflection {
+    pub fn new<A: bytemuck::Pod>(
+        state: &state::WgpuState,
+        uniforms_layout: &binding::BindGroupLayout,
+        textures: &mut Assets<Texture>,
+        uniforms: &A,
+    ) -> Self {
+        let (view, handle, depth_texture) = create_target(state, textures);
+        let uniform_buffer = binding::Buffer::new_init(
+            state,
+            "reflection",
+            std::slice::from_ref(uniforms),
+            binding::BufferUsage::Uniform,
+        );
+        let uniform_group = binding::BufferGroup::from_buffer(
+            state,
+            "reflection",
+            uniforms_layout,
+            &[&uniform_buffer],
+        );
+        Self {
+            view,
+            _handle: handle,
+            depth_texture,
+            uniform_buffer,
+            uniform_group,
+        }
+    }
+
+    /// Recreates the target at the window's size. Materials using it have
+    /// to be bound again.
+    pub fn resize(&mut self, state: &state::WgpuState, textures: &mut Assets<Texture>) {
+        let (view, handle, depth_texture) = create_target(state, textures);
+        self.view = view;
+        self._handle = handle;
+        let old = std::mem::replace(&mut self.depth_texture, depth_texture);
+        state.texture_pool().release(old);
+    }
+
+    pub fn write_uniforms<A: bytemuck::Pod>(&self, state: &state::WgpuState, uniforms: &A) {
+        self.uniform_buffer
+            .write(state, std::slice::from_ref(uniforms));
+    }
+
+    /// The color target to draw the reflection into.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
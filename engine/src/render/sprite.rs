@@ -0,0 +1,239 @@
+//! Textured quads drawn over the scene with an orthographic projection, in
+//! window pixels from the top left. Sprites sharing a texture and layer are
+//! drawn together.
+
+use std::{collections::HashMap, ops::Range};
+
+use nalgebra::{Matrix4, Vector2, Vector4};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    binding::{self, BufferGroup, TextureBinding},
+    state,
+    texture::Texture,
+    traits::{Binding, DrawSprites, Vertex},
+};
+use crate::assets::{Assets, Handle};
+
+/// A sprite entity. Its texture is an asset, an atlas when `region` picks
+/// part of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sprite {
+    /// Resource path of the texture
+    pub texture: String,
+    /// The texture at `texture` once the world has loaded it
+    #[serde(skip)]
+    pub handle: Option<Handle<Texture>>,
+    /// `[x, y, width, height]` of the texture shown, from 0 to 1
+    #[serde(default = "Sprite::full_region")]
+    pub region: [f32; 4],
+    /// Top left corner in pixels
+    pub position: [f32; 2],
+    /// Pixels
+    pub size: [f32; 2],
+    /// Higher layers are drawn over lower ones
+    #[serde(default)]
+    pub layer: i32,
+    #[serde(default = "Sprite::white")]
+    pub color: [f32; 4],
+}
+
+impl Sprite {
+    fn full_region() -> [f32; 4] {
+        [0.0, 0.0, 1.0, 1.0]
+    }
+
+    fn white() -> [f32; 4] {
+        [1.0, 1.0, 1.0, 1.0]
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SpriteUniforms {
+    projection: Matrix4<f32>,
+}
+
+unsafe impl bytemuck::Pod for SpriteUniforms {}
+unsafe impl bytemuck::Zeroable for SpriteUniforms {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SpriteVertex {
+    position: Vector2<f32>,
+    tex_coords: Vector2<f32>,
+    color: Vector4<f32>,
+}
+
+unsafe impl bytemuck::Pod for SpriteVertex {}
+unsafe impl bytemuck::Zeroable for SpriteVertex {}
+
+impl Vertex for SpriteVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<SpriteVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float4,
+                },
+            ],
+        }
+    }
+}
+
+/// Sprites of one frame, sorted into draws of one texture each. Upload
+/// them once before the pass that draws them.
+pub struct SpriteRenderer {
+    projection: binding::Buffer,
+    projection_group: BufferGroup,
+    buffer: Option<binding::Buffer>,
+    /// Vertices the buffer can hold
+    capacity: usize,
+    /// Texture id and vertices of each draw, in drawing order
+    batches: Vec<(u64, Range<u32>)>,
+    /// Bindings of the textures drawn last frame by texture id
+    bindings: HashMap<u64, TextureBinding>,
+}
+
+impl SpriteRenderer {
+    pub fn new(state: &state::WgpuState, uniforms_layout: &binding::BindGroupLayout) -> Self {
+        let projection = binding::Buffer::new_init(
+            state,
+            "sprite_projection",
+            &[SpriteUniforms {
+                projection: Matrix4::identity(),
+            }],
+            binding::BufferUsage::Uniform,
+        );
+        let projection_group =
+            BufferGroup::from_buffer(state, "sprite_projection", uniforms_layout, &[&projection]);
+        Self {
+            projection,
+            projection_group,
+            buffer: None,
+            capacity: 0,
+            batches: Vec::new(),
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Writes `sprites` to the GPU for the current window size. Sprites
+    /// whose texture isn't loaded are left out.
+    pub fn upload(
+        &mut self,
+        state: &state::WgpuState,
+        sprites: &mut [Sprite],
+        textures: &Assets<Texture>,
+        layout: &binding::BindGroupLayout,
+    ) {
+        let (width, height) = (state.width() as f32, state.height() as f32);
+        self.projection.write(
+            state,
+            &[SpriteUniforms {
+                projection: Matrix4::new_orthographic(0.0, width, height, 0.0, -1.0, 1.0),
+            }],
+        );
+
+        sprites.sort_by(|a, b| a.layer.cmp(&b.layer).then(a.texture.cmp(&b.texture)));
+        self.batches.clear();
+        let mut vertices = Vec::new();
+        let mut current = None;
+        for sprite in sprites.iter() {
+            let texture = match sprite
+                .handle
+                .as_ref()
+                .and_then(|handle| textures.get(handle))
+            {
+                Some(texture) => texture,
+                None => continue,
+            };
+            if current != Some(texture.id()) {
+                current = Some(texture.id());
+                self.bindings.entry(texture.id()).or_insert_with(|| {
+                    TextureBinding::new_ref(state, sprite.texture.as_str(), layout, &[texture])
+                });
+                let start = vertices.len() as u32;
+                self.batches.push((texture.id(), start..start));
+            }
+
+            let [x, y] = sprite.position;
+            let [w, h] = sprite.size;
+            let [u, v, uw, vh] = sprite.region;
+            let color = Vector4::from(sprite.color);
+            let corner = |x: f32, y: f32, u: f32, v: f32| SpriteVertex {
+                position: Vector2::new(x, y),
+                tex_coords: Vector2::new(u, v),
+                color,
+            };
+            let top_left = corner(x, y, u, v);
+            let top_right = corner(x + w, y, u + uw, v);
+            let bottom_left = corner(x, y + h, u, v + vh);
+            let bottom_right = corner(x + w, y + h, u + uw, v + vh);
+            vertices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                bottom_right,
+                top_left,
+                bottom_right,
+                top_right,
+            ]);
+            if let Some((_, range)) = self.batches.last_mut() {
+                range.end = vertices.len() as u32;
+            }
+        }
+
+        let batches = &self.batches;
+        self.bindings
+            .retain(|id, _| batches.iter().any(|(batch, _)| batch == id));
+
+        if vertices.is_empty() {
+            return;
+        }
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            vertices.resize(self.capacity, vertices[0]);
+            self.buffer = Some(binding::Buffer::new_init(
+                state,
+                "sprites",
+                &vertices,
+                binding::BufferUsage::DynamicVertex,
+            ));
+        } else if let Some(buffer) = &self.buffer {
+            buffer.write(state, &vertices);
+        }
+    }
+}
+
+impl<'a, 'b> DrawSprites<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_sprites(&mut self, sprites: &'b SpriteRenderer) {
+        let buffer = match &sprites.buffer {
+            Some(buffer) if !sprites.batches.is_empty() => buffer,
+            _ => return,
+        };
+        self.bind_vertex_buffer(0, buffer);
+        self.bind_group(0, &sprites.projection_group);
+        for (texture, range) in sprites.batches.iter() {
+            if let Some(texture) = sprites.bindings.get(texture) {
+                self.bind_textures(1, texture);
+                self.draw(range.clone(), 0..1);
+            }
+        }
+    }
+}
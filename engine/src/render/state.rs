@@ -1,39 +1,108 @@
-use std::{fs, path::Path};
+use std::{collections::HashMap, path::Path, sync::Arc};
 
 use anyhow::*;
 
 use log::info;
 use wgpu_mipmap::{MipmapGenerator, RecommendedMipmapGenerator};
-use winit::window::Window;
+use winit::window::{Window, WindowId};
 
-pub struct WgpuState {
+use super::{
+    binding::{BindGroupCache, BindGroupLayout},
+    texture::TexturePool,
+    validation::ErrorScopes,
+    IndexedPipeline,
+};
+use crate::{error::EngineError, resources::Resources};
+
+/// Surface and swapchain of a window besides the main one.
+struct WindowSurface {
     surface: wgpu::Surface,
+    swap_chain_descriptor: wgpu::SwapChainDescriptor,
+    swap_chain: wgpu::SwapChain,
+}
+
+pub struct WgpuState {
+    /// Creates the surfaces of windows opened later
+    instance: wgpu::Instance,
+    /// The main window's, none when headless
+    surface: Option<wgpu::Surface>,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    /// Labels the device's errors with what was being created
+    errors: ErrorScopes,
+    /// Size and format frames are drawn at, also without a swapchain
     swap_chain_descriptor: wgpu::SwapChainDescriptor,
-    swap_chain: wgpu::SwapChain,
+    swap_chain: Option<wgpu::SwapChain>,
     mipgen: Box<dyn MipmapGenerator>,
+    resources: Arc<Resources>,
+    bind_groups: BindGroupCache,
+    texture_pool: TexturePool,
+    /// Other windows drawn with the same device
+    windows: HashMap<WindowId, WindowSurface>,
 }
 
 impl WgpuState {
-    pub async fn new(window: &Window, present_format: wgpu::TextureFormat) -> Result<Self> {
+    pub async fn new(
+        backend: wgpu::BackendBit,
+        window: &Window,
+        present_format: wgpu::TextureFormat,
+        resources: Arc<Resources>,
+    ) -> Result<Self> {
         let size = window.inner_size().clone();
 
-        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        let instance = wgpu::Instance::new(backend);
         let surface = unsafe { instance.create_surface(window) };
+        let mut state = Self::with_instance(
+            instance,
+            Some(surface),
+            size.width,
+            size.height,
+            present_format,
+            resources,
+        )
+        .await?;
+        state.recreate_swapchain(size.width, size.height);
+        Ok(state)
+    }
+
+    /// A state without a window, drawing `width` by `height` frames into
+    /// offscreen targets like `headless::Offscreen`.
+    pub async fn new_headless(
+        backend: wgpu::BackendBit,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        resources: Arc<Resources>,
+    ) -> Result<Self> {
+        info!("Headless rendering at {}x{}", width, height);
+        let instance = wgpu::Instance::new(backend);
+        Self::with_instance(instance, None, width, height, format, resources).await
+    }
 
+    async fn with_instance(
+        instance: wgpu::Instance,
+        surface: Option<wgpu::Surface>,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        resources: Arc<Resources>,
+    ) -> Result<Self> {
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::Default,
-                compatible_surface: Some(&surface),
+                compatible_surface: surface.as_ref(),
             })
             .await
             .context("Could not request adapter")?;
 
+        // Compressed textures are optional, loading them fails without
+        let features = adapter.features() & wgpu::Features::TEXTURE_COMPRESSION_BC;
+        info!("Request device features {:?}", features);
+
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
+                    features,
                     limits: wgpu::Limits::default(),
                     shader_validation: true,
                 },
@@ -41,59 +110,104 @@ impl WgpuState {
             )
             .await
             .context("Could not request device and queue")?;
+        let errors = ErrorScopes::install(&device);
 
         let swap_chain_descriptor = wgpu::SwapChainDescriptor {
             usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-            format: present_format,
-            width: size.width,
-            height: size.height,
+            format,
+            width,
+            height,
             present_mode: wgpu::PresentMode::Mailbox,
         };
-        let swap_chain = device.create_swap_chain(&surface, &swap_chain_descriptor);
 
         let mipgen = Box::new(RecommendedMipmapGenerator::new(&device));
 
         Ok(Self {
+            instance,
             surface,
             device,
             queue,
+            errors,
             swap_chain_descriptor,
-            swap_chain,
+            swap_chain: None,
             mipgen,
+            resources,
+            bind_groups: BindGroupCache::default(),
+            texture_pool: TexturePool::default(),
+            windows: HashMap::new(),
         })
     }
 
-    pub fn create_layout<T: Into<Option<&'a str>>>(
+    pub fn resources(&self) -> &Arc<Resources> {
+        &self.resources
+    }
+
+    /// Bind groups shared by `binding::BufferGroup`s and
+    /// `binding::TextureBinding`s.
+    pub fn bind_groups(&self) -> &BindGroupCache {
+        &self.bind_groups
+    }
+
+    /// Textures sized to the swapchain that were replaced on resize.
+    pub fn texture_pool(&self) -> &TexturePool {
+        &self.texture_pool
+    }
+
+    /// Wraps the creation of wgpu objects so their errors are reported
+    /// with their label.
+    pub fn errors(&self) -> &ErrorScopes {
+        &self.errors
+    }
+
+    pub fn supports(&self, features: wgpu::Features) -> bool {
+        self.device.features().contains(features)
+    }
+
+    pub fn create_layout<'a, T: Into<Option<&'a str>>>(
         &self,
         name: T,
         entries: &[wgpu::BindGroupLayoutEntry],
-    ) -> wgpu::BindGroupLayout {
+    ) -> BindGroupLayout {
         let name = name.into();
         info!("Create {:?} layout", &name.unwrap_or(""));
-        self.device()
-            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries,
-                label: name,
-            })
+        BindGroupLayout::new(self.errors.scope("bind group layout", name, || {
+            self.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries,
+                    label: name,
+                })
+        }))
     }
 
-    pub fn create_pipeline_layout<T: Into<Option<&'a str>>>(
+    pub fn create_pipeline_layout<'a, T: Into<Option<&'a str>>>(
         &self,
         name: T,
-        bindings: &[&wgpu::BindGroupLayout],
+        bindings: &[&BindGroupLayout],
     ) -> Result<wgpu::PipelineLayout> {
         let name = name.into();
         info!("Create pipeline layout {:?}", &name.unwrap_or(""));
-        Ok(self
-            .device()
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: name,
-                bind_group_layouts: bindings,
-                push_constant_ranges: &[],
-            }))
+        let max = self.device.limits().max_bind_groups;
+        if bindings.len() as u32 > max {
+            return Err(EngineError::GpuLimit {
+                limit: "Bind groups",
+                requested: bindings.len() as u64,
+                max: max as u64,
+            }
+            .into());
+        }
+        let bindings = bindings.iter().map(|layout| &***layout).collect::<Vec<_>>();
+        self.errors.try_scope("pipeline layout", name, || {
+            self.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: name,
+                    bind_group_layouts: &bindings,
+                    push_constant_ranges: &[],
+                })
+        })
     }
 
     pub fn create_render_pipeline<
+        'a,
         P: AsRef<Path>,
         D: Into<Option<(wgpu::TextureFormat, bool)>>,
         T: Into<Option<&'a str>>,
@@ -106,99 +220,339 @@ impl WgpuState {
         alpha_blend: wgpu::BlendDescriptor,
         depth_format: D,
         vertex_descs: &[wgpu::VertexBufferDescriptor],
+        index_format: wgpu::IndexFormat,
         vertex_shader: P,
         fragment_shader: P,
         cull_face: bool,
+    ) -> Result<wgpu::RenderPipeline> {
+        self.create_pipeline(
+            layout,
+            pipeline,
+            color_format,
+            color_blend,
+            alpha_blend,
+            depth_format,
+            vertex_descs,
+            index_format,
+            vertex_shader,
+            fragment_shader,
+            cull_face,
+            wgpu::PrimitiveTopology::TriangleList,
+        )
+    }
+
+    /// Pipeline drawing unindexed line lists.
+    pub fn create_line_pipeline<
+        'a,
+        P: AsRef<Path>,
+        D: Into<Option<(wgpu::TextureFormat, bool)>>,
+        T: Into<Option<&'a str>>,
+    >(
+        &self,
+        layout: &wgpu::PipelineLayout,
+        pipeline: T,
+        color_format: wgpu::TextureFormat,
+        depth_format: D,
+        vertex_descs: &[wgpu::VertexBufferDescriptor],
+        vertex_shader: P,
+        fragment_shader: P,
+    ) -> Result<wgpu::RenderPipeline> {
+        self.create_pipeline(
+            layout,
+            pipeline,
+            color_format,
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+            depth_format,
+            vertex_descs,
+            wgpu::IndexFormat::Uint16,
+            vertex_shader,
+            fragment_shader,
+            false,
+            wgpu::PrimitiveTopology::LineList,
+        )
+    }
+
+    fn create_pipeline<
+        'a,
+        P: AsRef<Path>,
+        D: Into<Option<(wgpu::TextureFormat, bool)>>,
+        T: Into<Option<&'a str>>,
+    >(
+        &self,
+        layout: &wgpu::PipelineLayout,
+        pipeline: T,
+        color_format: wgpu::TextureFormat,
+        color_blend: wgpu::BlendDescriptor,
+        alpha_blend: wgpu::BlendDescriptor,
+        depth_format: D,
+        vertex_descs: &[wgpu::VertexBufferDescriptor],
+        index_format: wgpu::IndexFormat,
+        vertex_shader: P,
+        fragment_shader: P,
+        cull_face: bool,
+        topology: wgpu::PrimitiveTopology,
     ) -> Result<wgpu::RenderPipeline> {
         let pipeline = pipeline.into();
         info!("Init render pipeline {:?}", &pipeline.unwrap_or(""));
-        let res_dir = std::path::Path::new(env!("OUT_DIR")).join("res");
-        let vs_module = self.device().create_shader_module(wgpu::util::make_spirv(
-            fs::read(res_dir.join(vertex_shader.as_ref()))
-                .context(format!(
-                    "Could not read shader {:?}",
-                    vertex_shader.as_ref()
-                ))?
-                .as_slice(),
-        ));
+        let vs_module = self.load_shader(vertex_shader.as_ref())?;
         info!("Loaded vertex shader {:?}", vertex_shader.as_ref());
-        let fs_module = self.device().create_shader_module(wgpu::util::make_spirv(
-            fs::read(res_dir.join(fragment_shader.as_ref()))
-                .context(format!(
-                    "Could not read shader {:?}",
-                    fragment_shader.as_ref()
-                ))?
-                .as_slice(),
-        ));
+        let fs_module = self.load_shader(fragment_shader.as_ref())?;
         info!("Loaded fragment shader {:?}", fragment_shader.as_ref());
 
-        let result = self
-            .device()
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: pipeline,
-                layout: Some(layout),
-                vertex_stage: wgpu::ProgrammableStageDescriptor {
-                    module: &vs_module,
-                    entry_point: "main",
-                },
-                fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                    module: &fs_module,
-                    entry_point: "main",
-                }),
-                rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: if !cull_face {
-                        wgpu::CullMode::None
-                    } else {
-                        wgpu::CullMode::Back
+        let result = self.errors.try_scope("render pipeline", pipeline, || {
+            self.device()
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: pipeline,
+                    layout: Some(layout),
+                    vertex_stage: wgpu::ProgrammableStageDescriptor {
+                        module: &vs_module,
+                        entry_point: "main",
                     },
-                    depth_bias: 2,
-                    depth_bias_slope_scale: 2.0,
-                    depth_bias_clamp: 0.0,
-                    clamp_depth: self
-                        .device()
-                        .features()
-                        .contains(wgpu::Features::DEPTH_CLAMPING),
-                }),
-                primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-                color_states: &[wgpu::ColorStateDescriptor {
-                    format: color_format,
-                    color_blend,
-                    alpha_blend,
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-                depth_stencil_state: depth_format.into().map(|(format, write)| {
-                    wgpu::DepthStencilStateDescriptor {
-                        format,
-                        depth_write_enabled: write,
-                        depth_compare: wgpu::CompareFunction::Less,
-                        stencil: wgpu::StencilStateDescriptor::default(),
-                    }
-                }),
-                vertex_state: wgpu::VertexStateDescriptor {
-                    index_format: wgpu::IndexFormat::Uint32,
-                    vertex_buffers: vertex_descs,
-                },
-                sample_count: 1,
-                sample_mask: !0,
-                alpha_to_coverage_enabled: false,
-            });
+                    fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                        module: &fs_module,
+                        entry_point: "main",
+                    }),
+                    rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: if !cull_face {
+                            wgpu::CullMode::None
+                        } else {
+                            wgpu::CullMode::Back
+                        },
+                        depth_bias: 2,
+                        depth_bias_slope_scale: 2.0,
+                        depth_bias_clamp: 0.0,
+                        clamp_depth: self
+                            .device()
+                            .features()
+                            .contains(wgpu::Features::DEPTH_CLAMPING),
+                    }),
+                    primitive_topology: topology,
+                    color_states: &[wgpu::ColorStateDescriptor {
+                        format: color_format,
+                        color_blend,
+                        alpha_blend,
+                        write_mask: wgpu::ColorWrite::ALL,
+                    }],
+                    depth_stencil_state: depth_format.into().map(|(format, write)| {
+                        wgpu::DepthStencilStateDescriptor {
+                            format,
+                            depth_write_enabled: write,
+                            depth_compare: wgpu::CompareFunction::Less,
+                            stencil: wgpu::StencilStateDescriptor::default(),
+                        }
+                    }),
+                    vertex_state: wgpu::VertexStateDescriptor {
+                        index_format,
+                        vertex_buffers: vertex_descs,
+                    },
+                    sample_count: 1,
+                    sample_mask: !0,
+                    alpha_to_coverage_enabled: false,
+                })
+        })?;
         info!("Created pipeline {:?}", pipeline.clone().unwrap_or(""));
 
         Ok(result)
     }
 
+    /// Creates the same pipeline for both index formats, so meshes can pick
+    /// whichever fits their vertex count.
+    pub fn create_indexed_pipeline<
+        'a,
+        P: AsRef<Path>,
+        D: Into<Option<(wgpu::TextureFormat, bool)>>,
+        T: Into<Option<&'a str>>,
+    >(
+        &self,
+        layout: &wgpu::PipelineLayout,
+        pipeline: T,
+        color_format: wgpu::TextureFormat,
+        color_blend: wgpu::BlendDescriptor,
+        alpha_blend: wgpu::BlendDescriptor,
+        depth_format: D,
+        vertex_descs: &[wgpu::VertexBufferDescriptor],
+        vertex_shader: P,
+        fragment_shader: P,
+        cull_face: bool,
+    ) -> Result<IndexedPipeline> {
+        let pipeline = pipeline.into();
+        let depth_format = depth_format.into();
+        Ok(IndexedPipeline {
+            uint16: self.create_render_pipeline(
+                layout,
+                pipeline,
+                color_format,
+                color_blend.clone(),
+                alpha_blend.clone(),
+                depth_format,
+                vertex_descs,
+                wgpu::IndexFormat::Uint16,
+                vertex_shader.as_ref(),
+                fragment_shader.as_ref(),
+                cull_face,
+            )?,
+            uint32: self.create_render_pipeline(
+                layout,
+                pipeline,
+                color_format,
+                color_blend,
+                alpha_blend,
+                depth_format,
+                vertex_descs,
+                wgpu::IndexFormat::Uint32,
+                vertex_shader.as_ref(),
+                fragment_shader.as_ref(),
+                cull_face,
+            )?,
+        })
+    }
+
+    pub fn create_compute_pipeline<'a, P: AsRef<Path>, T: Into<Option<&'a str>>>(
+        &self,
+        layout: &wgpu::PipelineLayout,
+        pipeline: T,
+        compute_shader: P,
+    ) -> Result<wgpu::ComputePipeline> {
+        let pipeline = pipeline.into();
+        info!("Init compute pipeline {:?}", &pipeline.unwrap_or(""));
+        let cs_module = self.load_shader(compute_shader.as_ref())?;
+        info!("Loaded compute shader {:?}", compute_shader.as_ref());
+
+        let result = self.errors.try_scope("compute pipeline", pipeline, || {
+            self.device()
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: pipeline,
+                    layout: Some(layout),
+                    compute_stage: wgpu::ProgrammableStageDescriptor {
+                        module: &cs_module,
+                        entry_point: "main",
+                    },
+                })
+        })?;
+        info!("Created compute pipeline {:?}", pipeline.unwrap_or(""));
+
+        Ok(result)
+    }
+
+    fn load_shader(&self, shader: &Path) -> Result<wgpu::ShaderModule> {
+        let source = self
+            .resources
+            .read(shader)
+            .context(format!("Could not read shader {:?}", shader))?;
+        let name = shader.to_string_lossy();
+        match self.errors.check("shader module", Some(name.as_ref()), || {
+            self.device()
+                .create_shader_module(wgpu::util::make_spirv(&source))
+        }) {
+            (module, None) => Ok(module),
+            (_, Some(message)) => Err(EngineError::ShaderCompile {
+                shader: name.into_owned(),
+                message,
+            }
+            .into()),
+        }
+    }
+
     pub fn encoder(&self) -> wgpu::CommandEncoder {
         self.device()
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None })
     }
 
+    /// Waits for the display to present frames when `vsync` is set,
+    /// otherwise presents the latest frame without tearing.
+    pub fn set_vsync(&mut self, vsync: bool) {
+        let present_mode = if vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::Mailbox
+        };
+        if present_mode == self.swap_chain_descriptor.present_mode {
+            return;
+        }
+        self.swap_chain_descriptor.present_mode = present_mode;
+        self.create_swap_chain();
+        for window in self.windows.values_mut() {
+            window.swap_chain_descriptor.present_mode = present_mode;
+            window.swap_chain = self
+                .device
+                .create_swap_chain(&window.surface, &window.swap_chain_descriptor);
+        }
+    }
+
+    /// Creates a surface and swapchain for `window`, drawn like the main
+    /// window with the same device.
+    pub fn add_window(&mut self, window: &Window) {
+        let size = window.inner_size();
+        info!(
+            "Create surface for window {:?} ({}x{})",
+            window.id(),
+            size.width,
+            size.height
+        );
+        let surface = unsafe { self.instance.create_surface(window) };
+        let swap_chain_descriptor = wgpu::SwapChainDescriptor {
+            width: size.width,
+            height: size.height,
+            ..self.swap_chain_descriptor.clone()
+        };
+        let swap_chain = self
+            .device
+            .create_swap_chain(&surface, &swap_chain_descriptor);
+        self.windows.insert(
+            window.id(),
+            WindowSurface {
+                surface,
+                swap_chain_descriptor,
+                swap_chain,
+            },
+        );
+    }
+
+    /// Drops the surface of a window before it closes.
+    pub fn remove_window(&mut self, id: WindowId) {
+        self.windows.remove(&id);
+    }
+
+    pub fn resize_window(&mut self, id: WindowId, width: u32, height: u32) {
+        if let Some(window) = self.windows.get_mut(&id) {
+            window.swap_chain_descriptor.width = width;
+            window.swap_chain_descriptor.height = height;
+            window.swap_chain = self
+                .device
+                .create_swap_chain(&window.surface, &window.swap_chain_descriptor);
+        }
+    }
+
+    /// The next frame of the window `id`, none for unknown windows.
+    pub fn window_frame(
+        &mut self,
+        id: WindowId,
+    ) -> Option<Result<wgpu::SwapChainFrame, wgpu::SwapChainError>> {
+        self.windows
+            .get_mut(&id)
+            .map(|window| window.swap_chain.get_current_frame())
+    }
+
     pub fn recreate_swapchain(&mut self, width: u32, height: u32) {
         self.swap_chain_descriptor.width = width;
         self.swap_chain_descriptor.height = height;
-        self.swap_chain = self
-            .device
-            .create_swap_chain(&self.surface, &self.swap_chain_descriptor);
+        self.create_swap_chain();
+    }
+
+    fn create_swap_chain(&mut self) {
+        if let Some(surface) = &self.surface {
+            self.swap_chain = Some(
+                self.device
+                    .create_swap_chain(surface, &self.swap_chain_descriptor),
+            );
+        }
+    }
+
+    /// Whether there is no window to present to.
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
     }
 
     pub fn write_buffer<A: bytemuck::Pod>(&self, buffer: &wgpu::Buffer, data: &[A]) {
@@ -206,8 +560,13 @@ impl WgpuState {
             .write_buffer(&buffer, 0, bytemuck::cast_slice(&data));
     }
 
+    /// The main window's next frame. Headless states have none, they
+    /// report their swapchain lost.
     pub fn frame(&mut self) -> Result<wgpu::SwapChainFrame, wgpu::SwapChainError> {
-        self.swap_chain.get_current_frame()
+        match &mut self.swap_chain {
+            Some(swap_chain) => swap_chain.get_current_frame(),
+            None => Err(wgpu::SwapChainError::Lost),
+        }
     }
 
     pub fn device(&self) -> &wgpu::Device {
@@ -0,0 +1,278 @@
+//! Text drawn in the scene on quads facing the camera. Glyphs are
+//! rasterized the first time they are drawn and packed into an atlas
+//! texture, row by row.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::*;
+use log::{info, warn};
+use nalgebra::{Point3, Vector2, Vector3, Vector4};
+
+use super::{
+    binding::{self, BufferGroup, TextureBinding},
+    state,
+    texture::{SamplerDesc, Texture},
+    traits::{Binding, DrawText, Vertex},
+};
+use crate::{editor::theme::FONT_DIR, resources::Resources};
+
+/// Width and height of the glyph atlas
+pub const ATLAS_SIZE: u32 = 512;
+/// Pixel height glyphs are rasterized at, scaled to the text's height
+const RASTER_SIZE: f32 = 32.0;
+/// Empty pixels between glyphs, keeps filtering from bleeding
+const PADDING: u32 = 1;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TextVertex {
+    position: Vector3<f32>,
+    tex_coords: Vector2<f32>,
+    color: Vector4<f32>,
+}
+
+unsafe impl bytemuck::Pod for TextVertex {}
+unsafe impl bytemuck::Zeroable for TextVertex {}
+
+impl Vertex for TextVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float3,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float4,
+                },
+            ],
+        }
+    }
+}
+
+/// Where a glyph is in the atlas and how it sits on the baseline, in
+/// pixels at `RASTER_SIZE`.
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    uv_min: Vector2<f32>,
+    uv_max: Vector2<f32>,
+    /// Bottom left corner relative to the pen on the baseline
+    offset: Vector2<f32>,
+    size: Vector2<f32>,
+    advance: f32,
+}
+
+/// A string queued for this frame.
+struct QueuedText {
+    position: Point3<f32>,
+    text: String,
+    height: f32,
+    color: Vector4<f32>,
+}
+
+/// Strings queued during a frame and drawn in the scene, centered on their
+/// position. Queue them, then `upload` once before the passes that draw
+/// them. Nothing is drawn until a font is loaded.
+pub struct TextRenderer {
+    font: Option<(String, fontdue::Font)>,
+    atlas: Texture,
+    atlas_group: TextureBinding,
+    /// Glyphs rasterized so far, `None` for those the atlas had no room for
+    glyphs: HashMap<char, Option<Glyph>>,
+    /// Top left corner of the next glyph in the atlas
+    cursor: [u32; 2],
+    /// Height of the tallest glyph in the current atlas row
+    row_height: u32,
+    queued: Vec<QueuedText>,
+    buffer: Option<binding::Buffer>,
+    /// Vertices the buffer can hold
+    capacity: usize,
+    /// Vertices uploaded for this frame
+    count: u32,
+}
+
+impl TextRenderer {
+    pub fn new(state: &state::WgpuState, layout: &binding::BindGroupLayout) -> Self {
+        let atlas = Texture::blank(
+            state,
+            "glyph_atlas",
+            ATLAS_SIZE,
+            ATLAS_SIZE,
+            wgpu::TextureFormat::R8Unorm,
+            &SamplerDesc::linear(),
+        );
+        let atlas_group = TextureBinding::new_ref(state, "glyph_atlas", layout, &[&atlas]);
+        Self {
+            font: None,
+            atlas,
+            atlas_group,
+            glyphs: HashMap::new(),
+            cursor: [0, 0],
+            row_height: 0,
+            queued: Vec::new(),
+            buffer: None,
+            capacity: 0,
+            count: 0,
+        }
+    }
+
+    /// Name of the loaded font file.
+    pub fn font(&self) -> Option<&str> {
+        self.font.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// Draws text with the TTF file `name` in `FONT_DIR` from now on.
+    pub fn load_font(&mut self, resources: &Resources, name: &str) -> Result<()> {
+        info!("Load label font {:?}", name);
+        let data = resources.read(Path::new(FONT_DIR).join(name))?;
+        let font = fontdue::Font::from_bytes(data.as_ref(), fontdue::FontSettings::default())
+            .map_err(|e| anyhow!("Could not parse font {:?}: {}", name, e))?;
+        self.font = Some((name.to_string(), font));
+        // Atlas pixels of the old font are overwritten as glyphs come in
+        self.glyphs.clear();
+        self.cursor = [0, 0];
+        self.row_height = 0;
+        Ok(())
+    }
+
+    /// Queues `text`, `height` world units tall, centered on `position`.
+    pub fn text(
+        &mut self,
+        position: Point3<f32>,
+        text: impl Into<String>,
+        height: f32,
+        color: Vector4<f32>,
+    ) {
+        self.queued.push(QueuedText {
+            position,
+            text: text.into(),
+            height,
+            color,
+        });
+    }
+
+    /// Rasterizes `c` into the atlas unless it is there already.
+    fn glyph(&mut self, state: &state::WgpuState, c: char) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return *glyph;
+        }
+        let font = &self.font.as_ref()?.1;
+        let (metrics, pixels) = font.rasterize(c, RASTER_SIZE);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+
+        if self.cursor[0] + width > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.row_height + PADDING];
+            self.row_height = 0;
+        }
+        let glyph = if self.cursor[1] + height > ATLAS_SIZE {
+            warn!("Glyph atlas is full, {:?} is not drawn", c);
+            None
+        } else {
+            if width > 0 && height > 0 {
+                self.atlas
+                    .write_region(state, self.cursor, width, height, width, &pixels);
+            }
+            let min = Vector2::new(self.cursor[0] as f32, self.cursor[1] as f32);
+            let size = Vector2::new(width as f32, height as f32);
+            self.cursor[0] += width + PADDING;
+            self.row_height = self.row_height.max(height);
+            Some(Glyph {
+                uv_min: min / ATLAS_SIZE as f32,
+                uv_max: (min + size) / ATLAS_SIZE as f32,
+                offset: Vector2::new(metrics.xmin as f32, metrics.ymin as f32),
+                size,
+                advance: metrics.advance_width,
+            })
+        };
+        self.glyphs.insert(c, glyph);
+        glyph
+    }
+
+    /// Lays the queued text out on quads spanned by the camera's `right`
+    /// and `up`, writes them to the GPU and starts queueing the next
+    /// frame's.
+    pub fn upload(&mut self, state: &state::WgpuState, right: Vector3<f32>, up: Vector3<f32>) {
+        let queued = std::mem::take(&mut self.queued);
+        let mut vertices = Vec::new();
+        for text in queued.iter() {
+            let glyphs = text
+                .text
+                .chars()
+                .filter_map(|c| self.glyph(state, c))
+                .collect::<Vec<_>>();
+            let scale = text.height / RASTER_SIZE;
+            let width = glyphs.iter().map(|glyph| glyph.advance).sum::<f32>() * scale;
+            let mut pen = -width / 2.0;
+            for glyph in glyphs.iter() {
+                let min = Vector2::new(pen, 0.0) + glyph.offset * scale;
+                let max = min + glyph.size * scale;
+                pen += glyph.advance * scale;
+                if glyph.size.x == 0.0 {
+                    continue;
+                }
+                let corner = |x: f32, y: f32, u: f32, v: f32| TextVertex {
+                    position: (text.position + right * x + up * y).coords,
+                    tex_coords: Vector2::new(u, v),
+                    color: text.color,
+                };
+                // Bitmap rows run top down
+                let top_left = corner(min.x, max.y, glyph.uv_min.x, glyph.uv_min.y);
+                let top_right = corner(max.x, max.y, glyph.uv_max.x, glyph.uv_min.y);
+                let bottom_left = corner(min.x, min.y, glyph.uv_min.x, glyph.uv_max.y);
+                let bottom_right = corner(max.x, min.y, glyph.uv_max.x, glyph.uv_max.y);
+                vertices.extend_from_slice(&[
+                    top_left,
+                    bottom_left,
+                    bottom_right,
+                    top_left,
+                    bottom_right,
+                    top_right,
+                ]);
+            }
+        }
+
+        self.count = vertices.len() as u32;
+        if vertices.is_empty() {
+            return;
+        }
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            vertices.resize(self.capacity, vertices[0]);
+            self.buffer = Some(binding::Buffer::new_init(
+                state,
+                "text",
+                &vertices,
+                binding::BufferUsage::DynamicVertex,
+            ));
+        } else if let Some(buffer) = &self.buffer {
+            buffer.write(state, &vertices);
+        }
+    }
+}
+
+impl<'a, 'b> DrawText<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_text(&mut self, text: &'b TextRenderer, uniforms: &'b BufferGroup) {
+        let buffer = match &text.buffer {
+            Some(buffer) if text.count > 0 => buffer,
+            _ => return,
+        };
+        self.bind_vertex_buffer(0, buffer);
+        self.bind_group(0, uniforms);
+        self.bind_textures(1, &text.atlas_group);
+        self.draw(0..text.count, 0..1);
+    }
+}
@@ -1,20 +1,294 @@
-use std::{num::NonZeroU8, path::Path};
+use std::{collections::VecDeque, num::NonZeroU8, path::Path, sync::Mutex};
 
 use anyhow::*;
 use image::GenericImageView;
 use log::info;
 
-use super::state;
+use super::{binding, compressed::CompressedImage, hdr::HdrImage, state};
+use crate::{error::EngineError, stats};
+
+/// Widest and tallest 2D texture every wgpu backend can create
+pub const MAX_TEXTURE_SIZE: u32 = 8192;
+
+pub enum TexturePixels {
+    Image(image::DynamicImage),
+    /// Block compressed data with its mip chain, uploaded as is
+    Compressed(CompressedImage),
+    /// Linear float data, uploaded as `Rgba16Float`
+    Hdr(HdrImage),
+}
+
+/// Decoded image waiting to be uploaded, produced off the render thread.
+pub struct TextureData {
+    pub label: String,
+    pub pixels: TexturePixels,
+    pub is_normal_map: bool,
+    pub sampler: SamplerDesc,
+}
+
+/// Sampler settings of a texture, the defaults match what textures used
+/// before they were configurable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDesc {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Maximum anisotropy, `None` disables anisotropic filtering
+    pub anisotropy: Option<u8>,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            anisotropy: Some(16),
+        }
+    }
+}
+
+impl SamplerDesc {
+    pub fn repeat() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            ..Default::default()
+        }
+    }
+
+    pub fn linear() -> Self {
+        Self {
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }
+    }
+
+    pub fn create(&self, state: &state::WgpuState, label: Option<&str>) -> wgpu::Sampler {
+        state.device().create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: self.anisotropy.and_then(NonZeroU8::new),
+            ..Default::default()
+        })
+    }
+}
+
+/// Fails for textures wider or taller than `MAX_TEXTURE_SIZE`.
+fn check_size(size: u32) -> Result<()> {
+    if size > MAX_TEXTURE_SIZE {
+        return Err(EngineError::GpuLimit {
+            limit: "Texture size",
+            requested: size as u64,
+            max: MAX_TEXTURE_SIZE as u64,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Levels down to 1x1 for a `width` by `height` texture.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+impl TextureData {
+    pub fn load<P: AsRef<Path>>(path: P, is_normal_map: bool) -> Result<Self> {
+        info!("Decode texture {:?}", path.as_ref());
+        let pixels = if CompressedImage::is_compressed_path(path.as_ref()) {
+            TexturePixels::Compressed(CompressedImage::load(path.as_ref(), !is_normal_map)?)
+        } else if HdrImage::is_hdr_path(path.as_ref()) {
+            TexturePixels::Hdr(HdrImage::load(path.as_ref())?)
+        } else {
+            TexturePixels::Image(
+                image::open(path.as_ref())
+                    .context(format!("Could not open texture {:?}", path.as_ref()))?,
+            )
+        };
+
+        Ok(Self {
+            label: path.as_ref().to_string_lossy().into_owned(),
+            pixels,
+            is_normal_map,
+            sampler: SamplerDesc::default(),
+        })
+    }
+
+    /// 1x1 texture of a single color, `color` in 0..1 range. Labelled by
+    /// its color so equal colors share one cached texture.
+    pub fn solid(color: [f32; 4], is_normal_map: bool) -> Self {
+        let pixel = image::Rgba([
+            (color[0] * 255.0) as u8,
+            (color[1] * 255.0) as u8,
+            (color[2] * 255.0) as u8,
+            (color[3] * 255.0) as u8,
+        ]);
+        Self {
+            label: format!("solid {:?}", pixel.0),
+            pixels: TexturePixels::Image(image::DynamicImage::ImageRgba8(
+                image::RgbaImage::from_pixel(1, 1, pixel),
+            )),
+            is_normal_map,
+            sampler: SamplerDesc::default(),
+        }
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerDesc) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Key in the texture cache, the same image uploads differently as a
+    /// normal map.
+    pub fn cache_key(&self) -> String {
+        let mut key = self.label.clone();
+        if self.is_normal_map {
+            key.push_str(" (normal)");
+        }
+        // Textures own their sampler, so differently sampled uses of one
+        // image can't share a texture
+        if self.sampler != SamplerDesc::default() {
+            key.push_str(&format!(" {:?}", self.sampler));
+        }
+        key
+    }
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    /// Bytes counted towards `stats::TEXTURE_BYTES`
+    bytes: u64,
+    id: u64,
+    key: PoolKey,
+}
+
+/// Textures alike enough to stand in for each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PoolKey {
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsage,
+}
+
+impl PoolKey {
+    fn of(descriptor: &wgpu::TextureDescriptor) -> Self {
+        Self {
+            width: descriptor.size.width,
+            height: descriptor.size.height,
+            format: descriptor.format,
+            usage: descriptor.usage,
+        }
+    }
+}
+
+/// Most textures kept for reuse, the oldest are dropped first
+const POOL_CAPACITY: usize = 8;
+
+/// Targets and depth textures replaced on resize, handed out again when a
+/// texture of the same size and format is needed, so a window going back
+/// and forth between sizes doesn't create new ones every time.
+#[derive(Default)]
+pub struct TexturePool {
+    free: Mutex<VecDeque<Texture>>,
+}
+
+impl TexturePool {
+    fn take(&self, key: &PoolKey) -> Option<Texture> {
+        let mut free = self.free.lock().ok()?;
+        let index = free.iter().position(|texture| texture.key == *key)?;
+        free.remove(index)
+    }
+
+    /// Keeps `texture` for reuse once nothing draws to it anymore.
+    pub fn release(&self, texture: Texture) {
+        if let Ok(mut free) = self.free.lock() {
+            free.push_back(texture);
+            if free.len() > POOL_CAPACITY {
+                free.pop_front();
+            }
+        }
+    }
+}
+
+/// Rough size of a texture on the GPU, every mip level included.
+fn estimate_bytes(descriptor: &wgpu::TextureDescriptor) -> u64 {
+    use wgpu::TextureFormat::*;
+
+    let bytes_per_pixel = match descriptor.format {
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb | Bc4RUnorm | Bc4RSnorm => 0.5,
+        Bc2RgbaUnorm | Bc2RgbaUnormSrgb | Bc3RgbaUnorm | Bc3RgbaUnormSrgb | Bc5RgUnorm
+        | Bc5RgSnorm | Bc6hRgbUfloat | Bc6hRgbSfloat | Bc7RgbaUnorm | Bc7RgbaUnormSrgb => 1.0,
+        R8Unorm => 1.0,
+        Rgba16Float => 8.0,
+        Rgba32Float => 16.0,
+        _ => 4.0,
+    };
+    let size = descriptor.size;
+    let pixels = (0..descriptor.mip_level_count)
+        .map(|level| (size.width >> level).max(1) as u64 * (size.height >> level).max(1) as u64)
+        .sum::<u64>()
+        * size.depth as u64;
+    (pixels as f64 * bytes_per_pixel) as u64
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        stats::untrack_bytes(&stats::TEXTURE_BYTES, self.bytes);
+    }
 }
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+    fn tracked(
+        texture: wgpu::Texture,
+        view: wgpu::TextureView,
+        sampler: wgpu::Sampler,
+        descriptor: &wgpu::TextureDescriptor,
+    ) -> Self {
+        let bytes = estimate_bytes(descriptor);
+        stats::track_bytes(&stats::TEXTURE_BYTES, bytes);
+        Self {
+            texture,
+            view,
+            sampler,
+            bytes,
+            id: binding::next_resource_id(),
+            key: PoolKey::of(descriptor),
+        }
+    }
+
+    /// A released texture matching `descriptor` from the pool, otherwise
+    /// one made by `create`.
+    fn pooled(
+        state: &state::WgpuState,
+        descriptor: &wgpu::TextureDescriptor,
+        create: impl FnOnce() -> Self,
+    ) -> Self {
+        state
+            .texture_pool()
+            .take(&PoolKey::of(descriptor))
+            .unwrap_or_else(create)
+    }
+
+    /// Identifies the texture in the bind group cache.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     #[allow(dead_code)]
     pub fn from_bytes(
         state: &state::WgpuState,
@@ -23,7 +297,150 @@ impl Texture {
         is_normal_map: bool,
     ) -> Result<Self> {
         let img = image::load_from_memory(bytes)?;
-        Self::from_image(state, &img, Some(label), is_normal_map)
+        Self::from_image(
+            state,
+            &img,
+            Some(label),
+            is_normal_map,
+            &SamplerDesc::default(),
+        )
+    }
+
+    pub fn from_data(state: &state::WgpuState, data: &TextureData) -> Result<Self> {
+        match &data.pixels {
+            TexturePixels::Image(image) => Self::from_image(
+                state,
+                image,
+                Some(data.label.as_str()),
+                data.is_normal_map,
+                &data.sampler,
+            ),
+            TexturePixels::Compressed(image) => {
+                Self::from_compressed(state, image, Some(data.label.as_str()), &data.sampler)
+            }
+            TexturePixels::Hdr(image) => Self::from_hdr(
+                state,
+                image,
+                Some(data.label.as_str()),
+                wgpu::TextureFormat::Rgba16Float,
+                &data.sampler,
+            ),
+        }
+    }
+
+    /// Uploads a float image as `Rgba16Float` or `Rgba32Float`, the latter
+    /// keeps full precision but can't be filtered on every device.
+    pub fn from_hdr(
+        state: &state::WgpuState,
+        image: &HdrImage,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        sampler: &SamplerDesc,
+    ) -> Result<Self> {
+        check_size(image.width.max(image.height))?;
+        let size = wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth: 1,
+        };
+        let descriptor = wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        };
+        let texture = state.device().create_texture(&descriptor);
+
+        let (data, bytes_per_pixel) = match format {
+            wgpu::TextureFormat::Rgba16Float => {
+                (bytemuck::cast_slice(&image.to_rgba16f()).to_vec(), 8)
+            }
+            wgpu::TextureFormat::Rgba32Float => (bytemuck::cast_slice(&image.pixels).to_vec(), 16),
+            format => bail!("{:?} is not a HDR texture format", format),
+        };
+
+        state.queue().write_texture(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &data,
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: bytes_per_pixel * image.width,
+                rows_per_image: image.height,
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = sampler.create(state, label);
+
+        Ok(Self::tracked(texture, view, sampler, &descriptor))
+    }
+
+    /// Uploads every stored mip level, compressed data can't be used as a
+    /// render target to generate them.
+    pub fn from_compressed(
+        state: &state::WgpuState,
+        image: &CompressedImage,
+        label: Option<&str>,
+        sampler: &SamplerDesc,
+    ) -> Result<Self> {
+        ensure!(
+            !image.is_block_compressed() || state.supports(wgpu::Features::TEXTURE_COMPRESSION_BC),
+            "Device does not support BC compressed textures, needed by {}",
+            label.unwrap_or("unknown")
+        );
+
+        let descriptor = wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: image.width,
+                height: image.height,
+                depth: 1,
+            },
+            mip_level_count: image.mip_level_count(),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: image.format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        };
+        let texture = state.device().create_texture(&descriptor);
+
+        let (block, block_bytes) = image.block_size();
+        for (level, data) in image.levels.iter().enumerate() {
+            // Copies cover whole blocks, even for mips smaller than a block
+            let blocks_x = ((image.width >> level).max(1) + block - 1) / block;
+            let blocks_y = ((image.height >> level).max(1) + block - 1) / block;
+            state.queue().write_texture(
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: blocks_x * block_bytes,
+                    rows_per_image: blocks_y * block,
+                },
+                wgpu::Extent3d {
+                    width: blocks_x * block,
+                    height: blocks_y * block,
+                    depth: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = sampler.create(state, label);
+
+        Ok(Self::tracked(texture, view, sampler, &descriptor))
     }
 
     pub fn from_image(
@@ -31,9 +448,11 @@ impl Texture {
         img: &image::DynamicImage,
         label: Option<&str>,
         is_normal_map: bool,
+        sampler: &SamplerDesc,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
+        check_size(dimensions.0.max(dimensions.1))?;
+        let rgba = img.to_rgba8();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -44,7 +463,7 @@ impl Texture {
         let descriptor = wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 12,
+            mip_level_count: mip_level_count(dimensions.0, dimensions.1),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: if is_normal_map {
@@ -83,28 +502,217 @@ impl Texture {
         state.queue().submit(std::iter::once(encoder.finish()));
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = state.device().create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            anisotropy_clamp: NonZeroU8::new(16),
+        let sampler = sampler.create(state, label);
+
+        Ok(Self::tracked(texture, view, sampler, &descriptor))
+    }
+
+    /// Packs equally sized images into the layers of a 2D array texture.
+    /// Mips are downscaled on the CPU, the mipmap generator only handles
+    /// single layer textures.
+    pub fn from_layers(
+        state: &state::WgpuState,
+        layers: &[image::RgbaImage],
+        label: Option<&str>,
+        is_normal_map: bool,
+        sampler: &SamplerDesc,
+    ) -> Result<Self> {
+        let (width, height) = layers
+            .first()
+            .map(|layer| layer.dimensions())
+            .context("Texture array needs at least one layer")?;
+        ensure!(
+            layers
+                .iter()
+                .all(|layer| layer.dimensions() == (width, height)),
+            "Texture array layers must all be {}x{}",
+            width,
+            height
+        );
+
+        let mip_level_count = mip_level_count(width, height);
+        let descriptor = wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: layers.len() as u32,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: if is_normal_map {
+                wgpu::TextureFormat::Rgba8Unorm
+            } else {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            },
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        };
+        let texture = state.device().create_texture(&descriptor);
+
+        for (index, layer) in layers.iter().enumerate() {
+            for level in 0..mip_level_count {
+                let level_width = (width >> level).max(1);
+                let level_height = (height >> level).max(1);
+                let resized;
+                let data = if level == 0 {
+                    layer
+                } else {
+                    resized = image::imageops::resize(
+                        layer,
+                        level_width,
+                        level_height,
+                        image::imageops::FilterType::Triangle,
+                    );
+                    &resized
+                };
+
+                state.queue().write_texture(
+                    wgpu::TextureCopyView {
+                        texture: &texture,
+                        mip_level: level,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: index as u32,
+                        },
+                    },
+                    data,
+                    wgpu::TextureDataLayout {
+                        offset: 0,
+                        bytes_per_row: 4 * level_width,
+                        rows_per_image: level_height,
+                    },
+                    wgpu::Extent3d {
+                        width: level_width,
+                        height: level_height,
+                        depth: 1,
+                    },
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
             ..Default::default()
         });
+        let sampler = sampler.create(state, label);
 
-        Ok(Self {
-            texture,
-            view,
-            sampler,
+        Ok(Self::tracked(texture, view, sampler, &descriptor))
+    }
+
+    /// Single level texture filled in later with [`Texture::write_region`].
+    pub fn blank(
+        state: &state::WgpuState,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sampler: &SamplerDesc,
+    ) -> Self {
+        let descriptor = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        };
+        let texture = state.device().create_texture(&descriptor);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = sampler.create(state, Some(label));
+
+        Self::tracked(texture, view, sampler, &descriptor)
+    }
+
+    /// Texture rendered to and then sampled, by post processing.
+    pub fn render_target(
+        state: &state::WgpuState,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let descriptor = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+        };
+        Self::pooled(state, &descriptor, || {
+            let texture = state.device().create_texture(&descriptor);
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = SamplerDesc {
+                anisotropy: None,
+                ..SamplerDesc::linear()
+            }
+            .create(state, Some(label));
+
+            Self::tracked(texture, view, sampler, &descriptor)
         })
     }
 
+    /// Writes `pixels`, rows of `bytes_per_row`, to the `width` by `height`
+    /// region at `origin`.
+    pub fn write_region(
+        &self,
+        state: &state::WgpuState,
+        origin: [u32; 2],
+        width: u32,
+        height: u32,
+        bytes_per_row: u32,
+        pixels: &[u8],
+    ) {
+        state.queue().write_texture(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: 0,
+                },
+            },
+            pixels,
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row,
+                rows_per_image: height,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+    }
+
     pub fn create_depth_texture(state: &state::WgpuState, label: &str) -> Self {
+        Self::create_depth_texture_sized(state, label, state.width(), state.height())
+    }
+
+    /// Depth texture for a target other than the swapchain.
+    pub fn create_depth_texture_sized(
+        state: &state::WgpuState,
+        label: &str,
+        width: u32,
+        height: u32,
+    ) -> Self {
         let size = wgpu::Extent3d {
-            width: state.width(),
-            height: state.height(),
+            width,
+            height,
             depth: 1,
         };
         let desc = wgpu::TextureDescriptor {
@@ -116,40 +724,35 @@ impl Texture {
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
         };
-        let texture = state.device().create_texture(&desc);
+        Self::pooled(state, &desc, || {
+            let texture = state.device().create_texture(&desc);
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = state.device().create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            compare: Some(wgpu::CompareFunction::LessEqual),
-            lod_min_clamp: -100.0,
-            lod_max_clamp: 100.0,
-            ..Default::default()
-        });
-        info!("Create depth texture {:?}", label);
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = state.device().create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            });
+            info!("Create depth texture {:?}", label);
 
-        Self {
-            texture,
-            view,
-            sampler,
-        }
+            Self::tracked(texture, view, sampler, &desc)
+        })
     }
 
+    /// Loads `path` through the resource search paths.
     pub fn load<P: AsRef<Path>>(
         state: &state::WgpuState,
         path: P,
         is_normal_map: bool,
     ) -> Result<Self> {
-        let path_copy = path.as_ref().to_path_buf();
-        let label = path_copy.to_str();
-        info!("Load texture {:?}", path_copy);
-
-        let img = image::open(path)?;
-        Self::from_image(state, &img, label, is_normal_map)
+        let path = state.resources().path(path)?;
+        Self::from_data(state, &TextureData::load(path, is_normal_map)?)
     }
 }
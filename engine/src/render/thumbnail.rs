@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use anyhow::*;
+use log::error;
+use nalgebra::Vector3;
+
+use super::{
+    binding,
+    model::{self, Primitive},
+    renderpass, state,
+    traits::{Binding, DrawModel},
+    viewport::Viewport,
+    Layouts, Pipelines,
+};
+use crate::{assets, camera, transform};
+
+/// Width and height of a thumbnail in pixels
+pub const THUMBNAIL_SIZE: u32 = 64;
+/// New thumbnails rendered per frame, so opening a big library doesn't stall
+const THUMBNAILS_PER_FRAME: usize = 2;
+
+/// Kind of asset a thumbnail shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThumbnailKind {
+    Model,
+    /// Shown on a sphere
+    Material,
+    /// Shown on a plane
+    Texture,
+}
+
+/// Small offscreen renders of loaded assets for the asset browser. Each is
+/// rendered once, when the asset first shows up.
+pub struct Thumbnails {
+    viewports: HashMap<(ThumbnailKind, String), Viewport>,
+    sphere: model::Model,
+    plane: model::Model,
+    /// Identity transform the previewed models are drawn with
+    transform: transform::Transform,
+    projection: camera::projection::Projection,
+}
+
+impl Thumbnails {
+    pub fn new(
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        textures: &mut assets::Assets<super::texture::Texture>,
+        projection: camera::projection::Projection,
+    ) -> Result<Self> {
+        let sphere = Primitive::Sphere {
+            radius: 1.0,
+            segments: 24,
+            rings: 16,
+        };
+        let plane = Primitive::Plane {
+            width: 2.0,
+            depth: 2.0,
+            subdivisions: 1,
+        };
+
+        Ok(Self {
+            viewports: HashMap::new(),
+            sphere: model::Model::from_data(
+                state,
+                &layouts.material,
+                textures,
+                sphere.model_data(),
+            )?,
+            plane: model::Model::from_data(state, &layouts.material, textures, plane.model_data())?,
+            transform: transform::Transform::new(state, "thumbnail_transform"),
+            projection,
+        })
+    }
+
+    pub fn get(&self, kind: ThumbnailKind, name: &str) -> Option<imgui::TextureId> {
+        self.viewports
+            .get(&(kind, name.to_string()))
+            .map(|viewport| viewport.texture_id)
+    }
+
+    /// Renders the thumbnails still missing, a few per frame, and drops
+    /// those of assets that were unloaded. `uniforms` builds the camera
+    /// uniforms of a view.
+    pub fn update<A: bytemuck::Pod>(
+        &mut self,
+        state: &state::WgpuState,
+        renderer: &mut imgui_wgpu::Renderer,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &Pipelines,
+        layouts: &Layouts,
+        assets: &mut assets::AssetManager,
+        light: &binding::BufferGroup,
+        uniforms: impl Fn(&camera::Camera) -> A,
+    ) {
+        let loaded = assets
+            .models
+            .names()
+            .map(|name| (ThumbnailKind::Model, name.clone()))
+            .chain(
+                assets
+                    .materials
+                    .names()
+                    .map(|name| (ThumbnailKind::Material, name.clone())),
+            )
+            .chain(
+                assets
+                    .textures
+                    .names()
+                    .map(|name| (ThumbnailKind::Texture, name.clone())),
+            )
+            .collect::<Vec<_>>();
+
+        let unloaded = self
+            .viewports
+            .keys()
+            .filter(|key| !loaded.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in unloaded {
+            if let Some(viewport) = self.viewports.remove(&key) {
+                renderer.textures.remove(viewport.texture_id);
+            }
+        }
+
+        let missing = loaded
+            .into_iter()
+            .filter(|key| !self.viewports.contains_key(key))
+            .take(THUMBNAILS_PER_FRAME)
+            .collect::<Vec<_>>();
+        for (kind, name) in missing {
+            if let Err(e) = self.render(
+                state, renderer, encoder, pipelines, layouts, assets, light, &uniforms, kind, &name,
+            ) {
+                error!("Could not render thumbnail of {:?}: {:?}", name, e);
+            }
+        }
+    }
+
+    fn render<A: bytemuck::Pod>(
+        &mut self,
+        state: &state::WgpuState,
+        renderer: &mut imgui_wgpu::Renderer,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &Pipelines,
+        layouts: &Layouts,
+        assets: &mut assets::AssetManager,
+        light: &binding::BufferGroup,
+        uniforms: &impl Fn(&camera::Camera) -> A,
+        kind: ThumbnailKind,
+        name: &str,
+    ) -> Result<()> {
+        // Textures are drawn through a material made just for the thumbnail
+        let texture_material = match kind {
+            ThumbnailKind::Texture => Some(model::Material::from_cached_texture(
+                state,
+                name,
+                &mut assets.textures,
+                &layouts.material,
+            )?),
+            _ => None,
+        };
+        let (model, material) = match kind {
+            ThumbnailKind::Model => (
+                assets
+                    .models
+                    .get_by_name(name)
+                    .context("Model is not loaded")?,
+                None,
+            ),
+            ThumbnailKind::Material => (
+                &self.sphere,
+                Some(
+                    assets
+                        .materials
+                        .get_by_name(name)
+                        .context("Material is not loaded")?,
+                ),
+            ),
+            ThumbnailKind::Texture => (&self.plane, texture_material.as_ref()),
+        };
+
+        let sphere = model.geometry.bounding_sphere();
+        let center = *sphere.center();
+        let mut camera = camera::Camera::new(
+            center + Vector3::new(1.0, 0.8, 1.0),
+            center,
+            self.projection.clone(),
+        );
+        camera.resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        camera.frame(center, sphere.radius());
+
+        let label = format!("thumbnail {}", name);
+        let viewport = Viewport::new(
+            state,
+            renderer,
+            &layouts.uniforms,
+            &label,
+            THUMBNAIL_SIZE,
+            THUMBNAIL_SIZE,
+            &uniforms(&camera),
+        );
+
+        {
+            let view = viewport
+                .view(renderer)
+                .context("Thumbnail texture missing")?;
+            let color_attachments: &[&dyn renderpass::IntoColorAttachment] = &[&(
+                view,
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.15,
+                    g: 0.15,
+                    b: 0.15,
+                    a: 1.0,
+                }),
+            )];
+            let depth_attachment: &dyn renderpass::IntoDepthAttachment =
+                &(&viewport.depth_texture.view, wgpu::LoadOp::Clear(1.0));
+
+            let mut render_pass =
+                renderpass::render_pass(encoder, color_attachments, depth_attachment);
+            render_pass.set_pipeline(pipelines.forward.get(model.geometry.index_format()));
+            render_pass.bind_buffer(1, self.transform.buffer(state));
+            render_pass.draw_model_lod(model, 0, material, &viewport.uniform_group, light);
+        }
+
+        self.viewports.insert((kind, name.to_string()), viewport);
+        Ok(())
+    }
+}
@@ -1,10 +1,18 @@
 use std::ops::Range;
 
+use crate::{animation::MorphWeights, terrain::Terrain};
+
 use super::{
+    billboard::BillboardRenderer,
     binding::{self, Buffer, BufferGroup, TextureBinding},
+    debug::DebugLines,
+    decal::DecalRenderer,
     frame::Framebuffer,
     grid::Grid,
-    model::{Material, Mesh, Model},
+    model::{Material, MaterialArray, Mesh, Model},
+    particles::ParticleSystem,
+    sprite::SpriteRenderer,
+    text::TextRenderer,
 };
 
 pub trait Vertex {
@@ -61,7 +69,63 @@ where
         light: &'b binding::BufferGroup,
     );
 
+    /// Draws detail level `lod`, with `material` replacing the model's own
+    /// materials when given.
+    fn draw_model_lod(
+        &mut self,
+        model: &'b Model,
+        lod: usize,
+        material: Option<&'b Material>,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    );
+
+    /// Draws detail level `lod` with the arguments from `first` on, one
+    /// for each mesh, instanced from the buffer bound to slot 1.
+    fn draw_model_indirect(
+        &mut self,
+        model: &'b Model,
+        lod: usize,
+        material: Option<&'b Material>,
+        arguments: &'b Buffer,
+        first: usize,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    );
+
+    fn draw_model_morphed(
+        &mut self,
+        model: &'b Model,
+        morph: &'b MorphWeights,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    );
+
+    /// Draws the full detail geometry, picking `skinned` with `joints` for
+    /// meshes with skin weights and `rigid` for the rest of the model.
+    fn draw_model_skinned(
+        &mut self,
+        model: &'b Model,
+        material: Option<&'b Material>,
+        skinned: &'b wgpu::RenderPipeline,
+        rigid: &'b wgpu::RenderPipeline,
+        joints: &'b binding::BufferGroup,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    );
+
+    /// Draws with whatever material array is bound, the layer comes from
+    /// the instance buffer in slot 2.
+    fn draw_model_array(
+        &mut self,
+        model: &'b Model,
+        lod: usize,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    );
+
     fn bind_material(&mut self, index: u32, material: &'b Material);
+    fn bind_material_array(&mut self, index: u32, array: &'b MaterialArray);
 }
 
 pub trait DrawLight<'a, 'b>
@@ -121,3 +185,79 @@ where
 {
     fn draw_grid(&mut self, grid: &'b Grid, uniforms: &'b binding::BufferGroup);
 }
+
+pub trait DrawDebugLines<'a, 'b>
+where
+    'b: 'a,
+{
+    /// Draws the uploaded lines, the debug pipeline must be set.
+    fn draw_debug_lines(&mut self, lines: &'b DebugLines, uniforms: &'b BufferGroup);
+}
+
+pub trait DrawText<'a, 'b>
+where
+    'b: 'a,
+{
+    /// Draws the uploaded text, the text pipeline must be set.
+    fn draw_text(&mut self, text: &'b TextRenderer, uniforms: &'b BufferGroup);
+}
+
+pub trait DrawSprites<'a, 'b>
+where
+    'b: 'a,
+{
+    /// Draws the uploaded sprites, the sprite pipeline must be set.
+    fn draw_sprites(&mut self, sprites: &'b SpriteRenderer);
+}
+
+pub trait DrawBillboards<'a, 'b>
+where
+    'b: 'a,
+{
+    /// Draws the uploaded billboards facing the camera of `uniforms`, the
+    /// billboard pipeline must be set.
+    fn draw_billboards(&mut self, billboards: &'b BillboardRenderer, uniforms: &'b BufferGroup);
+}
+
+pub trait DrawDecals<'a, 'b>
+where
+    'b: 'a,
+{
+    /// Draws the uploaded decals, the decal pipeline must be set.
+    fn draw_decals(&mut self, decals: &'b DecalRenderer);
+}
+
+pub trait DrawParticles<'a, 'b>
+where
+    'b: 'a,
+{
+    /// Draws the particles simulated this frame with their own pipeline.
+    fn draw_particles(&mut self, particles: &'b ParticleSystem, uniforms: &'b BufferGroup);
+}
+
+pub trait ComputePassExt<'a, 'b>
+where
+    'b: 'a,
+{
+    fn bind_textures(&mut self, index: u32, textures: &'b TextureBinding);
+    fn bind_group(&mut self, index: u32, group: &'b BufferGroup);
+    fn dispatch_pipeline(
+        &mut self,
+        pipeline: &'b wgpu::ComputePipeline,
+        workgroups: (u32, u32, u32),
+    );
+}
+
+pub trait DrawTerrain<'a, 'b>
+where
+    'b: 'a,
+{
+    /// Draws every chunk at its current LOD, the terrain pipeline must be
+    /// set.
+    fn draw_terrain(
+        &mut self,
+        terrain: &'b Terrain,
+        uniforms: &'b BufferGroup,
+        light: &'b BufferGroup,
+    );
+}
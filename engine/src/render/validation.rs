@@ -0,0 +1,97 @@
+//! Errors wgpu reports instead of panicking. They are logged, ending up in
+//! the editor console, along with the label of the object being created
+//! when they were raised. Pipelines created with errors fail to load rather
+//! than being used invalid.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::*;
+use log::error;
+
+use crate::error::EngineError;
+
+#[derive(Debug, Default)]
+struct Scopes {
+    /// What is being created, innermost last
+    labels: Vec<String>,
+    /// First error raised in each scope
+    errors: Vec<Option<String>>,
+}
+
+/// Tracks what the device is creating, for the errors it reports.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorScopes(Arc<Mutex<Scopes>>);
+
+impl ErrorScopes {
+    /// Handles the errors of `device` from now on.
+    pub fn install(device: &wgpu::Device) -> Self {
+        let scopes = Self::default();
+        let handler = scopes.clone();
+        device.on_uncaptured_error(move |e| handler.report(e.to_string()));
+        scopes
+    }
+
+    fn report(&self, message: String) {
+        let mut scopes = match self.0.lock() {
+            Ok(scopes) => scopes,
+            Err(_) => {
+                error!("wgpu: {}", message);
+                return;
+            }
+        };
+        match scopes.labels.last() {
+            Some(label) => error!("wgpu error creating {}: {}", label, message),
+            None => error!("wgpu: {}", message),
+        }
+        if let Some(first) = scopes.errors.last_mut() {
+            first.get_or_insert(message);
+        }
+    }
+
+    /// Runs `create`, attributing the errors it raises to the `kind` named
+    /// `label`. Returns the first one raised, if any.
+    pub fn check<R>(
+        &self,
+        kind: &str,
+        label: Option<&str>,
+        create: impl FnOnce() -> R,
+    ) -> (R, Option<String>) {
+        if let Ok(mut scopes) = self.0.lock() {
+            scopes
+                .labels
+                .push(format!("{} {:?}", kind, label.unwrap_or("")));
+            scopes.errors.push(None);
+        }
+        let result = create();
+        let error = match self.0.lock() {
+            Ok(mut scopes) => {
+                scopes.labels.pop();
+                scopes.errors.pop().flatten()
+            }
+            Err(_) => None,
+        };
+        (result, error)
+    }
+
+    /// Runs `create`, logging its errors with `kind` and `label`.
+    pub fn scope<R>(&self, kind: &str, label: Option<&str>, create: impl FnOnce() -> R) -> R {
+        self.check(kind, label, create).0
+    }
+
+    /// Like `scope`, failing when `create` raised an error.
+    pub fn try_scope<R>(
+        &self,
+        kind: &str,
+        label: Option<&str>,
+        create: impl FnOnce() -> R,
+    ) -> Result<R> {
+        match self.check(kind, label, create) {
+            (result, None) => Ok(result),
+            (_, Some(message)) => Err(EngineError::Validation {
+                object: format!("{} {:?}", kind, label.unwrap_or("")),
+                message,
+            }
+            .into()),
+        }
+    }
+}
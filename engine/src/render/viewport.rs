@@ -0,0 +1,80 @@
+use log::info;
+
+use super::{binding, state, texture};
+
+/// Offscreen target a secondary camera renders into, shown in imgui as an
+/// image.
+pub struct Viewport {
+    pub texture_id: imgui::TextureId,
+    pub depth_texture: texture::Texture,
+    uniform_buffer: binding::Buffer,
+    /// Camera uniforms of the view, separate from the main ones since both
+    /// are written before the frame is submitted
+    pub uniform_group: binding::BufferGroup,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new<A: bytemuck::Pod>(
+        state: &state::WgpuState,
+        renderer: &mut imgui_wgpu::Renderer,
+        uniforms_layout: &binding::BindGroupLayout,
+        label: &str,
+        width: u32,
+        height: u32,
+        uniforms: &A,
+    ) -> Self {
+        let texture = imgui_wgpu::Texture::new(
+            state.device(),
+            renderer,
+            imgui_wgpu::TextureConfig {
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                label: Some(label),
+                format: Some(state.format()),
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+                ..Default::default()
+            },
+        );
+        let texture_id = renderer.textures.insert(texture);
+
+        let depth_texture =
+            texture::Texture::create_depth_texture_sized(state, label, width, height);
+
+        let uniform_buffer = binding::Buffer::new_init(
+            state,
+            label,
+            std::slice::from_ref(uniforms),
+            binding::BufferUsage::Uniform,
+        );
+        let uniform_group =
+            binding::BufferGroup::from_buffer(state, label, uniforms_layout, &[&uniform_buffer]);
+        info!("Create viewport {:?} ({}x{})", label, width, height);
+
+        Self {
+            texture_id,
+            depth_texture,
+            uniform_buffer,
+            uniform_group,
+            width,
+            height,
+        }
+    }
+
+    pub fn write_uniforms<A: bytemuck::Pod>(&self, state: &state::WgpuState, uniforms: &A) {
+        self.uniform_buffer
+            .write(state, std::slice::from_ref(uniforms));
+    }
+
+    /// The color target to render the view into.
+    pub fn view<'a>(&self, renderer: &'a imgui_wgpu::Renderer) -> Option<&'a wgpu::TextureView> {
+        renderer
+            .textures
+            .get(self.texture_id)
+            .map(|texture| texture.view())
+    }
+}
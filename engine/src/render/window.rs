@@ -0,0 +1,90 @@
+//! Windows besides the main one, drawing the scene from a camera of their
+//! own, like a game view detached from the editor. Each has its surface
+//! and swapchain in the `WgpuState`, sharing its device and the assets.
+
+use winit::{
+    dpi::PhysicalSize,
+    window::{Window, WindowId},
+};
+
+use super::{binding, state, texture};
+
+pub struct SceneWindow {
+    window: Window,
+    pub depth_texture: texture::Texture,
+    uniform_buffer: binding::Buffer,
+    /// Camera uniforms of the window, written before the frame is
+    /// submitted like those of a viewport
+    pub uniform_group: binding::BufferGroup,
+}
+
+impl SceneWindow {
+    pub fn new<A: bytemuck::Pod>(
+        state: &mut state::WgpuState,
+        window: Window,
+        uniforms_layout: &binding::BindGroupLayout,
+        uniforms: &A,
+    ) -> Self {
+        state.add_window(&window);
+        let size = window.inner_size();
+        let depth_texture = texture::Texture::create_depth_texture_sized(
+            state,
+            "scene_window_depth",
+            size.width,
+            size.height,
+        );
+        let uniform_buffer = binding::Buffer::new_init(
+            state,
+            "scene_window",
+            std::slice::from_ref(uniforms),
+            binding::BufferUsage::Uniform,
+        );
+        let uniform_group = binding::BufferGroup::from_buffer(
+            state,
+            "scene_window",
+            uniforms_layout,
+            &[&uniform_buffer],
+        );
+        Self {
+            window,
+            depth_texture,
+            uniform_buffer,
+            uniform_group,
+        }
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        let size = self.window.inner_size();
+        (size.width, size.height)
+    }
+
+    pub fn resize(&mut self, state: &mut state::WgpuState, size: PhysicalSize<u32>) {
+        // Minimized windows have no size to draw at
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        state.resize_window(self.id(), size.width, size.height);
+        let depth_texture = texture::Texture::create_depth_texture_sized(
+            state,
+            "scene_window_depth",
+            size.width,
+            size.height,
+        );
+        let old = std::mem::replace(&mut self.depth_texture, depth_texture);
+        state.texture_pool().release(old);
+    }
+
+    pub fn write_uniforms<A: bytemuck::Pod>(&self, state: &state::WgpuState, uniforms: &A) {
+        self.uniform_buffer
+            .write(state, std::slice::from_ref(uniforms));
+    }
+
+    /// Drops the window's surface, then the window.
+    pub fn close(self, state: &mut state::WgpuState) {
+        state.remove_window(self.id());
+    }
+}
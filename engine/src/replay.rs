@@ -0,0 +1,129 @@
+//! Recording of the input and frame times of a session, and replaying it.
+//! A replay feeds the recorded events back through [`input::Input`] one
+//! frame at a time with the recorded frame times, so the simulation takes
+//! the same steps it took while recording. Live input is ignored meanwhile.
+
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::input;
+
+/// What arrived before one frame and how long the frame took.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub delta: Duration,
+    pub events: Vec<input::InputEvent>,
+}
+
+/// The frames of a session, saved as RON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let source = fs::read_to_string(path.as_ref())
+            .context(format!("Could not read recording {:?}", path.as_ref()))?;
+        Ok(ron::de::from_str(&source)?)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let source = ron::ser::to_string(self)?;
+        fs::write(path.as_ref(), source)
+            .context(format!("Could not write recording {:?}", path.as_ref()))
+    }
+}
+
+pub enum Replay {
+    /// Saved to the path once stopped
+    Recording {
+        path: std::path::PathBuf,
+        recording: Recording,
+    },
+    Playing {
+        recording: Recording,
+        /// Index of the next frame
+        frame: usize,
+    },
+    /// Played to the end
+    Finished,
+}
+
+impl Replay {
+    pub fn record<P: AsRef<Path>>(input: &mut input::Input, path: P) -> Self {
+        info!("Recording input to {:?}", path.as_ref());
+        input.start_recording();
+        Replay::Recording {
+            path: path.as_ref().to_path_buf(),
+            recording: Recording::default(),
+        }
+    }
+
+    pub fn play<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let recording = Recording::read(path.as_ref())?;
+        info!(
+            "Replaying {} frames from {:?}",
+            recording.frames.len(),
+            path.as_ref()
+        );
+        Ok(Replay::Playing {
+            recording,
+            frame: 0,
+        })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self, Replay::Playing { .. })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Replay::Finished)
+    }
+
+    /// Records the events `input` got during a frame taking `delta`, or
+    /// applies the next recorded frame instead. Returns how long the frame
+    /// counts as.
+    pub fn frame(&mut self, input: &mut input::Input, delta: Duration) -> Duration {
+        match self {
+            Replay::Recording { recording, .. } => {
+                recording.frames.push(RecordedFrame {
+                    delta,
+                    events: input.take_recorded(),
+                });
+                delta
+            }
+            Replay::Playing { recording, frame } => match recording.frames.get(*frame) {
+                Some(recorded) => {
+                    *frame += 1;
+                    for event in recorded.events.iter() {
+                        input.apply(*event);
+                    }
+                    recorded.delta
+                }
+                None => {
+                    info!("Replay finished after {} frames", frame);
+                    *self = Replay::Finished;
+                    delta
+                }
+            },
+            Replay::Finished => delta,
+        }
+    }
+
+    /// Saves what was recorded so far.
+    pub fn save(&self) -> Result<()> {
+        if let Replay::Recording { path, recording } = self {
+            recording.write(path)?;
+            info!(
+                "Saved {} recorded frames to {:?}",
+                recording.frames.len(),
+                path
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    env,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::*;
+use log::info;
+
+use crate::error::EngineError;
+
+/// Name of the resource folder looked up in every search root.
+pub const RES_DIR: &str = "res";
+
+/// Embeds files from the build output `res` folder under their own name.
+#[cfg(feature = "embedded-assets")]
+macro_rules! embed_res {
+    ($resources:expr, $($file:literal),* $(,)?) => {
+        $(
+            $resources.embed(
+                $file,
+                include_bytes!(concat!(env!("OUT_DIR"), "/res/", $file)),
+            );
+        )*
+    };
+}
+
+/// Finds engine resources by relative name. Search paths are tried in order,
+/// files embedded with [`Resources::embed`] are used when none has the file.
+#[derive(Debug, Clone, Default)]
+pub struct Resources {
+    search_paths: Vec<PathBuf>,
+    embedded: HashMap<PathBuf, &'static [u8]>,
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Searches `res_dir` when given, then `./res`, the user data directory
+    /// and the build output, in that order, falling back to the default
+    /// assets when embedded.
+    pub fn with_res_dir(res_dir: Option<&Path>) -> Self {
+        let mut resources = Self::new();
+        if let Some(dir) = res_dir {
+            resources.add_search_path(dir);
+        }
+        if let Ok(dir) = env::current_dir() {
+            resources.add_search_path(dir.join(RES_DIR));
+        }
+        if let Some(dir) = user_dir() {
+            resources.add_search_path(dir.join(RES_DIR));
+        }
+        resources.add_search_path(Path::new(env!("OUT_DIR")).join(RES_DIR));
+        #[cfg(feature = "embedded-assets")]
+        resources.embed_defaults();
+        resources
+    }
+
+    /// Embeds the shaders and the cube model the engine needs to start.
+    #[cfg(feature = "embedded-assets")]
+    pub fn embed_defaults(&mut self) -> &mut Self {
+        embed_res!(
+            self,
+            "shader.vert.spv",
+            "shader.frag.spv",
+            "skinned.vert.spv",
+            "morph.vert.spv",
+            "material_array.vert.spv",
+            "material_array.frag.spv",
+            "terrain.vert.spv",
+            "terrain.frag.spv",
+            "light.vert.spv",
+            "light.frag.spv",
+            "depth_frame.vert.spv",
+            "depth_frame.frag.spv",
+            "grid.vert.spv",
+            "grid.frag.spv",
+            "text.vert.spv",
+            "text.frag.spv",
+            "sprite.vert.spv",
+            "sprite.frag.spv",
+            "decal.vert.spv",
+            "decal.frag.spv",
+            "particles.comp.spv",
+            "cull.comp.spv",
+            "particle.vert.spv",
+            "particle.frag.spv",
+            "post.vert.spv",
+            "tonemap.frag.spv",
+            "color_grading.frag.spv",
+            "fxaa.frag.spv",
+            "vignette.frag.spv",
+            "chromatic_aberration.frag.spv",
+            "taa.frag.spv",
+            "copy.frag.spv",
+            "cube.obj",
+            "cube.mtl",
+            "cube-diffuse.jpg",
+            "cube-normal.jpg",
+            "locale/en.toml",
+        );
+        self
+    }
+
+    pub fn add_search_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        let path = path.into();
+        info!("Add resource path {:?}", path);
+        self.search_paths.push(path);
+        self
+    }
+
+    pub fn search_paths(&self) -> &[PathBuf] {
+        &self.search_paths
+    }
+
+    pub fn embed<P: Into<PathBuf>>(&mut self, name: P, bytes: &'static [u8]) -> &mut Self {
+        self.embedded.insert(name.into(), bytes);
+        self
+    }
+
+    pub fn is_embedded<P: AsRef<Path>>(&self, name: P) -> bool {
+        self.embedded.contains_key(name.as_ref())
+    }
+
+    /// The first file on disk matching `name`. Absolute paths are returned
+    /// as is when they exist.
+    pub fn find<P: AsRef<Path>>(&self, name: P) -> Option<PathBuf> {
+        let name = name.as_ref();
+        if name.is_absolute() {
+            return Some(name.to_path_buf()).filter(|path| path.exists());
+        }
+
+        self.search_paths
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.exists())
+    }
+
+    /// Names of the files in `dir` ending in `extension`, found in any
+    /// search path.
+    pub fn list<P: AsRef<Path>>(&self, dir: P, extension: &str) -> Vec<String> {
+        let mut names = BTreeSet::new();
+        for entries in self
+            .search_paths
+            .iter()
+            .filter_map(|path| fs::read_dir(path.join(dir.as_ref())).ok())
+        {
+            for path in entries.flatten().map(|entry| entry.path()) {
+                if path.extension() != Some(OsStr::new(extension)) {
+                    continue;
+                }
+                if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        names.into_iter().collect()
+    }
+
+    pub fn exists<P: AsRef<Path>>(&self, name: P) -> bool {
+        self.find(name.as_ref()).is_some() || self.is_embedded(name)
+    }
+
+    pub fn read<P: AsRef<Path>>(&self, name: P) -> Result<Cow<'static, [u8]>> {
+        let name = name.as_ref();
+        match self.find(name) {
+            Some(path) => Ok(Cow::Owned(
+                fs::read(&path).context(format!("Could not read resource {:?}", path))?,
+            )),
+            None => self
+                .embedded
+                .get(name)
+                .map(|bytes| Cow::Borrowed(*bytes))
+                .ok_or_else(|| EngineError::asset_not_found("Resource", name.display()).into()),
+        }
+    }
+
+    /// Path to `name` for loaders that only read from disk. Embedded files
+    /// are written out to a temporary directory first, all of them so files
+    /// referencing each other by relative path still resolve.
+    pub fn path<P: AsRef<Path>>(&self, name: P) -> Result<PathBuf> {
+        let name = name.as_ref();
+        if let Some(path) = self.find(name) {
+            return Ok(path);
+        }
+        if !self.is_embedded(name) {
+            return Err(EngineError::asset_not_found("Resource", name.display()).into());
+        }
+
+        let dir = env::temp_dir().join("nodas").join(RES_DIR);
+        for (embedded, bytes) in &self.embedded {
+            let path = dir.join(embedded);
+            let current = fs::metadata(&path).map(|meta| meta.len()).ok();
+            if current == Some(bytes.len() as u64) {
+                continue;
+            }
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, bytes)
+                .context(format!("Could not extract resource {:?}", embedded))?;
+            info!("Extracted embedded resource {:?} to {:?}", embedded, path);
+        }
+        Ok(dir.join(name))
+    }
+}
+
+/// Per-user data directory, `%APPDATA%\nodas` on Windows and
+/// `$XDG_DATA_HOME/nodas` or `~/.local/share/nodas` elsewhere.
+pub fn user_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("nodas"));
+    }
+
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("nodas"))
+}
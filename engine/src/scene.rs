@@ -0,0 +1,170 @@
+//! Scene files, the entities of a world along with the assets they use.
+//! Stored as RON or JSON depending on the file extension.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::*;
+use log::info;
+use nalgebra::{Translation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::AssetManifest,
+    physics,
+    prefab::Prefab,
+    render::{billboard, decal, layer::RenderLayer, particles, reflection, sprite},
+    transform, world,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SceneFormat {
+    Ron,
+    Json,
+}
+
+impl SceneFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        match extension.as_deref() {
+            Some("ron") => Ok(Self::Ron),
+            Some("json") => Ok(Self::Json),
+            _ => bail!("Unsupported scene format {:?}", path.as_ref()),
+        }
+    }
+}
+
+/// Local transform as written to scene files, rotation in radians.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TransformData {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Default for TransformData {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl TransformData {
+    pub fn from_transform(transform: &transform::Transform) -> Self {
+        Self {
+            translation: transform.translation().vector.into(),
+            rotation: transform.rotation().into(),
+            scale: transform.scale().into(),
+        }
+    }
+
+    pub fn apply(&self, transform: &mut transform::Transform) {
+        transform
+            .set_position(Translation3::from(Vector3::from(self.translation)))
+            .set_rotation(Vector3::from(self.rotation))
+            .set_scale(Vector3::from(self.scale));
+    }
+}
+
+/// Components of one entity. Everything besides the model is optional so
+/// older scene files keep loading as components are added.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityData {
+    pub model: String,
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub transform: TransformData,
+    /// Prefab the entity was instantiated from, its model and material
+    /// are taken from the prefab on load.
+    #[serde(default)]
+    pub prefab: Option<String>,
+    /// Index of the parent entity in the scene
+    #[serde(default)]
+    pub parent: Option<usize>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub collider: Option<physics::ColliderShape>,
+    #[serde(default)]
+    pub layers: Option<physics::CollisionLayers>,
+    /// Script run for the entity, see `scripting`
+    #[serde(default)]
+    pub script: Option<String>,
+    #[serde(default)]
+    pub label: Option<world::Label>,
+    #[serde(default)]
+    pub mirror: Option<reflection::Mirror>,
+    #[serde(default)]
+    pub particles: Option<particles::ParticleEmitter>,
+    /// Layers the entity is drawn on, the default layer when unset
+    #[serde(default)]
+    pub render_layer: Option<RenderLayer>,
+    /// Drawn as a billboard from a distance
+    #[serde(default)]
+    pub billboard: Option<billboard::Billboard>,
+    /// Whether the entity is drawn, shown when unset
+    #[serde(default)]
+    pub visible: Option<bool>,
+}
+
+/// A decal entity, projected along the y axis of its transform.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecalData {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub transform: TransformData,
+    pub decal: decal::Decal,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    #[serde(default)]
+    pub assets: AssetManifest,
+    #[serde(default)]
+    pub prefabs: BTreeMap<String, Prefab>,
+    #[serde(default)]
+    pub entities: Vec<EntityData>,
+    #[serde(default)]
+    pub sprites: Vec<sprite::Sprite>,
+    #[serde(default)]
+    pub decals: Vec<DecalData>,
+    /// Script handling the scene's lifecycle events, a path in the resource
+    /// folders, see `scripting`
+    #[serde(default)]
+    pub startup_script: Option<String>,
+}
+
+impl Scene {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        info!("Read scene {:?}", path.as_ref());
+        let source = fs::read_to_string(path.as_ref())
+            .context(format!("Could not read scene {:?}", path.as_ref()))?;
+
+        Ok(match SceneFormat::from_path(path.as_ref())? {
+            SceneFormat::Ron => ron::de::from_str(&source)?,
+            SceneFormat::Json => serde_json::from_str(&source)?,
+        })
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        info!("Write scene {:?}", path.as_ref());
+        let source = match SceneFormat::from_path(path.as_ref())? {
+            SceneFormat::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?,
+            SceneFormat::Json => serde_json::to_string_pretty(self)?,
+        };
+
+        fs::write(path.as_ref(), source)
+            .context(format!("Could not write scene {:?}", path.as_ref()))
+    }
+}
@@ -0,0 +1,265 @@
+//! Frame stages and the legion schedules gameplay systems run in. The
+//! engine's own work for a stage runs first, then every system registered
+//! for it, sharing the resources in `World::resources`.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use legion::{
+    systems::{Builder, ParallelRunnable},
+    Resources, Schedule,
+};
+use nalgebra::{Matrix4, Point3};
+
+/// Stages of a frame, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    /// Input resource has been updated with this frame's events
+    Input,
+    /// Camera has moved
+    Camera,
+    /// Assets are loaded and animations advanced, gameplay goes here
+    Update,
+    /// Runs at `FixedTime::step` intervals, zero or more times a frame
+    FixedUpdate,
+    /// Collision world has been updated, after every fixed step
+    Collision,
+    /// World transforms and bounds are up to date
+    Transform,
+    /// Last chance to change what gets rendered this frame
+    Extract,
+}
+
+impl Stage {
+    /// Name the stats overlay shows for the stage's systems.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Input => "Input systems",
+            Stage::Camera => "Camera systems",
+            Stage::Update => "Update systems",
+            Stage::FixedUpdate => "FixedUpdate systems",
+            Stage::Collision => "Collision systems",
+            Stage::Transform => "Transform systems",
+            Stage::Extract => "Extract systems",
+        }
+    }
+}
+
+/// Frame timing, updated before the first stage.
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Time {
+    pub delta: Duration,
+    pub elapsed: Duration,
+    pub frame: u64,
+}
+
+impl Time {
+    pub fn advance(&mut self, delta: Duration) {
+        self.delta = delta;
+        self.elapsed += delta;
+        self.frame += 1;
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+}
+
+/// Rate of the fixed update, physics and collision run at this rate
+/// regardless of the frame rate.
+pub const FIXED_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Steps taken at most per frame, the rest of a long frame is dropped so
+/// a slow simulation can't fall further and further behind.
+pub const MAX_FIXED_STEPS: u32 = 8;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTime {
+    pub step: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTime {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step,
+            accumulator: Duration::default(),
+        }
+    }
+
+    /// Adds the frame time, returning the number of steps to run.
+    pub fn advance(&mut self, delta: Duration) -> u32 {
+        self.accumulator += delta;
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            if steps == MAX_FIXED_STEPS {
+                self.accumulator = Duration::default();
+                break;
+            }
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// How far the frame is into the next step, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    pub fn step_seconds(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+}
+
+impl Default for FixedTime {
+    fn default() -> Self {
+        Self::new(FIXED_STEP)
+    }
+}
+
+/// Longest a frame counts as. A hitch, like a blocking asset load or the
+/// window being dragged, becomes one slow frame instead of a leap of the
+/// camera and the simulation.
+pub const MAX_FRAME_DELTA: Duration = Duration::from_millis(100);
+
+/// Weight of the latest frame time in the smoothed one
+pub const FRAME_SMOOTHING: f64 = 0.25;
+
+/// Paces the main loop: measures frame times, clamps and smooths them,
+/// and holds frames back to stay under the frame rate limit.
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    last_frame: Instant,
+    smoothed: Option<Duration>,
+    /// Shortest time between frames, none when unlimited
+    min_frame: Option<Duration>,
+}
+
+impl FramePacer {
+    /// Limits the frame rate to `max_fps`, zero for unlimited.
+    pub fn new(max_fps: u32) -> Self {
+        let mut pacer = Self {
+            last_frame: Instant::now(),
+            smoothed: None,
+            min_frame: None,
+        };
+        pacer.set_max_fps(max_fps);
+        pacer
+    }
+
+    pub fn set_max_fps(&mut self, max_fps: u32) {
+        self.min_frame = match max_fps {
+            0 => None,
+            fps => Some(Duration::from_secs_f64(1.0 / fps as f64)),
+        };
+    }
+
+    /// When the next frame is due, none once it is.
+    pub fn wait_until(&self) -> Option<Instant> {
+        let due = self.last_frame + self.min_frame?;
+        if due > Instant::now() {
+            Some(due)
+        } else {
+            None
+        }
+    }
+
+    /// Starts a frame, returning how long it counts as.
+    pub fn frame(&mut self) -> Duration {
+        let now = Instant::now();
+        let delta = (now - self.last_frame).min(MAX_FRAME_DELTA);
+        self.last_frame = now;
+
+        let smoothed = match self.smoothed {
+            Some(smoothed) => {
+                smoothed.mul_f64(1.0 - FRAME_SMOOTHING) + delta.mul_f64(FRAME_SMOOTHING)
+            }
+            None => delta,
+        };
+        self.smoothed = Some(smoothed);
+        smoothed
+    }
+}
+
+/// Whether the simulation advances, as switched by the editor's play
+/// controls. Physics, scripts, animations and the update stages only run
+/// while playing, or for a single fixed step while paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayState {
+    /// The scene is being edited, nothing moves by itself
+    Edit,
+    Play,
+    Paused,
+}
+
+impl Default for PlayState {
+    fn default() -> Self {
+        PlayState::Edit
+    }
+}
+
+/// The camera as seen by this frame's render.
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub struct CameraState {
+    pub eye: Point3<f32>,
+    pub view: Matrix4<f32>,
+    pub view_proj: Matrix4<f32>,
+}
+
+#[derive(Default)]
+struct StageSchedule {
+    schedules: Vec<Schedule>,
+    pending: Option<Builder>,
+}
+
+/// Legion schedules for each stage. Systems can be added at any point,
+/// systems added since the last run are built into a new schedule that runs
+/// after the existing ones.
+#[derive(Default)]
+pub struct Systems {
+    stages: BTreeMap<Stage, StageSchedule>,
+}
+
+impl Systems {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_system<S: ParallelRunnable + 'static>(&mut self, stage: Stage, system: S) {
+        self.pending(stage).add_system(system);
+    }
+
+    /// Runs `f` on the main thread with exclusive access to the world.
+    pub fn add_thread_local_fn<F>(&mut self, stage: Stage, f: F)
+    where
+        F: FnMut(&mut legion::World, &mut Resources) + 'static,
+    {
+        self.pending(stage).add_thread_local_fn(f);
+    }
+
+    pub fn execute(&mut self, stage: Stage, world: &mut legion::World, resources: &mut Resources) {
+        if let Some(schedule) = self.stages.get_mut(&stage) {
+            if let Some(mut pending) = schedule.pending.take() {
+                schedule.schedules.push(pending.build());
+            }
+
+            for schedule in schedule.schedules.iter_mut() {
+                schedule.execute(world, resources);
+            }
+        }
+    }
+
+    fn pending(&mut self, stage: Stage) -> &mut Builder {
+        self.stages
+            .entry(stage)
+            .or_default()
+            .pending
+            .get_or_insert_with(Schedule::builder)
+    }
+}
@@ -0,0 +1,385 @@
+//! Rhai scripts attached to entities through a [`Script`] component. A
+//! script may define `on_start()`, `on_update(dt)` and `on_collision(other)`,
+//! `other` being the name of the entity it started touching, and moves its
+//! entity through the functions registered here:
+//!
+//! - `position()`, `set_position(v)`, `translate(v)`
+//! - `rotation()`, `set_rotation(v)`, `rotate(v)`, in radians
+//! - `scale()`, `set_scale(v)`
+//! - `spawn(model, position)`, `despawn()`
+//! - `raycast(origin, direction, max)`, the distance or `()` on a miss
+//!
+//! Vectors are made with `vec3(x, y, z)`, `print` goes to the log.
+//!
+//! A scene may name a startup script of its own, which has no entity. It
+//! gets the lifecycle events instead: `on_scene_loaded()`,
+//! `on_play_start()`, `on_play_stop()` and `on_frame(dt)`, and may `spawn`
+//! and `raycast` like the others.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+
+use log::{error, info};
+use nalgebra::{Point3, Translation3, Vector3};
+use rhai::{Dynamic, Engine, EvalAltResult, FuncArgs, RegisterFn, Scope, AST, FLOAT};
+
+use crate::{lifecycle::LifecycleEvent, physics, resources::Resources, transform};
+
+/// Script run for an entity, a path in the resource folders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script(pub String);
+
+/// Vector type seen by scripts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScriptVector(pub Vector3<f32>);
+
+/// World changes requested by scripts, applied after they all ran.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptCommand {
+    Spawn {
+        model: String,
+        position: Vector3<f32>,
+    },
+    Despawn(legion::Entity),
+}
+
+/// What the registered functions work on, filled in before each call.
+#[derive(Default)]
+struct Context {
+    entity: Option<legion::Entity>,
+    translation: Vector3<f32>,
+    rotation: Vector3<f32>,
+    scale: Vector3<f32>,
+    commands: Vec<ScriptCommand>,
+    /// Only set while `ScriptEngine::run` holds the borrow it came from
+    physics: Option<*const physics::Physics>,
+}
+
+struct Instance {
+    script: String,
+    scope: Scope<'static>,
+    started: bool,
+}
+
+pub struct ScriptEngine {
+    engine: Engine,
+    context: Rc<RefCell<Context>>,
+    /// Compiled scripts, `None` for ones that failed so they're reported once
+    scripts: HashMap<String, Option<AST>>,
+    instances: HashMap<legion::Entity, Instance>,
+    /// State of the scene's startup script
+    scene: Option<Instance>,
+    resources: Arc<Resources>,
+}
+
+impl ScriptEngine {
+    pub fn new(resources: Arc<Resources>) -> Self {
+        let mut engine = Engine::new();
+        let context = Rc::new(RefCell::new(Context::default()));
+
+        engine.on_print(|text| info!("{}", text));
+        register_vector(&mut engine);
+        register_context(&mut engine, &context);
+
+        Self {
+            engine,
+            context,
+            scripts: HashMap::new(),
+            instances: HashMap::new(),
+            scene: None,
+            resources,
+        }
+    }
+
+    /// Drops the compiled `script`, it is read again on its next run.
+    #[allow(unused)]
+    pub fn reload(&mut self, script: &str) {
+        self.scripts.remove(script);
+        for instance in self.instances.values_mut() {
+            if instance.script == script {
+                instance.started = false;
+            }
+        }
+    }
+
+    /// Forgets the state of entities that no longer have a script.
+    pub fn retain(&mut self, mut keep: impl FnMut(legion::Entity) -> bool) {
+        self.instances.retain(|entity, _| keep(*entity));
+    }
+
+    /// Runs the callbacks of `script` for `entity`, writing back the
+    /// transform it changed. Returns the commands it queued.
+    pub fn run(
+        &mut self,
+        entity: legion::Entity,
+        script: &str,
+        transform: &mut transform::Transform,
+        physics: &physics::Physics,
+        dt: f32,
+        collisions: &[String],
+    ) -> Vec<ScriptCommand> {
+        if !self.scripts.contains_key(script) {
+            let ast = self.compile(script);
+            self.scripts.insert(script.to_string(), ast);
+        }
+        let ast = match self.scripts.get(script) {
+            Some(Some(ast)) => ast,
+            _ => return Vec::new(),
+        };
+
+        let instance = self.instances.entry(entity).or_insert_with(|| Instance {
+            script: script.to_string(),
+            scope: Scope::new(),
+            started: false,
+        });
+        if instance.script != script {
+            *instance = Instance {
+                script: script.to_string(),
+                scope: Scope::new(),
+                started: false,
+            };
+        }
+
+        {
+            let mut context = self.context.borrow_mut();
+            context.entity = Some(entity);
+            context.translation = transform.translation().vector;
+            context.rotation = transform.rotation();
+            context.scale = transform.scale();
+            context.physics = Some(physics as *const _);
+        }
+
+        if !instance.started {
+            instance.started = true;
+            call(&self.engine, instance, ast, "on_start", ());
+        }
+        call(&self.engine, instance, ast, "on_update", (dt as FLOAT,));
+        for other in collisions {
+            call(
+                &self.engine,
+                instance,
+                ast,
+                "on_collision",
+                (other.clone(),),
+            );
+        }
+
+        let mut context = self.context.borrow_mut();
+        context.physics = None;
+        context.entity = None;
+        if transform.translation().vector != context.translation {
+            transform.set_position(Translation3::from(context.translation));
+        }
+        if transform.rotation() != context.rotation {
+            transform.set_rotation(context.rotation);
+        }
+        if transform.scale() != context.scale {
+            transform.set_scale(context.scale);
+        }
+        std::mem::take(&mut context.commands)
+    }
+
+    /// Calls the function of the scene's startup `script` handling `event`,
+    /// keeping its state between calls until `reset_scene`. Returns the
+    /// commands it queued.
+    pub fn run_scene(
+        &mut self,
+        script: &str,
+        event: LifecycleEvent,
+        physics: &physics::Physics,
+    ) -> Vec<ScriptCommand> {
+        if !self.scripts.contains_key(script) {
+            let ast = self.compile(script);
+            self.scripts.insert(script.to_string(), ast);
+        }
+        let ast = match self.scripts.get(script) {
+            Some(Some(ast)) => ast,
+            _ => return Vec::new(),
+        };
+
+        if self
+            .scene
+            .as_ref()
+            .map_or(true, |scene| scene.script != script)
+        {
+            self.scene = Some(Instance {
+                script: script.to_string(),
+                scope: Scope::new(),
+                started: true,
+            });
+        }
+        let instance = self.scene.as_mut().expect("Scene script not set");
+
+        {
+            let mut context = self.context.borrow_mut();
+            context.entity = None;
+            context.translation = Vector3::zeros();
+            context.rotation = Vector3::zeros();
+            context.scale = Vector3::repeat(1.0);
+            context.physics = Some(physics as *const _);
+        }
+        match event {
+            LifecycleEvent::SceneLoaded => call(&self.engine, instance, ast, "on_scene_loaded", ()),
+            LifecycleEvent::PlayStart => call(&self.engine, instance, ast, "on_play_start", ()),
+            LifecycleEvent::PlayStop => call(&self.engine, instance, ast, "on_play_stop", ()),
+            LifecycleEvent::Frame(dt) => call(
+                &self.engine,
+                instance,
+                ast,
+                "on_frame",
+                (dt.as_secs_f32() as FLOAT,),
+            ),
+        }
+
+        let mut context = self.context.borrow_mut();
+        context.physics = None;
+        std::mem::take(&mut context.commands)
+    }
+
+    /// Forgets the state of the scene's startup script, for a new scene.
+    pub fn reset_scene(&mut self) {
+        self.scene = None;
+    }
+
+    fn compile(&self, script: &str) -> Option<AST> {
+        let source = match self.resources.read(script) {
+            Ok(source) => String::from_utf8_lossy(&source).into_owned(),
+            Err(e) => {
+                error!("Could not read script {}: {:?}", script, e);
+                return None;
+            }
+        };
+        match self.engine.compile(&source) {
+            Ok(ast) => {
+                info!("Compiled script {}", script);
+                Some(ast)
+            }
+            Err(e) => {
+                error!("Could not compile script {}: {}", script, e);
+                None
+            }
+        }
+    }
+}
+
+/// Calls `name` if the script defines it, logging errors.
+fn call(engine: &Engine, instance: &mut Instance, ast: &AST, name: &str, args: impl FuncArgs) {
+    if let Err(e) = engine.call_fn::<_, Dynamic>(&mut instance.scope, ast, name, args) {
+        match *e {
+            EvalAltResult::ErrorFunctionNotFound(ref function, _) if function.starts_with(name) => {
+            }
+            _ => error!("Script {} failed in {}: {}", instance.script, name, e),
+        }
+    }
+}
+
+fn register_vector(engine: &mut Engine) {
+    engine.register_type_with_name::<ScriptVector>("Vector");
+    engine.register_fn("vec3", |x: FLOAT, y: FLOAT, z: FLOAT| {
+        ScriptVector(Vector3::new(x as f32, y as f32, z as f32))
+    });
+    engine.register_get_set(
+        "x",
+        |v: &mut ScriptVector| v.0.x as FLOAT,
+        |v: &mut ScriptVector, x: FLOAT| v.0.x = x as f32,
+    );
+    engine.register_get_set(
+        "y",
+        |v: &mut ScriptVector| v.0.y as FLOAT,
+        |v: &mut ScriptVector, y: FLOAT| v.0.y = y as f32,
+    );
+    engine.register_get_set(
+        "z",
+        |v: &mut ScriptVector| v.0.z as FLOAT,
+        |v: &mut ScriptVector, z: FLOAT| v.0.z = z as f32,
+    );
+    engine.register_fn("+", |a: ScriptVector, b: ScriptVector| {
+        ScriptVector(a.0 + b.0)
+    });
+    engine.register_fn("-", |a: ScriptVector, b: ScriptVector| {
+        ScriptVector(a.0 - b.0)
+    });
+    engine.register_fn("*", |a: ScriptVector, s: FLOAT| {
+        ScriptVector(a.0 * s as f32)
+    });
+    engine.register_fn("length", |v: &mut ScriptVector| v.0.norm() as FLOAT);
+    engine.register_fn("normalize", |v: &mut ScriptVector| {
+        ScriptVector(
+            v.0.try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::zeros),
+        )
+    });
+    engine.register_fn("to_string", |v: &mut ScriptVector| {
+        format!("({}, {}, {})", v.0.x, v.0.y, v.0.z)
+    });
+}
+
+fn register_context(engine: &mut Engine, context: &Rc<RefCell<Context>>) {
+    let c = context.clone();
+    engine.register_fn("position", move || ScriptVector(c.borrow().translation));
+    let c = context.clone();
+    engine.register_fn("set_position", move |v: ScriptVector| {
+        c.borrow_mut().translation = v.0
+    });
+    let c = context.clone();
+    engine.register_fn("translate", move |v: ScriptVector| {
+        c.borrow_mut().translation += v.0
+    });
+
+    let c = context.clone();
+    engine.register_fn("rotation", move || ScriptVector(c.borrow().rotation));
+    let c = context.clone();
+    engine.register_fn("set_rotation", move |v: ScriptVector| {
+        c.borrow_mut().rotation = v.0
+    });
+    let c = context.clone();
+    engine.register_fn("rotate", move |v: ScriptVector| {
+        c.borrow_mut().rotation += v.0
+    });
+
+    let c = context.clone();
+    engine.register_fn("scale", move || ScriptVector(c.borrow().scale));
+    let c = context.clone();
+    engine.register_fn("set_scale", move |v: ScriptVector| {
+        c.borrow_mut().scale = v.0
+    });
+
+    let c = context.clone();
+    engine.register_fn("spawn", move |model: &str, position: ScriptVector| {
+        c.borrow_mut().commands.push(ScriptCommand::Spawn {
+            model: model.to_string(),
+            position: position.0,
+        })
+    });
+    let c = context.clone();
+    engine.register_fn("despawn", move || {
+        let mut context = c.borrow_mut();
+        if let Some(entity) = context.entity {
+            context.commands.push(ScriptCommand::Despawn(entity));
+        }
+    });
+
+    let c = context.clone();
+    engine.register_fn(
+        "raycast",
+        move |origin: ScriptVector, direction: ScriptVector, max: FLOAT| {
+            let context = c.borrow();
+            let physics = match context.physics {
+                // Safety: set from a live borrow for the duration of
+                // `ScriptEngine::run` and cleared before it returns
+                Some(physics) => unsafe { &*physics },
+                None => return Dynamic::from(()),
+            };
+            let ray = ncollide3d::query::Ray::new(Point3::from(origin.0), direction.0);
+            physics
+                .raycast_all(
+                    &ray,
+                    max as f32,
+                    &ncollide3d::pipeline::CollisionGroups::new(),
+                )
+                .into_iter()
+                .find(|hit| Some(hit.entity) != context.entity)
+                .map(|hit| Dynamic::from(hit.distance as FLOAT))
+                .unwrap_or_else(|| Dynamic::from(()))
+        },
+    );
+}
@@ -0,0 +1,74 @@
+//! Editor state kept between runs: where the camera was, what was
+//! selected, which windows were open and the assets used last. Written to
+//! [`SESSION_FILE`] on a clean exit and restored at startup.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+pub const SESSION_FILE: &str = "session.ron";
+/// Recently used assets remembered at most
+pub const MAX_RECENT_ASSETS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub eye: [f32; 3],
+    pub at: [f32; 3],
+}
+
+/// The windows toggled open, the editor panels are always shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenPanels {
+    pub stats: bool,
+    pub settings: bool,
+    pub debug: bool,
+    /// The preview camera detached into a window of its own
+    pub game_window: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub camera: Option<CameraPose>,
+    /// Scene file open when the session ended
+    pub scene: Option<PathBuf>,
+    /// Name of the selected entity in `scene`, unnamed ones aren't kept
+    pub selected: Option<String>,
+    pub panels: OpenPanels,
+    /// Names of the models spawned last, most recent first
+    pub recent_assets: Vec<String>,
+}
+
+impl Session {
+    /// Reads `path`, an empty session when it doesn't exist yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Self::default());
+        }
+        info!("Read session {:?}", path.as_ref());
+        let source = fs::read_to_string(path.as_ref())
+            .context(format!("Could not read session {:?}", path.as_ref()))?;
+        Ok(ron::de::from_str(&source)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        info!("Write session {:?}", path.as_ref());
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?;
+        fs::write(path.as_ref(), source)
+            .context(format!("Could not write session {:?}", path.as_ref()))
+    }
+}
+
+/// Moves `name` to the front of `recent`, dropping the oldest past
+/// [`MAX_RECENT_ASSETS`].
+pub fn use_asset(recent: &mut Vec<String>, name: &str) {
+    recent.retain(|recent| recent != name);
+    recent.insert(0, name.to_string());
+    recent.truncate(MAX_RECENT_ASSETS);
+}
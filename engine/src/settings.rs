@@ -0,0 +1,180 @@
+//! User settings, read from [`SETTINGS_FILE`] at startup and written back
+//! whenever the settings window changes them.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::*;
+use log::{info, LevelFilter};
+use serde::{Deserialize, Serialize};
+
+use crate::{input, locale, render::grid};
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+/// How edges are smoothed, both run as part of post processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Antialiasing {
+    Off,
+    /// Blurs edges found in the final image
+    Fxaa,
+    /// Blends jittered frames over time
+    Taa,
+}
+
+impl Antialiasing {
+    pub const ALL: [Antialiasing; 3] = [Antialiasing::Off, Antialiasing::Fxaa, Antialiasing::Taa];
+
+    /// Key of its name in the string tables.
+    pub fn key(self) -> &'static str {
+        match self {
+            Antialiasing::Off => "antialiasing.off",
+            Antialiasing::Fxaa => "antialiasing.fxaa",
+            Antialiasing::Taa => "antialiasing.taa",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// Waits for the display before presenting each frame
+    pub vsync: bool,
+    pub antialiasing: Antialiasing,
+    /// Names of the enabled post processing effects
+    pub post_effects: Vec<String>,
+    /// File in `post::LUT_DIR` colors are graded with, none when unset
+    pub color_lut: Option<String>,
+    /// Draws entities sharing a model and material as one indirect batch
+    pub indirect_draw: bool,
+    /// Culls indirect batches in a compute pass instead of on the CPU
+    pub gpu_culling: bool,
+    /// Frames per second at most, zero for unlimited
+    pub max_fps: u32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            vsync: false,
+            antialiasing: Antialiasing::Off,
+            post_effects: Vec::new(),
+            color_lut: None,
+            indirect_draw: false,
+            gpu_culling: false,
+            max_fps: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSettings {
+    /// Top speed of the fly camera in units per second
+    pub camera_speed: f32,
+    /// World units between grid lines
+    pub grid_size: f32,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            camera_speed: 4.0,
+            grid_size: grid::CELL_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiSettings {
+    /// String table in `locale::LOCALE_DIR` the UI labels come from
+    pub language: String,
+    /// One of `theme::BUILTIN_THEMES` or a file in `theme::THEME_DIR`
+    pub theme: String,
+    /// File in `theme::FONT_DIR`, imgui's own font when unset
+    pub font: Option<String>,
+    /// Points, scaled by the window's DPI
+    pub font_size: f32,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        Self {
+            language: locale::DEFAULT_LANGUAGE.to_string(),
+            theme: "dark".to_string(),
+            font: None,
+            font_size: 13.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// Level of modules without a filter of their own
+    pub level: LevelFilter,
+    /// Records are also written here when set
+    pub file: Option<PathBuf>,
+    /// Bytes the log file grows to before it is rotated
+    pub max_file_size: u64,
+    /// Rotated log files kept besides the current one
+    pub max_files: usize,
+    /// Levels of modules and their children by path, like `wgpu_core`
+    pub modules: BTreeMap<String, LevelFilter>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            file: None,
+            max_file_size: 5 * 1024 * 1024,
+            max_files: 3,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub graphics: GraphicsSettings,
+    pub editor: EditorSettings,
+    pub ui: UiSettings,
+    pub logging: LogSettings,
+    /// Actions bound differently from the defaults, and what to
+    pub bindings: BTreeMap<String, Vec<input::Binding>>,
+}
+
+impl Settings {
+    /// Reads `path`, the defaults when it doesn't exist yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Self::default());
+        }
+        info!("Read settings {:?}", path.as_ref());
+        let source = fs::read_to_string(path.as_ref())
+            .context(format!("Could not read settings {:?}", path.as_ref()))?;
+        Ok(toml::from_str(&source)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        info!("Write settings {:?}", path.as_ref());
+        fs::write(path.as_ref(), toml::to_string_pretty(self)?)
+            .context(format!("Could not write settings {:?}", path.as_ref()))
+    }
+
+    /// The default input map with the rebound actions replaced.
+    pub fn input_map(&self) -> input::InputMap {
+        let mut map = input::InputMap::with_defaults();
+        for (action, bindings) in self.bindings.iter() {
+            map.set_action_bindings(action, bindings.clone());
+        }
+        map
+    }
+}
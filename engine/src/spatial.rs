@@ -0,0 +1,160 @@
+//! Bounding volume tree over the world space bounds of entities, so
+//! rendering and picking only look at entities near the frustum or ray
+//! instead of every entity.
+
+use std::collections::HashMap;
+
+use nalgebra::{Matrix4, Vector3, Vector4};
+use ncollide3d::{
+    bounding_volume::{BoundingVolume, AABB},
+    partitioning::{DBVTLeaf, DBVTLeafId, VisitStatus, Visitor, BVH, DBVT},
+    query::{
+        visitors::{BoundingVolumeInterferencesCollector, RayInterferencesCollector},
+        Ray,
+    },
+};
+
+/// Leaves are loosened by this much so small moves don't reinsert them
+const MARGIN: f32 = 0.1;
+
+/// Planes of a view frustum, pointing inwards.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Planes of a wgpu clip space matrix, with depth from 0 to 1.
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_proj.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        let normalize = |plane: Vector4<f32>| {
+            let length = plane.xyz().norm();
+            if length > f32::EPSILON {
+                plane / length
+            } else {
+                plane
+            }
+        };
+        Self {
+            planes: [
+                normalize(w + x),
+                normalize(w - x),
+                normalize(w + y),
+                normalize(w - y),
+                normalize(z),
+                normalize(w - z),
+            ],
+        }
+    }
+
+    /// The planes as `(a, b, c, d)`, with `ax + by + cz + d >= 0` inside.
+    pub fn planes(&self) -> &[Vector4<f32>; 6] {
+        &self.planes
+    }
+
+    /// Whether `aabb` is at least partly inside, boxes near the corners
+    /// may be reported although they are just outside.
+    pub fn intersects_aabb(&self, aabb: &AABB<f32>) -> bool {
+        let center = aabb.center().coords;
+        let half = aabb.half_extents();
+        self.planes.iter().all(|plane| {
+            let normal: Vector3<f32> = plane.xyz();
+            let radius = half.dot(&normal.abs());
+            normal.dot(&center) + plane.w >= -radius
+        })
+    }
+}
+
+struct FrustumCollector<'a> {
+    frustum: &'a Frustum,
+    entities: &'a mut Vec<legion::Entity>,
+}
+
+impl Visitor<legion::Entity, AABB<f32>> for FrustumCollector<'_> {
+    fn visit(&mut self, aabb: &AABB<f32>, entity: Option<&legion::Entity>) -> VisitStatus {
+        if !self.frustum.intersects_aabb(aabb) {
+            return VisitStatus::Stop;
+        }
+        if let Some(entity) = entity {
+            self.entities.push(*entity);
+        }
+        VisitStatus::Continue
+    }
+}
+
+pub struct SpatialIndex {
+    tree: DBVT<f32, legion::Entity, AABB<f32>>,
+    leaves: HashMap<legion::Entity, DBVTLeafId>,
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        Self {
+            tree: DBVT::new(),
+            leaves: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, entity: legion::Entity) -> bool {
+        self.leaves.contains_key(&entity)
+    }
+
+    /// Inserts `entity` or moves it to `aabb`.
+    pub fn update(&mut self, entity: legion::Entity, aabb: &AABB<f32>) {
+        if let Some(leaf) = self.leaves.get(&entity).copied() {
+            let fits = self
+                .tree
+                .get(leaf)
+                .map_or(false, |leaf| leaf.bounding_volume.contains(aabb));
+            if fits {
+                return;
+            }
+            self.tree.remove(leaf);
+        }
+
+        let leaf = self
+            .tree
+            .insert(DBVTLeaf::new(aabb.loosened(MARGIN), entity));
+        self.leaves.insert(entity, leaf);
+    }
+
+    pub fn remove(&mut self, entity: legion::Entity) {
+        if let Some(leaf) = self.leaves.remove(&entity) {
+            self.tree.remove(leaf);
+        }
+    }
+
+    /// Entities whose bounds may be in view.
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<legion::Entity> {
+        let mut entities = Vec::new();
+        self.tree.visit(&mut FrustumCollector {
+            frustum,
+            entities: &mut entities,
+        });
+        entities
+    }
+
+    /// Entities whose bounds `ray` passes through within `max_toi`.
+    pub fn query_ray(&self, ray: &Ray<f32>, max_toi: f32) -> Vec<legion::Entity> {
+        let mut entities = Vec::new();
+        self.tree.visit(&mut RayInterferencesCollector::new(
+            ray,
+            max_toi,
+            &mut entities,
+        ));
+        entities
+    }
+
+    /// Entities whose bounds overlap `aabb`.
+    pub fn query_aabb(&self, aabb: &AABB<f32>) -> Vec<legion::Entity> {
+        let mut entities = Vec::new();
+        self.tree
+            .visit(&mut BoundingVolumeInterferencesCollector::new(
+                aabb,
+                &mut entities,
+            ));
+        entities
+    }
+}
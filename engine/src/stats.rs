@@ -0,0 +1,88 @@
+//! Numbers for the stats overlay. Draws are counted by `World::render`,
+//! CPU time by the world's update and the schedule, GPU memory by buffers
+//! and textures as they are created and dropped.
+
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Bytes held by every live `binding::Buffer`
+pub static BUFFER_BYTES: AtomicU64 = AtomicU64::new(0);
+/// Estimated bytes held by every live `texture::Texture`, mips included
+pub static TEXTURE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Frame times kept for the graph
+pub const FRAME_HISTORY: usize = 120;
+
+/// What one frame did.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    /// CPU time of each part of the frame, in the order they first ran.
+    /// Stages that run several times a frame add up.
+    pub timings: Vec<(&'static str, Duration)>,
+}
+
+impl FrameStats {
+    pub fn draw(&mut self, draw_calls: u32, triangles: u64) {
+        self.draw_calls += draw_calls;
+        self.triangles += triangles;
+    }
+
+    /// Adds the time since `start` to `name`.
+    pub fn time(&mut self, name: &'static str, start: Instant) {
+        let elapsed = start.elapsed();
+        match self.timings.iter_mut().find(|(timed, _)| *timed == name) {
+            Some((_, total)) => *total += elapsed,
+            None => self.timings.push((name, elapsed)),
+        }
+    }
+}
+
+/// Stats of the frame being run and the last finished one, with the
+/// latest frame times.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub current: FrameStats,
+    pub last: FrameStats,
+    /// Seconds, oldest first
+    frame_times: VecDeque<f32>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finishes the previous frame, which took `dt`.
+    pub fn begin_frame(&mut self, dt: Duration) {
+        self.last = std::mem::take(&mut self.current);
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt.as_secs_f32());
+    }
+
+    pub fn frame_times(&self) -> Vec<f32> {
+        self.frame_times.iter().copied().collect()
+    }
+
+    pub fn buffer_bytes(&self) -> u64 {
+        BUFFER_BYTES.load(Ordering::Relaxed)
+    }
+
+    pub fn texture_bytes(&self) -> u64 {
+        TEXTURE_BYTES.load(Ordering::Relaxed)
+    }
+}
+
+pub fn track_bytes(counter: &AtomicU64, bytes: u64) {
+    counter.fetch_add(bytes, Ordering::Relaxed);
+}
+
+pub fn untrack_bytes(counter: &AtomicU64, bytes: u64) {
+    counter.fetch_sub(bytes, Ordering::Relaxed);
+}
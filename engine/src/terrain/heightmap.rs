@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use anyhow::*;
+use log::info;
+
+/// Grid of heights in 0..1, scaled to world units by the terrain.
+#[derive(Debug, Clone)]
+pub struct HeightMap {
+    width: usize,
+    depth: usize,
+    heights: Vec<f32>,
+}
+
+impl HeightMap {
+    /// Loads a grayscale image, 16 bit images keep their full precision.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        info!("Load heightmap {:?}", path.as_ref());
+        let image = image::open(path.as_ref())
+            .context(format!("Could not open heightmap {:?}", path.as_ref()))?;
+        Ok(Self::from_image(&image))
+    }
+
+    pub fn from_image(image: &image::DynamicImage) -> Self {
+        let luma = image.to_luma16();
+        Self {
+            width: luma.width() as usize,
+            depth: luma.height() as usize,
+            heights: luma
+                .pixels()
+                .map(|pixel| pixel[0] as f32 / u16::MAX as f32)
+                .collect(),
+        }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> f32>(width: usize, depth: usize, height: F) -> Self {
+        Self {
+            width,
+            depth,
+            heights: (0..depth)
+                .flat_map(|z| (0..width).map(move |x| (x, z)))
+                .map(|(x, z)| height(x, z))
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Height of a sample, coordinates outside the map are clamped.
+    pub fn get(&self, x: isize, z: isize) -> f32 {
+        let x = x.max(0).min(self.width as isize - 1) as usize;
+        let z = z.max(0).min(self.depth as isize - 1) as usize;
+        self.heights[z * self.width + x]
+    }
+
+    /// Bilinear height at fractional sample coordinates.
+    pub fn sample(&self, x: f32, z: f32) -> f32 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (tx, tz) = (x - x0, z - z0);
+        let (x0, z0) = (x0 as isize, z0 as isize);
+
+        let top = self.get(x0, z0) * (1.0 - tx) + self.get(x0 + 1, z0) * tx;
+        let bottom = self.get(x0, z0 + 1) * (1.0 - tx) + self.get(x0 + 1, z0 + 1) * tx;
+        top * (1.0 - tz) + bottom * tz
+    }
+}
@@ -0,0 +1,350 @@
+pub mod heightmap;
+
+pub use heightmap::HeightMap;
+
+use anyhow::*;
+use log::info;
+use nalgebra::{DMatrix, Point2, Point3, Vector2, Vector3};
+
+use crate::render::{
+    binding::{self, Buffer, BufferUsage},
+    model::{self, tangent, ModelVertex},
+    state, texture,
+    traits::{Binding, DrawTerrain},
+};
+
+/// Each level halves the vertex density of the previous one.
+pub const LOD_LEVELS: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TerrainDesc {
+    /// Extent on the x and z axes, the terrain is centered on the origin
+    pub size: Vector2<f32>,
+    /// World height of a heightmap value of 1
+    pub height: f32,
+    /// Heightmap cells per chunk side, a power of two keeps every LOD exact
+    pub chunk_cells: usize,
+    /// Camera distance at which chunks drop to the next LOD
+    pub lod_distance: f32,
+    /// Repetitions of the splat layer textures across the terrain
+    pub layer_tiling: f32,
+}
+
+impl Default for TerrainDesc {
+    fn default() -> Self {
+        Self {
+            size: Vector2::new(100.0, 100.0),
+            height: 10.0,
+            chunk_cells: 32,
+            lod_distance: 40.0,
+            layer_tiling: 32.0,
+        }
+    }
+}
+
+/// Up to four layer textures blended by the channels of a splat map.
+pub struct SplatMaterial {
+    pub(crate) textures: binding::TextureBinding,
+}
+
+impl SplatMaterial {
+    pub const MAX_LAYERS: usize = 4;
+
+    pub fn new(
+        state: &state::WgpuState,
+        splat: &texture::TextureData,
+        layers: &[texture::TextureData],
+        layer_size: u32,
+        layout: &binding::BindGroupLayout,
+    ) -> Result<Self> {
+        ensure!(
+            !layers.is_empty() && layers.len() <= Self::MAX_LAYERS,
+            "Splat materials take 1 to {} layers",
+            Self::MAX_LAYERS
+        );
+
+        let mut weights = match &splat.pixels {
+            texture::TexturePixels::Image(image) => image.to_rgba8(),
+            _ => bail!("Splat maps must be 8 bit images"),
+        };
+        // Channels without a layer would blend in the last one, since array
+        // lookups clamp the layer index
+        for pixel in weights.pixels_mut() {
+            for channel in pixel.0.iter_mut().skip(layers.len()) {
+                *channel = 0;
+            }
+        }
+
+        // Weights are linear, upload them like a normal map
+        let splat = texture::Texture::from_image(
+            state,
+            &image::DynamicImage::ImageRgba8(weights),
+            Some(splat.label.as_str()),
+            true,
+            &texture::SamplerDesc::linear(),
+        )?;
+        let layers = layers
+            .iter()
+            .map(|layer| model::material_array::layer_image(Some(layer), [1.0; 4], layer_size))
+            .collect::<Result<Vec<_>>>()?;
+        let layers = texture::Texture::from_layers(
+            state,
+            &layers,
+            Some("terrain layers"),
+            false,
+            &texture::SamplerDesc {
+                anisotropy: Some(16),
+                ..texture::SamplerDesc::repeat()
+            },
+        )?;
+
+        Ok(Self {
+            textures: binding::TextureBinding::new(state, "terrain", layout, &[splat, layers]),
+        })
+    }
+}
+
+/// Marks the entity holding a terrain's collider, indexes the world's
+/// terrains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerrainEntity(pub usize);
+
+/// Square patch of the terrain sharing one vertex buffer between its LODs.
+pub struct TerrainChunk {
+    pub(crate) vertex_buffer: Buffer,
+    pub(crate) lods: Vec<(Buffer, u32)>,
+    center: Point3<f32>,
+    pub lod: usize,
+}
+
+impl TerrainChunk {
+    pub fn center(&self) -> Point3<f32> {
+        self.center
+    }
+}
+
+pub struct Terrain {
+    heightmap: HeightMap,
+    desc: TerrainDesc,
+    pub(crate) chunks: Vec<TerrainChunk>,
+    pub(crate) material: SplatMaterial,
+}
+
+impl Terrain {
+    /// Chunks drawn and the triangles they draw at their current detail.
+    pub fn draw_stats(&self) -> (u32, u64) {
+        let triangles = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.lods[chunk.lod].1 as u64 / 3)
+            .sum();
+        (self.chunks.len() as u32, triangles)
+    }
+
+    pub fn new(
+        state: &state::WgpuState,
+        heightmap: HeightMap,
+        desc: TerrainDesc,
+        material: SplatMaterial,
+    ) -> Result<Self> {
+        ensure!(
+            heightmap.width() >= 2 && heightmap.depth() >= 2,
+            "Heightmaps need at least 2x2 samples"
+        );
+        let chunk_cells = desc.chunk_cells.max(1);
+        let cells_x = heightmap.width() - 1;
+        let cells_z = heightmap.depth() - 1;
+        info!(
+            "Create terrain of {}x{} cells in {}x{} chunks",
+            cells_x,
+            cells_z,
+            (cells_x + chunk_cells - 1) / chunk_cells,
+            (cells_z + chunk_cells - 1) / chunk_cells
+        );
+
+        let mut terrain = Self {
+            heightmap,
+            desc,
+            chunks: Vec::new(),
+            material,
+        };
+
+        for z in (0..cells_z).step_by(chunk_cells) {
+            for x in (0..cells_x).step_by(chunk_cells) {
+                let chunk = terrain.build_chunk(
+                    state,
+                    x,
+                    z,
+                    chunk_cells.min(cells_x - x),
+                    chunk_cells.min(cells_z - z),
+                );
+                terrain.chunks.push(chunk);
+            }
+        }
+
+        Ok(terrain)
+    }
+
+    pub fn desc(&self) -> &TerrainDesc {
+        &self.desc
+    }
+
+    pub fn heightmap(&self) -> &HeightMap {
+        &self.heightmap
+    }
+
+    /// World position of heightmap sample `x`, `z`.
+    fn sample_position(&self, x: usize, z: usize) -> Point3<f32> {
+        let u = x as f32 / (self.heightmap.width() - 1) as f32;
+        let v = z as f32 / (self.heightmap.depth() - 1) as f32;
+        Point3::new(
+            (u - 0.5) * self.desc.size.x,
+            self.heightmap.get(x as isize, z as isize) * self.desc.height,
+            (v - 0.5) * self.desc.size.y,
+        )
+    }
+
+    fn sample_normal(&self, x: usize, z: usize) -> Vector3<f32> {
+        let (x, z) = (x as isize, z as isize);
+        let step_x = self.desc.size.x / (self.heightmap.width() - 1) as f32;
+        let step_z = self.desc.size.y / (self.heightmap.depth() - 1) as f32;
+        let dx = (self.heightmap.get(x + 1, z) - self.heightmap.get(x - 1, z)) * self.desc.height;
+        let dz = (self.heightmap.get(x, z + 1) - self.heightmap.get(x, z - 1)) * self.desc.height;
+        Vector3::new(-dx / (2.0 * step_x), 1.0, -dz / (2.0 * step_z)).normalize()
+    }
+
+    /// World height under `x`, `z`, or `None` outside the terrain.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let u = x / self.desc.size.x + 0.5;
+        let v = z / self.desc.size.y + 0.5;
+        if u < 0.0 || u > 1.0 || v < 0.0 || v > 1.0 {
+            return None;
+        }
+
+        Some(
+            self.heightmap.sample(
+                u * (self.heightmap.width() - 1) as f32,
+                v * (self.heightmap.depth() - 1) as f32,
+            ) * self.desc.height,
+        )
+    }
+
+    fn build_chunk(
+        &self,
+        state: &state::WgpuState,
+        start_x: usize,
+        start_z: usize,
+        cells_x: usize,
+        cells_z: usize,
+    ) -> TerrainChunk {
+        let mut vertices = Vec::new();
+        for z in start_z..=start_z + cells_z {
+            for x in start_x..=start_x + cells_x {
+                let uv = Point2::new(
+                    x as f32 / (self.heightmap.width() - 1) as f32,
+                    z as f32 / (self.heightmap.depth() - 1) as f32,
+                );
+                vertices.push(ModelVertex::new(
+                    self.sample_position(x, z),
+                    uv,
+                    self.sample_normal(x, z),
+                    Point2::from(uv.coords * self.desc.layer_tiling),
+                ));
+            }
+        }
+
+        let row = cells_x + 1;
+        let lod_indices = (0..LOD_LEVELS)
+            .map(|lod| {
+                // The last row and column are always kept so chunks meet
+                // their neighbours at the edges
+                let samples = |cells: usize| {
+                    let mut samples = (0..=cells).step_by(1 << lod).collect::<Vec<_>>();
+                    if samples.last() != Some(&cells) {
+                        samples.push(cells);
+                    }
+                    samples
+                };
+                let xs = samples(cells_x);
+                let zs = samples(cells_z);
+
+                let mut indices = Vec::new();
+                for z in zs.windows(2) {
+                    for x in xs.windows(2) {
+                        let a = (z[0] * row + x[0]) as u32;
+                        let b = (z[0] * row + x[1]) as u32;
+                        let c = (z[1] * row + x[0]) as u32;
+                        let d = (z[1] * row + x[1]) as u32;
+                        indices.extend_from_slice(&[a, c, b, b, c, d]);
+                    }
+                }
+                indices
+            })
+            .collect::<Vec<_>>();
+
+        tangent::generate_tangents(&mut vertices, &lod_indices[0]);
+
+        let label = format!("terrain chunk {} {}", start_x, start_z);
+        let lods = lod_indices
+            .iter()
+            .map(|indices| {
+                (
+                    Buffer::new_init(state, label.as_str(), indices, BufferUsage::Index),
+                    indices.len() as u32,
+                )
+            })
+            .collect();
+
+        let center = self.sample_position(start_x + cells_x / 2, start_z + cells_z / 2);
+
+        TerrainChunk {
+            vertex_buffer: Buffer::new_init(state, label.as_str(), &vertices, BufferUsage::Vertex),
+            lods,
+            center,
+            lod: 0,
+        }
+    }
+
+    /// Picks each chunk's LOD from its distance to `eye`.
+    pub fn update_lod(&mut self, eye: &Point3<f32>) {
+        let lod_distance = self.desc.lod_distance.max(f32::EPSILON);
+        for chunk in self.chunks.iter_mut() {
+            let distance = nalgebra::distance(&chunk.center, eye);
+            chunk.lod = ((distance / lod_distance) as usize).min(LOD_LEVELS - 1);
+        }
+    }
+
+    /// Heightfield matching the full resolution mesh.
+    pub fn collider_shape(&self) -> ncollide3d::shape::HeightField<f32> {
+        // Rows run along z and columns along x
+        let heights = DMatrix::from_fn(self.heightmap.depth(), self.heightmap.width(), |z, x| {
+            self.heightmap.get(x as isize, z as isize)
+        });
+        ncollide3d::shape::HeightField::new(
+            heights,
+            Vector3::new(self.desc.size.x, self.desc.height, self.desc.size.y),
+        )
+    }
+}
+
+impl<'a, 'b> DrawTerrain<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_terrain(
+        &mut self,
+        terrain: &'b Terrain,
+        uniforms: &'b binding::BufferGroup,
+        light: &'b binding::BufferGroup,
+    ) {
+        self.bind_textures(0, &terrain.material.textures);
+        self.bind_group(1, uniforms);
+        self.bind_group(2, light);
+        for chunk in terrain.chunks.iter() {
+            let (indices, count) = &chunk.lods[chunk.lod];
+            self.bind_vertex_buffer(0, &chunk.vertex_buffer);
+            self.bind_index_buffer(indices);
+            self.draw_indexed(0..*count, 0, 0..1);
+        }
+    }
+}
@@ -50,10 +50,30 @@ pub struct Transform {
     translation: Translation3<f32>,
     rotation: Vector3<f32>,
     scale: Vector3<f32>,
+    world_isometry: Isometry3<f32>,
+    world_scale: Vector3<f32>,
+    /// World isometry at the start of the last fixed step
+    previous_isometry: Option<Isometry3<f32>>,
+    /// What the buffer holds, the world isometry unless interpolated
+    render_isometry: Isometry3<f32>,
     buffer: binding::Buffer,
     pub dirty: bool,
 }
 
+/// What `Transform::set_world` changed. Only a new scale needs the
+/// collider rebuilt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WorldChange {
+    pub moved: bool,
+    pub rescaled: bool,
+}
+
+/// Marks entities moved by the fixed update, their rendered transform is
+/// blended between the last two steps.
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Interpolate;
+
 impl IntoInspect for crate::transform::Transform {
     type Output = InspectTransform;
 
@@ -63,7 +83,7 @@ impl IntoInspect for crate::transform::Transform {
 }
 
 impl Transform {
-    pub fn new<L: Into<Option<&'a str>>>(state: &state::WgpuState, label: L) -> Self {
+    pub fn new<'a, L: Into<Option<&'a str>>>(state: &state::WgpuState, label: L) -> Self {
         let translation = Translation3::identity();
         let scale = Vector3::new(1.0, 1.0, 1.0);
         let buffer = binding::Buffer::new_init(
@@ -84,6 +104,10 @@ impl Transform {
             translation,
             rotation: Vector3::new(0.0, 0.0, 0.0),
             scale,
+            world_isometry: Isometry3::identity(),
+            world_scale: scale,
+            previous_isometry: None,
+            render_isometry: Isometry3::identity(),
             buffer,
             dirty: false,
         }
@@ -98,10 +122,70 @@ impl Transform {
         )
     }
 
+    pub fn translation(&self) -> Translation3<f32> {
+        self.translation
+    }
+
+    /// Euler angles in radians, applied x, y then z.
+    pub fn rotation(&self) -> Vector3<f32> {
+        self.rotation
+    }
+
     pub fn scale(&self) -> Vector3<f32> {
         self.scale
     }
 
+    /// Where the entity ends up after its parents are applied, kept up to
+    /// date by `World::propagate_transforms`.
+    pub fn world_isometry(&self) -> Isometry3<f32> {
+        self.world_isometry
+    }
+
+    pub fn world_scale(&self) -> Vector3<f32> {
+        self.world_scale
+    }
+
+    pub fn set_world(&mut self, isometry: Isometry3<f32>, scale: Vector3<f32>) -> WorldChange {
+        let change = WorldChange {
+            moved: self.world_isometry != isometry,
+            rescaled: self.world_scale != scale,
+        };
+        if !change.moved && !change.rescaled {
+            return change;
+        }
+
+        self.world_isometry = isometry;
+        self.render_isometry = isometry;
+        self.world_scale = scale;
+        self.dirty = true;
+        change
+    }
+
+    /// Remembers the current world isometry as the start of a fixed step.
+    pub fn snapshot(&mut self) {
+        self.previous_isometry = Some(self.world_isometry);
+    }
+
+    /// World isometry the entity is drawn with this frame.
+    pub fn render_isometry(&self) -> Isometry3<f32> {
+        self.render_isometry
+    }
+
+    /// Renders the entity `alpha` of the way from the previous fixed step
+    /// to the current one.
+    pub fn interpolate(&mut self, alpha: f32) {
+        let isometry = match self.previous_isometry {
+            Some(previous) if previous != self.world_isometry => {
+                previous.lerp_slerp(&self.world_isometry, alpha)
+            }
+            _ => self.world_isometry,
+        };
+        if isometry != self.render_isometry {
+            self.render_isometry = isometry;
+            self.dirty = true;
+        }
+    }
+
     pub fn set_position(&mut self, position: Translation3<f32>) -> &mut Self {
         self.dirty = true;
         self.translation = position;
@@ -114,6 +198,17 @@ impl Transform {
         self
     }
 
+    /// Sets the local translation and rotation from `isometry`.
+    pub fn set_isometry(&mut self, isometry: Isometry3<f32>) -> &mut Self {
+        // Euler angles are applied x, y then z, the reverse order of the
+        // roll, pitch, yaw nalgebra extracts, so go through the inverse
+        let (x, y, z) = isometry.rotation.inverse().euler_angles();
+        self.dirty = true;
+        self.translation = isometry.translation;
+        self.rotation = Vector3::new(-x, -y, -z);
+        self
+    }
+
     pub fn set_scale(&mut self, scale: Vector3<f32>) -> &mut Self {
         self.dirty = true;
         self.scale = scale;
@@ -133,8 +228,16 @@ impl Transform {
         &self.buffer
     }
 
+    /// What the buffer holds, for drawing the entity as one of many
+    /// instances.
+    pub fn instance(&self) -> InstanceRaw {
+        InstanceRaw {
+            model: self.matrix().into(),
+        }
+    }
+
     #[inline]
     fn matrix(&self) -> Matrix4<f32> {
-        self.isometry().to_matrix() * Matrix4::new_nonuniform_scaling(&self.scale)
+        self.render_isometry.to_matrix() * Matrix4::new_nonuniform_scaling(&self.world_scale)
     }
 }
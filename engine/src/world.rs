@@ -1,167 +1,2769 @@
 use anyhow::*;
-use ncollide3d::pipeline::CollisionObjectSlabHandle;
+use log::{error, info};
 
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    animation, assets, audio, behaviour, bounds, camera, character, editor,
+    error::EngineError,
+    events::{self, Events},
+    hierarchy, input, lifecycle, net, physics, prefab,
+    render::{
+        billboard, binding, culling, decal, indirect,
+        layer::RenderLayer,
+        model, particles, reflection, sprite, state, texture,
+        traits::{Binding, DrawModel, DrawTerrain},
+        Layouts, Pipelines,
+    },
+    replay,
+    resources::Resources,
+    scene,
+    schedule::{self, Stage},
+    scripting, spatial, stats, terrain, transform,
+};
+
+use legion::IntoQuery;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ModelIdent(pub String);
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct MaterialIdent(pub String);
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct CollisionGroup(usize);
+/// Display name of an entity, not required to be unique.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Name(pub String);
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Tag(pub String);
+/// Whether an entity is drawn, entities without it are.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Visible(pub bool);
+
+/// Whether an entity with `visible` and `layer` is drawn by a camera seeing
+/// `layers`.
+fn is_drawn(visible: Option<&Visible>, layer: Option<&RenderLayer>, layers: RenderLayer) -> bool {
+    visible.map_or(true, |visible| visible.0)
+        && layer.copied().unwrap_or_default().intersects(layers)
+}
+/// Text shown above an entity in the viewport, facing the camera.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Label {
+    pub text: String,
+    /// World units
+    #[serde(default = "Label::default_height")]
+    pub height: f32,
+    #[serde(default = "Label::default_color")]
+    pub color: [f32; 4],
+}
+
+impl Label {
+    fn default_height() -> f32 {
+        0.25
+    }
+
+    fn default_color() -> [f32; 4] {
+        [1.0, 1.0, 1.0, 1.0]
+    }
+}
+
+pub struct World {
+    pub assets: assets::AssetManager,
+    world: legion::World,
+    /// Kept out of the ECS, terrains render after the entity query. Slots
+    /// of removed terrains stay empty so `TerrainEntity` indices hold.
+    terrains: Vec<Option<terrain::Terrain>>,
+    prefabs: BTreeMap<String, prefab::Prefab>,
+    physics: physics::Physics,
+    /// Whether fixed updates step the simulation, colliders still answer
+    /// queries without it
+    simulate_physics: bool,
+    /// Bounds of every entity with a model, for culling and picking
+    spatial: spatial::SpatialIndex,
+    scripts: scripting::ScriptEngine,
+    audio: audio::Audio,
+    gamepads: input::Gamepads,
+    /// Shared with the systems, holds `Time`, `Input` and `CameraState`
+    shared: legion::Resources,
+    systems: schedule::Systems,
+    /// Swaps the buffers of every registered event type
+    event_updates: Vec<fn(&mut legion::Resources)>,
+    /// A paused simulation advances one fixed step on the next update
+    pending_step: bool,
+    stats: stats::Stats,
+    /// Batches plain forward drawn entities into indirect draws
+    indirect_draw: bool,
+    /// Indirect draws of the scene and its reflection, both written before
+    /// the same submit
+    scene_draws: indirect::IndirectDraws,
+    reflection_draws: indirect::IndirectDraws,
+    /// Entities drawn as billboards, left out of every view until
+    /// `billboards` is called again
+    billboarded: std::collections::HashSet<legion::Entity>,
+    /// Startup script of the loaded scene, see `scripting`
+    startup_script: Option<String>,
+    /// Queued by the startup script, applied on the next update
+    startup_commands: Vec<scripting::ScriptCommand>,
+    /// Input being recorded or replayed
+    replay: Option<replay::Replay>,
+    /// Replication to clients, or from a server
+    net: Option<net::Net>,
+}
+
+impl World {
+    pub fn new(resources: Arc<Resources>) -> Self {
+        let mut physics = physics::Physics::new(nalgebra::Vector3::y() * -physics::GRAVITY);
+        physics.set_timestep(schedule::FIXED_STEP.as_secs_f32());
+
+        let mut shared = legion::Resources::default();
+        shared.insert(schedule::Time::default());
+        shared.insert(schedule::FixedTime::default());
+        shared.insert(schedule::PlayState::default());
+        shared.insert(input::Input::default());
+        shared.insert(editor::Selection::default());
+
+        let mut world = Self {
+            scripts: scripting::ScriptEngine::new(resources.clone()),
+            assets: assets::AssetManager::new(resources),
+            world: legion::World::new(legion::WorldOptions::default()),
+            terrains: Vec::new(),
+            prefabs: BTreeMap::new(),
+            physics,
+            simulate_physics: false,
+            spatial: spatial::SpatialIndex::new(),
+            audio: audio::Audio::disabled(),
+            gamepads: input::Gamepads::new(),
+            shared,
+            systems: schedule::Systems::new(),
+            event_updates: Vec::new(),
+            pending_step: false,
+            stats: stats::Stats::new(),
+            indirect_draw: false,
+            scene_draws: indirect::IndirectDraws::default(),
+            reflection_draws: indirect::IndirectDraws::default(),
+            billboarded: std::collections::HashSet::new(),
+            startup_script: None,
+            startup_commands: Vec::new(),
+            replay: None,
+            net: None,
+        };
+        world.add_event::<events::WindowEvent>();
+        world.add_event::<events::CollisionEvent>();
+        world.add_event::<events::TriggerEvent>();
+        world.add_event::<events::AssetEvent>();
+        world.add_event::<events::EntityEvent>();
+        world
+    }
+
+    /// Adds an `Events<T>` resource, cleared every other frame.
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        if self.shared.contains::<Events<T>>() {
+            return;
+        }
+        self.shared.insert(Events::<T>::new());
+        self.event_updates.push(|resources| {
+            if let Some(mut events) = resources.get_mut::<Events<T>>() {
+                events.update();
+            }
+        });
+    }
+
+    /// Sends `event` if its type was added with [`World::add_event`].
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) {
+        match self.shared.get_mut::<Events<T>>() {
+            Some(mut events) => events.send(event),
+            None => error!("Event type {} was never added", std::any::type_name::<T>()),
+        }
+    }
+
+    pub fn resources(&self) -> &legion::Resources {
+        &self.shared
+    }
+
+    #[allow(unused)]
+    pub fn resources_mut(&mut self) -> &mut legion::Resources {
+        &mut self.shared
+    }
+
+    /// Registers a gameplay system to run every frame after the engine's
+    /// own work for `stage`.
+    #[allow(unused)]
+    pub fn add_system<S: legion::systems::ParallelRunnable + 'static>(
+        &mut self,
+        stage: Stage,
+        system: S,
+    ) {
+        self.systems.add_system(stage, system);
+    }
+
+    #[allow(unused)]
+    pub fn add_thread_local_fn<F>(&mut self, stage: Stage, f: F)
+    where
+        F: FnMut(&mut legion::World, &mut legion::Resources) + 'static,
+    {
+        self.systems.add_thread_local_fn(stage, f);
+    }
+
+    pub fn run_stage(&mut self, stage: Stage) {
+        let start = Instant::now();
+        self.systems
+            .execute(stage, &mut self.world, &mut self.shared);
+        self.stats.current.time(stage.name(), start);
+    }
+
+    /// Draw counts, timings and frame times for the stats overlay.
+    pub fn stats(&self) -> &stats::Stats {
+        &self.stats
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.world.len()
+    }
+
+    /// Latches this frame's `Input` and advances `Time`, then runs the
+    /// input stage. Returns how long the frame counts as, the recorded
+    /// time while replaying.
+    pub fn begin_frame(&mut self, dt: Duration) -> Duration {
+        self.stats.begin_frame(dt);
+        for update in self.event_updates.iter() {
+            update(&mut self.shared);
+        }
+        let mut dt = dt;
+        if let Some(mut input) = self.shared.get_mut::<input::Input>() {
+            let replaying = self
+                .replay
+                .as_ref()
+                .map_or(false, replay::Replay::is_playing);
+            if !replaying {
+                self.gamepads.poll(&mut input);
+            }
+            if let Some(replay) = &mut self.replay {
+                dt = replay.frame(&mut input, dt);
+            }
+            input.begin_frame();
+        }
+        if let Some(mut time) = self.shared.get_mut::<schedule::Time>() {
+            time.advance(dt);
+        }
+        self.run_stage(Stage::Input);
+        dt
+    }
+
+    /// Records the input and frame times until `save_recording`.
+    pub fn start_recording<P: AsRef<Path>>(&mut self, path: P) {
+        if let Some(mut input) = self.shared.get_mut::<input::Input>() {
+            self.replay = Some(replay::Replay::record(&mut input, path));
+        }
+    }
+
+    /// Saves the input recorded so far, if recording.
+    pub fn save_recording(&self) -> Result<()> {
+        match &self.replay {
+            Some(replay) => replay.save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Feeds the recording at `path` to `Input` in place of live input.
+    pub fn start_replay<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.replay = Some(replay::Replay::play(path)?);
+        Ok(())
+    }
+
+    /// Replicates the entities with a model to the clients connecting to
+    /// `addr`.
+    pub fn serve(&mut self, addr: &str) -> Result<()> {
+        self.net = Some(net::Net::Server(net::Server::bind(addr)?));
+        Ok(())
+    }
+
+    /// Mirrors the entities of the server at `addr`, in place of the local
+    /// ones once connected.
+    pub fn connect(&mut self, addr: &str) -> Result<()> {
+        self.net = Some(net::Net::Client(net::Client::connect(addr)?));
+        Ok(())
+    }
+
+    pub fn net(&self) -> Option<&net::Net> {
+        self.net.as_ref()
+    }
+
+    /// Whether live input is ignored for a replay.
+    pub fn is_replaying(&self) -> bool {
+        self.replay
+            .as_ref()
+            .map_or(false, replay::Replay::is_playing)
+    }
+
+    pub fn replay_finished(&self) -> bool {
+        self.replay
+            .as_ref()
+            .map_or(false, replay::Replay::is_finished)
+    }
+
+    /// Publishes the moved camera and runs the camera stage.
+    pub fn update_camera(&mut self, camera: &camera::Camera) {
+        self.shared.insert(schedule::CameraState {
+            eye: camera.eye,
+            view: camera.view_transform().to_homogeneous(),
+            view_proj: camera.view_proj,
+        });
+        self.audio.set_listener(camera.eye, camera.right());
+        self.run_stage(Stage::Camera);
+    }
+
+    pub fn play_state(&self) -> schedule::PlayState {
+        self.shared
+            .get::<schedule::PlayState>()
+            .map(|play| *play)
+            .unwrap_or_default()
+    }
+
+    pub fn set_play_state(&mut self, play: schedule::PlayState) {
+        info!("{:?}", play);
+        self.shared.insert(play);
+        self.pending_step = false;
+    }
+
+    /// Advances a paused simulation by one fixed step on the next update.
+    pub fn step(&mut self) {
+        if self.play_state() == schedule::PlayState::Paused {
+            self.pending_step = true;
+        }
+    }
+
+    /// Runs the update stage, as many fixed steps as the frame time calls
+    /// for, then the transform stage, each after the engine work it
+    /// depends on. Outside of play mode only assets, transforms and bounds
+    /// are kept up to date.
+    pub fn update(&mut self, state: &state::WgpuState, layouts: &Layouts) {
+        let delta = match self.shared.get::<schedule::Time>() {
+            Some(time) => time.delta,
+            None => Duration::default(),
+        };
+        let eye = self.shared.get::<schedule::CameraState>().map(|c| c.eye);
+        let step = std::mem::replace(&mut self.pending_step, false);
+        // Time the simulation advances by, a whole fixed step when stepping
+        let simulated = match self.play_state() {
+            schedule::PlayState::Play => Some(delta),
+            schedule::PlayState::Paused if step => Some(
+                self.shared
+                    .get::<schedule::FixedTime>()
+                    .map(|fixed| fixed.step)
+                    .unwrap_or(schedule::FIXED_STEP),
+            ),
+            _ => None,
+        };
+
+        let start = Instant::now();
+        for name in self.poll_loading(state, layouts) {
+            self.send_event(events::AssetEvent::ModelLoaded(name));
+        }
+        self.update_net(state, layouts);
+        let commands = std::mem::take(&mut self.startup_commands);
+        self.apply_script_commands(state, commands);
+        if let Some(eye) = eye {
+            self.update_terrain_lod(&eye);
+        }
+        self.load_textures(state);
+        self.stats.current.time("Assets and terrain", start);
+
+        // Drawn where they are when nothing moves them
+        let mut alpha = 1.0;
+        if let Some(delta) = simulated {
+            let dt = delta.as_secs_f32();
+            let start = Instant::now();
+            self.update_animations(state, dt);
+            self.stats.current.time("Animation", start);
+            self.run_stage(Stage::Update);
+            self.propagate_transforms();
+
+            for contacts in <&mut physics::Contacts>::query().iter_mut(&mut self.world) {
+                contacts.begin_frame();
+            }
+            let steps = if step {
+                1
+            } else {
+                match self.shared.get_mut::<schedule::FixedTime>() {
+                    Some(mut fixed) => {
+                        let steps = fixed.advance(delta);
+                        alpha = fixed.alpha();
+                        steps
+                    }
+                    None => 0,
+                }
+            };
+            for _ in 0..steps {
+                self.fixed_update();
+            }
+            let start = Instant::now();
+            self.run_scripts(state, dt);
+            self.stats.current.time("Scripts", start);
+            let start = Instant::now();
+            self.run_behaviours(state, dt);
+            self.stats.current.time("Behaviours", start);
+        }
+        let start = Instant::now();
+        self.propagate_transforms();
+
+        <&mut transform::Transform>::query()
+            .filter(legion::query::component::<transform::Interpolate>())
+            .par_for_each_mut(&mut self.world, |transform| transform.interpolate(alpha));
+        self.update_bounds();
+        self.stats.current.time("Transforms and bounds", start);
+        let start = Instant::now();
+        self.update_audio();
+        self.stats.current.time("Audio", start);
+        self.run_stage(Stage::Transform);
+    }
+
+    fn update_net(&mut self, state: &state::WgpuState, layouts: &Layouts) {
+        let mut net = match self.net.take() {
+            Some(net) => net,
+            None => return,
+        };
+        match &mut net {
+            net::Net::Server(server) => self.update_server(server),
+            net::Net::Client(client) => self.update_client(state, layouts, client),
+        }
+        self.net = Some(net);
+    }
+
+    /// Sends the entities that spawned, what clients asked for and the
+    /// snapshot when due.
+    fn update_server(&mut self, server: &mut net::Server) {
+        let world = &self.world;
+        let events = server.update(|entity| world.contains(entity));
+
+        let spawned = <(legion::Entity, &ModelIdent)>::query()
+            .filter(!legion::query::component::<net::NetId>())
+            .iter(&self.world)
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in spawned {
+            let id = server.replicate(entity);
+            if let Some(mut entry) = self.world.entry(entity) {
+                entry.add_component(id);
+            }
+            match self.net_spawn(id, entity) {
+                Ok(spawn) => server.broadcast(&spawn),
+                Err(e) => error!("Could not replicate {:?}: {:?}", entity, e),
+            }
+        }
+
+        for event in events {
+            match event {
+                net::server::ServerEvent::Joined(addr) => {
+                    server.welcome(addr, self.assets.manifest());
+                    for (id, entity) in server.entities() {
+                        match self.net_spawn(id, entity) {
+                            Ok(spawn) => server.send_to(addr, &spawn),
+                            Err(e) => error!("Could not replicate {:?}: {:?}", entity, e),
+                        }
+                    }
+                }
+                net::server::ServerEvent::Requested(addr, id) => {
+                    if let Some(entity) = server.entity(id) {
+                        match self.net_spawn(id, entity) {
+                            Ok(spawn) => server.send_to(addr, &spawn),
+                            Err(e) => error!("Could not replicate {:?}: {:?}", entity, e),
+                        }
+                    }
+                }
+            }
+        }
+
+        if server.snapshot_due() {
+            let transforms = <(&net::NetId, &transform::Transform)>::query()
+                .iter(&self.world)
+                .map(|(id, transform)| net::TransformState::new(*id, transform))
+                .collect::<Vec<_>>();
+            server.send_snapshot(&transforms);
+        }
+    }
+
+    /// The spawn message of the replicated `entity`.
+    fn net_spawn(&self, id: net::NetId, entity: legion::Entity) -> Result<net::Message> {
+        let parent = self
+            .world
+            .entry_ref(entity)?
+            .get_component::<hierarchy::Parent>()
+            .ok()
+            .map(|parent| parent.0);
+        let parent = match parent {
+            Some(parent) => self
+                .world
+                .entry_ref(parent)?
+                .get_component::<net::NetId>()
+                .ok()
+                .copied(),
+            None => None,
+        };
+        net::Message::spawn(id, parent, self.entity_data(entity)?)
+    }
+
+    /// Applies what the server sent and moves the mirrored entities to
+    /// their interpolated transforms.
+    fn update_client(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        client: &mut net::Client,
+    ) {
+        for event in client.update() {
+            match event {
+                net::client::ClientEvent::Connected(manifest) => {
+                    // Loaded first so the models both use stay loaded
+                    if let Err(e) = self
+                        .assets
+                        .load_manifest(state, &layouts.material, &manifest)
+                    {
+                        error!("Could not load the server's models: {:?}", e);
+                    }
+                    self.clear_entities();
+                }
+                net::client::ClientEvent::Spawn {
+                    id,
+                    parent,
+                    mut data,
+                } => {
+                    // Scripts run on the server, the mirror only follows
+                    data.script = None;
+                    let entity = match self.spawn(state, &data) {
+                        Ok(entity) => entity,
+                        Err(e) => {
+                            error!("Could not spawn replicated {}: {:?}", data.model, e);
+                            continue;
+                        }
+                    };
+                    if let Some(mut entry) = self.world.entry(entity) {
+                        entry.add_component(id);
+                    }
+                    client.insert(id, entity, parent);
+                    // Children and parents may arrive in any order
+                    if let Some(parent) = parent.and_then(|parent| client.entity(parent)) {
+                        if let Err(e) = self.set_parent(entity, Some(parent)) {
+                            error!("Could not parent {:?}: {:?}", entity, e);
+                        }
+                    }
+                    for child in client.children(id) {
+                        if let Err(e) = self.set_parent(child, Some(entity)) {
+                            error!("Could not parent {:?}: {:?}", child, e);
+                        }
+                    }
+                }
+                net::client::ClientEvent::Despawn(entity) => {
+                    self.remove_entity(entity);
+                }
+            }
+        }
+
+        for (entity, isometry, scale) in client.interpolate() {
+            if let Some(mut entry) = self.world.entry(entity) {
+                if let Ok(transform) = entry.get_component_mut::<transform::Transform>() {
+                    transform.set_isometry(isometry).set_scale(scale);
+                }
+            }
+        }
+    }
+
+    /// Keeps the sounds of `AudioSource`s playing from their entities.
+    fn update_audio(&mut self) {
+        let sounds = &self.assets.sounds;
+        let mut query = <(legion::Entity, &audio::AudioSource, &transform::Transform)>::query();
+        let finished = self.audio.update(
+            query.iter(&self.world).map(|(entity, source, transform)| {
+                (
+                    *entity,
+                    source,
+                    nalgebra::Point3::from(transform.render_isometry().translation.vector),
+                )
+            }),
+            |clip| sounds.get_by_name(clip),
+        );
+
+        for entity in finished {
+            if let Some(mut entry) = self.world.entry(entity) {
+                if let Ok(source) = entry.get_component_mut::<audio::AudioSource>() {
+                    source.playing = false;
+                }
+            }
+        }
+    }
+
+    /// Plays the clip called `clip` once, at `position` or everywhere.
+    #[allow(unused)]
+    pub fn play_sound(&mut self, clip: &str, position: Option<nalgebra::Point3<f32>>, volume: f32) {
+        match self.assets.sounds.get_by_name(clip) {
+            Some(clip) => self.audio.play(clip, position, volume),
+            None => error!("Audio clip {} not loaded", clip),
+        }
+    }
+
+    #[allow(unused)]
+    pub fn load_sound<P: AsRef<Path>, N: Into<String>>(&mut self, name: N, path: P) -> Result<()> {
+        self.assets.load_sound(name, path).map(|_| ())
+    }
+
+    /// Runs the script of every entity that has one, then applies what
+    /// they queued.
+    fn run_scripts(&mut self, state: &state::WgpuState, dt: f32) {
+        let scripted = <(legion::Entity, &scripting::Script)>::query()
+            .iter(&self.world)
+            .map(|(entity, script)| (*entity, script.0.clone()))
+            .collect::<Vec<_>>();
+        self.scripts
+            .retain(|entity| scripted.iter().any(|(scripted, _)| *scripted == entity));
+
+        let mut commands = Vec::new();
+        for (entity, script) in scripted {
+            let collisions = self
+                .world
+                .entry_ref(entity)
+                .ok()
+                .and_then(|entry| entry.get_component::<physics::Contacts>().ok().cloned())
+                .map(|contacts| {
+                    contacts
+                        .started
+                        .iter()
+                        .map(|other| {
+                            self.world
+                                .entry_ref(*other)
+                                .ok()
+                                .and_then(|entry| {
+                                    entry
+                                        .get_component::<Name>()
+                                        .ok()
+                                        .map(|name| name.0.clone())
+                                })
+                                .unwrap_or_default()
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            if let Some(mut entry) = self.world.entry(entity) {
+                if let Ok(transform) = entry.get_component_mut::<transform::Transform>() {
+                    commands.extend(self.scripts.run(
+                        entity,
+                        &script,
+                        transform,
+                        &self.physics,
+                        dt,
+                        &collisions,
+                    ));
+                }
+            }
+        }
+        self.apply_script_commands(state, commands);
+    }
+
+    /// Calls the function of the scene's startup script handling `event`,
+    /// the world changes it asks for are made on the next update.
+    pub fn run_startup_script(&mut self, event: lifecycle::LifecycleEvent) {
+        if let Some(script) = &self.startup_script {
+            let commands = self.scripts.run_scene(script, event, &self.physics);
+            self.startup_commands.extend(commands);
+        }
+    }
+
+    /// Runs the behaviours of every entity that has some.
+    fn run_behaviours(&mut self, state: &state::WgpuState, dt: f32) {
+        let input = self.shared.get::<input::Input>();
+        let mut commands = Vec::new();
+        let mut query = <(
+            legion::Entity,
+            &mut behaviour::Behaviours,
+            &mut transform::Transform,
+            Option<&physics::Contacts>,
+        )>::query();
+        for (entity, behaviours, transform, contacts) in query.iter_mut(&mut self.world) {
+            let collisions = contacts
+                .map(|contacts| contacts.started.clone())
+                .unwrap_or_default();
+            let mut ctx = behaviour::ScriptCtx::new(
+                *entity,
+                transform,
+                input.as_deref(),
+                &self.physics,
+                &mut commands,
+            );
+            behaviours.run(&mut ctx, dt, &collisions);
+        }
+        drop(input);
+
+        self.apply_script_commands(state, commands);
+    }
+
+    /// Adds `behaviour` to the ones `entity` runs every frame.
+    #[allow(unused)]
+    pub fn add_behaviour<B: behaviour::Behaviour>(
+        &mut self,
+        entity: legion::Entity,
+        behaviour: B,
+    ) -> Result<()> {
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        match entry.get_component_mut::<behaviour::Behaviours>() {
+            Ok(behaviours) => behaviours.push(behaviour),
+            Err(_) => {
+                let mut behaviours = behaviour::Behaviours::default();
+                behaviours.push(behaviour);
+                entry.add_component(behaviours);
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawns and removes what scripts and behaviours asked for.
+    fn apply_script_commands(
+        &mut self,
+        state: &state::WgpuState,
+        commands: Vec<scripting::ScriptCommand>,
+    ) {
+        for command in commands {
+            match command {
+                scripting::ScriptCommand::Spawn { model, position } => {
+                    if let Err(e) =
+                        self.spawn_model(state, &model, nalgebra::Point3::from(position))
+                    {
+                        error!("Script could not spawn {}: {:?}", model, e);
+                    }
+                }
+                scripting::ScriptCommand::Despawn(entity) => {
+                    self.remove_entity(entity);
+                }
+            }
+        }
+    }
+
+    fn fixed_update(&mut self) {
+        <&mut transform::Transform>::query()
+            .filter(legion::query::component::<transform::Interpolate>())
+            .par_for_each_mut(&mut self.world, |transform| transform.snapshot());
+        self.run_stage(Stage::FixedUpdate);
+        let start = Instant::now();
+        self.update_characters();
+        // Kinematic bodies follow their transforms, then dynamic ones lead
+        self.propagate_transforms();
+        if self.simulate_physics {
+            self.physics.step();
+            self.sync_bodies();
+        }
+        self.propagate_transforms();
+        self.stats.current.time("Physics", start);
+
+        self.send_collision_events();
+        self.run_stage(Stage::Collision);
+    }
+
+    #[allow(unused)]
+    pub fn load_model<P: AsRef<Path>, M: Into<String>>(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        name: M,
+        path: P,
+    ) -> Result<assets::Handle<model::Model>> {
+        self.assets.load_model(state, &layouts.material, name, path)
+    }
+
+    pub fn load_model_with_lods<P: AsRef<Path>, M: Into<String>>(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        name: M,
+        path: P,
+        screen_sizes: &[f32],
+    ) -> Result<assets::Handle<model::Model>> {
+        self.assets
+            .load_model_with_lods(state, &layouts.material, name, path, screen_sizes)
+    }
+
+    #[allow(unused)]
+    pub fn load_lod<P: AsRef<Path>>(
+        &mut self,
+        state: &state::WgpuState,
+        name: &str,
+        path: P,
+        screen_size: f32,
+    ) -> Result<()> {
+        self.assets.load_lod(state, name, path, screen_size)
+    }
+
+    pub fn load_primitive<M: Into<String>>(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        name: M,
+        primitive: model::Primitive,
+    ) -> Result<assets::Handle<model::Model>> {
+        self.assets
+            .load_primitive(state, &layouts.material, name, primitive)
+    }
+
+    pub fn load_model_async<P: AsRef<Path>, M: Into<String>>(&mut self, name: M, path: P) {
+        self.assets.load_model_async(name, path);
+    }
+
+    /// Uploads finished loads and reloads of changed models, refreshing
+    /// the colliders of entities using them.
+    pub fn poll_loading(&mut self, state: &state::WgpuState, layouts: &Layouts) -> Vec<String> {
+        self.assets.poll_changes();
+        let loaded = self.assets.poll_loading(state, &layouts.material);
+        for name in loaded.iter() {
+            self.physics.forget_model_shapes(name);
+        }
+
+        let entities = <(legion::Entity, &ModelIdent)>::query()
+            .iter(&self.world)
+            .filter(|(_, model)| loaded.contains(&model.0))
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in entities {
+            if let Err(e) = self.update_entity_world_transform(entity) {
+                error!("Could not refresh entity {:?}: {:?}", entity, e);
+            }
+        }
+
+        loaded
+    }
+
+    #[allow(unused)]
+    pub fn load_material_raw(
+        &mut self,
+        state: &state::WgpuState,
+        name: &str,
+        diffuse_texture: texture::Texture,
+        normal_texture: texture::Texture,
+        metallic_roughness_texture: texture::Texture,
+        material_layout: &binding::BindGroupLayout,
+    ) -> assets::Handle<model::Material> {
+        self.assets.materials.insert(
+            name,
+            model::Material::new(
+                &state,
+                name,
+                diffuse_texture,
+                normal_texture,
+                metallic_roughness_texture,
+                material_layout,
+            ),
+        )
+    }
+
+    /// Packs `materials` into one array, entities using it need an
+    /// [`model::ArrayLayer`] picking their material.
+    #[allow(unused)]
+    pub fn create_material_array(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        name: &str,
+        materials: &[model::MaterialData],
+        layer_size: u32,
+    ) -> Result<assets::Handle<model::MaterialArray>> {
+        let array =
+            model::MaterialArray::new(state, name, materials, layer_size, &layouts.material_array)?;
+        Ok(self.assets.material_arrays.insert(name, array))
+    }
+
+    pub fn push_entity<T>(&mut self, components: T) -> Result<legion::Entity>
+    where
+        Option<T>: legion::storage::IntoComponentSource,
+    {
+        let entity = self.world.push(components);
+        self.update_world_transform(entity)?;
+
+        if let Some(mut entry) = self.world.entry(entity) {
+            let isometry = entry
+                .get_component::<transform::Transform>()?
+                .world_isometry();
+            let scale = entry.get_component::<transform::Transform>()?.world_scale();
+            let collision_groups = match entry.get_component::<physics::CollisionLayers>() {
+                Ok(layers) => Some(self.physics.layers.groups(layers)),
+                Err(_) => entry
+                    .get_component::<ncollide3d::pipeline::object::CollisionGroups>()
+                    .ok()
+                    .cloned(),
+            };
+            let mut body = entry
+                .get_component::<physics::RigidBody>()
+                .ok()
+                .copied()
+                .unwrap_or_else(physics::RigidBody::kinematic);
+            let trigger = entry
+                .get_component::<physics::TriggerVolume>()
+                .ok()
+                .copied();
+            let collider_shape = entry
+                .get_component::<physics::ColliderShape>()
+                .ok()
+                .copied();
+            let model_ident = entry.get_component::<ModelIdent>()?.clone();
+            let model_handle = self
+                .assets
+                .models
+                .handle(&model_ident.0)
+                .ok_or_else(|| EngineError::asset_not_found("Model", &model_ident.0))?;
+            let model = self
+                .assets
+                .models
+                .get(&model_handle)
+                .ok_or_else(|| EngineError::asset_not_found("Model", &model_ident.0))?;
+            let material_handle = entry
+                .get_component::<MaterialIdent>()
+                .ok()
+                .and_then(|material| self.assets.materials.handle(&material.0));
+            let bounds = bounds::Bounds::new(
+                &model.geometry,
+                entry.get_component::<transform::Transform>()?,
+            );
+            let shape = self.physics.model_shape(
+                &model_ident.0,
+                &model.geometry,
+                scale,
+                collider_shape,
+                trigger,
+            );
+            let collider = self.physics.add(
+                entity,
+                &mut body,
+                isometry,
+                shape,
+                collision_groups.unwrap_or(ncollide3d::pipeline::object::CollisionGroups::new()),
+                trigger.is_some(),
+            );
+
+            entry.add_component(body);
+            entry.add_component(collider);
+            entry.add_component(physics::Contacts::default());
+            entry.add_component(bounds);
+            // Entities keep their assets alive through handles
+            entry.add_component(model_handle);
+            if let Some(material_handle) = material_handle {
+                entry.add_component(material_handle);
+            }
+        }
+
+        self.send_event(events::EntityEvent::Spawned(entity));
+        Ok(entity)
+    }
+
+    /// Rebuilds the collider of `entity` from its model or shape, and moves
+    /// it to the entity's world transform.
+    pub fn update_entity_world_transform(&mut self, entity: legion::Entity) -> Result<()> {
+        if let Some(mut entry) = self.world.entry(entity) {
+            if entry.get_component::<ModelIdent>().is_err() {
+                // Triggers and characters without a model are primitives
+                let transform = entry.get_component::<transform::Transform>()?;
+                let shape = match entry.get_component::<character::CharacterController>() {
+                    Ok(controller) => controller.shape(),
+                    Err(_) => entry
+                        .get_component::<physics::TriggerVolume>()?
+                        .shape_handle(transform.world_scale())
+                        .ok_or(EngineError::ComponentMissing {
+                            entity,
+                            component: "ModelIdent",
+                        })?,
+                };
+                self.physics
+                    .set_shape(entry.get_component::<physics::Collider>()?, shape);
+                return self.move_collider(entity);
+            }
+
+            let model_ident = entry.get_component::<ModelIdent>()?.clone();
+            let model_handle = self
+                .assets
+                .models
+                .handle(&model_ident.0)
+                .ok_or_else(|| EngineError::asset_not_found("Model", &model_ident.0))?;
+            if entry.get_component::<assets::Handle<model::Model>>().ok() != Some(&model_handle) {
+                entry.add_component(model_handle.clone());
+            }
+
+            let transform = entry.get_component::<transform::Transform>()?;
+            let model = self
+                .assets
+                .models
+                .get(&model_handle)
+                .ok_or_else(|| EngineError::asset_not_found("Model", &model_ident.0))?;
+            let shape = self.physics.model_shape(
+                &model_ident.0,
+                &model.geometry,
+                transform.world_scale(),
+                entry
+                    .get_component::<physics::ColliderShape>()
+                    .ok()
+                    .copied(),
+                entry
+                    .get_component::<physics::TriggerVolume>()
+                    .ok()
+                    .copied(),
+            );
+            self.physics
+                .set_shape(entry.get_component::<physics::Collider>()?, shape);
+
+            let bounds = bounds::Bounds::new(&model.geometry, transform);
+            entry.add_component(bounds);
+        }
+
+        self.move_collider(entity)
+    }
+
+    /// Moves the body of `entity` to its world transform, keeping the
+    /// collider's shape.
+    fn move_collider(&mut self, entity: legion::Entity) -> Result<()> {
+        let entry = self.world.entry_ref(entity)?;
+        let transform = entry.get_component::<transform::Transform>()?;
+        let isometry = match entry.get_component::<character::CharacterController>() {
+            // Characters stay upright whatever their rotation
+            Ok(_) => nalgebra::Isometry3::from_parts(
+                transform.world_isometry().translation,
+                nalgebra::UnitQuaternion::identity(),
+            ),
+            Err(_) => transform.world_isometry(),
+        };
+        self.physics
+            .set_pose(entry.get_component::<physics::RigidBody>()?, isometry);
+        Ok(())
+    }
+
+    /// Recomputes the world transform of `entity` from its parent's,
+    /// returning what changed.
+    fn update_world_transform(&mut self, entity: legion::Entity) -> Result<transform::WorldChange> {
+        let parent = self
+            .world
+            .entry_ref(entity)?
+            .get_component::<hierarchy::Parent>()
+            .ok()
+            .copied();
+        let parent_world = match parent {
+            Some(parent) => {
+                let entry = self.world.entry_ref(parent.0)?;
+                let transform = entry.get_component::<transform::Transform>()?;
+                Some((transform.world_isometry(), transform.world_scale()))
+            }
+            None => None,
+        };
+
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        let transform = entry.get_component_mut::<transform::Transform>()?;
+        let local = (transform.isometry(), transform.scale());
+        let (isometry, scale) = match parent_world {
+            Some(parent_world) => hierarchy::compose(parent_world, local),
+            None => local,
+        };
+        Ok(transform.set_world(isometry, scale))
+    }
+
+    /// Walks every hierarchy from its root, updating world transforms and
+    /// the colliders of entities that moved.
+    pub fn propagate_transforms(&mut self) {
+        let mut stack = <(
+            legion::Entity,
+            &transform::Transform,
+            Option<&hierarchy::Parent>,
+        )>::query()
+        .iter(&self.world)
+        .filter(|(_, _, parent)| parent.is_none())
+        .map(|(entity, _, _)| *entity)
+        .collect::<Vec<_>>();
+
+        let mut moved = Vec::new();
+        let mut rescaled = Vec::new();
+        while let Some(entity) = stack.pop() {
+            match self.update_world_transform(entity) {
+                Ok(change) if change.rescaled => rescaled.push(entity),
+                Ok(change) if change.moved => moved.push(entity),
+                Ok(_) => {}
+                Err(e) => error!("Could not update transform of {:?}: {:?}", entity, e),
+            }
+
+            if let Ok(entry) = self.world.entry_ref(entity) {
+                if let Ok(children) = entry.get_component::<hierarchy::Children>() {
+                    stack.extend(children.0.iter().copied());
+                }
+            }
+        }
+
+        // Only a new scale needs a new shape, moving keeps the old one
+        for entity in moved {
+            if let Err(e) = self.move_collider(entity) {
+                error!("Could not move collider of {:?}: {:?}", entity, e);
+            }
+        }
+        for entity in rescaled {
+            if let Err(e) = self.update_entity_world_transform(entity) {
+                error!("Could not rescale collider of {:?}: {:?}", entity, e);
+            }
+        }
+    }
+
+    /// Moves `child` under `parent`, or back to the root with `None`. The
+    /// local transform is kept, so the child moves along with the parent.
+    pub fn set_parent(
+        &mut self,
+        child: legion::Entity,
+        parent: Option<legion::Entity>,
+    ) -> Result<()> {
+        if let Some(parent) = parent {
+            // Walk up from the new parent, reaching the child means a cycle
+            let mut ancestor = Some(parent);
+            while let Some(entity) = ancestor {
+                if entity == child {
+                    bail!("{:?} cannot be parented to its own descendant", child);
+                }
+                ancestor = self
+                    .world
+                    .entry_ref(entity)?
+                    .get_component::<hierarchy::Parent>()
+                    .ok()
+                    .map(|parent| parent.0);
+            }
+        }
+
+        let mut entry = self
+            .world
+            .entry(child)
+            .ok_or(EngineError::EntityNotFound(child))?;
+        let old_parent = entry
+            .get_component::<hierarchy::Parent>()
+            .ok()
+            .map(|parent| parent.0);
+        match parent {
+            Some(parent) => entry.add_component(hierarchy::Parent(parent)),
+            None => entry.remove_component::<hierarchy::Parent>(),
+        }
+
+        if let Some(old_parent) = old_parent {
+            if let Some(mut entry) = self.world.entry(old_parent) {
+                if let Ok(children) = entry.get_component_mut::<hierarchy::Children>() {
+                    children.0.retain(|entity| *entity != child);
+                }
+            }
+        }
+        if let Some(parent) = parent {
+            let mut entry = self
+                .world
+                .entry(parent)
+                .ok_or(EngineError::EntityNotFound(parent))?;
+            match entry.get_component_mut::<hierarchy::Children>() {
+                Ok(children) => children.0.push(child),
+                Err(_) => entry.add_component(hierarchy::Children(vec![child])),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every entity with a transform, as the hierarchy panel lists them.
+    pub fn hierarchy(&self) -> Vec<editor::hierarchy::HierarchyEntry> {
+        <(
+            legion::Entity,
+            &transform::Transform,
+            Option<&Name>,
+            Option<&hierarchy::Parent>,
+            Option<&hierarchy::Children>,
+            Option<&Visible>,
+        )>::query()
+        .iter(&self.world)
+        .map(
+            |(entity, _, name, parent, children, visible)| editor::hierarchy::HierarchyEntry {
+                entity: *entity,
+                name: name.map(|name| name.0.clone()),
+                parent: parent.map(|parent| parent.0),
+                children: children
+                    .map(|children| children.0.clone())
+                    .unwrap_or_default(),
+                visible: visible.map_or(true, |visible| visible.0),
+            },
+        )
+        .collect()
+    }
+
+    pub fn set_name(&mut self, entity: legion::Entity, name: &str) -> Result<()> {
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        entry.add_component(Name(name.to_string()));
+        Ok(())
+    }
+
+    /// Shows or hides `entity` and its descendants.
+    pub fn set_visible(&mut self, entity: legion::Entity, visible: bool) -> Result<()> {
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        entry.add_component(Visible(visible));
+        let children = entry
+            .get_component::<hierarchy::Children>()
+            .map(|children| children.0.clone())
+            .unwrap_or_default();
+        for child in children {
+            self.set_visible(child, visible)?;
+        }
+        Ok(())
+    }
+
+    #[allow(unused)]
+    pub fn children(&self, entity: legion::Entity) -> Vec<legion::Entity> {
+        self.world
+            .entry_ref(entity)
+            .ok()
+            .and_then(|entry| {
+                entry
+                    .get_component::<hierarchy::Children>()
+                    .ok()
+                    .map(|children| children.0.clone())
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn parent(&self, entity: legion::Entity) -> Option<legion::Entity> {
+        self.world
+            .entry_ref(entity)
+            .ok()?
+            .get_component::<hierarchy::Parent>()
+            .ok()
+            .map(|parent| parent.0)
+    }
+
+    /// Adds `terrain` with an entity for its heightfield collider.
+    #[allow(unused)]
+    pub fn add_terrain(&mut self, terrain: terrain::Terrain) -> legion::Entity {
+        let shape = ncollide3d::shape::ShapeHandle::new(terrain.collider_shape());
+        let entity = self
+            .world
+            .push((terrain::TerrainEntity(self.terrains.len()),));
+        self.terrains.push(Some(terrain));
+        let collider = self
+            .physics
+            .add_static(entity, nalgebra::Isometry3::identity(), shape);
+        if let Some(mut entry) = self.world.entry(entity) {
+            entry.add_component(collider);
+            entry.add_component(physics::Contacts::default());
+        }
+        self.send_event(events::EntityEvent::Spawned(entity));
+        entity
+    }
+
+    pub fn update_terrain_lod(&mut self, eye: &nalgebra::Point3<f32>) {
+        for terrain in self.terrains.iter_mut().flatten() {
+            terrain.update_lod(eye);
+        }
+    }
+
+    /// Writes every model entity and the assets they use to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.scene()?.write(path)
+    }
+
+    /// The model entities and the assets they use, as scene files store
+    /// them.
+    pub fn scene(&self) -> Result<scene::Scene> {
+        let mut query = <(
+            legion::Entity,
+            &ModelIdent,
+            &transform::Transform,
+            Option<&hierarchy::Parent>,
+        )>::query();
+        let saved = query.iter(&self.world).collect::<Vec<_>>();
+        let index_of =
+            |entity: legion::Entity| saved.iter().position(|(saved, _, _, _)| **saved == entity);
+
+        let entities = saved
+            .iter()
+            .map(|(entity, _, _, parent)| {
+                let mut data = self.entity_data(**entity)?;
+                data.parent = parent.and_then(|parent| index_of(parent.0));
+                Ok(data)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(scene::Scene {
+            assets: self.assets.manifest().clone(),
+            prefabs: self.prefabs.clone(),
+            entities,
+            sprites: <&sprite::Sprite>::query()
+                .iter(&self.world)
+                .cloned()
+                .collect(),
+            decals: <(&decal::Decal, &transform::Transform, Option<&Name>)>::query()
+                .iter(&self.world)
+                .map(|(decal, transform, name)| scene::DecalData {
+                    name: name.map(|name| name.0.clone()),
+                    transform: scene::TransformData::from_transform(transform),
+                    decal: decal.clone(),
+                })
+                .collect(),
+            startup_script: self.startup_script.clone(),
+        })
+    }
+
+    /// The name `entity` was given, if any.
+    pub fn name(&self, entity: legion::Entity) -> Option<String> {
+        let entry = self.world.entry_ref(entity).ok()?;
+        entry
+            .get_component::<Name>()
+            .ok()
+            .map(|name| name.0.clone())
+    }
+
+    /// Replaces the model entities with the ones saved in `path`, loading
+    /// the assets they need first.
+    pub fn load<P: AsRef<Path>>(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        path: P,
+    ) -> Result<()> {
+        self.load_scene(state, layouts, scene::Scene::read(path)?)
+    }
+
+    /// Replaces the model entities with the ones in `scene`, loading the
+    /// assets they need first.
+    pub fn load_scene(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        scene: scene::Scene,
+    ) -> Result<()> {
+        self.assets
+            .load_manifest(state, &layouts.material, &scene.assets)?;
+
+        self.prefabs.extend(scene.prefabs);
+        self.startup_script = scene.startup_script;
+        self.scripts.reset_scene();
+
+        self.clear_entities();
+        let mut spawned = Vec::new();
+        for sprite in scene.sprites {
+            self.add_sprite(state, sprite);
+        }
+        for data in scene.decals.iter() {
+            self.add_decal(state, data)?;
+        }
+        for entity in scene.entities.iter() {
+            let prefab = entity
+                .prefab
+                .as_ref()
+                .and_then(|name| Some((name, self.prefabs.get(name)?)));
+            let entity = match prefab {
+                Some((name, prefab)) => self.spawn(state, &prefab.merge(name, entity))?,
+                None => self.spawn(state, entity)?,
+            };
+            spawned.push(entity);
+        }
+
+        for (entity, data) in spawned.iter().zip(scene.entities.iter()) {
+            if let Some(parent) = data.parent.and_then(|parent| spawned.get(parent)) {
+                self.set_parent(*entity, Some(*parent))?;
+            }
+        }
+        self.propagate_transforms();
+        self.update_collision_world();
+
+        Ok(())
+    }
+
+    /// Pushes an entity with the components in `data`.
+    pub fn spawn(
+        &mut self,
+        state: &state::WgpuState,
+        data: &scene::EntityData,
+    ) -> Result<legion::Entity> {
+        let mut transform = transform::Transform::new(state, data.model.as_str());
+        data.transform.apply(&mut transform);
+        let model = ModelIdent(data.model.clone());
+        let entity = match &data.material {
+            Some(material) => {
+                self.push_entity((model, transform, MaterialIdent(material.clone())))?
+            }
+            None => self.push_entity((model, transform))?,
+        };
+
+        if let Some(mut entry) = self.world.entry(entity) {
+            if let Some(prefab) = &data.prefab {
+                entry.add_component(prefab::PrefabInstance(prefab.clone()));
+            }
+            if let Some(name) = &data.name {
+                entry.add_component(Name(name.clone()));
+            }
+            if let Some(tag) = &data.tag {
+                entry.add_component(Tag(tag.clone()));
+            }
+            if let Some(script) = &data.script {
+                entry.add_component(scripting::Script(script.clone()));
+            }
+            if let Some(label) = &data.label {
+                entry.add_component(label.clone());
+            }
+            if let Some(mirror) = &data.mirror {
+                entry.add_component(mirror.clone());
+            }
+            if let Some(emitter) = &data.particles {
+                entry.add_component(emitter.clone());
+            }
+            if let Some(layer) = data.render_layer {
+                entry.add_component(layer);
+            }
+            if let Some(billboard) = &data.billboard {
+                entry.add_component(billboard.clone());
+            }
+            if let Some(visible) = data.visible {
+                entry.add_component(Visible(visible));
+            }
+        }
+        if let Some(shape) = data.collider {
+            self.set_collider_shape(entity, shape)?;
+        }
+        if let Some(layers) = &data.layers {
+            self.set_collision_layers(entity, layers.clone())?;
+        }
+        Ok(entity)
+    }
+
+    /// Components of the model entity `entity` as scene files store them,
+    /// without its parent.
+    pub fn entity_data(&self, entity: legion::Entity) -> Result<scene::EntityData> {
+        let entry = self.world.entry_ref(entity)?;
+        Ok(scene::EntityData {
+            model: entry.get_component::<ModelIdent>()?.0.clone(),
+            material: entry
+                .get_component::<MaterialIdent>()
+                .ok()
+                .map(|material| material.0.clone()),
+            transform: scene::TransformData::from_transform(
+                entry.get_component::<transform::Transform>()?,
+            ),
+            prefab: entry
+                .get_component::<prefab::PrefabInstance>()
+                .ok()
+                .map(|prefab| prefab.0.clone()),
+            parent: None,
+            name: entry
+                .get_component::<Name>()
+                .ok()
+                .map(|name| name.0.clone()),
+            tag: entry.get_component::<Tag>().ok().map(|tag| tag.0.clone()),
+            collider: entry
+                .get_component::<physics::ColliderShape>()
+                .ok()
+                .copied(),
+            layers: entry
+                .get_component::<physics::CollisionLayers>()
+                .ok()
+                .cloned(),
+            script: entry
+                .get_component::<scripting::Script>()
+                .ok()
+                .map(|script| script.0.clone()),
+            label: entry.get_component::<Label>().ok().cloned(),
+            mirror: entry.get_component::<reflection::Mirror>().ok().cloned(),
+            particles: entry
+                .get_component::<particles::ParticleEmitter>()
+                .ok()
+                .cloned(),
+            render_layer: entry.get_component::<RenderLayer>().ok().copied(),
+            billboard: entry.get_component::<billboard::Billboard>().ok().cloned(),
+            visible: entry
+                .get_component::<Visible>()
+                .ok()
+                .map(|visible| visible.0),
+        })
+    }
+
+    /// Sets the components of `entity` stored in `data`, all but its parent.
+    pub fn apply_entity_data(
+        &mut self,
+        entity: legion::Entity,
+        data: &scene::EntityData,
+    ) -> Result<()> {
+        {
+            let mut entry = self
+                .world
+                .entry(entity)
+                .ok_or(EngineError::EntityNotFound(entity))?;
+            data.transform
+                .apply(entry.get_component_mut::<transform::Transform>()?);
+            entry.add_component(ModelIdent(data.model.clone()));
+            match &data.material {
+                Some(material) => entry.add_component(MaterialIdent(material.clone())),
+                None => entry.remove_component::<MaterialIdent>(),
+            }
+            match &data.prefab {
+                Some(prefab) => entry.add_component(prefab::PrefabInstance(prefab.clone())),
+                None => entry.remove_component::<prefab::PrefabInstance>(),
+            }
+            match &data.name {
+                Some(name) => entry.add_component(Name(name.clone())),
+                None => entry.remove_component::<Name>(),
+            }
+            match &data.tag {
+                Some(tag) => entry.add_component(Tag(tag.clone())),
+                None => entry.remove_component::<Tag>(),
+            }
+            match &data.script {
+                Some(script) => entry.add_component(scripting::Script(script.clone())),
+                None => entry.remove_component::<scripting::Script>(),
+            }
+            match &data.label {
+                Some(label) => entry.add_component(label.clone()),
+                None => entry.remove_component::<Label>(),
+            }
+            match &data.mirror {
+                Some(mirror) => entry.add_component(mirror.clone()),
+                None => entry.remove_component::<reflection::Mirror>(),
+            }
+            match &data.particles {
+                Some(emitter) => entry.add_component(emitter.clone()),
+                None => entry.remove_component::<particles::ParticleEmitter>(),
+            }
+            match data.render_layer {
+                Some(layer) => entry.add_component(layer),
+                None => entry.remove_component::<RenderLayer>(),
+            }
+            match &data.billboard {
+                Some(billboard) => entry.add_component(billboard.clone()),
+                None => entry.remove_component::<billboard::Billboard>(),
+            }
+        }
+        if let Some(shape) = data.collider {
+            self.set_collider_shape(entity, shape)?;
+        }
+        if let Some(layers) = &data.layers {
+            self.set_collision_layers(entity, layers.clone())?;
+        }
+        self.propagate_transforms();
+        self.update_entity_world_transform(entity)
+    }
+
+    /// Spawns a copy of `entity` and its children under the same parent.
+    pub fn duplicate(
+        &mut self,
+        state: &state::WgpuState,
+        entity: legion::Entity,
+    ) -> Result<legion::Entity> {
+        let copy = self.duplicate_tree(state, entity)?;
+        let name = self
+            .world
+            .entry_ref(entity)?
+            .get_component::<Name>()
+            .map(|name| name.0.clone());
+        if let Ok(name) = name {
+            self.set_name(copy, &format!("{} copy", name))?;
+        }
+        self.set_parent(copy, self.parent(entity))?;
+        self.propagate_transforms();
+        self.update_collision_world();
+        Ok(copy)
+    }
+
+    fn duplicate_tree(
+        &mut self,
+        state: &state::WgpuState,
+        entity: legion::Entity,
+    ) -> Result<legion::Entity> {
+        let data = self.entity_data(entity)?;
+        let copy = self.spawn(state, &data)?;
+        for child in self.children(entity) {
+            let child_copy = self.duplicate_tree(state, child)?;
+            self.set_parent(child_copy, Some(copy))?;
+        }
+        Ok(copy)
+    }
+
+    /// Pushes an entity showing `model` at `position`.
+    pub fn spawn_model(
+        &mut self,
+        state: &state::WgpuState,
+        model: &str,
+        position: nalgebra::Point3<f32>,
+    ) -> Result<legion::Entity> {
+        self.spawn(
+            state,
+            &scene::EntityData {
+                model: model.to_string(),
+                material: None,
+                transform: scene::TransformData {
+                    translation: position.coords.into(),
+                    ..Default::default()
+                },
+                prefab: None,
+                parent: None,
+                name: None,
+                tag: None,
+                collider: None,
+                layers: None,
+                script: None,
+                label: None,
+                mirror: None,
+                particles: None,
+                render_layer: None,
+                billboard: None,
+                visible: None,
+            },
+        )
+    }
+
+    /// First entity called `name`.
+    pub fn find_by_name(&self, name: &str) -> Option<legion::Entity> {
+        <(legion::Entity, &Name)>::query()
+            .iter(&self.world)
+            .find(|(_, entity_name)| entity_name.0 == name)
+            .map(|(entity, _)| *entity)
+    }
+
+    #[allow(unused)]
+    pub fn find_by_tag(&self, tag: &str) -> Vec<legion::Entity> {
+        <(legion::Entity, &Tag)>::query()
+            .iter(&self.world)
+            .filter(|(_, entity_tag)| entity_tag.0 == tag)
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    pub fn register_prefab<N: Into<String>>(&mut self, name: N, prefab: prefab::Prefab) {
+        self.prefabs.insert(name.into(), prefab);
+    }
+
+    #[allow(unused)]
+    pub fn prefab(&self, name: &str) -> Option<&prefab::Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn prefab_names(&self) -> impl Iterator<Item = &String> {
+        self.prefabs.keys()
+    }
+
+    /// Registers the components of `entity` as the prefab `name`, the
+    /// entity becomes its first instance.
+    pub fn create_prefab<N: Into<String>>(
+        &mut self,
+        name: N,
+        entity: legion::Entity,
+    ) -> Result<()> {
+        let name = name.into();
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        let prefab = prefab::Prefab {
+            model: entry.get_component::<ModelIdent>()?.0.clone(),
+            material: entry
+                .get_component::<MaterialIdent>()
+                .ok()
+                .map(|material| material.0.clone()),
+            transform: scene::TransformData::from_transform(
+                entry.get_component::<transform::Transform>()?,
+            ),
+            tag: entry.get_component::<Tag>().ok().map(|tag| tag.0.clone()),
+        };
+        entry.add_component(prefab::PrefabInstance(name.clone()));
+        self.register_prefab(name, prefab);
+        Ok(())
+    }
+
+    /// Spawns an instance of the prefab `name`, `transform` replacing the
+    /// prefab's own.
+    pub fn instantiate(
+        &mut self,
+        state: &state::WgpuState,
+        name: &str,
+        transform: Option<scene::TransformData>,
+    ) -> Result<legion::Entity> {
+        let data = self
+            .prefabs
+            .get(name)
+            .ok_or_else(|| EngineError::asset_not_found("Prefab", name))?
+            .instance(name, transform);
+        self.spawn(state, &data)
+    }
+
+    /// Removes every entity with a model along with its collider, then
+    /// unloads the assets only they used.
+    fn clear_entities(&mut self) {
+        let sprites = <(legion::Entity, &sprite::Sprite)>::query()
+            .iter(&self.world)
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in sprites {
+            self.world.remove(entity);
+        }
+
+        let decals = <(legion::Entity, &decal::Decal)>::query()
+            .iter(&self.world)
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in decals {
+            self.remove_tree(entity);
+        }
+
+        let entities = <(legion::Entity, &ModelIdent)>::query()
+            .iter(&self.world)
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+
+        for entity in entities {
+            // Children of an earlier entity are already gone
+            if self.world.contains(entity) {
+                self.remove_tree(entity);
+            }
+        }
+        self.assets.unload_unused();
+    }
+
+    /// Removes `entity` and its children, unregistering their colliders.
+    /// Their buffers and asset handles are dropped with the components,
+    /// unloading the assets nothing else uses. Returns whether the entity
+    /// existed.
+    pub fn remove_entity(&mut self, entity: legion::Entity) -> bool {
+        let removed = self.remove_tree(entity);
+        if removed {
+            self.assets.unload_unused();
+        }
+        removed
+    }
+
+    fn remove_tree(&mut self, entity: legion::Entity) -> bool {
+        if !self.world.contains(entity) {
+            return false;
+        }
+        if let Err(e) = self.set_parent(entity, None) {
+            error!("Could not detach {:?}: {:?}", entity, e);
+        }
+
+        let mut stack = vec![entity];
+        let mut colliders = Vec::new();
+        let mut bodies = Vec::new();
+        let mut removed = Vec::new();
+        while let Some(entity) = stack.pop() {
+            removed.push(entity);
+            self.send_event(events::EntityEvent::Removed(entity));
+            if let Some(entry) = self.world.entry(entity) {
+                if let Ok(children) = entry.get_component::<hierarchy::Children>() {
+                    stack.extend(children.0.iter().copied());
+                }
+                if let Ok(collider) = entry.get_component::<physics::Collider>() {
+                    colliders.push(*collider);
+                }
+                if let Ok(body) = entry.get_component::<physics::RigidBody>() {
+                    bodies.extend(body.handle());
+                }
+                if let Ok(terrain) = entry.get_component::<terrain::TerrainEntity>() {
+                    if let Some(slot) = self.terrains.get_mut(terrain.0) {
+                        slot.take();
+                    }
+                }
+            }
+            self.world.remove(entity);
+        }
+
+        self.physics.remove(&colliders, &bodies);
+        for entity in removed.iter() {
+            self.spatial.remove(*entity);
+        }
+        // Removed colliders don't report their contacts ending
+        for contacts in <&mut physics::Contacts>::query().iter_mut(&mut self.world) {
+            contacts.touching.retain(|entity| !removed.contains(entity));
+        }
+        true
+    }
+
+    pub fn entry(&mut self, entity: legion::Entity) -> Option<legion::world::Entry> {
+        self.world.entry(entity)
+    }
+
+    /// Poses every animated entity on the worker threads, then uploads
+    /// the poses.
+    pub fn update_animations(&mut self, state: &state::WgpuState, dt: f32) {
+        let models = &self.assets.models;
+        <(&mut animation::AnimationPlayer, &ModelIdent)>::query().par_for_each_mut(
+            &mut self.world,
+            |(player, model)| {
+                if let Some(model) = models.get_by_name(&model.0) {
+                    if let Some(skeleton) = &model.skeleton {
+                        player.advance(skeleton, &model.animations, dt);
+                    }
+                }
+            },
+        );
+
+        for player in <&mut animation::AnimationPlayer>::query().iter_mut(&mut self.world) {
+            player.upload(state);
+        }
+    }
+
+    /// Refreshes the bounds of entities whose transform changed since the
+    /// last render on the worker threads, then their place in the spatial
+    /// index.
+    pub fn update_bounds(&mut self) {
+        let models = &self.assets.models;
+        let spatial = &self.spatial;
+        <(
+            legion::Entity,
+            &transform::Transform,
+            &ModelIdent,
+            &mut bounds::Bounds,
+        )>::query()
+        .par_for_each_mut(&mut self.world, |(entity, transform, model, bounds)| {
+            if !transform.dirty && spatial.contains(*entity) {
+                return;
+            }
+            if let Some(model) = models.get_by_name(&model.0) {
+                *bounds = bounds::Bounds::new(&model.geometry, transform);
+            }
+        });
+
+        let mut query = <(
+            legion::Entity,
+            &transform::Transform,
+            &ModelIdent,
+            &bounds::Bounds,
+        )>::query();
+        for (entity, transform, _, bounds) in query.iter(&self.world) {
+            if transform.dirty || !self.spatial.contains(*entity) {
+                self.spatial.update(*entity, &bounds.aabb);
+            }
+        }
+    }
+
+    /// Entities whose bounds may be seen by `camera`.
+    pub fn visible_entities(&self, camera: &camera::Camera) -> Vec<legion::Entity> {
+        self.spatial
+            .query_frustum(&spatial::Frustum::from_view_proj(&camera.view_proj))
+    }
+
+    /// Entities whose bounds `ray` passes through, a cheap first pass
+    /// before testing their meshes.
+    #[allow(unused)]
+    pub fn entities_along_ray(
+        &self,
+        ray: &ncollide3d::query::Ray<f32>,
+        max_toi: f32,
+    ) -> Vec<legion::Entity> {
+        self.spatial.query_ray(ray, max_toi)
+    }
+
+    /// Entities whose bounds overlap `aabb`.
+    #[allow(unused)]
+    pub fn entities_in_aabb(
+        &self,
+        aabb: &ncollide3d::bounding_volume::AABB<f32>,
+    ) -> Vec<legion::Entity> {
+        self.spatial.query_aabb(aabb)
+    }
+
+    /// World space bounds of `entity`, `None` for entities without a model.
+    #[allow(unused)]
+    /// The selected entity, if it still exists.
+    pub fn selection(&self) -> Option<legion::Entity> {
+        let selected = self.shared.get::<editor::Selection>()?.0?;
+        if self.world.contains(selected) {
+            Some(selected)
+        } else {
+            None
+        }
+    }
+
+    pub fn select(&mut self, entity: Option<legion::Entity>) {
+        if let Some(mut selection) = self.shared.get_mut::<editor::Selection>() {
+            selection.0 = entity;
+        }
+    }
+
+    /// Materials `entity` is drawn with, its material asset or else the
+    /// materials of its model.
+    pub fn entity_materials(&self, entity: legion::Entity) -> Vec<editor::material::MaterialEntry> {
+        use editor::material::MaterialSource;
+
+        let entry = match self.world.entry_ref(entity) {
+            Ok(entry) => entry,
+            Err(_) => return Vec::new(),
+        };
+        if let Ok(material) = entry.get_component::<MaterialIdent>() {
+            return self
+                .assets
+                .materials
+                .get_by_name(&material.0)
+                .map(|asset| {
+                    vec![self.material_entry(MaterialSource::Asset(material.0.clone()), asset)]
+                })
+                .unwrap_or_default();
+        }
+        let model = match entry.get_component::<ModelIdent>() {
+            Ok(model) => model.0.clone(),
+            Err(_) => return Vec::new(),
+        };
+        self.assets
+            .models
+            .get_by_name(&model)
+            .map(|asset| {
+                asset
+                    .materials
+                    .iter()
+                    .enumerate()
+                    .map(|(i, material)| {
+                        self.material_entry(MaterialSource::Model(model.clone(), i), material)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn material_entry(
+        &self,
+        source: editor::material::MaterialSource,
+        material: &model::Material,
+    ) -> editor::material::MaterialEntry {
+        editor::material::MaterialEntry {
+            name: material.name().to_string(),
+            source,
+            base_color: material.uniform().base_color,
+            slots: material
+                .texture_handles()
+                .iter()
+                .map(|handle| self.assets.textures.name(handle).map(String::from))
+                .collect(),
+        }
+    }
+
+    /// Changes the base color of a material, seen right away by every
+    /// entity using it.
+    pub fn set_material_color(
+        &mut self,
+        state: &state::WgpuState,
+        source: &editor::material::MaterialSource,
+        color: [f32; 4],
+    ) -> Result<()> {
+        material_mut(&mut self.assets.materials, &mut self.assets.models, source)?
+            .set_base_color(state, color);
+        Ok(())
+    }
+
+    /// Puts the loaded texture `texture` in `slot` of a material.
+    pub fn set_material_texture(
+        &mut self,
+        state: &state::WgpuState,
+        layouts: &Layouts,
+        source: &editor::material::MaterialSource,
+        slot: usize,
+        texture: &str,
+    ) -> Result<()> {
+        let handle = self
+            .assets
+            .textures
+            .handle(texture)
+            .ok_or_else(|| EngineError::asset_not_found("Texture", texture))?;
+        let material = material_mut(&mut self.assets.materials, &mut self.assets.models, source)?;
+        material.set_texture(
+            state,
+            slot,
+            handle,
+            &self.assets.textures,
+            &layouts.material,
+        )
+    }
+
+    /// Binds the materials sampling the texture `name` again, after it was
+    /// replaced.
+    pub fn rebind_materials(&mut self, state: &state::WgpuState, layouts: &Layouts, name: &str) {
+        let textures = &self.assets.textures;
+        let handle = match textures.handle(name) {
+            Some(handle) => handle,
+            None => return,
+        };
+        let materials = self
+            .assets
+            .materials
+            .iter_mut()
+            .map(|(_, material)| material)
+            .chain(
+                self.assets
+                    .models
+                    .iter_mut()
+                    .flat_map(|(_, model)| model.materials.iter_mut()),
+            );
+        for material in materials {
+            if material.texture_handles().contains(&handle) {
+                if let Err(e) = material.rebind(state, textures, &layouts.material) {
+                    error!("Could not rebind material {:?}: {:?}", material.name(), e);
+                }
+            }
+        }
+    }
+
+    /// Plane of the first visible mirror and its clip plane, moved in front
+    /// by the mirror's offset.
+    pub fn mirror_plane(&self) -> Option<(reflection::Plane, reflection::Plane)> {
+        <(&transform::Transform, &reflection::Mirror, Option<&Visible>)>::query()
+            .iter(&self.world)
+            .filter(|(_, _, visible)| visible.map_or(true, |visible| visible.0))
+            .find_map(|(transform, mirror, _)| {
+                let isometry = transform.world_isometry();
+                let normal =
+                    (isometry.rotation * nalgebra::Vector3::y()).try_normalize(f32::EPSILON)?;
+                let point = nalgebra::Point3::from(isometry.translation.vector);
+                Some((
+                    reflection::Plane { point, normal },
+                    reflection::Plane {
+                        point: point + normal * mirror.offset,
+                        normal,
+                    },
+                ))
+            })
+    }
+
+    /// Pushes an entity drawn as `sprite` over the scene, loading its
+    /// texture.
+    pub fn add_sprite(
+        &mut self,
+        state: &state::WgpuState,
+        mut sprite: sprite::Sprite,
+    ) -> legion::Entity {
+        self.assets
+            .resolve_texture(state, &sprite.texture, &mut sprite.handle);
+        self.world.push((sprite,))
+    }
+
+    /// Loads the textures sprites, decals and billboards were given since
+    /// the last call.
+    fn load_textures(&mut self, state: &state::WgpuState) {
+        for sprite in <&mut sprite::Sprite>::query().iter_mut(&mut self.world) {
+            self.assets
+                .resolve_texture(state, &sprite.texture, &mut sprite.handle);
+        }
+        for decal in <&mut decal::Decal>::query().iter_mut(&mut self.world) {
+            self.assets
+                .resolve_texture(state, &decal.texture, &mut decal.handle);
+        }
+        // Empty paths show impostors, they don't load a texture
+        for billboard in <&mut billboard::Billboard>::query().iter_mut(&mut self.world) {
+            self.assets
+                .resolve_texture(state, &billboard.texture, &mut billboard.handle);
+        }
+    }
+
+    /// Sprites of the entities a camera seeing `layers` draws.
+    pub fn sprites(&self, layers: RenderLayer) -> Vec<sprite::Sprite> {
+        <(&sprite::Sprite, Option<&Visible>, Option<&RenderLayer>)>::query()
+            .iter(&self.world)
+            .filter(|(_, visible, layer)| is_drawn(*visible, *layer, layers))
+            .map(|(sprite, _, _)| sprite.clone())
+            .collect()
+    }
+
+    /// Billboards of the entities a camera seeing `camera.layers` draws
+    /// that are at least their billboard's distance from it. Until the
+    /// next call their models are left out of every view, which all draw
+    /// these billboards instead.
+    pub fn billboards(&mut self, camera: &camera::Camera) -> Vec<billboard::BillboardInstance> {
+        let mut billboarded = std::collections::HashSet::new();
+        let mut billboards = Vec::new();
+        let mut query = <(
+            legion::Entity,
+            &transform::Transform,
+            &billboard::Billboard,
+            Option<&ModelIdent>,
+            Option<&bounds::Bounds>,
+            Option<&Visible>,
+            Option<&RenderLayer>,
+        )>::query();
+        for (entity, transform, entity_billboard, model, bounds, visible, layer) in
+            query.iter(&self.world)
+        {
+            if !is_drawn(visible, layer, camera.layers) {
+                continue;
+            }
+            // Where the model would be drawn this frame
+            let isometry = transform.render_isometry();
+            let (center, fitted_size, texture) = if entity_billboard.texture.is_empty() {
+                // Impostors are framed on the bounding sphere of the model
+                let (name, model) = match model.and_then(|model| {
+                    self.assets
+                        .models
+                        .get_by_name(&model.0)
+                        .map(|asset| (&model.0, asset))
+                }) {
+                    Some(model) => model,
+                    None => continue,
+                };
+                let scale = transform.world_scale();
+                let sphere = model.geometry.bounding_sphere();
+                let center =
+                    isometry * nalgebra::Point3::from(sphere.center().coords.component_mul(&scale));
+                (
+                    center,
+                    billboard::impostor_size(sphere.radius()) * scale.abs().max(),
+                    billboard::BillboardTexture::Impostor(name.clone()),
+                )
+            } else if let Some(handle) = &entity_billboard.handle {
+                let (center, radius) = match bounds {
+                    Some(bounds) => (*bounds.sphere.center(), bounds.sphere.radius()),
+                    None => (nalgebra::Point3::from(isometry.translation.vector), 0.5),
+                };
+                (
+                    center,
+                    radius * 2.0,
+                    billboard::BillboardTexture::Asset(handle.clone()),
+                )
+            } else {
+                // The model stays until the texture loads
+                continue;
+            };
+            if (center - camera.eye).norm() < entity_billboard.distance {
+                continue;
+            }
+            billboarded.insert(*entity);
+            billboards.push(billboard::BillboardInstance {
+                center,
+                size: if entity_billboard.size > 0.0 {
+                    entity_billboard.size
+                } else {
+                    fitted_size
+                },
+                texture,
+            });
+        }
+        self.billboarded = billboarded;
+        billboards
+    }
+
+    /// Adds a decal entity without a model, see `render::decal`.
+    pub fn add_decal(
+        &mut self,
+        state: &state::WgpuState,
+        data: &scene::DecalData,
+    ) -> Result<legion::Entity> {
+        let name = data
+            .name
+            .clone()
+            .unwrap_or_else(|| data.decal.texture.clone());
+        let mut transform = transform::Transform::new(state, name.as_str());
+        data.transform.apply(&mut transform);
+        let mut decal = data.decal.clone();
+        self.assets
+            .resolve_texture(state, &decal.texture, &mut decal.handle);
+        let entity = self.world.push((transform, decal, Name(name)));
+        self.update_world_transform(entity)?;
+        self.send_event(events::EntityEvent::Spawned(entity));
+        Ok(entity)
+    }
+
+    /// Decals of the entities a camera seeing `layers` draws, with the
+    /// world matrix of their box.
+    pub fn decals(&self, layers: RenderLayer) -> Vec<(nalgebra::Matrix4<f32>, decal::Decal)> {
+        <(
+            &transform::Transform,
+            &decal::Decal,
+            Option<&Visible>,
+            Option<&RenderLayer>,
+        )>::query()
+        .iter(&self.world)
+        .filter(|(_, _, visible, layer)| is_drawn(*visible, *layer, layers))
+        .map(|(transform, decal, _, _)| {
+            let matrix = transform.world_isometry().to_homogeneous()
+                * nalgebra::Matrix4::new_nonuniform_scaling(&transform.world_scale());
+            (matrix, decal.clone())
+        })
+        .collect()
+    }
+
+    /// Particle emitters of the entities a camera seeing `layers` draws,
+    /// placed at their world isometry.
+    pub fn particle_emitters(
+        &self,
+        layers: RenderLayer,
+    ) -> Vec<(nalgebra::Isometry3<f32>, particles::ParticleEmitter)> {
+        <(
+            &transform::Transform,
+            &particles::ParticleEmitter,
+            Option<&Visible>,
+            Option<&RenderLayer>,
+        )>::query()
+        .iter(&self.world)
+        .filter(|(_, _, visible, layer)| is_drawn(*visible, *layer, layers))
+        .map(|(transform, emitter, _, _)| (transform.world_isometry(), emitter.clone()))
+        .collect()
+    }
+
+    /// Where to draw the text of each entity with a label a camera seeing
+    /// `layers` draws, or with a name when `names` is set, just above its
+    /// bounds.
+    pub fn labels(&self, names: bool, layers: RenderLayer) -> Vec<(nalgebra::Point3<f32>, Label)> {
+        <(
+            &transform::Transform,
+            Option<&Label>,
+            Option<&Name>,
+            Option<&bounds::Bounds>,
+            Option<&Visible>,
+            Option<&RenderLayer>,
+        )>::query()
+        .iter(&self.world)
+        .filter(|(_, _, _, _, visible, layer)| is_drawn(*visible, *layer, layers))
+        .filter_map(|(transform, label, name, bounds, _, _)| {
+            let label = match (label, name) {
+                (Some(label), _) => label.clone(),
+                (None, Some(name)) if names => Label {
+                    text: name.0.clone(),
+                    height: Label::default_height(),
+                    color: Label::default_color(),
+                },
+                _ => return None,
+            };
+            let position = match bounds {
+                Some(bounds) => {
+                    let center = bounds.center();
+                    nalgebra::Point3::new(center.x, bounds.aabb.maxs.y + label.height, center.z)
+                }
+                None => nalgebra::Point3::from(transform.world_isometry().translation.vector),
+            };
+            Some((position, label))
+        })
+        .collect()
+    }
+
+    pub fn bounds(&self, entity: legion::Entity) -> Option<bounds::Bounds> {
+        self.world
+            .entry_ref(entity)
+            .ok()?
+            .get_component::<bounds::Bounds>()
+            .ok()
+            .cloned()
+    }
 
-use crate::{
-    render::{
-        binding, model, state, texture,
-        traits::{Binding, DrawModel},
-        Layouts,
-    },
-    transform,
-};
+    /// Registers moved and added colliders so raycasts see them before
+    /// the next fixed step.
+    pub fn update_collision_world(&mut self) {
+        self.physics.maintain();
+    }
 
-use legion::IntoQuery;
+    fn send_collision_events(&mut self) {
+        for collision in self.physics.collision_events() {
+            let (a, b) = collision.entities();
+            for (entity, other) in [(a, b), (b, a)].iter() {
+                if let Some(mut entry) = self.world.entry(*entity) {
+                    if let Ok(contacts) = entry.get_component_mut::<physics::Contacts>() {
+                        contacts.apply(*other, collision.is_start());
+                    }
+                }
+            }
+            self.send_trigger_event(collision);
+            self.send_event(collision);
+        }
+    }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub struct ModelIdent(pub String);
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub struct MaterialIdent(pub String);
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub struct CollisionGroup(usize);
+    fn send_trigger_event(&mut self, collision: events::CollisionEvent) {
+        let is_trigger = |entity| {
+            self.world
+                .entry_ref(entity)
+                .map(|entry| entry.get_component::<physics::TriggerVolume>().is_ok())
+                .unwrap_or(false)
+        };
+        let (a, b) = collision.entities();
+        let (trigger, entity) = if is_trigger(a) {
+            (a, b)
+        } else if is_trigger(b) {
+            (b, a)
+        } else {
+            return;
+        };
 
-pub struct Collider(Option<CollisionObjectSlabHandle>);
+        match collision {
+            events::CollisionEvent::ProximityStarted(..) => {
+                self.send_event(events::TriggerEvent::Entered { trigger, entity })
+            }
+            events::CollisionEvent::ProximityStopped(..) => {
+                self.send_event(events::TriggerEvent::Exited { trigger, entity })
+            }
+            _ => {}
+        }
+    }
 
-pub struct World {
-    pub models: HashMap<ModelIdent, model::Model>,
-    materials: HashMap<MaterialIdent, model::Material>,
-    world: legion::World,
-    collision_world: ncollide3d::world::CollisionWorld<f32, legion::Entity>,
-}
+    /// Layers known so far, in collision group order.
+    pub fn collision_layer_names(&self) -> &[String] {
+        self.physics.layers.names()
+    }
 
-impl World {
-    pub fn new() -> Self {
-        Self {
-            models: HashMap::new(),
-            materials: HashMap::new(),
-            world: legion::World::new(legion::WorldOptions::default()),
-            collision_world: ncollide3d::world::CollisionWorld::new(0.01),
+    /// Registers the layer `name` so editors list it before any entity
+    /// uses it.
+    #[allow(unused)]
+    pub fn add_collision_layer(&mut self, name: &str) -> Option<usize> {
+        self.physics.layers.index(name)
+    }
+
+    pub fn set_collision_layers(
+        &mut self,
+        entity: legion::Entity,
+        layers: physics::CollisionLayers,
+    ) -> Result<()> {
+        let groups = self.physics.layers.groups(&layers);
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        if let Ok(collider) = entry.get_component::<physics::Collider>() {
+            self.physics.set_collision_groups(collider, groups);
         }
+        entry.add_component(layers);
+        Ok(())
     }
 
-    pub fn load_model<P: AsRef<Path>, M: Into<String>>(
+    /// Rebuilds the collider of `entity` from `shape`.
+    pub fn set_collider_shape(
         &mut self,
-        state: &state::WgpuState,
-        layouts: &Layouts,
-        name: M,
-        path: P,
+        entity: legion::Entity,
+        shape: physics::ColliderShape,
     ) -> Result<()> {
-        self.models.insert(
-            ModelIdent(name.into()),
-            model::Model::load(state, &layouts.material, path)?,
+        self.world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?
+            .add_component(shape);
+        self.update_entity_world_transform(entity)
+    }
+
+    /// Adds a trigger without a model, `volume` can't use `TriggerShape::Mesh`.
+    #[allow(unused)]
+    pub fn add_trigger(
+        &mut self,
+        transform: transform::Transform,
+        volume: physics::TriggerVolume,
+    ) -> Result<legion::Entity> {
+        let entity = self.world.push((transform, volume));
+        self.update_world_transform(entity)?;
+
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        let transform = entry.get_component::<transform::Transform>()?;
+        let shape =
+            volume
+                .shape_handle(transform.world_scale())
+                .ok_or(EngineError::ComponentMissing {
+                    entity,
+                    component: "ModelIdent",
+                })?;
+        let isometry = transform.world_isometry();
+        let mut body = physics::RigidBody::kinematic();
+        let collider = self.physics.add(
+            entity,
+            &mut body,
+            isometry,
+            shape,
+            ncollide3d::pipeline::object::CollisionGroups::new(),
+            true,
         );
-        Ok(())
+        entry.add_component(body);
+        entry.add_component(collider);
+        entry.add_component(physics::Contacts::default());
+
+        self.send_event(events::EntityEvent::Spawned(entity));
+        Ok(entity)
     }
 
+    /// Spawns a character capsule without a model, moved by
+    /// `set_character_input` or by writing its `CharacterController`.
     #[allow(unused)]
-    pub fn load_material_raw(
+    pub fn add_character(
         &mut self,
-        state: &state::WgpuState,
         name: &str,
-        diffuse_texture: texture::Texture,
-        normal_texture: texture::Texture,
-        material_layout: &wgpu::BindGroupLayout,
-    ) {
-        self.materials.insert(
-            MaterialIdent(name.into()),
-            model::Material::new(
-                &state,
-                name,
-                diffuse_texture,
-                normal_texture,
-                material_layout,
-            ),
+        transform: transform::Transform,
+        controller: character::CharacterController,
+    ) -> Result<legion::Entity> {
+        let entity = self.world.push((
+            transform,
+            controller,
+            transform::Interpolate,
+            Name(name.to_string()),
+        ));
+        self.update_world_transform(entity)?;
+
+        let mut entry = self
+            .world
+            .entry(entity)
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        let position = entry
+            .get_component::<transform::Transform>()?
+            .world_isometry()
+            .translation;
+        let mut body = physics::RigidBody::kinematic();
+        let collider = self.physics.add(
+            entity,
+            &mut body,
+            nalgebra::Isometry3::from_parts(position, nalgebra::UnitQuaternion::identity()),
+            controller.shape(),
+            ncollide3d::pipeline::object::CollisionGroups::new(),
+            false,
         );
+        entry.add_component(body);
+        entry.add_component(collider);
+        entry.add_component(physics::Contacts::default());
+
+        self.send_event(events::EntityEvent::Spawned(entity));
+        Ok(entity)
     }
 
-    pub fn push_entity<T>(&mut self, components: T) -> Result<legion::Entity>
-    where
-        Option<T>: legion::storage::IntoComponentSource,
-    {
-        let entity = self.world.push(components);
-        self.world
+    /// Sets the direction a character walks in and whether it jumps on the
+    /// next fixed step.
+    pub fn set_character_input(
+        &mut self,
+        entity: legion::Entity,
+        movement: nalgebra::Vector3<f32>,
+        jump: bool,
+    ) -> Result<()> {
+        let mut entry = self
+            .world
             .entry(entity)
-            .unwrap()
-            .add_component(Collider(None));
+            .ok_or(EngineError::EntityNotFound(entity))?;
+        let controller = entry.get_component_mut::<character::CharacterController>()?;
+        controller.movement = movement;
+        controller.jump |= jump;
+        Ok(())
+    }
 
-        if let Some(mut entry) = self.world.entry(entity) {
-            let isometry = entry.get_component::<transform::Transform>()?.isometry();
-            let scale = entry.get_component::<transform::Transform>()?.scale();
-            let collision_groups = entry
-                .get_component::<ncollide3d::pipeline::object::CollisionGroups>()
-                .ok()
-                .cloned();
-            let model_ident = entry.get_component::<ModelIdent>()?;
-            let model = self.models.get(model_ident).context("Cannot find model")?;
-            let collider_group = entry.get_component_mut::<Collider>()?;
-
-            collider_group.0.replace(
-                self.collision_world
-                    .add(
-                        isometry,
-                        ncollide3d::shape::ShapeHandle::new(model.geometry.scaled(scale)),
-                        collision_groups
-                            .unwrap_or(ncollide3d::pipeline::object::CollisionGroups::new())
-                            .clone(),
-                        ncollide3d::pipeline::object::GeometricQueryType::Contacts(0.0, 0.0),
-                        entity,
-                    )
-                    .0,
-            );
+    /// Adds a camera entity looking along the z axis of `transform`.
+    pub fn add_camera(
+        &mut self,
+        name: &str,
+        transform: transform::Transform,
+        projection: camera::projection::Projection,
+    ) -> Result<legion::Entity> {
+        let entity = self.world.push((
+            transform,
+            camera::CameraComponent::new(projection),
+            Name(name.to_string()),
+        ));
+        self.update_world_transform(entity)?;
+        self.send_event(events::EntityEvent::Spawned(entity));
+        Ok(entity)
+    }
+
+    pub fn cameras(&self) -> Vec<legion::Entity> {
+        <(legion::Entity, &camera::CameraComponent)>::query()
+            .iter(&self.world)
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    /// Looks through `entity`, or the editor camera with `None`.
+    pub fn set_active_camera(&mut self, entity: Option<legion::Entity>) {
+        for (camera_entity, camera) in
+            <(legion::Entity, &mut camera::CameraComponent)>::query().iter_mut(&mut self.world)
+        {
+            camera.active = Some(*camera_entity) == entity;
         }
+    }
 
-        Ok(entity)
+    /// Shows `entity` in the preview window, or hides it with `None`.
+    pub fn set_preview_camera(&mut self, entity: Option<legion::Entity>) {
+        for (camera_entity, camera) in
+            <(legion::Entity, &mut camera::CameraComponent)>::query().iter_mut(&mut self.world)
+        {
+            camera.preview = Some(*camera_entity) == entity;
+        }
     }
 
-    pub fn update_entity_world_transform(&mut self, entity: legion::Entity) -> Result<()> {
-        if let Some(entry) = self.world.entry(entity) {
-            let transform = entry.get_component::<transform::Transform>()?;
-            let model_ident = entry.get_component::<ModelIdent>()?;
-            let model = self.models.get(model_ident).context("Cannot find model")?;
-            let collider = entry
-                .get_component::<Collider>()?
-                .0
-                .context("Cannot find collider")?;
+    /// Adds an entity holding a camera track.
+    pub fn add_camera_path(
+        &mut self,
+        name: &str,
+        path: camera::path::CameraPath,
+    ) -> legion::Entity {
+        let entity = self.world.push((path, Name(name.to_string())));
+        self.send_event(events::EntityEvent::Spawned(entity));
+        entity
+    }
+
+    pub fn camera_paths(&self) -> Vec<legion::Entity> {
+        <(legion::Entity, &camera::path::CameraPath)>::query()
+            .iter(&self.world)
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    /// Pose along the camera track of `entity`, `None` once it's over.
+    pub fn camera_path_pose(
+        &self,
+        entity: legion::Entity,
+        time: f32,
+    ) -> Option<nalgebra::Isometry3<f32>> {
+        self.world
+            .entry_ref(entity)
+            .ok()?
+            .get_component::<camera::path::CameraPath>()
+            .ok()?
+            .sample(time)
+    }
+
+    /// What every camera entity sees.
+    pub fn camera_views(&self) -> Vec<(legion::Entity, camera::Camera)> {
+        <(
+            legion::Entity,
+            &transform::Transform,
+            &camera::CameraComponent,
+        )>::query()
+        .iter(&self.world)
+        .map(|(entity, transform, camera)| {
+            (*entity, camera.view_from(&transform.render_isometry()))
+        })
+        .collect()
+    }
+
+    /// The active camera entity and what it sees.
+    pub fn active_camera(&self) -> Option<(legion::Entity, camera::Camera)> {
+        self.find_camera(|camera| camera.active)
+    }
+
+    pub fn preview_camera(&self) -> Option<(legion::Entity, camera::Camera)> {
+        self.find_camera(|camera| camera.preview)
+    }
 
-            let collision_object = self
-                .collision_world
-                .get_mut(collider)
-                .context("No collision object in world")?;
+    fn find_camera(
+        &self,
+        filter: impl Fn(&camera::CameraComponent) -> bool,
+    ) -> Option<(legion::Entity, camera::Camera)> {
+        <(
+            legion::Entity,
+            &transform::Transform,
+            &camera::CameraComponent,
+        )>::query()
+        .iter(&self.world)
+        .find(|(_, _, camera)| filter(camera))
+        .map(|(entity, transform, camera)| {
+            (*entity, camera.view_from(&transform.render_isometry()))
+        })
+    }
 
-            collision_object.set_position(transform.isometry());
-            collision_object.set_shape(ncollide3d::shape::ShapeHandle::new(
-                model.geometry.scaled(transform.scale()),
-            ));
+    /// Matches the aspect ratio of camera entities to the window.
+    pub fn resize_cameras(&mut self, width: u32, height: u32) {
+        for camera in <&mut camera::CameraComponent>::query().iter_mut(&mut self.world) {
+            camera.camera.resize(width, height);
         }
+    }
 
-        Ok(())
+    /// Interpolated position of an entity, the center of a character's
+    /// capsule.
+    pub fn position(&self, entity: legion::Entity) -> Option<nalgebra::Point3<f32>> {
+        let entry = self.world.entry_ref(entity).ok()?;
+        let transform = entry.get_component::<transform::Transform>().ok()?;
+        Some(nalgebra::Point3::from(
+            transform.render_isometry().translation.vector,
+        ))
     }
 
-    pub fn entry(&mut self, entity: legion::Entity) -> Option<legion::world::Entry> {
-        self.world.entry(entity)
+    /// Moves every character through the collision world for one fixed
+    /// step.
+    fn update_characters(&mut self) {
+        let dt = self
+            .shared
+            .get::<schedule::FixedTime>()
+            .map(|time| time.step_seconds())
+            .unwrap_or_else(|| schedule::FIXED_STEP.as_secs_f32());
+        let characters = <(
+            legion::Entity,
+            &character::CharacterController,
+            &transform::Transform,
+        )>::query()
+        .iter(&self.world)
+        .map(|(entity, controller, transform)| {
+            (
+                *entity,
+                *controller,
+                nalgebra::Point3::from(transform.world_isometry().translation.vector),
+            )
+        })
+        .collect::<Vec<_>>();
+
+        for (entity, mut controller, position) in characters {
+            let position = controller.step(&self.physics, entity, position, dt);
+            if let Ok(mut entry) = self.world.entry(entity) {
+                if let Ok(current) = entry.get_component_mut::<character::CharacterController>() {
+                    *current = controller;
+                }
+                if let Ok(transform) = entry.get_component_mut::<transform::Transform>() {
+                    transform.set_position(nalgebra::Translation3::from(position.coords));
+                }
+            }
+        }
     }
 
-    pub fn update_collision_world(&mut self) {
-        self.collision_world.update();
+    /// Copies the poses of dynamic bodies into their transforms.
+    fn sync_bodies(&mut self) {
+        let poses = <(
+            legion::Entity,
+            &physics::RigidBody,
+            Option<&hierarchy::Parent>,
+        )>::query()
+        .iter(&self.world)
+        .filter(|(_, body, _)| body.is_dynamic())
+        .filter_map(|(entity, body, parent)| {
+            Some((
+                *entity,
+                self.physics.body_position(body)?,
+                parent.map(|parent| parent.0),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+        for (entity, isometry, parent) in poses {
+            let parent_world = parent
+                .and_then(|parent| self.world.entry_ref(parent).ok())
+                .and_then(|entry| {
+                    entry
+                        .get_component::<transform::Transform>()
+                        .ok()
+                        .map(|transform| (transform.world_isometry(), transform.world_scale()))
+                });
+            let local = match parent_world {
+                Some(parent_world) => hierarchy::decompose(parent_world, isometry),
+                None => isometry,
+            };
+
+            if let Some(mut entry) = self.world.entry(entity) {
+                if let Ok(transform) = entry.get_component_mut::<transform::Transform>() {
+                    transform.set_isometry(local);
+                }
+            }
+        }
+    }
+
+    fn physics_body(&self, entity: legion::Entity) -> Option<physics::RigidBody> {
+        self.world
+            .entry_ref(entity)
+            .ok()?
+            .get_component::<physics::RigidBody>()
+            .ok()
+            .copied()
+    }
+
+    /// Pushes `entity` with a continuous force, `Impulse` for a one-off
+    /// push or `AccelerationChange` to ignore its mass.
+    #[allow(unused)]
+    pub fn apply_force(
+        &mut self,
+        entity: legion::Entity,
+        force: nalgebra::Vector3<f32>,
+        force_type: nphysics3d::algebra::ForceType,
+    ) {
+        if let Some(body) = self.physics_body(entity) {
+            self.physics.apply_force(&body, force, force_type);
+        }
+    }
+
+    #[allow(unused)]
+    pub fn apply_torque(
+        &mut self,
+        entity: legion::Entity,
+        torque: nalgebra::Vector3<f32>,
+        force_type: nphysics3d::algebra::ForceType,
+    ) {
+        if let Some(body) = self.physics_body(entity) {
+            self.physics.apply_torque(&body, torque, force_type);
+        }
     }
 
+    #[allow(unused)]
+    pub fn set_velocity(&mut self, entity: legion::Entity, velocity: nalgebra::Vector3<f32>) {
+        if let Some(body) = self.physics_body(entity) {
+            self.physics.set_velocity(&body, velocity);
+        }
+    }
+
+    #[allow(unused)]
+    pub fn physics(&mut self) -> &mut physics::Physics {
+        &mut self.physics
+    }
+
+    /// Nearest hit of `ray`, including the mesh of the model that was hit.
     pub fn raycast(
         &self,
         ray: &ncollide3d::query::Ray<f32>,
         max_toi: f32,
-    ) -> Option<legion::Entity> {
-        Some(
-            self.collision_world
-                .first_interference_with_ray(
-                    ray,
-                    max_toi,
-                    &ncollide3d::pipeline::CollisionGroups::new(),
-                )?
-                .co
-                .data()
-                .clone(),
-        )
+    ) -> Option<physics::RaycastHit> {
+        let mut hit = self.physics.raycast(ray, max_toi)?;
+        hit.mesh = self.hit_mesh(hit.entity, ray, max_toi);
+        Some(hit)
+    }
+
+    /// Every hit of `ray` nearest first, only colliders in `groups`.
+    #[allow(unused)]
+    pub fn raycast_all(
+        &self,
+        ray: &ncollide3d::query::Ray<f32>,
+        max_toi: f32,
+        groups: &ncollide3d::pipeline::CollisionGroups,
+    ) -> Vec<physics::RaycastHit> {
+        self.physics
+            .raycast_all(ray, max_toi, groups)
+            .into_iter()
+            .map(|mut hit| {
+                hit.mesh = self.hit_mesh(hit.entity, ray, max_toi);
+                hit
+            })
+            .collect()
+    }
+
+    #[allow(unused)]
+    pub fn sphere_cast(
+        &self,
+        origin: nalgebra::Point3<f32>,
+        direction: nalgebra::Vector3<f32>,
+        radius: f32,
+        max_toi: f32,
+    ) -> Option<physics::RaycastHit> {
+        self.physics.sphere_cast(origin, direction, radius, max_toi)
+    }
+
+    #[allow(unused)]
+    pub fn aabb_overlap(
+        &self,
+        aabb: &ncollide3d::bounding_volume::AABB<f32>,
+    ) -> Vec<legion::Entity> {
+        self.physics.aabb_overlap(aabb)
+    }
+
+    /// Casts `ray` against the meshes of `entity`'s model in model space,
+    /// the collider may be simplified and can't tell them apart.
+    fn hit_mesh(
+        &self,
+        entity: legion::Entity,
+        ray: &ncollide3d::query::Ray<f32>,
+        max_toi: f32,
+    ) -> Option<usize> {
+        let entry = self.world.entry_ref(entity).ok()?;
+        let transform = entry.get_component::<transform::Transform>().ok()?;
+        let model = entry.get_component::<ModelIdent>().ok()?;
+        let model = self.assets.models.get_by_name(&model.0)?;
+
+        let scale = transform.world_scale();
+        let local = ray.inverse_transform_by(&transform.world_isometry());
+        let local = ncollide3d::query::Ray::new(
+            nalgebra::Point3::from(local.origin.coords.component_div(&scale)),
+            local.dir.component_div(&scale),
+        );
+        model
+            .geometry
+            .mesh_with_ray(&nalgebra::Isometry3::identity(), &local, max_toi, true)
+            .map(|(mesh, _)| mesh)
+    }
+
+    /// Whether every model and material the entities use is loaded.
+    pub fn assets_ready(&self) -> bool {
+        self.ensure_models_and_materials().is_ok()
     }
 
     fn ensure_models_and_materials(&self) -> Result<()> {
@@ -170,12 +2772,17 @@ impl World {
 
         let models_not_found = models
             .iter(&self.world)
-            .map(|model| (model.clone(), self.models.contains_key(model)))
+            .map(|model| (model.clone(), self.assets.models.contains(&model.0)))
             .filter(|(_, exists)| !exists)
             .collect::<Vec<_>>();
         let materials_not_found = materials
             .iter(&self.world)
-            .map(|material| (material.clone(), self.materials.contains_key(material)))
+            .map(|material| {
+                (
+                    material.clone(),
+                    self.assets.materials.contains(&material.0),
+                )
+            })
             .filter(|(_, exists)| !exists)
             .collect::<Vec<_>>();
 
@@ -209,45 +2816,452 @@ impl World {
         }
     }
 
+    pub fn set_physics_simulated(&mut self, simulated: bool) {
+        self.simulate_physics = simulated;
+    }
+
+    /// Opens the audio output, sounds are skipped until then.
+    pub fn enable_audio(&mut self) {
+        self.audio = audio::Audio::new();
+    }
+
+    pub fn set_indirect_draw(&mut self, enabled: bool) {
+        self.indirect_draw = enabled;
+    }
+
+    /// Writes the indirect draws of the scene seen by `camera`, or of its
+    /// reflection with `skip_mirrors`, before the pass drawing them. With
+    /// `culling` the entities are tested against the frustum on the GPU,
+    /// recorded to `encoder`, rather than here.
+    pub fn prepare_indirect(
+        &mut self,
+        state: &state::WgpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        culling: Option<&culling::Culling>,
+        camera: &camera::Camera,
+        skip_mirrors: bool,
+    ) -> Result<()> {
+        if !self.indirect_draw {
+            return Ok(());
+        }
+        self.ensure_models_and_materials()?;
+
+        let visible = match culling {
+            Some(_) => None,
+            None => Some(
+                self.visible_entities(camera)
+                    .into_iter()
+                    .collect::<std::collections::HashSet<_>>(),
+            ),
+        };
+        let mirrors = if skip_mirrors {
+            <(legion::Entity, &reflection::Mirror)>::query()
+                .iter(&self.world)
+                .map(|(entity, _)| *entity)
+                .collect()
+        } else {
+            std::collections::HashSet::new()
+        };
+
+        let mut models = <(
+            legion::Entity,
+            &transform::Transform,
+            &ModelIdent,
+            Option<&MaterialIdent>,
+            Option<&animation::AnimationPlayer>,
+            Option<&bounds::Bounds>,
+            Option<&Visible>,
+            Option<&RenderLayer>,
+        )>::query()
+        .filter(
+            !legion::component::<animation::MorphWeights>()
+                & !legion::component::<model::ArrayLayer>(),
+        );
+
+        // Instances by model, detail level and material
+        let mut batches: BTreeMap<(String, usize, Option<String>), Vec<culling::CullInstance>> =
+            BTreeMap::new();
+        for (entity, transform, model_ident, material, player, bounds, shown, layer) in
+            models.iter(&self.world)
+        {
+            if mirrors.contains(entity)
+                || !is_drawn(shown, layer, camera.layers)
+                || self.billboarded.contains(entity)
+            {
+                continue;
+            }
+            if let (Some(visible), Some(_)) = (&visible, bounds) {
+                if !visible.contains(entity) {
+                    continue;
+                }
+            }
+            let model = self
+                .assets
+                .models
+                .get_by_name(&model_ident.0)
+                .expect("Model not found");
+            if player.is_some() && model.geometry.is_skinned() {
+                continue;
+            }
+
+            let (center, half_extents) = match bounds {
+                Some(bounds) => {
+                    let center = bounds.aabb.center();
+                    let half = bounds.aabb.half_extents();
+                    (
+                        [center.x, center.y, center.z, 1.0],
+                        [half.x, half.y, half.z, 0.0],
+                    )
+                }
+                None => ([0.0; 4], [0.0; 4]),
+            };
+            let key = (
+                model_ident.0.clone(),
+                lod_for(model, false, transform, bounds, camera),
+                material.map(|material| material.0.clone()),
+            );
+            let batch = batches.entry(key).or_default();
+            batch.push(culling::CullInstance {
+                instance: transform.instance(),
+                center,
+                half_extents,
+                batch: [0; 4],
+            });
+        }
+
+        let mut draws = Vec::with_capacity(batches.len());
+        let mut instances = Vec::new();
+        let mut arguments = Vec::new();
+        for (index, ((model_name, lod, material), batch)) in batches.into_iter().enumerate() {
+            let model = self
+                .assets
+                .models
+                .get_by_name(&model_name)
+                .expect("Model not found");
+            let first_instance = instances.len() as u32;
+            let meshes = &model.lod_geometry(lod).meshes;
+            draws.push(indirect::IndirectBatch {
+                model: model_name,
+                lod,
+                material,
+                first_argument: arguments.len(),
+                mesh_count: meshes.len(),
+                first_instance,
+                instance_count: batch.len() as u32,
+            });
+            for mesh in meshes.iter() {
+                arguments.push(indirect::DrawIndexedIndirect {
+                    index_count: mesh.num_elements,
+                    instance_count: batch.len() as u32,
+                    first_index: 0,
+                    base_vertex: 0,
+                    first_instance,
+                });
+            }
+            instances.extend(batch.into_iter().map(|instance| culling::CullInstance {
+                batch: [index as u32, 0, 0, 0],
+                ..instance
+            }));
+        }
+
+        let frustum = spatial::Frustum::from_view_proj(&camera.view_proj);
+        let draws_of_view = if skip_mirrors {
+            &mut self.reflection_draws
+        } else {
+            &mut self.scene_draws
+        };
+        draws_of_view.upload(
+            state,
+            encoder,
+            culling.map(|culling| (culling, &frustum)),
+            draws,
+            &instances,
+            &arguments,
+        );
+        Ok(())
+    }
+
     pub fn render<'a>(
         &'a mut self,
         state: &state::WgpuState,
         render_pass: &mut wgpu::RenderPass<'a>,
+        pipelines: &'a Pipelines,
+        camera: &camera::Camera,
+        uniforms: &'a binding::BufferGroup,
+        light: &'a binding::BufferGroup,
+    ) -> Result<()> {
+        self.render_entities(
+            state,
+            render_pass,
+            pipelines,
+            camera,
+            uniforms,
+            light,
+            false,
+        )
+    }
+
+    /// Draws the scene seen in a mirror, leaving out the mirrors, which
+    /// may sample the target being drawn.
+    pub fn render_reflection<'a>(
+        &'a mut self,
+        state: &state::WgpuState,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        pipelines: &'a Pipelines,
+        camera: &camera::Camera,
+        uniforms: &'a binding::BufferGroup,
+        light: &'a binding::BufferGroup,
+    ) -> Result<()> {
+        self.render_entities(state, render_pass, pipelines, camera, uniforms, light, true)
+    }
+
+    fn render_entities<'a>(
+        &'a mut self,
+        state: &state::WgpuState,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        pipelines: &'a Pipelines,
+        camera: &camera::Camera,
         uniforms: &'a binding::BufferGroup,
         light: &'a binding::BufferGroup,
+        skip_mirrors: bool,
     ) -> Result<()> {
         if let Err(e) = self.ensure_models_and_materials() {
             return Err(e);
         }
+        let start = Instant::now();
+
+        // Entities the index hasn't seen yet have no bounds and are drawn
+        let visible = self
+            .visible_entities(camera)
+            .into_iter()
+            .collect::<std::collections::HashSet<_>>();
+        let mirrors = if skip_mirrors {
+            <(legion::Entity, &reflection::Mirror)>::query()
+                .iter(&self.world)
+                .map(|(entity, _)| *entity)
+                .collect()
+        } else {
+            std::collections::HashSet::new()
+        };
 
         let mut models = <(
+            legion::Entity,
             &mut transform::Transform,
             &ModelIdent,
             Option<&MaterialIdent>,
+            Option<&animation::AnimationPlayer>,
+            Option<&mut animation::MorphWeights>,
+            Option<&model::ArrayLayer>,
+            Option<&bounds::Bounds>,
+            Option<&Visible>,
+            Option<&RenderLayer>,
         )>::query();
 
-        for (transform, model, material) in models.iter_mut(&mut self.world) {
-            render_pass.bind_buffer(1, transform.buffer(state));
+        // Entities sharing a material array keep its bind group bound
+        let mut bound_array: Option<&str> = None;
+
+        for (
+            entity,
+            transform,
+            model,
+            material,
+            player,
+            morph,
+            layer,
+            bounds,
+            shown,
+            render_layer,
+        ) in models.iter_mut(&mut self.world)
+        {
+            if (bounds.is_some() && !visible.contains(entity))
+                || mirrors.contains(entity)
+                || !is_drawn(shown, render_layer, camera.layers)
+                || self.billboarded.contains(entity)
+            {
+                continue;
+            }
 
-            match material {
-                Some(material) => {
-                    render_pass.draw_model_with_material(
-                        &self.models.get(model).expect("Model not found"),
-                        &self.materials.get(material).expect("Material not found"),
-                        &uniforms,
-                        &light,
-                    );
+            let model = self
+                .assets
+                .models
+                .get_by_name(&model.0)
+                .expect("Model not found");
+
+            let lod = lod_for(model, morph.is_some(), transform, bounds, camera);
+            let index_format = model.lod_geometry(lod).index_format();
+
+            if let Some(layer) = layer {
+                render_pass.set_pipeline(pipelines.material_array.get(index_format));
+                if bound_array != Some(layer.array.as_str()) {
+                    let array = self
+                        .assets
+                        .material_arrays
+                        .get_by_name(&layer.array)
+                        .expect("Material array not found");
+                    render_pass.bind_material_array(0, array);
+                    bound_array = Some(layer.array.as_str());
                 }
-                None => {
-                    render_pass.draw_model(
-                        &self.models.get(model).expect("Model not found"),
-                        &uniforms,
-                        &light,
-                    );
+                render_pass.bind_buffer(1, transform.buffer(state));
+                render_pass.bind_buffer(2, layer.buffer());
+                render_pass.draw_model_array(model, lod, &uniforms, &light);
+                let geometry = model.lod_geometry(lod);
+                self.stats
+                    .current
+                    .draw(geometry.mesh_count() as u32, geometry.triangle_count());
+                continue;
+            }
+            // Other pipelines bind their own materials to group 0
+            bound_array = None;
+
+            if let Some(morph) = morph {
+                morph.flush(state);
+                render_pass.set_pipeline(pipelines.morph.get(index_format));
+                render_pass.bind_buffer(1, transform.buffer(state));
+                render_pass.draw_model_morphed(model, morph, &uniforms, &light);
+                self.stats.current.draw(
+                    model.geometry.mesh_count() as u32,
+                    model.geometry.triangle_count(),
+                );
+                continue;
+            }
+
+            let skinned = player.is_some() && model.geometry.is_skinned();
+            // Written by `prepare_indirect`
+            if self.indirect_draw && !skinned {
+                continue;
+            }
+
+            render_pass.bind_buffer(1, transform.buffer(state));
+
+            let material = match material {
+                Some(material) => Some(
+                    self.assets
+                        .materials
+                        .get_by_name(&material.0)
+                        .expect("Material not found"),
+                ),
+                None => None,
+            };
+            match player {
+                Some(player) if skinned => render_pass.draw_model_skinned(
+                    model,
+                    material,
+                    pipelines.skinned.get(index_format),
+                    pipelines.forward.get(index_format),
+                    &player.joint_group,
+                    &uniforms,
+                    &light,
+                ),
+                _ => {
+                    render_pass.set_pipeline(pipelines.forward.get(index_format));
+                    render_pass.draw_model_lod(model, lod, material, &uniforms, &light);
                 }
             }
+            let geometry = model.lod_geometry(lod);
+            self.stats
+                .current
+                .draw(geometry.mesh_count() as u32, geometry.triangle_count());
+        }
+
+        let draws: &'a indirect::IndirectDraws = if skip_mirrors {
+            &self.reflection_draws
+        } else {
+            &self.scene_draws
+        };
+        if let (true, Some(instances), Some(arguments)) =
+            (self.indirect_draw, draws.instances(), draws.arguments())
+        {
+            for batch in draws.batches() {
+                let model = self
+                    .assets
+                    .models
+                    .get_by_name(&batch.model)
+                    .expect("Model not found");
+                let material = batch.material.as_ref().map(|material| {
+                    self.assets
+                        .materials
+                        .get_by_name(material)
+                        .expect("Material not found")
+                });
+                let geometry = model.lod_geometry(batch.lod);
+                render_pass.set_pipeline(pipelines.forward.get(geometry.index_format()));
+                render_pass.bind_buffer(1, instances);
+                render_pass.draw_model_indirect(
+                    model,
+                    batch.lod,
+                    material,
+                    arguments,
+                    batch.first_argument,
+                    &uniforms,
+                    &light,
+                );
+                // Culled on the GPU the drawn instances aren't known here
+                self.stats.current.draw(
+                    geometry.mesh_count() as u32,
+                    geometry.triangle_count() * batch.instance_count as u64,
+                );
+            }
+        }
+
+        render_pass.set_pipeline(&pipelines.terrain);
+        for terrain in self.terrains.iter().flatten() {
+            render_pass.draw_terrain(terrain, &uniforms, &light);
+            let (draw_calls, triangles) = terrain.draw_stats();
+            self.stats.current.draw(draw_calls, triangles);
         }
+        self.stats.current.time("Render", start);
 
         Ok(())
     }
 }
+
+/// Detail level `model` is drawn at for `camera`. Skinned and morphed
+/// meshes only have their full detail geometry.
+fn lod_for(
+    model: &model::Model,
+    morphed: bool,
+    transform: &transform::Transform,
+    bounds: Option<&bounds::Bounds>,
+    camera: &camera::Camera,
+) -> usize {
+    if morphed || model.geometry.is_skinned() {
+        return 0;
+    }
+    let (center, radius) = match bounds {
+        Some(bounds) => (bounds.center(), bounds.sphere.radius()),
+        None => {
+            let sphere = model.geometry.bounding_sphere();
+            (
+                transform.world_isometry() * sphere.center(),
+                sphere.radius() * transform.world_scale().amax(),
+            )
+        }
+    };
+    model.select_lod(camera.screen_size(&center, radius))
+}
+
+/// The material `source` refers to, borrowing only the material and model
+/// assets so the textures can be borrowed alongside.
+fn material_mut<'a>(
+    materials: &'a mut assets::Assets<model::Material>,
+    models: &'a mut assets::Assets<model::Model>,
+    source: &editor::material::MaterialSource,
+) -> Result<&'a mut model::Material> {
+    match source {
+        editor::material::MaterialSource::Asset(name) => {
+            let handle = materials
+                .handle(name)
+                .ok_or_else(|| EngineError::asset_not_found("Material", name))?;
+            materials.get_mut(&handle).context("Material missing")
+        }
+        editor::material::MaterialSource::Model(name, index) => {
+            let handle = models
+                .handle(name)
+                .ok_or_else(|| EngineError::asset_not_found("Model", name))?;
+            models
+                .get_mut(&handle)
+                .and_then(|model| model.materials.get_mut(*index))
+                .with_context(|| format!("Model {:?} has no material {}", name, index))
+        }
+    }
+}